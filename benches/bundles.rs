@@ -0,0 +1,51 @@
+//! Times recording 10k copies of one indexed draw into render bundles on one
+//! thread and on every available core.
+//!
+//! Run with `cargo bench --bench bundles --features test-support`.
+
+use std::thread;
+use std::time::Instant;
+
+use learn1::bundle::{encode_bundles, BundleTarget};
+use learn1::config::AppConfig;
+use learn1::context::GpuContext;
+use learn1::test_support::triangle_pipeline;
+
+const DRAWS: usize = 10_000;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let config = AppConfig::default();
+    let instance = GpuContext::create_instance(&config);
+    let gpu = match pollster::block_on(GpuContext::new(instance, None, &config)) {
+        Ok(gpu) => gpu,
+        Err(e) => {
+            eprintln!("skipping the bundle benchmark: {e}");
+            return;
+        }
+    };
+    let device = &gpu.device;
+    let target = BundleTarget {
+        color_format: wgpu::TextureFormat::Rgba8Unorm,
+        depth_format: None,
+        sample_count: 1,
+    };
+    let (pipeline, mesh) = triangle_pipeline(device, &target);
+
+    let cores = thread::available_parallelism()
+        .map_or(4, |n| n.get())
+        .max(2);
+    for threads in [1, cores] {
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            encode_bundles(device, &target, DRAWS, threads, |encoder, range| {
+                encoder.set_pipeline(&pipeline);
+                for _ in range {
+                    mesh.draw_bundle(encoder, 0..1);
+                }
+            });
+        }
+        let per_frame = start.elapsed() / ITERATIONS;
+        println!("{DRAWS} draws on {threads} thread(s): {per_frame:?}");
+    }
+}
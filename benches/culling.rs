@@ -0,0 +1,70 @@
+//! Times culling a 100k-instance grid on the CPU and with the compute pass,
+//! each including its upload or dispatch and waiting for the GPU to finish.
+//!
+//! Run with `cargo bench --bench culling`.
+
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+use learn1::camera::Camera;
+use learn1::config::AppConfig;
+use learn1::context::GpuContext;
+use learn1::culling::{CullMode, InstanceCuller};
+use learn1::frame::FrameEncoder;
+use learn1::scene::{grid_instances, pentagon_model};
+
+/// 317 x 317 is just over 100k instances.
+const INSTANCES_PER_ROW: u32 = 317;
+const ITERATIONS: u32 = 50;
+
+fn main() {
+    let config = AppConfig::default();
+    let instance = GpuContext::create_instance(&config);
+    let gpu = match pollster::block_on(GpuContext::new(instance, None, &config)) {
+        Ok(gpu) => gpu,
+        Err(e) => {
+            eprintln!("skipping the culling benchmark: {e}");
+            return;
+        }
+    };
+    let (device, queue) = (&gpu.device, &gpu.queue);
+    let model = pentagon_model(device, queue);
+    let instances = grid_instances(INSTANCES_PER_ROW);
+    // Low over one corner of the grid, looking across it, so that some of
+    // the instances are in view.
+    let mut camera = Camera::new(16.0 / 9.0);
+    camera.eye = Vec3::new(-150.0, 3.0, -150.0);
+    camera.target = Vec3::new(-100.0, 0.0, -120.0);
+    let view_proj = camera.build_view_projection_matrix();
+
+    println!("culling {} instances", instances.len());
+    for mode in [CullMode::Cpu, CullMode::Gpu] {
+        let mut culler = InstanceCuller::new(device, &gpu.adapter, mode, false);
+        if culler.mode() != mode {
+            println!("{mode}: not supported by the adapter");
+            continue;
+        }
+        culler.set_model(device, queue, &model);
+        culler.set_instances(device, queue, &instances);
+        let cull = |culler: &mut InstanceCuller| {
+            let mut frame = FrameEncoder::new(device, queue, None);
+            culler.cull(device, queue, &mut frame, view_proj, None);
+            frame.submit();
+            device
+                .poll(wgpu::PollType::wait_indefinitely())
+                .expect("the device was lost");
+        };
+        // The first run also compiles the pipeline and grows the buffers.
+        cull(&mut culler);
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            cull(&mut culler);
+        }
+        let per_cull = start.elapsed() / ITERATIONS;
+        println!("{mode}: {:.3} ms per cull", as_millis(per_cull));
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
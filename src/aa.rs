@@ -0,0 +1,608 @@
+use std::fmt;
+use std::str::FromStr;
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("aa.wgsl");
+
+/// Share of the current frame blended into the TAA history each frame.
+const TAA_CURRENT_WEIGHT: f32 = 0.1;
+
+/// Jitter positions cycled through by [`taa_jitter`].
+const TAA_JITTER_SAMPLES: u64 = 8;
+
+/// How the scene's edges are antialiased.
+///
+/// MSAA multisamples the scene pass itself, while FXAA and TAA render it
+/// single-sampled into an intermediate texture and filter that in a
+/// [`PostAa`] pass afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AaMode {
+    None,
+    /// Multisampling with this many samples.
+    Msaa(u32),
+    /// Fast approximate antialiasing: blurs along edges found from luma.
+    Fxaa,
+    /// Temporal antialiasing: jitters the projection every frame and
+    /// accumulates the frames in a history texture.
+    Taa,
+}
+
+impl AaMode {
+    /// Samples per pixel of the scene pass.
+    pub fn sample_count(self) -> u32 {
+        match self {
+            Self::Msaa(samples) => samples.max(1),
+            _ => 1,
+        }
+    }
+
+    /// Whether the mode filters the scene in a [`PostAa`] pass.
+    pub fn is_post_process(self) -> bool {
+        matches!(self, Self::Fxaa | Self::Taa)
+    }
+
+    /// The name the mode parses from, e.g. `msaa4`, unlike its
+    /// human-readable [`Display`](fmt::Display).
+    pub fn name(self) -> String {
+        match self {
+            Self::None => "none".to_string(),
+            Self::Msaa(samples) => format!("msaa{samples}"),
+            Self::Fxaa => "fxaa".to_string(),
+            Self::Taa => "taa".to_string(),
+        }
+    }
+
+    /// The mode after this one when cycling through them with a key.
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Msaa(4),
+            Self::Msaa(_) => Self::Fxaa,
+            Self::Fxaa => Self::Taa,
+            Self::Taa => Self::None,
+        }
+    }
+}
+
+impl fmt::Display for AaMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "off"),
+            Self::Msaa(samples) => write!(f, "{samples}x MSAA"),
+            Self::Fxaa => write!(f, "FXAA"),
+            Self::Taa => write!(f, "TAA"),
+        }
+    }
+}
+
+impl FromStr for AaMode {
+    type Err = String;
+
+    /// Parses `none`, `msaa` (4 samples), `msaa2`, `msaa4`, ..., `fxaa` or
+    /// `taa`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "none" | "off" => Ok(Self::None),
+            "msaa" => Ok(Self::Msaa(4)),
+            "fxaa" => Ok(Self::Fxaa),
+            "taa" => Ok(Self::Taa),
+            _ => s
+                .strip_prefix("msaa")
+                .and_then(|samples| samples.parse().ok())
+                .filter(|samples: &u32| samples.is_power_of_two())
+                .map(Self::Msaa)
+                .ok_or_else(|| format!("unknown antialiasing mode `{s}`")),
+        }
+    }
+}
+
+/// Sub-pixel offset of the projection for TAA frame `frame`, in pixels
+/// within -0.5..0.5: the Halton (2, 3) sequence, which covers the pixel
+/// evenly over a few frames.
+pub fn taa_jitter(frame: u64) -> Vec2 {
+    let index = frame % TAA_JITTER_SAMPLES + 1;
+    Vec2::new(halton(index, 2), halton(index, 3)) - 0.5
+}
+
+fn halton(mut index: u64, base: u64) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    texel_size: [f32; 2],
+    current_weight: f32,
+    _padding: f32,
+}
+
+/// Owns what the post-processing modes need: the texture the scene is
+/// rendered into, and for TAA the history textures. Only these are rebuilt
+/// when the target size or format changes; switching between FXAA and TAA
+/// only swaps the pipeline in use.
+pub struct PostAa {
+    mode: AaMode,
+    format: wgpu::TextureFormat,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: UniformBuffer<Params>,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    fxaa_pipeline: wgpu::RenderPipeline,
+    taa_pipeline: wgpu::RenderPipeline,
+    targets: Targets,
+    /// Frames drawn since the history was last reset.
+    frame: u64,
+}
+
+/// The size-dependent textures, with a bind group per history texture:
+/// bind group `i` reads history `i` and the pass writes the other one.
+struct Targets {
+    input: wgpu::TextureView,
+    history: [wgpu::TextureView; 2],
+    bind_groups: [wgpu::BindGroup; 2],
+    size: [u32; 2],
+}
+
+impl PostAa {
+    /// `mode` must be [`AaMode::Fxaa`] or [`AaMode::Taa`]. The pass reads
+    /// and writes `format`, the scene format.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        mode: AaMode,
+    ) -> Self {
+        debug_assert!(mode.is_post_process(), "{mode} has no post pass");
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Params",
+            &crate::struct_layout!(Params {
+                texel_size,
+                current_weight,
+                _padding,
+            }),
+        );
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("aa::bind_group_layout"),
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: label!("aa::sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params = UniformBuffer::new(device, label!("aa::params"), wgpu::ShaderStages::FRAGMENT);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("aa::pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = create_shader(device);
+        let (fxaa_pipeline, taa_pipeline) =
+            create_pipelines(device, &shader, &pipeline_layout, format);
+        let targets = Targets::new(device, &layout, &sampler, &params, format, width, height);
+        Self {
+            mode,
+            format,
+            layout,
+            sampler,
+            params,
+            pipeline_layout,
+            shader,
+            fxaa_pipeline,
+            taa_pipeline,
+            targets,
+            frame: 0,
+        }
+    }
+
+    pub fn mode(&self) -> AaMode {
+        self.mode
+    }
+
+    /// Switches between FXAA and TAA; the history restarts.
+    pub fn set_mode(&mut self, mode: AaMode) {
+        debug_assert!(mode.is_post_process(), "{mode} has no post pass");
+        self.mode = mode;
+        self.frame = 0;
+    }
+
+    /// Recreates the textures for a new size, and the pipelines as well for
+    /// a new format. Does nothing if neither changed.
+    pub fn set_target(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) {
+        if format == self.format && self.targets.size == [width.max(1), height.max(1)] {
+            return;
+        }
+        if format != self.format {
+            self.format = format;
+            self.shader = create_shader(device);
+            (self.fxaa_pipeline, self.taa_pipeline) =
+                create_pipelines(device, &self.shader, &self.pipeline_layout, format);
+        }
+        self.targets = Targets::new(
+            device,
+            &self.layout,
+            &self.sampler,
+            &self.params,
+            format,
+            width,
+            height,
+        );
+        self.frame = 0;
+    }
+
+    /// The view to render the scene into, single-sampled and in the scene
+    /// format.
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.targets.input
+    }
+
+    /// `view_proj` shifted by the sub-pixel jitter of the frame about to be
+    /// drawn, in TAA mode; unchanged otherwise.
+    pub fn jitter_view_projection(&self, view_proj: Mat4) -> Mat4 {
+        if self.mode != AaMode::Taa {
+            return view_proj;
+        }
+        let jitter = taa_jitter(self.frame);
+        let [width, height] = self.targets.size;
+        // NDC spans two units per axis, and y points up.
+        let offset = Vec3::new(
+            2.0 * jitter.x / width as f32,
+            -2.0 * jitter.y / height as f32,
+            0.0,
+        );
+        Mat4::from_translation(offset) * view_proj
+    }
+
+    /// Filters the input into `output`, which must be the same size and in
+    /// the scene format, and moves on to the next frame's jitter.
+    pub fn draw(
+        &mut self,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        output: &wgpu::TextureView,
+    ) {
+        let [width, height] = self.targets.size;
+        let current_weight = if self.frame == 0 {
+            1.0
+        } else {
+            TAA_CURRENT_WEIGHT
+        };
+        self.params.update(
+            queue,
+            &Params {
+                texel_size: [1.0 / width as f32, 1.0 / height as f32],
+                current_weight,
+                _padding: 0.0,
+            },
+        );
+        let read = (self.frame % 2) as usize;
+        let taa = self.mode == AaMode::Taa;
+        let attachment = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let attachments = [
+            attachment(output),
+            attachment(&self.targets.history[1 - read]),
+        ];
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("aa::render_pass"),
+            color_attachments: if taa { &attachments } else { &attachments[..1] },
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(if taa {
+            &self.taa_pipeline
+        } else {
+            &self.fxaa_pipeline
+        });
+        render_pass.set_bind_group(0, &self.targets.bind_groups[read], &[]);
+        render_pass.draw(0..3, 0..1);
+        self.frame += 1;
+    }
+}
+
+impl Targets {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params: &UniformBuffer<Params>,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let size = [width.max(1), height.max(1)];
+        let create_view = |label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label,
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let input = create_view(label!("aa::input"));
+        let history = [
+            create_view(label!("aa::history")),
+            create_view(label!("aa::history")),
+        ];
+        let bind_group = |history: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: label!("aa::bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(history),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params.buffer().as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let bind_groups = [bind_group(&history[0]), bind_group(&history[1])];
+        Self {
+            input,
+            history,
+            bind_groups,
+            size,
+        }
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("aa::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+/// The FXAA and TAA pipelines for `format`. TAA also writes the history
+/// texture, in the same format.
+fn create_pipelines(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let create = |entry_point, targets: &[Option<wgpu::ColorTargetState>]| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: label!("aa::pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                targets,
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    };
+    (
+        create("fs_fxaa", &[Some(format.into())]),
+        create("fs_taa", &[Some(format.into()), Some(format.into())]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_gpu;
+
+    #[test]
+    fn modes_parse_and_cycle() {
+        assert_eq!("FXAA".parse(), Ok(AaMode::Fxaa));
+        assert_eq!("msaa".parse(), Ok(AaMode::Msaa(4)));
+        assert_eq!("msaa8".parse(), Ok(AaMode::Msaa(8)));
+        assert!("msaa3".parse::<AaMode>().is_err());
+        assert_eq!(AaMode::Msaa(8).sample_count(), 8);
+        assert_eq!(AaMode::Taa.sample_count(), 1);
+
+        let mut mode = AaMode::None;
+        let mut seen = Vec::new();
+        loop {
+            seen.push(mode);
+            mode = mode.next();
+            if mode == AaMode::None {
+                break;
+            }
+        }
+        assert_eq!(
+            seen,
+            [AaMode::None, AaMode::Msaa(4), AaMode::Fxaa, AaMode::Taa]
+        );
+        for mode in seen {
+            assert_eq!(mode.name().parse(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn jitter_stays_within_the_pixel() {
+        let jitters: Vec<Vec2> = (0..TAA_JITTER_SAMPLES).map(taa_jitter).collect();
+        assert!(jitters.iter().all(|j| j.abs().max_element() < 0.5));
+        // Every frame in the cycle lands somewhere different.
+        for (i, a) in jitters.iter().enumerate() {
+            assert!(jitters[i + 1..].iter().all(|b| a != b));
+        }
+        assert_eq!(taa_jitter(TAA_JITTER_SAMPLES), jitters[0]);
+    }
+
+    #[test]
+    fn fxaa_softens_a_hard_edge() {
+        let gpu = test_gpu!();
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let size = 16;
+        let mut aa = PostAa::new(device, format, size, size, AaMode::Fxaa);
+        // Stands in for the scene: white above the diagonal, black below.
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                "@vertex fn vs(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                     let corners = array(vec2(-1.0, 1.0), vec2(1.0, 1.0), vec2(1.0, -1.0));
+                     return vec4<f32>(corners[i], 0.0, 1.0);
+                 }
+                 @fragment fn fs() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }"
+                    .into(),
+            ),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache: None,
+        });
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut frame = FrameEncoder::new(device, queue, None);
+        {
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: aa.input_view(),
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+        aa.draw(
+            queue,
+            &mut frame,
+            &output.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        frame.submit();
+        let pixels = crate::capture::read_texture_rgba(device, queue, &output).unwrap();
+        let gray = |x: u32, y: u32| pixels[((y * size + x) * 4) as usize];
+        // Flat areas are kept, while some pixels along the edge end up
+        // between black and white.
+        assert_eq!(gray(12, 2), 255);
+        assert_eq!(gray(2, 12), 0);
+        assert!((0..size).any(|x| (0..size).any(|y| (1..255).contains(&gray(x, y)))));
+    }
+}
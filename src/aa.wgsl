@@ -0,0 +1,105 @@
+struct Params {
+    // Size of one texel of the input in UV units.
+    texel_size: vec2<f32>,
+    // How much of the current frame goes into the TAA result; 1.0 drops the
+    // history, e.g. on the first frame.
+    current_weight: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+@group(0) @binding(2)
+var history: texture_2d<f32>;
+@group(0) @binding(3)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A single triangle covering the viewport.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+fn luma(rgb: vec3<f32>) -> f32 {
+    return dot(rgb, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_at(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    return textureSample(input, input_sampler, uv + offset * params.texel_size).rgb;
+}
+
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+// FXAA in the style of the original console version: find the edge
+// direction from the luma of the diagonal neighbours and blur along it,
+// unless that blur pulls in colors from across the edge.
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    let center = textureSample(input, input_sampler, uv);
+    let luma_nw = luma(sample_at(uv, vec2<f32>(-1.0, -1.0)));
+    let luma_ne = luma(sample_at(uv, vec2<f32>(1.0, -1.0)));
+    let luma_sw = luma(sample_at(uv, vec2<f32>(-1.0, 1.0)));
+    let luma_se = luma(sample_at(uv, vec2<f32>(1.0, 1.0)));
+    let luma_m = luma(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN,
+    );
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * scale, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX));
+
+    let near = 0.5 * (sample_at(uv, dir * (1.0 / 3.0 - 0.5)) + sample_at(uv, dir * (2.0 / 3.0 - 0.5)));
+    let far = near * 0.5 + 0.25 * (sample_at(uv, dir * -0.5) + sample_at(uv, dir * 0.5));
+    let luma_far = luma(far);
+    let rgb = select(far, near, luma_far < luma_min || luma_far > luma_max);
+    return vec4<f32>(rgb, center.a);
+}
+
+struct TaaOutput {
+    @location(0) color: vec4<f32>,
+    // The same again, read back as the history next frame.
+    @location(1) history: vec4<f32>,
+}
+
+// Blends the jittered current frame into the accumulated history. Without
+// motion vectors the history is only clamped to the current neighbourhood's
+// color range, which limits ghosting when things move.
+@fragment
+fn fs_taa(in: VertexOutput) -> TaaOutput {
+    let uv = in.tex_coords;
+    let current = textureSample(input, input_sampler, uv);
+    var low = current.rgb;
+    var high = current.rgb;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = sample_at(uv, vec2<f32>(f32(x), f32(y)));
+            low = min(low, neighbour);
+            high = max(high, neighbour);
+        }
+    }
+    let previous = clamp(textureSample(history, input_sampler, uv).rgb, low, high);
+    let rgb = mix(previous, current.rgb, params.current_weight);
+    let color = vec4<f32>(rgb, current.a);
+    return TaaOutput(color, color);
+}
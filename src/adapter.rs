@@ -0,0 +1,115 @@
+use crate::error::InitError;
+
+/// Environment variable naming the backends to use, e.g. `dx12,gl`.
+pub const BACKEND_ENV: &str = "WGPU_BACKEND";
+
+/// Parses a comma-separated list of `vulkan`, `dx12`, `metal`, `gl` and
+/// `webgpu`, returning the first unknown name on failure.
+pub fn parse_backends(list: &str) -> Result<wgpu::Backends, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(wgpu::Backends::empty(), |backends, name| {
+            let backend = match name.to_ascii_lowercase().as_str() {
+                "vulkan" => wgpu::Backends::VULKAN,
+                "dx12" => wgpu::Backends::DX12,
+                "metal" => wgpu::Backends::METAL,
+                "gl" => wgpu::Backends::GL,
+                "webgpu" => wgpu::Backends::BROWSER_WEBGPU,
+                _ => return Err(name.to_string()),
+            };
+            Ok(backends | backend)
+        })
+}
+
+/// The backends listed in `WGPU_BACKEND`, or all of them if it is unset,
+/// empty or names an unknown backend.
+pub fn backends_from_env() -> wgpu::Backends {
+    backends_or_all(std::env::var(BACKEND_ENV).ok().as_deref())
+}
+
+/// The backends in `list`, or all of them if there is no list, it is empty
+/// or it names an unknown backend.
+fn backends_or_all(list: Option<&str>) -> wgpu::Backends {
+    let Some(list) = list else {
+        return wgpu::Backends::all();
+    };
+    match parse_backends(list) {
+        Ok(backends) if !backends.is_empty() => backends,
+        Ok(_) => {
+            log::warn!("{BACKEND_ENV} lists no backends, using all of them");
+            wgpu::Backends::all()
+        }
+        Err(name) => {
+            log::warn!("unknown backend `{name}` in {BACKEND_ENV}, using all of them");
+            wgpu::Backends::all()
+        }
+    }
+}
+
+/// Logs the adapter's downlevel capabilities and checks they include `required`.
+pub fn check_downlevel_capabilities(
+    adapter: &wgpu::Adapter,
+    required: wgpu::DownlevelFlags,
+) -> Result<(), InitError> {
+    let capabilities = adapter.get_downlevel_capabilities();
+    log::info!(
+        "downlevel flags: {:?}, shader model: {:?}",
+        capabilities.flags,
+        capabilities.shader_model
+    );
+
+    let missing = required - capabilities.flags;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(InitError::MissingDownlevelFlags {
+            adapter: adapter.get_info().name,
+            missing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_names_ignore_case_and_whitespace() {
+        assert_eq!(
+            parse_backends(" Vulkan,GL , dx12 "),
+            Ok(wgpu::Backends::VULKAN | wgpu::Backends::GL | wgpu::Backends::DX12)
+        );
+        assert_eq!(
+            parse_backends("METAL,webgpu"),
+            Ok(wgpu::Backends::METAL | wgpu::Backends::BROWSER_WEBGPU)
+        );
+    }
+
+    #[test]
+    fn empty_backend_lists_parse_to_no_backends() {
+        for list in ["", " ", ",", " , ,"] {
+            assert_eq!(
+                parse_backends(list),
+                Ok(wgpu::Backends::empty()),
+                "{list:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_backend_is_returned() {
+        assert_eq!(
+            parse_backends("vulkan, OpenGL ,gl"),
+            Err("OpenGL".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_empty_or_bad_lists_fall_back_to_all_backends() {
+        for list in [None, Some(""), Some(" , "), Some("vulkan,glide")] {
+            assert_eq!(backends_or_all(list), wgpu::Backends::all(), "{list:?}");
+        }
+        assert_eq!(backends_or_all(Some("gl")), wgpu::Backends::GL);
+    }
+}
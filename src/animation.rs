@@ -0,0 +1,390 @@
+use std::time::Duration;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+/// A node's translation, rotation and scale, applied in that order from
+/// the outside in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// A scene's node tree, for turning per-node transforms into world
+/// matrices.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeHierarchy {
+    /// Each node's transform before any animation, indexed by node.
+    pub rest: Vec<Transform>,
+    /// Nodes in the scene and their parents, each parent before its
+    /// children.
+    order: Vec<(usize, Option<usize>)>,
+}
+
+impl NodeHierarchy {
+    /// A hierarchy of nodes with the given rest transforms and no scene
+    /// nodes yet.
+    pub fn new(rest: Vec<Transform>) -> Self {
+        Self {
+            rest,
+            order: Vec::new(),
+        }
+    }
+
+    /// Adds `node` to the scene, under `parent`, which must have been added
+    /// before it.
+    pub fn push(&mut self, node: usize, parent: Option<usize>) {
+        self.order.push((node, parent));
+    }
+
+    /// Each node's world matrix with `transforms`, indexed by node. Nodes
+    /// outside the scene get the identity.
+    pub fn world_matrices(&self, transforms: &[Transform]) -> Vec<Mat4> {
+        let mut world = vec![Mat4::IDENTITY; transforms.len()];
+        for &(node, parent) in &self.order {
+            let parent = parent.map_or(Mat4::IDENTITY, |parent| world[parent]);
+            world[node] = parent * transforms[node].matrix();
+        }
+        world
+    }
+}
+
+/// Which part of a node's [`Transform`] a [`Channel`] drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// How a [`Channel`] fills the time between keyframes, as in glTF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight lines, with rotations spherically interpolated.
+    Linear,
+    /// Holds each keyframe until the next.
+    Step,
+    /// Hermite splines; each keyframe has an in-tangent, a value and an
+    /// out-tangent, in that order.
+    CubicSpline,
+}
+
+/// Keyframes animating one property of one node.
+///
+/// Values are stored as `Vec4`: translations and scales with a zero `w`,
+/// rotations as quaternions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds, ascending.
+    pub times: Vec<f32>,
+    /// One value per keyframe, or three with [`Interpolation::CubicSpline`].
+    pub values: Vec<Vec4>,
+}
+
+impl Channel {
+    /// The value at `time`, holding the first and last keyframes outside
+    /// their range.
+    pub fn sample(&self, time: f32) -> Vec4 {
+        let value = |key: usize| match self.interpolation {
+            Interpolation::CubicSpline => self.values[3 * key + 1],
+            _ => self.values[key],
+        };
+        let last = self.times.len() - 1;
+        // The first keyframe after `time`.
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return value(0);
+        }
+        if next > last {
+            return value(last);
+        }
+        let key = next - 1;
+        let span = self.times[next] - self.times[key];
+        let t = (time - self.times[key]) / span;
+        match (self.interpolation, self.property) {
+            (Interpolation::Step, _) => value(key),
+            (Interpolation::Linear, Property::Rotation) => {
+                let from = Quat::from_vec4(value(key));
+                Vec4::from(from.slerp(Quat::from_vec4(value(next)), t))
+            }
+            (Interpolation::Linear, _) => value(key).lerp(value(next), t),
+            (Interpolation::CubicSpline, property) => {
+                let out_tangent = self.values[3 * key + 2] * span;
+                let in_tangent = self.values[3 * next] * span;
+                let (t2, t3) = (t * t, t * t * t);
+                let value = (2.0 * t3 - 3.0 * t2 + 1.0) * value(key)
+                    + (t3 - 2.0 * t2 + t) * out_tangent
+                    + (-2.0 * t3 + 3.0 * t2) * value(next)
+                    + (t3 - t2) * in_tangent;
+                match property {
+                    Property::Rotation => value.normalize_or(Vec4::W),
+                    _ => value,
+                }
+            }
+        }
+    }
+
+    /// Sets the channel's property of `transform` to its value at `time`.
+    pub fn apply(&self, time: f32, transform: &mut Transform) {
+        let value = self.sample(time);
+        match self.property {
+            Property::Translation => transform.translation = value.truncate(),
+            Property::Rotation => transform.rotation = Quat::from_vec4(value),
+            Property::Scale => transform.scale = value.truncate(),
+        }
+    }
+}
+
+/// A named set of channels played together, e.g. one glTF animation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    /// Time of the last keyframe of any channel, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|channel| channel.times.last())
+            .fold(0.0, |duration, &time| duration.max(time))
+    }
+
+    /// Poses `transforms`, indexed by node, at `time`. Nodes without a
+    /// channel keep their transforms.
+    pub fn apply(&self, time: f32, transforms: &mut [Transform]) {
+        for channel in &self.channels {
+            if let Some(transform) = transforms.get_mut(channel.node) {
+                channel.apply(time, transform);
+            }
+        }
+    }
+}
+
+/// The clock of a playing [`AnimationClip`].
+///
+/// [`advance`](Self::advance) moves the time by the frame time scaled by
+/// `speed`, which may be negative to play backwards. A looping player wraps
+/// around; otherwise it stops at either end.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationPlayer {
+    time: f32,
+    duration: f32,
+    playing: bool,
+    pub looping: bool,
+    pub speed: f32,
+}
+
+impl AnimationPlayer {
+    /// A looping player at the start of a clip of `duration` seconds,
+    /// paused until [`play`](Self::play).
+    pub fn new(duration: f32) -> Self {
+        Self {
+            time: 0.0,
+            duration: duration.max(0.0),
+            playing: false,
+            looping: true,
+            speed: 1.0,
+        }
+    }
+
+    /// Plays from the current time, or from the start again after a
+    /// non-looping clip has finished.
+    pub fn play(&mut self) {
+        if !self.looping && self.at_end() {
+            self.time = if self.speed < 0.0 { self.duration } else { 0.0 };
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Seconds into the clip.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Jumps to `time` seconds, clamped to the clip.
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration);
+    }
+
+    /// Moves the clock by `dt` if playing, and returns whether the time
+    /// changed.
+    pub fn advance(&mut self, dt: Duration) -> bool {
+        if !self.playing || self.duration == 0.0 {
+            return false;
+        }
+        let previous = self.time;
+        let time = self.time + dt.as_secs_f32() * self.speed;
+        if self.looping {
+            self.time = time.rem_euclid(self.duration);
+        } else {
+            self.time = time.clamp(0.0, self.duration);
+            if self.at_end() {
+                self.playing = false;
+            }
+        }
+        self.time != previous
+    }
+
+    /// Whether the clock has reached the end it is playing towards.
+    fn at_end(&self) -> bool {
+        if self.speed < 0.0 {
+            self.time <= 0.0
+        } else {
+            self.time >= self.duration
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(property: Property, interpolation: Interpolation, values: Vec<Vec4>) -> Channel {
+        Channel {
+            node: 0,
+            property,
+            interpolation,
+            times: vec![1.0, 3.0],
+            values,
+        }
+    }
+
+    #[test]
+    fn interpolates_between_two_keyframes() {
+        let translation = channel(
+            Property::Translation,
+            Interpolation::Linear,
+            vec![
+                Vec4::new(0.0, 2.0, 0.0, 0.0),
+                Vec4::new(4.0, 2.0, -8.0, 0.0),
+            ],
+        );
+        // A quarter of the way from 1 s to 3 s.
+        assert!(translation
+            .sample(1.5)
+            .abs_diff_eq(Vec4::new(1.0, 2.0, -2.0, 0.0), 1e-6));
+        // Held before the first and after the last keyframe.
+        assert_eq!(translation.sample(0.0), translation.values[0]);
+        assert_eq!(translation.sample(5.0), translation.values[1]);
+
+        let rotation = channel(
+            Property::Rotation,
+            Interpolation::Linear,
+            vec![
+                Vec4::from(Quat::IDENTITY),
+                Vec4::from(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+            ],
+        );
+        let mut transform = Transform::default();
+        rotation.apply(2.0, &mut transform);
+        assert!(transform
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4), 1e-6));
+
+        let step = channel(
+            Property::Scale,
+            Interpolation::Step,
+            vec![Vec4::ONE, Vec4::splat(2.0)],
+        );
+        assert_eq!(step.sample(2.9), Vec4::ONE);
+        assert_eq!(step.sample(3.0), Vec4::splat(2.0));
+    }
+
+    #[test]
+    fn cubic_spline_passes_through_its_keyframes() {
+        let (a, b) = (Vec4::new(0.0, 0.0, 0.0, 0.0), Vec4::new(2.0, 0.0, 0.0, 0.0));
+        let tangent = Vec4::new(1.0, 0.0, 0.0, 0.0);
+        let spline = channel(
+            Property::Translation,
+            Interpolation::CubicSpline,
+            vec![tangent, a, tangent, tangent, b, tangent],
+        );
+        assert_eq!(spline.sample(1.0), a);
+        assert_eq!(spline.sample(3.0), b);
+        // Tangents matching the straight line give the straight line.
+        assert!(spline
+            .sample(2.0)
+            .abs_diff_eq(Vec4::new(1.0, 0.0, 0.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn children_inherit_their_parents_transform() {
+        let mut nodes = NodeHierarchy::new(vec![
+            Transform {
+                translation: Vec3::X,
+                rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                ..Transform::default()
+            },
+            Transform {
+                translation: Vec3::X,
+                ..Transform::default()
+            },
+        ]);
+        nodes.push(0, None);
+        nodes.push(1, Some(0));
+        let world = nodes.world_matrices(&nodes.rest);
+        // The child's offset is rotated onto +Y by its parent.
+        assert!(world[1]
+            .transform_point3(Vec3::ZERO)
+            .abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn player_loops_pauses_and_stops() {
+        let second = Duration::from_secs(1);
+        let mut player = AnimationPlayer::new(2.0);
+        assert!(!player.advance(second));
+        player.play();
+        player.speed = 1.5;
+        assert!(player.advance(second));
+        assert_eq!(player.time(), 1.5);
+        player.advance(second);
+        assert_eq!(player.time(), 1.0);
+
+        player.pause();
+        assert!(!player.advance(second));
+        assert_eq!(player.time(), 1.0);
+
+        player.looping = false;
+        player.speed = -1.0;
+        player.play();
+        player.advance(second * 3);
+        assert_eq!(player.time(), 0.0);
+        assert!(!player.is_playing());
+        // Playing again after the end starts over.
+        player.speed = 1.0;
+        player.play();
+        player.advance(second);
+        assert_eq!(player.time(), 1.0);
+    }
+}
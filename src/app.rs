@@ -0,0 +1,513 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use web_time::Instant;
+use winit::application::ApplicationHandler;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy};
+use winit::window::{Window, WindowAttributes, WindowId};
+
+use crate::config::AppConfig;
+use crate::context::GpuContext;
+use crate::error::{InitError, SpawnWindowError};
+use crate::event::UserEvent;
+use crate::present::default_present_mode;
+use crate::ratelimit::RateLimitedLogger;
+use crate::surface::select_format;
+use crate::window_target::WindowTarget;
+
+/// The device and the main window, lent to [`Application`] callbacks.
+pub struct Context<'a> {
+    pub gpu: &'a GpuContext,
+    /// The main window and its surface. Closing it hides it and drops the
+    /// surface; the app exits once every window is closed.
+    pub target: &'a mut WindowTarget,
+    /// Sends [`UserEvent`]s to the app, e.g. from other threads; clone it to
+    /// keep one.
+    pub proxy: &'a EventLoopProxy<UserEvent>,
+    /// The windows still open, the main one included.
+    windows: &'a mut HashSet<WindowId>,
+}
+
+impl Context<'_> {
+    /// Opens another window sharing the device, with a surface configured
+    /// like the main window's at its own size.
+    ///
+    /// The app owns the returned target and handles the window's events
+    /// itself: dropping the target on `CloseRequested` closes the window.
+    /// The event loop exits once the last window is closed.
+    pub fn spawn_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Result<WindowTarget, SpawnWindowError> {
+        let window = event_loop
+            .create_window(attributes)
+            .map_err(SpawnWindowError::CreateWindow)?;
+        let mut target = WindowTarget::new(self.gpu, Arc::new(window), &self.target.config)
+            .map_err(SpawnWindowError::CreateSurface)?;
+        let surface = target.surface.as_ref().expect("created above");
+        let caps = surface.get_capabilities(&self.gpu.adapter);
+        if !caps.formats.contains(&target.config.format) {
+            return Err(SpawnWindowError::UnsupportedFormat(target.config.format));
+        }
+        target.resize_debounce = self.target.resize_debounce;
+        target.reconfigure(&self.gpu.device);
+        self.windows.insert(target.window.id());
+        Ok(target)
+    }
+}
+
+/// An app drawn into a main window by [`WgpuAppHandler`], which owns the
+/// device, the window's surface and the event loop plumbing.
+///
+/// Only [`init`](Self::init), [`resize`](Self::resize) and
+/// [`render`](Self::render) are required; the other callbacks default to
+/// doing nothing, except [`about_to_wait`](Self::about_to_wait), which
+/// redraws continuously.
+pub trait Application: Sized + 'static {
+    /// Startup options passed from [`run`] to [`init`](Self::init), e.g.
+    /// parsed from the command line; `()` for none.
+    type Options: 'static;
+
+    /// Creates the app's GPU resources once the device and the main
+    /// window's surface exist. [`resize`](Self::resize) is called with the
+    /// initial size right after.
+    fn init(
+        ctx: &mut Context<'_>,
+        config: &AppConfig,
+        options: Self::Options,
+    ) -> Result<Self, InitError>;
+
+    /// The surface was resized to `width` by `height`, both at least 1.
+    fn resize(&mut self, ctx: &mut Context<'_>, width: u32, height: u32);
+
+    /// Advances the app by `dt`, called before every frame.
+    fn update(&mut self, _ctx: &mut Context<'_>, _dt: Duration) {}
+
+    /// Draws a frame to the surface texture `view`. The handler presents it
+    /// afterwards.
+    fn render(
+        &mut self,
+        ctx: &mut Context<'_>,
+        view: &wgpu::TextureView,
+    ) -> Result<(), wgpu::SurfaceError>;
+
+    /// Called after every redraw of the main window, once its frame was
+    /// presented or failed to render.
+    fn frame_finished(&mut self, _ctx: &mut Context<'_>) {}
+
+    /// Any window event, before the handler acts on it. Events of the main
+    /// window are followed by the handler's own resizing and redrawing;
+    /// events of windows from [`Context::spawn_window`] are left to the
+    /// app. Closing any window exits once it was the last one open.
+    fn window_event(
+        &mut self,
+        _ctx: &mut Context<'_>,
+        _event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        _event: &WindowEvent,
+    ) {
+    }
+
+    /// Raw device input, e.g. unaccelerated mouse motion.
+    fn device_event(
+        &mut self,
+        _ctx: &mut Context<'_>,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        _event: &DeviceEvent,
+    ) {
+    }
+
+    /// An event sent through [`Context::proxy`].
+    fn user_event(
+        &mut self,
+        _ctx: &mut Context<'_>,
+        _event_loop: &ActiveEventLoop,
+        _event: UserEvent,
+    ) {
+    }
+
+    /// The event loop is about to wait for events. Decide here when the
+    /// next frame is drawn; the default redraws continuously.
+    fn about_to_wait(&mut self, ctx: &mut Context<'_>, _event_loop: &ActiveEventLoop) {
+        ctx.target.window.request_redraw();
+    }
+
+    /// The main window's surface was dropped; drop any other surfaces too.
+    fn suspended(&mut self, _ctx: &mut Context<'_>) {}
+
+    /// The main window's surface was recreated after a suspend.
+    fn resumed(&mut self, _ctx: &mut Context<'_>) {}
+
+    /// The event loop is exiting.
+    fn exiting(&mut self, _ctx: &mut Context<'_>) {}
+}
+
+struct AppState<A> {
+    context: GpuContext,
+    target: WindowTarget,
+    windows: HashSet<WindowId>,
+    app: A,
+    last_update: Option<Instant>,
+}
+
+impl<A: Application> AppState<A> {
+    async fn new(
+        window: Arc<Window>,
+        config: &AppConfig,
+        options: A::Options,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) -> Result<Self, InitError> {
+        let instance = GpuContext::create_instance(config);
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(InitError::CreateSurface)?;
+        let context = GpuContext::new(instance, Some(&surface), config).await?;
+
+        let caps = surface.get_capabilities(&context.adapter);
+        #[cfg(target_arch = "wasm32")]
+        let mut size = crate::web::canvas_size(&window);
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut size = window.inner_size();
+        size.width = size.width.max(1);
+        size.height = size.height.max(1);
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: select_format(&caps.formats, config.hdr_output),
+            width: size.width,
+            height: size.height,
+            present_mode: default_present_mode(&caps.present_modes, config.safe_mode),
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let mut target = WindowTarget::with_surface(window, surface, surface_config);
+        target.resize_debounce = config.resize_debounce;
+        target.reconfigure(&context.device);
+
+        // Report invalid resources as an error from here rather than through
+        // the uncaptured error handler, which can only log them.
+        context
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut windows = HashSet::from([target.window.id()]);
+        let mut ctx = Context {
+            gpu: &context,
+            target: &mut target,
+            proxy,
+            windows: &mut windows,
+        };
+        let app = A::init(&mut ctx, config, options).map(|mut app| {
+            app.resize(&mut ctx, size.width, size.height);
+            app
+        });
+        if let Some(error) = context.device.pop_error_scope().await {
+            return Err(InitError::Validation(error));
+        }
+        Ok(Self {
+            app: app?,
+            context,
+            target,
+            windows,
+            last_update: None,
+        })
+    }
+
+    /// Splits off the app from the [`Context`] lent to it.
+    fn parts<'a>(&'a mut self, proxy: &'a EventLoopProxy<UserEvent>) -> (&'a mut A, Context<'a>) {
+        let ctx = Context {
+            gpu: &self.context,
+            target: &mut self.target,
+            proxy,
+            windows: &mut self.windows,
+        };
+        (&mut self.app, ctx)
+    }
+
+    /// Recreates the surface after a suspend, keeping the app and its GPU
+    /// resources.
+    fn resume(&mut self, proxy: &EventLoopProxy<UserEvent>) {
+        if !self.main_window_open() {
+            let (app, mut ctx) = self.parts(proxy);
+            app.resumed(&mut ctx);
+            return;
+        }
+        if let Err(e) = self.target.resume(&self.context) {
+            log::error!("failed to recreate the surface after resuming: {e}");
+            return;
+        }
+        let size = self.target.window.inner_size();
+        if size.width > 0 && size.height > 0 {
+            self.target.set_resized(size, Instant::now());
+        }
+        let (app, mut ctx) = self.parts(proxy);
+        app.resumed(&mut ctx);
+        self.target.window.request_redraw();
+    }
+
+    fn main_window_open(&self) -> bool {
+        self.windows.contains(&self.target.window.id())
+    }
+
+    /// Forgets a closed window, hiding the main one and dropping its surface
+    /// since the app keeps it. Returns whether it was the last one open.
+    fn close_window(&mut self, id: WindowId) -> bool {
+        self.windows.remove(&id);
+        if id == self.target.window.id() {
+            self.target.suspend();
+            self.target.window.set_visible(false);
+        }
+        self.windows.is_empty()
+    }
+
+    fn resized(&mut self, proxy: &EventLoopProxy<UserEvent>) {
+        let wgpu::SurfaceConfiguration { width, height, .. } = self.target.config;
+        let (app, mut ctx) = self.parts(proxy);
+        app.resize(&mut ctx, width, height);
+    }
+
+    fn render(&mut self, proxy: &EventLoopProxy<UserEvent>) -> Result<(), wgpu::SurfaceError> {
+        let now = Instant::now();
+        if self.target.resize_if_needed(&self.context.device, now) {
+            self.resized(proxy);
+        }
+        let dt = self
+            .last_update
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        let (app, mut ctx) = self.parts(proxy);
+        app.update(&mut ctx, dt);
+
+        // Suspended: nothing to draw to until `resume`.
+        let Some(surface) = &self.target.surface else {
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (app, mut ctx) = self.parts(proxy);
+        app.render(&mut ctx, &view)?;
+        self.target.window.pre_present_notify();
+        output.present();
+        Ok(())
+    }
+}
+
+/// Runs an [`Application`] in a main window.
+///
+/// Pass it to [`run`], or to `EventLoop::run_app` directly.
+pub struct WgpuAppHandler<A: Application> {
+    title: String,
+    config: AppConfig,
+    /// Taken by the first `resumed`, which creates the app.
+    options: Option<A::Options>,
+    proxy: EventLoopProxy<UserEvent>,
+    state: Arc<Mutex<Option<AppState<A>>>>,
+    error_log: RateLimitedLogger,
+}
+
+impl<A: Application> WgpuAppHandler<A> {
+    /// `proxy` comes from the event loop the handler will run on, created
+    /// with `EventLoop::with_user_event`.
+    pub fn new(
+        title: impl Into<String>,
+        config: AppConfig,
+        options: A::Options,
+        proxy: EventLoopProxy<UserEvent>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            config,
+            options: Some(options),
+            proxy,
+            state: Arc::new(Mutex::new(None)),
+            error_log: RateLimitedLogger::default(),
+        }
+    }
+}
+
+impl<A: Application> ApplicationHandler<UserEvent> for WgpuAppHandler<A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Resumed again after a suspend, or a repeated event on platforms
+        // that send more than one: keep the app and only restore surfaces.
+        if let Some(state) = self.state.lock().as_mut() {
+            state.resume(&self.proxy);
+            return;
+        }
+        let Some(options) = self.options.take() else {
+            return;
+        };
+
+        let window_attributes = Window::default_attributes().with_title(&self.title);
+        let window = match event_loop.create_window(window_attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                log::error!("failed to create the window: {e}");
+                event_loop.exit();
+                return;
+            }
+        };
+        let config = self.config.clone();
+        let proxy = self.proxy.clone();
+        let init = {
+            let window = window.clone();
+            async move { AppState::<A>::new(window, &config, options, &proxy).await }
+        };
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                // Adapter and device requests can't be blocked on in the
+                // browser; until they finish events are ignored.
+                crate::web::attach_canvas(&window);
+                let state = self.state.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match init.await {
+                        Ok(app_state) => {
+                            state.lock().replace(app_state);
+                            window.request_redraw();
+                        }
+                        Err(e) => log::error!("failed to initialize: {e}"),
+                    }
+                });
+            } else {
+                match pollster::block_on(init) {
+                    Ok(app_state) => {
+                        self.state.lock().replace(app_state);
+                    }
+                    Err(e) => {
+                        log::error!("failed to initialize: {e}");
+                        event_loop.exit();
+                    }
+                }
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let mut state_guard = self.state.lock();
+        let Some(state) = state_guard.as_mut() else {
+            return;
+        };
+        let (app, mut ctx) = state.parts(&self.proxy);
+        app.window_event(&mut ctx, event_loop, window_id, &event);
+        if matches!(event, WindowEvent::CloseRequested) && state.close_window(window_id) {
+            event_loop.exit();
+            return;
+        }
+        if window_id != state.target.window.id() || !state.main_window_open() {
+            return;
+        }
+        match event {
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                state.target.set_resized(size, Instant::now());
+            }
+            WindowEvent::ScaleFactorChanged { .. } => {
+                let size = state.target.window.inner_size();
+                if size.width > 0 && size.height > 0 {
+                    state.target.set_resized(size, Instant::now());
+                }
+            }
+            // The app may have exited while handling the event.
+            WindowEvent::RedrawRequested if !event_loop.exiting() => {
+                match state.render(&self.proxy) {
+                    Ok(()) => {}
+                    Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                        self.error_log
+                            .log(log::Level::Warn, &format!("{e}, reconfiguring the surface"));
+                        // The window may be outdated because of a size that
+                        // hasn't settled yet; apply it now.
+                        if state.target.apply_resize(&state.context.device) {
+                            state.resized(&self.proxy);
+                        } else {
+                            state.target.reconfigure(&state.context.device);
+                        }
+                        state.target.window.request_redraw();
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("out of memory acquiring a frame, exiting");
+                        event_loop.exit();
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        log::debug!("timed out acquiring a frame, skipping it");
+                    }
+                    Err(e) => self.error_log.log(log::Level::Error, &format!("{e:?}")),
+                }
+                let (app, mut ctx) = state.parts(&self.proxy);
+                app.frame_finished(&mut ctx);
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let Some(state) = self.state.lock().as_mut() {
+            let (app, mut ctx) = state.parts(&self.proxy);
+            app.device_event(&mut ctx, event_loop, device_id, &event);
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        if let Some(state) = self.state.lock().as_mut() {
+            let (app, mut ctx) = state.parts(&self.proxy);
+            app.user_event(&mut ctx, event_loop, event);
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = self.state.lock().as_mut() {
+            state.target.suspend();
+            let (app, mut ctx) = state.parts(&self.proxy);
+            app.suspended(&mut ctx);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(state) = self.state.lock().as_mut() {
+            let (app, mut ctx) = state.parts(&self.proxy);
+            app.about_to_wait(&mut ctx, event_loop);
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = self.state.lock().as_mut() {
+            let (app, mut ctx) = state.parts(&self.proxy);
+            app.exiting(&mut ctx);
+        }
+    }
+}
+
+/// Opens a window titled `title` and runs `A` in it until it is closed.
+///
+/// On the web this returns immediately and the browser drives the loop.
+pub fn run<A: Application>(
+    title: &str,
+    config: AppConfig,
+    options: A::Options,
+) -> Result<(), winit::error::EventLoopError> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let handler = WgpuAppHandler::<A>::new(title, config, options, event_loop.create_proxy());
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            use winit::platform::web::EventLoopExtWebSys;
+            event_loop.spawn_app(handler);
+            Ok(())
+        } else {
+            let mut handler = handler;
+            event_loop.run_app(&mut handler)
+        }
+    }
+}
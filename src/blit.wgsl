@@ -0,0 +1,60 @@
+// Set when the intermediate target holds linear values and the output
+// format is not sRGB, so the encode happens here instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+// Set when the intermediate target holds encoded values and the output
+// format expects linear ones.
+override DECODE_SRGB: bool = false;
+
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+@group(0) @binding(1)
+var scene_sampler: sampler;
+
+struct Output {
+    // Largest value the output can show: 1.0 for SDR, more on an HDR
+    // surface, where values up to the display's peak pass through.
+    max_value: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> output: Output;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A single triangle covering the viewport.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+fn srgb_to_linear(value: vec3<f32>) -> vec3<f32> {
+    let low = value / 12.92;
+    let high = pow((value + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, value <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(scene, scene_sampler, in.tex_coords);
+    let rgb = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(output.max_value));
+    if ENCODE_SRGB {
+        return vec4<f32>(linear_to_srgb(min(rgb, vec3<f32>(1.0))), color.a);
+    }
+    if DECODE_SRGB {
+        return vec4<f32>(srgb_to_linear(min(rgb, vec3<f32>(1.0))), color.a);
+    }
+    return vec4<f32>(rgb, color.a);
+}
@@ -0,0 +1,173 @@
+use std::ops::Range;
+use std::thread;
+
+use crate::label;
+
+/// The attachments of the pass bundles will be executed in; a bundle can
+/// only run in a pass with exactly these formats and sample count.
+#[derive(Debug, Clone, Copy)]
+pub struct BundleTarget {
+    pub color_format: wgpu::TextureFormat,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub sample_count: u32,
+}
+
+/// Records `count` draws into render bundles, splitting them into up to
+/// `threads` contiguous ranges that are each encoded on their own thread.
+///
+/// Executing the bundles in the returned order draws the same as calling
+/// `encode` once with `0..count`. Bundles don't inherit pass state, so
+/// `encode` must set the pipeline, bind groups and buffers itself.
+/// `RenderBundleEncoder` isn't `Send`, so every thread creates its own and
+/// only the finished bundles are handed back. With one thread, or on the web
+/// where there are none, everything goes into a single bundle on the calling
+/// thread.
+pub fn encode_bundles<'a>(
+    device: &wgpu::Device,
+    target: &BundleTarget,
+    count: usize,
+    threads: usize,
+    encode: impl Fn(&mut wgpu::RenderBundleEncoder<'a>, Range<usize>) + Sync,
+) -> Vec<wgpu::RenderBundle> {
+    // No threads to spread over on the web.
+    let threads = if cfg!(target_arch = "wasm32") {
+        1
+    } else {
+        threads
+    };
+    let ranges = split(count, threads);
+    let record = |range: Range<usize>| {
+        let mut encoder =
+            device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: label!("bundle::encoder"),
+                color_formats: &[Some(target.color_format)],
+                depth_stencil: target
+                    .depth_format
+                    .map(|format| wgpu::RenderBundleDepthStencil {
+                        format,
+                        depth_read_only: false,
+                        stencil_read_only: true,
+                    }),
+                sample_count: target.sample_count,
+                multiview: None,
+            });
+        encode(&mut encoder, range);
+        encoder.finish(&wgpu::RenderBundleDescriptor {
+            label: label!("bundle::bundle"),
+        })
+    };
+    if ranges.len() <= 1 {
+        return ranges.into_iter().map(record).collect();
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = ranges
+            .into_iter()
+            .map(|range| scope.spawn(|| record(range)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+/// Render bundles kept from frame to frame, recorded again only when the key
+/// they were recorded for changes.
+///
+/// The key should hold everything the bundles bind and how many draws they
+/// make. wgpu handles compare by identity, so a pipeline or bind group that
+/// was rebuilt, e.g. for a new depth bias, sampler or LOD bias, counts as a
+/// change, while new contents written into the same buffers don't.
+pub struct BundleCache<K> {
+    recorded: Option<(K, Vec<wgpu::RenderBundle>)>,
+}
+
+impl<K> Default for BundleCache<K> {
+    fn default() -> Self {
+        Self { recorded: None }
+    }
+}
+
+impl<K: PartialEq> BundleCache<K> {
+    /// The bundles recorded for `key`, calling `record` for them first if
+    /// the last ones were recorded for a different key.
+    pub fn get_or_record(
+        &mut self,
+        key: K,
+        record: impl FnOnce() -> Vec<wgpu::RenderBundle>,
+    ) -> &[wgpu::RenderBundle] {
+        let (_, bundles) = match self.recorded.take() {
+            Some((recorded, bundles)) if recorded == key => {
+                self.recorded.insert((recorded, bundles))
+            }
+            _ => self.recorded.insert((key, record())),
+        };
+        bundles
+    }
+}
+
+/// Splits `0..count` into at most `parts` non-empty ranges of nearly equal
+/// length, in order.
+fn split(count: usize, parts: usize) -> Vec<Range<usize>> {
+    let parts = parts.clamp(1, count.max(1));
+    let chunk = count.div_ceil(parts);
+    (0..count)
+        .step_by(chunk.max(1))
+        .map(|start| start..(start + chunk).min(count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_gpu, triangle_pipeline};
+
+    #[test]
+    fn split_covers_the_range_in_order() {
+        assert!(split(0, 4).is_empty());
+        assert_eq!(split(3, 8), vec![0..1, 1..2, 2..3]);
+        assert_eq!(split(10, 3), vec![0..4, 4..8, 8..10]);
+        assert_eq!(split(10, 0), vec![0..10]);
+    }
+
+    #[test]
+    fn cache_records_again_only_for_a_new_key() {
+        let mut cache = BundleCache::default();
+        let mut recorded = 0;
+        let mut record = || {
+            recorded += 1;
+            Vec::new()
+        };
+        cache.get_or_record((1, 100), &mut record);
+        cache.get_or_record((1, 100), &mut record);
+        cache.get_or_record((2, 100), &mut record);
+        cache.get_or_record((2, 50), &mut record);
+        cache.get_or_record((2, 50), &mut record);
+        assert_eq!(recorded, 3);
+    }
+
+    #[test]
+    fn encodes_a_bundle_per_thread() {
+        let gpu = test_gpu!();
+        let device = &gpu.device;
+        let target = BundleTarget {
+            color_format: wgpu::TextureFormat::Rgba8Unorm,
+            depth_format: None,
+            sample_count: 1,
+        };
+        let (pipeline, mesh) = triangle_pipeline(device, &target);
+        for (threads, expected) in [(1, 1), (4, 4), (16, 8)] {
+            let bundles = encode_bundles(device, &target, 8, threads, |encoder, range| {
+                encoder.set_pipeline(&pipeline);
+                for _ in range {
+                    mesh.draw_bundle(encoder, 0..1);
+                }
+            });
+            assert_eq!(bundles.len(), expected);
+        }
+    }
+}
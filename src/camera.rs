@@ -0,0 +1,378 @@
+use std::time::Duration;
+
+use glam::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::input::InputState;
+
+/// Vertical field of view of a new camera, in degrees.
+pub const DEFAULT_FOVY: f32 = 45.0;
+
+/// Range `fovy` and `target_fovy` are clamped to, in degrees.
+pub const FOVY_RANGE: (f32, f32) = (10.0, 120.0);
+
+/// How quickly `fovy` closes in on `target_fovy`: the remaining difference
+/// shrinks by a factor of e every `1 / FOVY_RATE` seconds.
+const FOVY_RATE: f32 = 10.0;
+
+/// Differences below this many degrees snap to the target, so the easing
+/// ends instead of creeping closer forever.
+const FOVY_EPSILON: f32 = 0.01;
+
+/// Pitch is kept this far short of straight up or down, in radians, where
+/// the view would flip around `up`.
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// A perspective camera looking from `eye` at `target`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    /// Width over height of the viewport.
+    pub aspect: f32,
+    /// Vertical field of view in degrees.
+    pub fovy: f32,
+    /// Field of view [`update_fovy`](Self::update_fovy) eases `fovy` toward.
+    pub target_fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            eye: Vec3::new(0.0, 1.0, 2.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            aspect,
+            fovy: DEFAULT_FOVY,
+            target_fovy: DEFAULT_FOVY,
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+
+    /// Sets the field of view to ease toward, clamped to [`FOVY_RANGE`].
+    pub fn set_target_fovy(&mut self, fovy: f32) {
+        self.target_fovy = fovy.clamp(FOVY_RANGE.0, FOVY_RANGE.1);
+    }
+
+    /// Moves `fovy` part of the way to `target_fovy` for a frame of `dt`.
+    /// The step is a fraction of the remaining difference that depends only
+    /// on `dt`, so the zoom looks the same at any frame rate and never
+    /// overshoots. Returns whether `fovy` changed, i.e. the projection needs
+    /// uploading again.
+    pub fn update_fovy(&mut self, dt: Duration) -> bool {
+        let target = self.target_fovy.clamp(FOVY_RANGE.0, FOVY_RANGE.1);
+        if self.fovy == target {
+            return false;
+        }
+        let t = 1.0 - (-FOVY_RATE * dt.as_secs_f32()).exp();
+        self.fovy += (target - self.fovy) * t;
+        if (target - self.fovy).abs() < FOVY_EPSILON {
+            self.fovy = target;
+        }
+        true
+    }
+
+    /// A camera `height` above `center` looking straight down, with -Z up on
+    /// screen and +X to the right, e.g. for a minimap.
+    pub fn top_down(center: Vec3, height: f32, aspect: f32) -> Self {
+        Self {
+            eye: center + Vec3::Y * height,
+            target: center,
+            up: Vec3::NEG_Z,
+            zfar: height * 2.0,
+            ..Self::new(aspect)
+        }
+    }
+
+    /// Right-handed view-projection matrix mapping depth to wgpu's 0..1 NDC
+    /// range (not OpenGL's -1..1).
+    pub fn build_view_projection_matrix(&self) -> Mat4 {
+        let view = Mat4::look_at_rh(self.eye, self.target, self.up);
+        let projection =
+            Mat4::perspective_rh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar);
+        projection * view
+    }
+}
+
+/// Flies a [`Camera`] with WASD, Q and E, and turns it with the mouse while
+/// looking around.
+///
+/// Input moves a target position and orientation; [`update`](Self::update)
+/// then eases the camera toward them. With a `smoothing` of 0 the camera
+/// jumps straight to the target, so input moves it directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraController {
+    /// Movement speed in units per second.
+    pub speed: f32,
+    /// Turn per unit of mouse motion, in radians.
+    pub sensitivity: f32,
+    /// Time constant of the easing in seconds: the remaining distance to the
+    /// target shrinks by a factor of e every `smoothing` seconds. 0 disables
+    /// smoothing.
+    pub smoothing: f32,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    target_position: Vec3,
+    target_yaw: f32,
+    target_pitch: f32,
+}
+
+impl CameraController {
+    /// Starts at `camera`'s position and view direction.
+    pub fn new(camera: &Camera, smoothing: f32) -> Self {
+        let forward = (camera.target - camera.eye).normalize_or(Vec3::NEG_Z);
+        let yaw = forward.z.atan2(forward.x);
+        let pitch = forward.y.asin().clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        Self {
+            speed: 3.0,
+            sensitivity: 0.003,
+            smoothing: smoothing.max(0.0),
+            position: camera.eye,
+            yaw,
+            pitch,
+            target_position: camera.eye,
+            target_yaw: yaw,
+            target_pitch: pitch,
+        }
+    }
+
+    /// Jumps to `camera`'s position and view direction, e.g. when it was
+    /// set from outside, without easing there.
+    pub fn set_pose(&mut self, camera: &Camera) {
+        *self = Self {
+            speed: self.speed,
+            sensitivity: self.sensitivity,
+            ..Self::new(camera, self.smoothing)
+        };
+    }
+
+    /// Moves the target by the keys held for a frame of `dt`, and turns it
+    /// by the frame's mouse motion if `mouse_look` is on. Nothing moves
+    /// while Ctrl, Alt or Super is held, so shortcuts like Ctrl+S don't
+    /// also walk the camera.
+    pub fn handle_input(&mut self, input: &InputState, mouse_look: bool, dt: Duration) {
+        let shortcut = ModifiersState::CONTROL | ModifiersState::ALT | ModifiersState::SUPER;
+        let moving = !input.modifiers().intersects(shortcut);
+        let axis = |positive, negative| {
+            if !moving {
+                return 0.0;
+            }
+            f32::from(u8::from(input.is_pressed(positive)))
+                - f32::from(u8::from(input.is_pressed(negative)))
+        };
+        let (sin_yaw, cos_yaw) = self.target_yaw.sin_cos();
+        let forward = Vec3::new(cos_yaw, 0.0, sin_yaw);
+        let right = Vec3::new(-sin_yaw, 0.0, cos_yaw);
+        let direction = forward * axis(KeyCode::KeyW, KeyCode::KeyS)
+            + right * axis(KeyCode::KeyD, KeyCode::KeyA)
+            + Vec3::Y * axis(KeyCode::KeyE, KeyCode::KeyQ);
+        self.target_position += direction.normalize_or_zero() * self.speed * dt.as_secs_f32();
+        if mouse_look {
+            let (dx, dy) = input.mouse_delta();
+            self.target_yaw += dx as f32 * self.sensitivity;
+            self.target_pitch =
+                (self.target_pitch - dy as f32 * self.sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        }
+    }
+
+    /// Eases the camera toward the target for a frame of `dt`. Returns
+    /// whether the camera moved, i.e. it needs uploading again.
+    pub fn update(&mut self, camera: &mut Camera, dt: Duration) -> bool {
+        let t = smoothing_weight(self.smoothing, dt);
+        self.position = self.position.lerp(self.target_position, t);
+        self.yaw += (self.target_yaw - self.yaw) * t;
+        self.pitch += (self.target_pitch - self.pitch) * t;
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let forward = Vec3::new(cos_yaw * cos_pitch, sin_pitch, sin_yaw * cos_pitch);
+        let before = (camera.eye, camera.target);
+        camera.eye = self.position;
+        camera.target = self.position + forward;
+        (camera.eye, camera.target) != before
+    }
+}
+
+/// Fraction of the remaining distance to cover in a frame of `dt` with a
+/// smoothing time constant of `smoothing` seconds; all of it at 0.
+fn smoothing_weight(smoothing: f32, dt: Duration) -> f32 {
+    if smoothing > 0.0 {
+        1.0 - (-dt.as_secs_f32() / smoothing).exp()
+    } else {
+        1.0
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+impl From<&Camera> for CameraUniform {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            view_proj: camera.build_view_projection_matrix().to_cols_array_2d(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_down_camera_looks_down_with_north_up() {
+        let center = Vec3::new(3.0, 0.0, -2.0);
+        let matrix = Camera::top_down(center, 10.0, 1.0).build_view_projection_matrix();
+        let project = |point: Vec3| matrix.project_point3(point);
+        assert!(project(center)
+            .truncate()
+            .abs_diff_eq(glam::Vec2::ZERO, 1e-5));
+        assert!(project(center + Vec3::X).x > 0.0);
+        assert!(project(center + Vec3::NEG_Z).y > 0.0);
+        assert!((0.0..1.0).contains(&project(center).z));
+    }
+
+    #[test]
+    fn fovy_converges_without_overshooting() {
+        for (from, to) in [(45.0, 20.0), (20.0, 45.0)] {
+            let mut camera = Camera::new(1.0);
+            camera.fovy = from;
+            camera.set_target_fovy(to);
+            let mut frames = 0;
+            while camera.update_fovy(Duration::from_millis(16)) {
+                let (low, high) = if from < to { (from, to) } else { (to, from) };
+                assert!((low..=high).contains(&camera.fovy), "{}", camera.fovy);
+                frames += 1;
+                assert!(frames < 1000, "never reached {to}");
+            }
+            assert_eq!(camera.fovy, to);
+        }
+    }
+
+    #[test]
+    fn fovy_eases_in_the_same_time_at_any_frame_rate() {
+        let elapsed = |dt: Duration| {
+            let mut camera = Camera::new(1.0);
+            camera.set_target_fovy(20.0);
+            let mut frames = 0;
+            while camera.update_fovy(dt) {
+                frames += 1;
+            }
+            dt * frames
+        };
+        let fast = elapsed(Duration::from_micros(4167)).as_secs_f32();
+        let slow = elapsed(Duration::from_micros(33_333)).as_secs_f32();
+        assert!((fast - slow).abs() < 0.05, "{fast}s vs {slow}s");
+    }
+
+    #[test]
+    fn smoothed_position_approaches_the_target_asymptotically() {
+        let mut camera = Camera::new(1.0);
+        let mut controller = CameraController::new(&camera, 0.1);
+        controller.target_position = camera.eye + Vec3::X;
+        let dt = Duration::from_millis(16);
+        let mut remaining = 1.0;
+        for _ in 0..50 {
+            controller.update(&mut camera, dt);
+            let distance = controller.target_position.distance(camera.eye);
+            // Always closer, but never there or past it.
+            assert!(distance < remaining && distance > 0.0, "{distance}");
+            assert!(camera.eye.x < controller.target_position.x);
+            remaining = distance;
+        }
+        assert!(remaining < 1e-3, "{remaining}");
+        // After one time constant about 1/e of the distance is left.
+        let mut camera = Camera::new(1.0);
+        let mut controller = CameraController::new(&camera, 0.1);
+        controller.target_position = camera.eye + Vec3::X;
+        controller.update(&mut camera, Duration::from_millis(100));
+        let left = controller.target_position.distance(camera.eye);
+        assert!((left - (-1.0f32).exp()).abs() < 1e-4, "{left}");
+    }
+
+    #[test]
+    fn zero_smoothing_moves_the_camera_directly() {
+        let mut camera = Camera::new(1.0);
+        camera.eye = Vec3::new(0.0, 2.5, 5.0);
+        let start = camera;
+        let mut controller = CameraController::new(&camera, 0.0);
+        // Starting out keeps the position and view direction.
+        controller.update(&mut camera, Duration::from_millis(16));
+        assert_eq!(camera.eye, start.eye);
+        let direction = (camera.target - camera.eye).normalize();
+        assert!(direction.abs_diff_eq((start.target - start.eye).normalize(), 1e-5));
+
+        controller.target_position += Vec3::new(1.0, 0.0, -2.0);
+        controller.target_yaw += 0.5;
+        assert!(controller.update(&mut camera, Duration::from_millis(16)));
+        assert_eq!(camera.eye, controller.target_position);
+        assert_eq!(
+            (controller.yaw, controller.pitch),
+            (controller.target_yaw, controller.target_pitch)
+        );
+    }
+
+    #[test]
+    fn set_pose_jumps_without_easing() {
+        let mut camera = Camera::new(1.0);
+        let mut controller = CameraController::new(&camera, 0.5);
+        controller.speed = 7.0;
+        camera.eye = Vec3::new(4.0, 1.0, -3.0);
+        camera.target = camera.eye + Vec3::Z;
+        let pose = camera;
+        controller.set_pose(&camera);
+        assert!(!controller.update(&mut camera, Duration::from_millis(16)));
+        assert!(camera.eye.abs_diff_eq(pose.eye, 1e-5));
+        assert!(camera.target.abs_diff_eq(pose.target, 1e-5));
+        assert_eq!((controller.speed, controller.smoothing), (7.0, 0.5));
+    }
+
+    #[test]
+    fn movement_keys_are_ignored_while_a_shortcut_modifier_is_held() {
+        use crate::replay::InputEvent;
+
+        let dt = Duration::from_millis(100);
+        for (modifiers, moves) in [
+            (ModifiersState::empty(), true),
+            (ModifiersState::SHIFT, true),
+            (ModifiersState::CONTROL, false),
+            (ModifiersState::ALT, false),
+            (ModifiersState::SUPER, false),
+            (ModifiersState::CONTROL | ModifiersState::SHIFT, false),
+        ] {
+            let camera = Camera::new(1.0);
+            let mut controller = CameraController::new(&camera, 0.0);
+            let mut input = InputState::default();
+            input.handle(&InputEvent::Modifiers(modifiers));
+            for code in [KeyCode::KeyW, KeyCode::KeyE] {
+                input.handle(&InputEvent::Key {
+                    code,
+                    pressed: true,
+                    repeat: false,
+                });
+            }
+            let start = controller.target_position;
+            controller.handle_input(&input, false, dt);
+            assert_eq!(controller.target_position != start, moves, "{modifiers:?}");
+        }
+    }
+
+    #[test]
+    fn target_fovy_is_clamped() {
+        let mut camera = Camera::new(1.0);
+        camera.set_target_fovy(1.0);
+        assert_eq!(camera.target_fovy, FOVY_RANGE.0);
+        camera.set_target_fovy(500.0);
+        assert_eq!(camera.target_fovy, FOVY_RANGE.1);
+        // A target set directly is clamped on the way.
+        camera.target_fovy = 500.0;
+        while camera.update_fovy(Duration::from_millis(16)) {}
+        assert_eq!(camera.fovy, FOVY_RANGE.1);
+    }
+}
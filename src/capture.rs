@@ -0,0 +1,329 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The requested capture exceeds `max_texture_dimension_2d`.
+    TooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    /// Only 8-bit RGBA/BGRA color and `R32Float` targets can be read back.
+    UnsupportedFormat(wgpu::TextureFormat),
+    Map(wgpu::BufferAsyncError),
+    Poll(wgpu::PollError),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { width, height, max } => write!(
+                f,
+                "capture size {width}x{height} exceeds the maximum texture dimension {max}"
+            ),
+            Self::UnsupportedFormat(format) => write!(f, "cannot capture format {format:?}"),
+            Self::Map(e) => write!(f, "failed to map readback buffer: {e}"),
+            Self::Poll(e) => write!(f, "failed to wait for the GPU: {e}"),
+            Self::Image(e) => write!(f, "failed to encode image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<image::ImageError> for CaptureError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+/// Copies a single-sampled 8-bit color texture to the CPU as tightly packed RGBA.
+///
+/// The texture needs `COPY_SRC` usage. Row padding is stripped and BGRA is
+/// swizzled to RGBA.
+pub fn read_texture_rgba(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, CaptureError> {
+    let format = texture.format();
+    let bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => return Err(CaptureError::UnsupportedFormat(format)),
+    };
+    let mut pixels = read_texture_rows(device, queue, texture)?;
+    if bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Copies a single-sampled `R32Float` texture, e.g. a
+/// [`LinearDepth`](crate::linear_depth::LinearDepth) target, to the CPU as
+/// tightly packed floats.
+///
+/// The texture needs `COPY_SRC` usage. Row padding is stripped.
+pub fn read_texture_r32float(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<f32>, CaptureError> {
+    let format = texture.format();
+    if format != wgpu::TextureFormat::R32Float {
+        return Err(CaptureError::UnsupportedFormat(format));
+    }
+    let bytes = read_texture_rows(device, queue, texture)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|texel| f32::from_ne_bytes([texel[0], texel[1], texel[2], texel[3]]))
+        .collect())
+}
+
+/// Copies a texture with 4-byte texels to the CPU, stripping the padding
+/// that rounds each row up to `COPY_BYTES_PER_ROW_ALIGNMENT` in the copy.
+fn read_texture_rows(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, CaptureError> {
+    let (width, height) = (texture.width(), texture.height());
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: crate::label!("capture::readback"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: crate::label!("capture::encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .map_err(CaptureError::Poll)?;
+    receiver
+        .recv()
+        .expect("map_async callback dropped")
+        .map_err(CaptureError::Map)?;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+    Ok(pixels)
+}
+
+/// Box-filters an RGBA image down by `factor` in each dimension.
+///
+/// With `srgb` set the color channels are averaged in linear space.
+pub fn downsample_rgba(pixels: &[u8], width: u32, height: u32, factor: u32, srgb: bool) -> Vec<u8> {
+    let (out_width, out_height) = (width / factor, height / factor);
+    let samples = (factor * factor) as f32;
+    let mut out = Vec::with_capacity((out_width * out_height * 4) as usize);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0.0f32; 4];
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let index = (((y * factor + sy) * width + x * factor + sx) * 4) as usize;
+                    for (channel, total) in sum.iter_mut().enumerate() {
+                        let value = pixels[index + channel] as f32 / 255.0;
+                        *total += if srgb && channel < 3 {
+                            srgb_to_linear(value)
+                        } else {
+                            value
+                        };
+                    }
+                }
+            }
+            for (channel, total) in sum.iter().enumerate() {
+                let value = total / samples;
+                let value = if srgb && channel < 3 {
+                    linear_to_srgb(value)
+                } else {
+                    value
+                };
+                out.push((value * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+    out
+}
+
+/// Sets the alpha channel of every RGBA pixel to fully opaque.
+pub fn force_opaque(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::test_support::test_gpu;
+
+    /// Draws a quad over the middle of the target in 50% transparent red.
+    const QUAD_SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    return vec4<f32>(corner - 0.5, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.5, 0.0, 0.0, 0.5);
+}
+"#;
+
+    #[test]
+    fn downsampling_averages_alpha() {
+        let pixels = [
+            [255, 0, 0, 255],
+            [255, 0, 0, 0],
+            [255, 0, 0, 255],
+            [255, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(downsample_rgba(&pixels, 2, 2, 2, false), [255, 0, 0, 128]);
+        let mut pixels = pixels;
+        force_opaque(&mut pixels);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn saved_capture_keeps_alpha() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: crate::label!("capture::test_target"),
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: crate::label!("capture::test_shader"),
+            source: wgpu::ShaderSource::Wgsl(QUAD_SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: crate::label!("capture::test_pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: crate::label!("capture::test_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.draw(0..4, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let pixels = read_texture_rgba(&device, &queue, &texture).unwrap();
+        let path = std::env::temp_dir().join(format!("learn1-alpha-{}.png", std::process::id()));
+        image::RgbaImage::from_raw(4, 4, pixels)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let saved = image::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(saved.color(), image::ColorType::Rgba8);
+        let saved = saved.to_rgba8();
+        assert_eq!(saved.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(saved.get_pixel(1, 1).0, [128, 0, 0, 128]);
+        assert_eq!(saved.get_pixel(2, 2).0, [128, 0, 0, 128]);
+        assert_eq!(saved.get_pixel(3, 3).0, [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use wgpu::util::DeviceExt;
+
+use crate::label;
+
+const SHADER: &str = include_str!("compute.wgsl");
+
+/// Invocations per workgroup; shaders run by [`ComputePass`] must declare
+/// `@workgroup_size(64)`.
+pub const WORKGROUP_SIZE: u32 = 64;
+
+#[derive(Debug)]
+pub enum ComputeError {
+    Map(wgpu::BufferAsyncError),
+    Poll(wgpu::PollError),
+}
+
+impl fmt::Display for ComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Map(e) => write!(f, "failed to map the result buffer: {e}"),
+            Self::Poll(e) => write!(f, "failed to wait for the GPU: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ComputeError {}
+
+/// A compute pipeline that runs over an array of `f32`s in place, bound as
+/// `var<storage, read_write>` at group 0, binding 0.
+pub struct ComputePass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl ComputePass {
+    /// Builds the pipeline for `entry_point` in the WGSL `source`.
+    pub fn new(device: &wgpu::Device, source: &str, entry_point: &str) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("compute::shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("compute::bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("compute::pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: label!("compute::pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Runs one invocation per element of `input` and returns the array
+    /// afterwards, blocking until the GPU is done.
+    pub fn run(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        input: &[f32],
+    ) -> Result<Vec<f32>, ComputeError> {
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        let size = std::mem::size_of_val(input) as wgpu::BufferAddress;
+        let storage = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: label!("compute::storage"),
+            contents: bytemuck::cast_slice(input),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("compute::staging"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: label!("compute::bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: storage.as_entire_binding(),
+            }],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: label!("compute::encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: label!("compute::pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroup_count(input.len()), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(ComputeError::Poll)?;
+        receiver
+            .recv()
+            .expect("map_async callback dropped")
+            .map_err(ComputeError::Map)?;
+        let output = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        Ok(output)
+    }
+}
+
+/// Workgroups needed for one invocation per element; the last one is only
+/// partly used, so shaders must bounds-check their index.
+pub(crate) fn workgroup_count(len: usize) -> u32 {
+    (len as u32).div_ceil(WORKGROUP_SIZE)
+}
+
+/// Doubles every element of `input` on the GPU, as an example of
+/// [`ComputePass`].
+pub fn run_compute(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    input: &[f32],
+) -> Result<Vec<f32>, ComputeError> {
+    ComputePass::new(device, SHADER, "double").run(device, queue, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::test_support::test_gpu;
+
+    #[test]
+    fn dispatch_rounds_up_to_whole_workgroups() {
+        assert_eq!(workgroup_count(0), 0);
+        assert_eq!(workgroup_count(1), 1);
+        assert_eq!(workgroup_count(64), 1);
+        assert_eq!(workgroup_count(65), 2);
+        assert_eq!(workgroup_count(1000), 16);
+    }
+
+    #[test]
+    fn doubles_inputs_of_any_length() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        for len in [0, 1, 3, 64, 65, 1000] {
+            let input: Vec<f32> = (0..len).map(|i| i as f32 - 10.0).collect();
+            let output = run_compute(&device, &queue, &input).unwrap();
+            let expected: Vec<f32> = input.iter().map(|x| x * 2.0).collect();
+            assert_eq!(output, expected, "length {len}");
+        }
+    }
+}
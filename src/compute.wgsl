@@ -0,0 +1,15 @@
+// Must match WORKGROUP_SIZE in compute.rs.
+const WORKGROUP_SIZE: u32 = 64u;
+
+@group(0) @binding(0)
+var<storage, read_write> data: array<f32>;
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn double(@builtin(global_invocation_id) id: vec3<u32>) {
+    // The last workgroup runs past the end unless the length is a multiple
+    // of the workgroup size.
+    if id.x >= arrayLength(&data) {
+        return;
+    }
+    data[id.x] = data[id.x] * 2.0;
+}
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use crate::aa::AaMode;
+use crate::culling::CullMode;
+use crate::gpu_timer::TimedPass;
+use crate::msaa::MsaaResolve;
+
+/// Startup options for the renderer.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Present to an HDR (`Rgba16Float`, extended-range) surface when the
+    /// surface supports one, falling back to SDR otherwise.
+    pub hdr_output: bool,
+    /// Peak luminance of the display in nits. In extended-range output a
+    /// value of 1.0 is 80 nits, so colors are passed through up to
+    /// `max_luminance / 80.0`.
+    pub max_luminance: f32,
+    /// Draw once with every pipeline before the first frame so driver shader
+    /// compilation doesn't stall it.
+    pub warm_up_pipelines: bool,
+    /// Start with optional features off and the most compatible settings,
+    /// e.g. after the previous run crashed.
+    pub safe_mode: bool,
+    /// Drop to `idle_fps` after this long without input; `None`, the
+    /// default, disables it.
+    pub idle_timeout: Option<Duration>,
+    pub idle_fps: u32,
+    /// On the first uncaptured GPU error, save a screenshot and a report with
+    /// the error text and current settings.
+    pub dump_on_gpu_error: bool,
+    /// Downlevel capabilities the adapter must support, e.g. when targeting
+    /// WebGL2/GLES. Initialization fails listing whichever are missing.
+    pub required_downlevel_flags: wgpu::DownlevelFlags,
+    /// Lower bound on the time between presents, to cap GPU load (heat, coil
+    /// whine) even with vsync off. Unlike the frame pacer's target interval,
+    /// which aims for an exact cadence and corrects toward it, this is only
+    /// a floor: frames are never started sooner, but may come later.
+    pub min_frame_time: Option<Duration>,
+    /// Frame rate cap, e.g. for `Mailbox` or `Immediate` present modes,
+    /// which otherwise render as fast as possible. `None` is uncapped.
+    pub max_fps: Option<u32>,
+    /// Wake a little before each capped frame and busy-wait the rest, for
+    /// steadier frame times at the cost of a spinning core. Off by default,
+    /// leaving the waiting to `ControlFlow::WaitUntil`.
+    pub spin_frame_limiter: bool,
+    /// How long the window size must stay unchanged before the surface is
+    /// resized to it, so a live resize doesn't reconfigure every frame.
+    pub resize_debounce: Duration,
+    /// Adapter preference used the next time the GPU is initialized.
+    pub power_preference: wgpu::PowerPreference,
+    /// Frame cap while running on battery (with the `battery` feature).
+    pub battery_fps: u32,
+    /// Keep the rendered alpha channel in screenshots, e.g. for compositing.
+    /// Otherwise captures are made opaque to match what is shown on screen.
+    pub capture_with_alpha: bool,
+    /// Render the scene into a linear `Rgba16Float` target and encode to the
+    /// surface format in a final pass, so blending is done in linear space.
+    /// Takes precedence over `offscreen_format`.
+    pub linear_working_space: bool,
+    /// Format of the intermediate texture the scene is rendered into before
+    /// being blitted to the surface. `None` renders to the surface directly.
+    pub offscreen_format: Option<wgpu::TextureFormat>,
+    /// Fraction of the window's resolution the scene is drawn at in each
+    /// direction, clamped to 0.25-1. Below 1 the scene is upscaled to the
+    /// window before the overlays, text and UI are drawn at full resolution,
+    /// so they stay sharp.
+    pub render_scale: f32,
+    /// GPU frame time the render scale adapts to: once a second it steps
+    /// down while the timed passes average over this, and back up while
+    /// they'd stay well under it. `None` keeps `render_scale` fixed. Needs
+    /// timestamp queries.
+    pub render_scale_budget: Option<Duration>,
+    /// Lowest and highest render scale the budget adjusts it to.
+    pub render_scale_bounds: (f32, f32),
+    /// How the scene is antialiased. MSAA falls back to 1 sample if the
+    /// adapter can't multisample the surface or depth format.
+    pub aa_mode: AaMode,
+    /// How the multisampled scene is resolved; see [`MsaaResolve`] for when
+    /// each fits.
+    pub msaa_resolve: MsaaResolve,
+    /// Graphics APIs the adapter may use. `None` reads them from the
+    /// `WGPU_BACKEND` environment variable, falling back to all of them.
+    pub backends: Option<wgpu::Backends>,
+    /// Threads that record the scene's draws into render bundles. 1 records
+    /// them straight into the render pass.
+    pub encode_threads: usize,
+    /// Longer side, in physical pixels, of the debug texture viewer.
+    pub texture_viewer_size: u32,
+    /// Have the scene's vertex shader read mesh vertices and instances from
+    /// storage buffers instead of vertex buffers, where the adapter supports
+    /// it; see [`pulling`](crate::pulling).
+    pub vertex_pulling: bool,
+    /// Time constant in seconds the camera eases toward where the controls
+    /// move it; 0 moves it directly. See
+    /// [`CameraController::smoothing`](crate::camera::CameraController::smoothing).
+    pub camera_smoothing: f32,
+    /// GPU time budgets for individual passes: a pass whose average goes
+    /// over its budget is logged by name. Needs timestamp queries.
+    pub gpu_pass_budgets: Vec<(TimedPass, Duration)>,
+    /// How long the clear color takes to fade to the next scene's preferred
+    /// color when switching scenes; zero switches at once.
+    pub scene_fade: Duration,
+    /// Where the model grid's instances are culled before drawing; cycled
+    /// with K. Render bundles and vertex pulling still draw every instance.
+    pub culling: CullMode,
+    /// With GPU culling, also skip instances hidden behind the previous
+    /// frame's depth buffer. Needs single-sampled depth, so not with MSAA.
+    pub occlusion_culling: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            hdr_output: false,
+            max_luminance: 1000.0,
+            warm_up_pipelines: true,
+            safe_mode: false,
+            idle_timeout: None,
+            idle_fps: 15,
+            dump_on_gpu_error: true,
+            required_downlevel_flags: wgpu::DownlevelFlags::empty(),
+            min_frame_time: None,
+            max_fps: None,
+            spin_frame_limiter: false,
+            resize_debounce: crate::window_target::RESIZE_DEBOUNCE,
+            power_preference: wgpu::PowerPreference::default(),
+            battery_fps: 30,
+            capture_with_alpha: false,
+            linear_working_space: false,
+            offscreen_format: Some(crate::offscreen::DEFAULT_FORMAT),
+            render_scale: 1.0,
+            render_scale_budget: None,
+            render_scale_bounds: (0.5, 1.0),
+            aa_mode: AaMode::Msaa(4),
+            msaa_resolve: MsaaResolve::Implicit,
+            backends: None,
+            encode_threads: 1,
+            texture_viewer_size: 256,
+            vertex_pulling: false,
+            camera_smoothing: 0.0,
+            gpu_pass_budgets: Vec::new(),
+            scene_fade: Duration::from_millis(300),
+            culling: CullMode::Off,
+            occlusion_culling: true,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Turns on safe mode, disabling HDR output, the linear working space,
+    /// the offscreen target, antialiasing, non-`Fifo` present modes, vertex
+    /// pulling, GPU culling, multithreaded encoding and the pipeline warm-up.
+    pub fn enable_safe_mode(&mut self) {
+        self.safe_mode = true;
+        self.hdr_output = false;
+        self.linear_working_space = false;
+        self.offscreen_format = None;
+        self.aa_mode = AaMode::None;
+        self.vertex_pulling = false;
+        self.culling = CullMode::Off;
+        self.encode_threads = 1;
+        self.warm_up_pipelines = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_mode_turns_off_the_optional_features() {
+        let mut config = AppConfig {
+            hdr_output: true,
+            linear_working_space: true,
+            vertex_pulling: true,
+            culling: CullMode::Gpu,
+            encode_threads: 4,
+            ..AppConfig::default()
+        };
+        config.enable_safe_mode();
+        assert!(config.safe_mode);
+        assert!(!config.hdr_output);
+        assert!(!config.linear_working_space);
+        assert_eq!(config.offscreen_format, None);
+        assert_eq!(config.aa_mode, AaMode::None);
+        assert!(!config.vertex_pulling);
+        assert_eq!(config.culling, CullMode::Off);
+        assert_eq!(config.encode_threads, 1);
+        assert!(!config.warm_up_pipelines);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::adapter::{backends_from_env, check_downlevel_capabilities};
+use crate::config::AppConfig;
+use crate::error::InitError;
+use crate::label;
+
+/// The instance, adapter, device and queue, shared by every window and by
+/// the headless renderer.
+pub struct GpuContext {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl GpuContext {
+    /// Creates an instance for the backends in `config`.
+    pub fn create_instance(config: &AppConfig) -> wgpu::Instance {
+        let backends = config.backends.unwrap_or_else(backends_from_env);
+        log::info!("requested backends: {backends:?}");
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        })
+    }
+
+    /// Picks an adapter, compatible with `surface` when one is given, and
+    /// opens a device on it. `instance` should come from
+    /// [`create_instance`](Self::create_instance).
+    ///
+    /// If no hardware adapter is found, a software fallback adapter is tried
+    /// before giving up.
+    pub async fn new(
+        instance: wgpu::Instance,
+        surface: Option<&wgpu::Surface<'_>>,
+        config: &AppConfig,
+    ) -> Result<Self, InitError> {
+        let options = wgpu::RequestAdapterOptions {
+            power_preference: config.power_preference,
+            compatible_surface: surface,
+            force_fallback_adapter: false,
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Ok(adapter) => adapter,
+            Err(e) => {
+                log::warn!("no hardware adapter ({e}), trying a fallback adapter");
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        force_fallback_adapter: true,
+                        ..options
+                    })
+                    .await
+                    .map_err(InitError::NoAdapter)?
+            }
+        };
+        check_downlevel_capabilities(&adapter, config.required_downlevel_flags)?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                // Optional: GPU timings turn themselves off without timestamp
+                // queries, and cubemaps fall back to a pass per face without
+                // multiview.
+                required_features: adapter.features()
+                    & (wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::MULTIVIEW),
+                // Whatever the adapter supports: the defaults are above
+                // what WebGL2 and some GL drivers offer, and support checks
+                // like vertex pulling's look at the adapter's limits.
+                required_limits: adapter.limits(),
+                label: label!("device"),
+                memory_hints: wgpu::MemoryHints::Performance,
+                trace: wgpu::Trace::Off,
+                experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            })
+            .await
+            .map_err(InitError::RequestDevice)?;
+
+        let info = adapter.get_info();
+        log::info!(
+            "adapter: {} ({:?}) using the {:?} backend",
+            info.name,
+            info.device_type,
+            info.backend
+        );
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
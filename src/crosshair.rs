@@ -0,0 +1,179 @@
+use crate::label;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("crosshair.wgsl");
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrosshairUniform {
+    screen_size: [f32; 2],
+    center: [f32; 2],
+    color: [f32; 4],
+    half_length: f32,
+    half_thickness: f32,
+    _padding: [f32; 2],
+}
+
+/// A screen-space crosshair made of two thin quads.
+///
+/// The position is given in physical pixels; `None` centers it on the screen.
+pub struct Crosshair {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    constants: ShaderConstants,
+    uniform: CrosshairUniform,
+    uniform_buffer: UniformBuffer<CrosshairUniform>,
+    position: Option<[f32; 2]>,
+}
+
+impl Crosshair {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Crosshair",
+            &crate::struct_layout!(CrosshairUniform {
+                screen_size,
+                center,
+                color,
+                half_length,
+                half_thickness,
+                _padding,
+            }),
+        );
+        let shader = create_shader(device);
+
+        let uniform = CrosshairUniform {
+            screen_size: [width as f32, height as f32],
+            center: [width as f32 / 2.0, height as f32 / 2.0],
+            color: [1.0, 1.0, 1.0, 0.8],
+            half_length: 10.0,
+            half_thickness: 1.0,
+            _padding: [0.0; 2],
+        };
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("crosshair::uniform"),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+        );
+        uniform_buffer.update(queue, &uniform);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("crosshair::pipeline_layout"),
+            bind_group_layouts: &[uniform_buffer.layout()],
+            push_constant_ranges: &[],
+        });
+        let constants = ShaderConstants::default();
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format, &constants);
+
+        Self {
+            shader,
+            pipeline_layout,
+            pipeline,
+            constants,
+            uniform,
+            uniform_buffer,
+            position: None,
+        }
+    }
+
+    pub fn constants(&self) -> &ShaderConstants {
+        &self.constants
+    }
+
+    /// Rebuilds the pipeline with new override constants, e.g. `GAP` (the
+    /// radius in pixels left empty around the center).
+    pub fn set_constants(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        constants: ShaderConstants,
+    ) {
+        self.pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            create_pipeline(device, shader, &self.pipeline_layout, format, &constants)
+        });
+        self.constants = constants;
+    }
+
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.uniform.screen_size = [width as f32, height as f32];
+        self.update(queue);
+    }
+
+    pub fn set_position(&mut self, queue: &wgpu::Queue, position: Option<[f32; 2]>) {
+        if self.position == position {
+            return;
+        }
+        self.position = position;
+        self.update(queue);
+    }
+
+    fn update(&mut self, queue: &wgpu::Queue) {
+        let [width, height] = self.uniform.screen_size;
+        self.uniform.center = self.position.unwrap_or([width / 2.0, height / 2.0]);
+        self.uniform_buffer.update(queue, &self.uniform);
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.uniform_buffer.bind_group(), &[]);
+        render_pass.draw(0..12, 0..1);
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("crosshair::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    constants: &ShaderConstants,
+) -> wgpu::RenderPipeline {
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("crosshair::pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
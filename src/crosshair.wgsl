@@ -0,0 +1,45 @@
+struct Crosshair {
+    screen_size: vec2<f32>,
+    center: vec2<f32>,
+    color: vec4<f32>,
+    half_length: f32,
+    half_thickness: f32,
+}
+
+// Radius in pixels left empty around the center; set at pipeline creation.
+override GAP: f32 = 0.0;
+
+@group(0) @binding(0)
+var<uniform> crosshair: Crosshair;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // Two quads: a horizontal bar (vertices 0..6) and a vertical bar (6..12).
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var extent = vec2<f32>(crosshair.half_length, crosshair.half_thickness);
+    if index >= 6u {
+        extent = extent.yx;
+    }
+    let pixel = crosshair.center + corners[index % 6u] * extent;
+    let ndc = vec2<f32>(
+        pixel.x / crosshair.screen_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / crosshair.screen_size.y * 2.0,
+    );
+    return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let offset = abs(position.xy - crosshair.center);
+    if max(offset.x, offset.y) < GAP {
+        discard;
+    }
+    return crosshair.color;
+}
@@ -0,0 +1,660 @@
+use std::num::NonZeroU32;
+
+use glam::{Mat4, Vec3};
+
+use crate::frame::FrameEncoder;
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::label;
+use crate::model::Model;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::texture::{Texture, DEPTH_FORMAT};
+use crate::vertex::{ModelVertex, Vertex};
+
+const SHADER: &str = include_str!("cubemap.wgsl");
+const MULTIVIEW_SHADER: &str = include_str!("cubemap_multiview.wgsl");
+const FACE_SHADER: &str = include_str!("cubemap_face.wgsl");
+const UNFOLD_SHADER: &str = include_str!("cubemap_unfold.wgsl");
+
+/// Faces in array layer order, which is how cube views and samplers
+/// address them.
+pub const FACE_NAMES: [&str; 6] = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+
+/// Clip planes of the faces' projections.
+pub const ZNEAR: f32 = 0.1;
+pub const ZFAR: f32 = 100.0;
+
+/// Forward and up of each face's camera. Cube lookups treat the faces as
+/// seen from inside with a left-handed basis, so the views are left-handed
+/// too; that mirrors the scene, which flips its triangles' winding.
+const FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::NEG_Z),
+    (Vec3::NEG_Y, Vec3::Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FacesUniform {
+    view_proj: [[[f32; 4]; 4]; 6],
+}
+
+/// View-projection matrices of the six faces of a cubemap centered on
+/// `center`, in [`FACE_NAMES`] order, with a 90 degree field of view.
+pub fn face_view_projections(center: Vec3) -> [Mat4; 6] {
+    let projection = Mat4::perspective_lh(std::f32::consts::FRAC_PI_2, 1.0, ZNEAR, ZFAR);
+    FACES.map(|(forward, up)| projection * Mat4::look_at_lh(center, center + forward, up))
+}
+
+/// Renders a [`Model`]'s instances into the six faces of a cubemap, e.g.
+/// for dynamic reflections.
+///
+/// Where the device has [`wgpu::Features::MULTIVIEW`], all faces are drawn
+/// in one pass with one view per array layer, the vertex shader picking the
+/// face's matrix by `view_index`. Otherwise each face gets its own pass,
+/// with the face index bound at a dynamic uniform offset.
+///
+/// The faces can also be unfolded into a strip, a plain 2D texture for
+/// display and readback; the GL backend neither copies out of cube textures
+/// nor samples their faces as 2D views.
+pub struct CubemapRenderer {
+    multiview: bool,
+    texture: wgpu::Texture,
+    cube_view: wgpu::TextureView,
+    /// All layers, the attachment of the multiview pass.
+    array_view: wgpu::TextureView,
+    face_views: Vec<wgpu::TextureView>,
+    depth_array_view: wgpu::TextureView,
+    depth_face_views: Vec<wgpu::TextureView>,
+    /// The faces side by side, written by [`unfold`](Self::unfold).
+    strip: wgpu::Texture,
+    strip_view: wgpu::TextureView,
+    faces_buffer: wgpu::Buffer,
+    /// Bytes between the faces' indices in `face_buffer`.
+    face_stride: u32,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    unfold_bind_group: wgpu::BindGroup,
+    unfold_pipeline: wgpu::RenderPipeline,
+}
+
+impl CubemapRenderer {
+    /// Creates a cubemap with `size` by `size` faces of `format`, which must
+    /// be renderable and filterable.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: u32) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Faces",
+            &crate::struct_layout!(FacesUniform { view_proj }),
+        );
+        let multiview = device.features().contains(wgpu::Features::MULTIVIEW);
+        let size = size.max(1);
+        let texture = create_cube_texture(
+            device,
+            label!("cubemap::texture"),
+            format,
+            size,
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth = create_cube_texture(
+            device,
+            label!("cubemap::depth"),
+            DEPTH_FORMAT,
+            size,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let cube_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: label!("cubemap::cube_view"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let faces_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("cubemap::faces"),
+            size: std::mem::size_of::<FacesUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let face_stride = device.limits().min_uniform_buffer_offset_alignment;
+        let face_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("cubemap::face"),
+            size: u64::from(face_stride) * FACE_NAMES.len() as u64,
+            usage: wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: true,
+        });
+        {
+            let mut data = face_buffer.slice(..).get_mapped_range_mut();
+            for index in 0..FACE_NAMES.len() as u32 {
+                let offset = (index * face_stride) as usize;
+                data[offset..offset + 4].copy_from_slice(&index.to_ne_bytes());
+            }
+        }
+        face_buffer.unmap();
+
+        let layout = bind_group_layout(device);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: label!("cubemap::bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: faces_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &face_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(4),
+                    }),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("cubemap::pipeline_layout"),
+            bind_group_layouts: &[
+                &layout,
+                &Texture::array_bind_group_layout(device),
+                &InstanceBuffer::bind_group_layout(device),
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, &pipeline_layout, format, multiview);
+
+        let strip = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("cubemap::strip"),
+            size: wgpu::Extent3d {
+                width: size * FACE_NAMES.len() as u32,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let strip_view = strip.create_view(&Default::default());
+        let (unfold_bind_group, unfold_pipeline) = create_unfold(device, &cube_view, format);
+
+        Self {
+            multiview,
+            array_view: array_view(&texture),
+            face_views: face_views(&texture),
+            depth_array_view: array_view(&depth),
+            depth_face_views: face_views(&depth),
+            texture,
+            cube_view,
+            strip,
+            strip_view,
+            faces_buffer,
+            face_stride,
+            bind_group,
+            pipeline,
+            unfold_bind_group,
+            unfold_pipeline,
+        }
+    }
+
+    /// Whether all faces are drawn in one multiview pass.
+    pub fn is_multiview(&self) -> bool {
+        self.multiview
+    }
+
+    /// The cubemap, six array layers in [`FACE_NAMES`] order.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// A cube view of the cubemap, for sampling with a direction.
+    pub fn cube_view(&self) -> &wgpu::TextureView {
+        &self.cube_view
+    }
+
+    /// The faces side by side in [`FACE_NAMES`] order, as last unfolded.
+    pub fn strip(&self) -> &wgpu::Texture {
+        &self.strip
+    }
+
+    /// A view of [`strip`](Self::strip), e.g. for the texture viewer.
+    pub fn strip_view(&self) -> &wgpu::TextureView {
+        &self.strip_view
+    }
+
+    /// Copies the faces into the strip by sampling the cube view, so the
+    /// strip shows them the way lookups see them.
+    pub fn unfold(&self, frame: &mut FrameEncoder) {
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("cubemap::unfold_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.strip_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.unfold_pipeline);
+        render_pass.set_bind_group(0, &self.unfold_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Renders `model` once per instance in `instances` into every face,
+    /// seen from `center`, over `clear_color`.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        center: Vec3,
+        model: &Model,
+        instances: &InstanceBuffer,
+        clear_color: wgpu::Color,
+    ) {
+        let faces = FacesUniform {
+            view_proj: face_view_projections(center).map(|m| m.to_cols_array_2d()),
+        };
+        queue.write_buffer(&self.faces_buffer, 0, bytemuck::bytes_of(&faces));
+        let passes: Vec<_> = if self.multiview {
+            vec![(&self.array_view, &self.depth_array_view, 0)]
+        } else {
+            (0..FACE_NAMES.len())
+                .map(|face| {
+                    (
+                        &self.face_views[face],
+                        &self.depth_face_views[face],
+                        face as u32 * self.face_stride,
+                    )
+                })
+                .collect()
+        };
+        for (view, depth_view, face_offset) in passes {
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: label!("cubemap::render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[face_offset]);
+            render_pass.set_bind_group(2, instances.bind_group(), &[]);
+            render_pass.set_vertex_buffer(1, instances.slice());
+            model.draw(&mut render_pass, 0..instances.len());
+        }
+    }
+}
+
+fn create_cube_texture(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    format: wgpu::TextureFormat,
+    size: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label,
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: FACE_NAMES.len() as u32,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn array_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: label!("cubemap::array_view"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
+
+fn face_views(texture: &wgpu::Texture) -> Vec<wgpu::TextureView> {
+    (0..FACE_NAMES.len() as u32)
+        .map(|face| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: label!("cubemap::face_view"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The face matrices at binding 0, and the face index at binding 1 with a
+/// dynamic offset, which only the per-face shader reads.
+fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: label!("cubemap::bind_group_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<FacesUniform>() as u64
+                    ),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(4),
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    multiview: bool,
+) -> wgpu::RenderPipeline {
+    let entry = if multiview {
+        MULTIVIEW_SHADER
+    } else {
+        FACE_SHADER
+    };
+    let source = format!("{SHADER}\n{entry}");
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("cubemap::shader"),
+        source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+    });
+    let mut constants = ShaderConstants::default();
+    let encode = if is_linear_output(format) { 0.0 } else { 1.0 };
+    constants.set("ENCODE_SRGB", encode);
+    let constants = constants.validated(&source);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("cubemap::pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[ModelVertex::layout(), InstanceRaw::layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            // The left-handed face views mirror the scene's counter-clockwise
+            // front faces.
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: multiview.then(|| NonZeroU32::new(FACE_NAMES.len() as u32).unwrap()),
+        cache: None,
+    })
+}
+
+/// The bind group and pipeline that unfold `cube_view` into the strip.
+fn create_unfold(
+    device: &wgpu::Device,
+    cube_view: &wgpu::TextureView,
+    format: wgpu::TextureFormat,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: label!("cubemap::unfold_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                count: None,
+            },
+        ],
+    });
+    // Texel centers map to texel centers, so nearest copies them exactly.
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: label!("cubemap::unfold_sampler"),
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("cubemap::unfold_bind_group"),
+        layout: &layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(cube_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: label!("cubemap::unfold_pipeline_layout"),
+        bind_group_layouts: &[&layout],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("cubemap::unfold_shader"),
+        source: wgpu::ShaderSource::Wgsl(UNFOLD_SHADER.into()),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("cubemap::unfold_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    (bind_group, pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3Swizzles, Vec4Swizzles};
+
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::instance::{Instance, MaterialProperties};
+    use crate::test_support::test_gpu;
+
+    /// Where a direction from the center lands on each face, following the
+    /// cube sampling rules: the major axis picks the face, and the other two
+    /// give the texel's u (right) and v (down).
+    const SAMPLING_AXES: [(Vec3, Vec3); 6] = [
+        (Vec3::NEG_Z, Vec3::NEG_Y),
+        (Vec3::Z, Vec3::NEG_Y),
+        (Vec3::X, Vec3::Z),
+        (Vec3::X, Vec3::NEG_Z),
+        (Vec3::X, Vec3::NEG_Y),
+        (Vec3::NEG_X, Vec3::NEG_Y),
+    ];
+
+    #[test]
+    fn faces_match_cube_sampling() {
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        let matrices = face_view_projections(center);
+        for (face, matrix) in matrices.iter().enumerate() {
+            let project = |offset: Vec3| {
+                let clip = *matrix * (center + offset).extend(1.0);
+                clip.xyz() / clip.w
+            };
+            let forward = FACES[face].0;
+            let (right, down) = SAMPLING_AXES[face];
+            assert!(project(forward).xy().abs_diff_eq(glam::Vec2::ZERO, 1e-5));
+            // Right is +x in NDC and down is -y.
+            let corner = project(forward + 0.5 * right + 0.5 * down);
+            assert!(
+                corner.x > 0.4 && corner.y < -0.4,
+                "{}: {corner}",
+                FACE_NAMES[face]
+            );
+            assert!((0.0..1.0).contains(&corner.z));
+        }
+    }
+
+    const SIZE: u32 = 32;
+
+    #[test]
+    fn renders_every_face() {
+        let gpu = test_gpu!();
+        let cubemap = CubemapRenderer::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, SIZE);
+        assert_eq!(
+            cubemap.is_multiview(),
+            gpu.device.features().contains(wgpu::Features::MULTIVIEW)
+        );
+        check_faces(&gpu, &cubemap);
+    }
+
+    #[test]
+    fn renders_every_face_in_one_multiview_pass() {
+        let gpu = test_gpu!();
+        if !gpu.device.features().contains(wgpu::Features::MULTIVIEW) {
+            eprintln!("skipping multiview test: the adapter lacks MULTIVIEW");
+            return;
+        }
+        let cubemap = CubemapRenderer::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, SIZE);
+        assert!(cubemap.is_multiview());
+        check_faces(&gpu, &cubemap);
+    }
+
+    /// Draws a red pentagon off to +X on the -Z side and checks that it
+    /// shows up on that face only.
+    fn check_faces(gpu: &GpuContext, cubemap: &CubemapRenderer) {
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let model = crate::scene::pentagon_model(device, queue);
+        let mut instances = InstanceBuffer::new(device);
+        instances.set_materials(
+            device,
+            queue,
+            &[MaterialProperties {
+                tint: [1.0, 0.0, 0.0, 1.0],
+            }],
+        );
+        // Facing the center from the -Z side, off to +X.
+        let instance = Instance {
+            position: Vec3::new(0.6, 0.0, -2.0),
+            ..Instance::default()
+        };
+        instances.update(device, queue, &[instance]).unwrap();
+        let mut frame = FrameEncoder::new(device, queue, None);
+        cubemap.render(
+            queue,
+            &mut frame,
+            Vec3::ZERO,
+            &model,
+            &instances,
+            wgpu::Color::BLACK,
+        );
+        cubemap.unfold(&mut frame);
+        frame.submit();
+
+        let pixels = crate::capture::read_texture_rgba(device, queue, cubemap.strip()).unwrap();
+        let width = SIZE as usize * FACE_NAMES.len();
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel[3] == 255));
+        for (face, name) in FACE_NAMES.iter().enumerate() {
+            let red: Vec<usize> = pixels
+                .chunks_exact(4)
+                .enumerate()
+                .map(|(i, pixel)| (i % width, pixel))
+                .filter(|(x, pixel)| x / SIZE as usize == face && pixel[0] > 0)
+                .map(|(x, _)| x % SIZE as usize)
+                .collect();
+            if *name == "-Z" {
+                assert!(red.len() > 20, "only {} red pixels", red.len());
+                // On the -Z face +X is to the left.
+                let mean = red.iter().sum::<usize>() as f32 / red.len() as f32;
+                assert!(mean < SIZE as f32 / 2.0, "centered at x = {mean}");
+            } else {
+                assert!(red.is_empty(), "{name} shows the pentagon");
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Set when the target format is not sRGB, so the encode happens here
+// instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+
+struct Faces {
+    // One per face, in the order +X, -X, +Y, -Y, +Z, -Z.
+    view_proj: array<mat4x4<f32>, 6>,
+}
+
+@group(0) @binding(0)
+var<uniform> faces: Faces;
+
+@group(1) @binding(0)
+var diffuse_texture: texture_2d_array<f32>;
+@group(1) @binding(1)
+var diffuse_sampler: sampler;
+
+struct Material {
+    tint: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<storage, read> materials: array<Material>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) material: u32,
+    @location(10) layer: u32,
+    @location(11) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) material: u32,
+    @location(2) @interpolate(flat) layer: u32,
+    @location(3) @interpolate(flat) tint: vec4<f32>,
+}
+
+// The entry point, appended from `cubemap_multiview.wgsl` or
+// `cubemap_face.wgsl`, picks the face.
+fn vertex(in: VertexInput, instance: InstanceInput, face: u32) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    var out: VertexOutput;
+    out.clip_position = faces.view_proj[face] * model * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.material = instance.material;
+    out.layer = instance.layer;
+    out.tint = instance.tint;
+    return out;
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let material = materials[min(in.material, arrayLength(&materials) - 1u)];
+    let layer = min(in.layer, textureNumLayers(diffuse_texture) - 1u);
+    let color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords, layer) * material.tint
+        * in.tint;
+    if ENCODE_SRGB {
+        return vec4<f32>(linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0))), color.a);
+    }
+    return color;
+}
@@ -0,0 +1,8 @@
+// Bound at a dynamic offset per face pass.
+@group(0) @binding(1)
+var<uniform> face_index: u32;
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    return vertex(in, instance, face_index);
+}
@@ -0,0 +1,5 @@
+// All six faces in one pass, one view per array layer.
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput, @builtin(view_index) view: i32) -> VertexOutput {
+    return vertex(in, instance, u32(view));
+}
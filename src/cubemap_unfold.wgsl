@@ -0,0 +1,33 @@
+@group(0) @binding(0)
+var cube: texture_cube<f32>;
+@group(0) @binding(1)
+var cube_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the viewport.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// The direction through `uv`, in -1..1 with v down, on `face`, following the
+// cube sampling rules.
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -uv.y, -uv.x); }
+        case 1u: { return vec3<f32>(-1.0, -uv.y, uv.x); }
+        case 2u: { return vec3<f32>(uv.x, 1.0, uv.y); }
+        case 3u: { return vec3<f32>(uv.x, -1.0, -uv.y); }
+        case 4u: { return vec3<f32>(uv.x, -uv.y, 1.0); }
+        default: { return vec3<f32>(-uv.x, -uv.y, -1.0); }
+    }
+}
+
+// The faces side by side, in array layer order.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = f32(textureDimensions(cube).x);
+    let face = u32(position.x / size);
+    let uv = vec2<f32>(position.x - f32(face) * size, position.y) / size * 2.0 - 1.0;
+    return textureSampleLevel(cube, cube_sampler, face_direction(face, uv), 0.0);
+}
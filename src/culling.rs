@@ -0,0 +1,894 @@
+use std::fmt;
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::compute;
+use crate::frame::FrameEncoder;
+use crate::hiz::{self, DepthPyramid};
+use crate::instance::{Instance, InstanceRaw};
+use crate::label;
+use crate::model::Model;
+use crate::uniform::UniformBuffer;
+use crate::vertex::ModelVertex;
+
+const SHADER: &str = include_str!("culling.wgsl");
+
+/// Size of one `DrawIndexedIndirectArgs`, and the offset of its instance
+/// count.
+const ARGS_SIZE: wgpu::BufferAddress =
+    std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as _;
+const INSTANCE_COUNT_OFFSET: wgpu::BufferAddress = 4;
+
+/// Where the instances of the model grid are culled before drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    /// Every instance is drawn.
+    Off,
+    /// Instances outside the view frustum are skipped on the CPU, which
+    /// uploads the rest every frame.
+    Cpu,
+    /// A compute pass writes the visible instances and their count for an
+    /// indirect draw, optionally also skipping instances hidden behind last
+    /// frame's depth. Falls back to [`Cpu`](Self::Cpu) where the adapter
+    /// has no compute shaders or indirect draws.
+    Gpu,
+}
+
+impl CullMode {
+    /// The mode after this one when cycling through them with a key.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Cpu,
+            Self::Cpu => Self::Gpu,
+            Self::Gpu => Self::Off,
+        }
+    }
+}
+
+impl fmt::Display for CullMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Cpu => "CPU",
+            Self::Gpu => "GPU",
+        })
+    }
+}
+
+/// A sphere enclosing a mesh, for culling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere around the box bounding `points`: not the tightest, but
+    /// quick. Without points it is a point at the origin.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let points: Vec<Vec3> = points.into_iter().collect();
+        let Some(&first) = points.first() else {
+            return Self {
+                center: Vec3::ZERO,
+                radius: 0.0,
+            };
+        };
+        let (min, max) = points
+            .iter()
+            .fold((first, first), |(min, max), &p| (min.min(p), max.max(p)));
+        let center = (min + max) / 2.0;
+        let radius = points
+            .iter()
+            .map(|p| p.distance(center))
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// [`from_points`](Self::from_points) for a mesh's vertices.
+    pub fn from_vertices(vertices: &[ModelVertex]) -> Self {
+        Self::from_points(vertices.iter().map(|vertex| Vec3::from(vertex.position)))
+    }
+
+    /// The smallest sphere enclosing both.
+    pub fn merge(self, other: Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        Self { center, radius }
+    }
+
+    /// The sphere moved by `transform`, grown to its largest scale.
+    pub fn transformed(self, transform: Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .truncate()
+            .length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+        Self {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// The six planes bounding what a camera sees, with normals pointing in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, as `(normal, distance)`.
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a view-projection matrix with wgpu's 0..1
+    /// depth range, like
+    /// [`Camera::build_view_projection_matrix`](crate::camera::Camera::build_view_projection_matrix).
+    pub fn from_view_projection(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes =
+            [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    /// Whether any part of `sphere` may be inside. Spheres just outside a
+    /// corner, where two planes meet, pass too.
+    pub fn intersects(&self, sphere: BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+}
+
+/// The instances whose copy of a mesh bounded by `bounds` may be in
+/// `frustum`, in their original order.
+pub fn cull_instances(
+    instances: &[Instance],
+    bounds: BoundingSphere,
+    frustum: &Frustum,
+) -> Vec<Instance> {
+    instances
+        .iter()
+        .filter(|instance| frustum.intersects(bounds.transformed(instance.model_matrix())))
+        .copied()
+        .collect()
+}
+
+/// `CullParams` in `culling.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    occlusion_view_proj: [[f32; 4]; 4],
+    sphere: [f32; 4],
+    depth_size: [f32; 2],
+    instance_count: u32,
+    pyramid_levels: u32,
+}
+
+/// How many instances the last [`InstanceCuller::cull`] left to draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visible {
+    /// Counted on the CPU.
+    Count(u32),
+    /// Counted on the GPU, into the indirect draw arguments.
+    Indirect,
+}
+
+/// The compute side of GPU culling.
+struct GpuCulling {
+    pipeline: wgpu::ComputePipeline,
+    buffers_layout: wgpu::BindGroupLayout,
+    /// The instances, the visible instances and their count.
+    buffers: wgpu::BindGroup,
+    params: UniformBuffer<CullParams>,
+    pyramid_layout: wgpu::BindGroupLayout,
+    /// Bound in place of the pyramid while occlusion culling is off.
+    empty_pyramid: wgpu::BindGroup,
+    /// `None` where the adapter can't build one.
+    pyramid: Option<DepthPyramid>,
+    /// The camera last frame's depth was drawn with, while it can be
+    /// tested against.
+    history: Option<Mat4>,
+}
+
+/// Culls the instances of the model grid, on the CPU or with a compute pass
+/// feeding indirect draws, so only instances that may be visible are drawn.
+///
+/// Culling needs the model's [`bounding_sphere`](Model::bounding_sphere),
+/// so animated models are always drawn whole. With occlusion culling on,
+/// the GPU path also skips instances hidden behind the previous frame's
+/// depth buffer, which needs that frame to have drawn the culled instances
+/// into the depth buffer passed to [`cull`](Self::cull): call
+/// [`reset_history`](Self::reset_history) whenever it didn't. Instances
+/// revealed from behind an occluder show up a frame late.
+pub struct InstanceCuller {
+    mode: CullMode,
+    occlusion: bool,
+    gpu: Option<GpuCulling>,
+    instances: Vec<Instance>,
+    bounds: Option<BoundingSphere>,
+    /// The instances, read by the compute pass.
+    source: wgpu::Buffer,
+    /// The instances left to draw, bound at vertex slot 1.
+    visible: wgpu::Buffer,
+    capacity: usize,
+    /// A `u32` the compute pass counts the visible instances into.
+    counter: wgpu::Buffer,
+    /// One `DrawIndexedIndirectArgs` per mesh of the model.
+    args: wgpu::Buffer,
+    index_counts: Vec<u32>,
+    drawn: Visible,
+}
+
+impl InstanceCuller {
+    /// Culls in `mode`, falling back to the CPU where `adapter` can't cull on
+    /// the GPU. `occlusion` adds occlusion culling to the GPU path where the
+    /// adapter can build a [`DepthPyramid`].
+    pub fn new(
+        device: &wgpu::Device,
+        adapter: &wgpu::Adapter,
+        mode: CullMode,
+        occlusion: bool,
+    ) -> Self {
+        let supported = Self::is_supported(adapter);
+        let storage = if supported {
+            wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::empty()
+        };
+        let capacity = 1;
+        let source = create_instance_buffer(device, capacity, storage);
+        let visible =
+            create_instance_buffer(device, capacity, storage | wgpu::BufferUsages::VERTEX);
+        let counter = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("culling::counter"),
+            size: 4,
+            usage: storage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let gpu = supported.then(|| {
+            let pyramid = DepthPyramid::is_supported(adapter).then(|| DepthPyramid::new(device));
+            GpuCulling::new(device, pyramid, [&source, &visible, &counter])
+        });
+        let mut culler = Self {
+            mode: CullMode::Off,
+            occlusion,
+            gpu,
+            instances: Vec::new(),
+            bounds: None,
+            source,
+            visible,
+            capacity,
+            counter,
+            args: create_args_buffer(device, 1),
+            index_counts: Vec::new(),
+            drawn: Visible::Count(0),
+        };
+        culler.set_mode(mode);
+        culler
+    }
+
+    /// Whether `adapter` can cull on the GPU: it needs compute shaders,
+    /// indirect draws and three storage buffers in the compute stage.
+    pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+        let required =
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION;
+        adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(required)
+            && adapter.limits().max_storage_buffers_per_shader_stage >= 3
+    }
+
+    /// The mode in use, which is [`CullMode::Cpu`] where GPU culling was
+    /// asked for but isn't supported.
+    pub fn mode(&self) -> CullMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CullMode) {
+        self.mode = match mode {
+            CullMode::Gpu if self.gpu.is_none() => {
+                log::warn!(
+                    "GPU culling needs compute shaders and indirect draws, culling on the CPU"
+                );
+                CullMode::Cpu
+            }
+            mode => mode,
+        };
+        self.reset_history();
+    }
+
+    /// Whether [`draw`](Self::draw) should be used in place of drawing every
+    /// instance.
+    pub fn is_active(&self) -> bool {
+        self.mode != CullMode::Off && self.bounds.is_some()
+    }
+
+    /// Whether instances are tested against the previous frame's depth.
+    pub fn occlusion(&self) -> bool {
+        self.occlusion
+            && self.mode == CullMode::Gpu
+            && self.gpu.as_ref().is_some_and(|gpu| gpu.pyramid.is_some())
+    }
+
+    /// Takes the bounds and index counts of the model's meshes.
+    pub fn set_model(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, model: &Model) {
+        self.bounds = model.bounding_sphere();
+        self.index_counts = model
+            .meshes
+            .iter()
+            .map(|mesh| mesh.mesh.num_indices())
+            .collect();
+        let args: Vec<wgpu::util::DrawIndexedIndirectArgs> = self
+            .index_counts
+            .iter()
+            .map(|&index_count| wgpu::util::DrawIndexedIndirectArgs {
+                index_count,
+                instance_count: 0,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: 0,
+            })
+            .collect();
+        if self.args.size() < args.len() as u64 * ARGS_SIZE {
+            self.args = create_args_buffer(device, args.len());
+        }
+        let bytes: Vec<u8> = args
+            .iter()
+            .flat_map(|args| args.as_bytes())
+            .copied()
+            .collect();
+        queue.write_buffer(&self.args, 0, &bytes);
+        self.drawn = Visible::Count(0);
+        self.reset_history();
+    }
+
+    /// Replaces the instances to cull, which must be the ones drawn with
+    /// [`Model::draw`] otherwise.
+    pub fn set_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[Instance],
+    ) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            let storage = self.source.usage() & wgpu::BufferUsages::STORAGE;
+            self.source = create_instance_buffer(device, self.capacity, storage);
+            self.visible =
+                create_instance_buffer(device, self.capacity, storage | wgpu::BufferUsages::VERTEX);
+            if let Some(gpu) = &mut self.gpu {
+                gpu.buffers = buffers_bind_group(
+                    device,
+                    &gpu.buffers_layout,
+                    [&self.source, &self.visible, &self.counter],
+                );
+            }
+        }
+        let raw: Vec<InstanceRaw> = instances.iter().map(InstanceRaw::from).collect();
+        queue.write_buffer(&self.source, 0, bytemuck::cast_slice(&raw));
+        self.instances = instances.to_vec();
+        self.reset_history();
+    }
+
+    /// Forgets the previous frame's depth, e.g. after the depth buffer was
+    /// recreated or a frame was drawn without culling, so the next frame is
+    /// only frustum culled.
+    pub fn reset_history(&mut self) {
+        if let Some(gpu) = &mut self.gpu {
+            gpu.history = None;
+        }
+    }
+
+    /// Culls the instances for a camera with `view_proj`, whose depth is
+    /// drawn into `depth` this frame. On the GPU the passes are recorded
+    /// into `frame`, ahead of the draws.
+    pub fn cull(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        view_proj: Mat4,
+        depth: Option<&wgpu::Texture>,
+    ) {
+        let Some(bounds) = self.bounds.filter(|_| self.mode != CullMode::Off) else {
+            return;
+        };
+        let frustum = Frustum::from_view_projection(view_proj);
+        let gpu = match &mut self.gpu {
+            Some(gpu) if self.mode == CullMode::Gpu => gpu,
+            _ => {
+                let visible = cull_instances(&self.instances, bounds, &frustum);
+                let raw: Vec<InstanceRaw> = visible.iter().map(InstanceRaw::from).collect();
+                queue.write_buffer(&self.visible, 0, bytemuck::cast_slice(&raw));
+                self.drawn = Visible::Count(visible.len() as u32);
+                return;
+            }
+        };
+
+        // Only a single-sampled depth buffer can be read.
+        let depth = depth.filter(|depth| {
+            depth.sample_count() == 1
+                && depth.usage().contains(wgpu::TextureUsages::TEXTURE_BINDING)
+        });
+        let occlusion = match (self.occlusion, gpu.history, &mut gpu.pyramid, depth) {
+            (true, Some(history), Some(pyramid), Some(depth)) => {
+                pyramid.build(device, frame, depth);
+                Some((history, &*pyramid, depth))
+            }
+            _ => None,
+        };
+        let params = CullParams {
+            planes: frustum.planes.map(|plane| plane.to_array()),
+            occlusion_view_proj: occlusion
+                .as_ref()
+                .map_or(Mat4::IDENTITY, |(history, ..)| *history)
+                .to_cols_array_2d(),
+            sphere: bounds.center.extend(bounds.radius).to_array(),
+            depth_size: occlusion.as_ref().map_or([1.0; 2], |(_, _, depth)| {
+                [depth.width() as f32, depth.height() as f32]
+            }),
+            instance_count: self.instances.len() as u32,
+            pyramid_levels: occlusion
+                .as_ref()
+                .map_or(0, |(_, pyramid, _)| pyramid.level_count()),
+        };
+        gpu.params.update(queue, &params);
+        let pyramid_bind_group = occlusion
+            .as_ref()
+            .and_then(|(_, pyramid, _)| pyramid.view())
+            .map(|view| pyramid_bind_group(device, &gpu.pyramid_layout, view));
+        gpu.history = self
+            .occlusion
+            .then_some(view_proj)
+            .filter(|_| depth.is_some());
+
+        let encoder = frame.encoder();
+        encoder.clear_buffer(&self.counter, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: label!("culling::pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&gpu.pipeline);
+            pass.set_bind_group(0, &gpu.buffers, &[]);
+            pass.set_bind_group(1, gpu.params.bind_group(), &[]);
+            pass.set_bind_group(
+                2,
+                pyramid_bind_group.as_ref().unwrap_or(&gpu.empty_pyramid),
+                &[],
+            );
+            pass.dispatch_workgroups(compute::workgroup_count(self.instances.len()), 1, 1);
+        }
+        for mesh in 0..self.index_counts.len() as u64 {
+            encoder.copy_buffer_to_buffer(
+                &self.counter,
+                0,
+                &self.args,
+                mesh * ARGS_SIZE + INSTANCE_COUNT_OFFSET,
+                4,
+            );
+        }
+        self.drawn = Visible::Indirect;
+    }
+
+    /// Draws the instances left by the last [`cull`](Self::cull) like
+    /// [`Model::draw`], binding them at vertex slot 1.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, model: &Model) {
+        render_pass.set_vertex_buffer(1, self.visible.slice(..));
+        match self.drawn {
+            Visible::Count(count) => model.draw(render_pass, 0..count),
+            Visible::Indirect => model.draw_indirect(render_pass, &self.args),
+        }
+    }
+}
+
+impl GpuCulling {
+    /// Binds `buffers`: the instances, the visible instances and their
+    /// count.
+    fn new(
+        device: &wgpu::Device,
+        pyramid: Option<DepthPyramid>,
+        buffers: [&wgpu::Buffer; 3],
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "CullParams",
+            &crate::struct_layout!(CullParams {
+                planes,
+                occlusion_view_proj,
+                sphere,
+                depth_size,
+                instance_count,
+                pyramid_levels,
+            }),
+        );
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let buffers_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("culling::buffers_layout"),
+            entries: &[storage(0, true), storage(1, false), storage(2, false)],
+        });
+        let params = UniformBuffer::new(
+            device,
+            label!("culling::params"),
+            wgpu::ShaderStages::COMPUTE,
+        );
+        let pyramid_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("culling::pyramid_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("culling::pipeline_layout"),
+            bind_group_layouts: &[&buffers_layout, params.layout(), &pyramid_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("culling::shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: label!("culling::pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cull"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let empty = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("culling::empty_pyramid"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: hiz::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let empty_pyramid = pyramid_bind_group(
+            device,
+            &pyramid_layout,
+            &empty.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        let buffers = buffers_bind_group(device, &buffers_layout, buffers);
+        Self {
+            pipeline,
+            buffers_layout,
+            buffers,
+            params,
+            pyramid_layout,
+            empty_pyramid,
+            pyramid,
+            history: None,
+        }
+    }
+}
+
+fn buffers_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffers: [&wgpu::Buffer; 3],
+) -> wgpu::BindGroup {
+    let entries: Vec<_> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("culling::buffers"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn pyramid_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("culling::pyramid"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(view),
+        }],
+    })
+}
+
+fn create_instance_buffer(
+    device: &wgpu::Device,
+    capacity: usize,
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: label!("culling::instances"),
+        size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+        // COPY_SRC lets the culled instances be read back, e.g. in tests.
+        usage: usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_args_buffer(device: &wgpu::Device, meshes: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: label!("culling::args"),
+        size: meshes.max(1) as u64 * ARGS_SIZE,
+        usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::scene::pentagon_model;
+    use crate::test_support::test_gpu;
+    use crate::texture::{Texture, DEPTH_FORMAT};
+
+    const SIZE: u32 = 64;
+
+    fn instance(x: f32, z: f32) -> Instance {
+        Instance {
+            position: Vec3::new(x, 0.0, z),
+            ..Instance::default()
+        }
+    }
+
+    /// A square camera at z = 5 looking down -Z.
+    fn view_projection() -> Mat4 {
+        let mut camera = Camera::new(1.0);
+        camera.eye = Vec3::new(0.0, 0.0, 5.0);
+        camera.build_view_projection_matrix()
+    }
+
+    #[test]
+    fn frustum_keeps_spheres_touching_it() {
+        let frustum = Frustum::from_view_projection(view_projection());
+        let sphere = |x, y, z, radius| BoundingSphere {
+            center: Vec3::new(x, y, z),
+            radius,
+        };
+        assert!(frustum.intersects(sphere(0.0, 0.0, 0.0, 0.1)));
+        // Behind the camera, past the far plane and off to the side.
+        assert!(!frustum.intersects(sphere(0.0, 0.0, 6.0, 0.5)));
+        assert!(!frustum.intersects(sphere(0.0, 0.0, -200.0, 1.0)));
+        assert!(!frustum.intersects(sphere(3.0, 0.0, 0.0, 0.5)));
+        // The half-width at z = 0 is 5 * tan(22.5°), about 2.07.
+        assert!(frustum.intersects(sphere(2.5, 0.0, 0.0, 0.5)));
+        assert!(!frustum.intersects(sphere(0.0, -2.5, 0.0, 0.3)));
+    }
+
+    #[test]
+    fn spheres_enclose_points_and_each_other() {
+        let sphere =
+            BoundingSphere::from_points([Vec3::new(-1.0, 0.0, 0.0), Vec3::new(3.0, 2.0, 0.0)]);
+        assert_eq!(sphere.center, Vec3::new(1.0, 1.0, 0.0));
+        assert!((sphere.radius - 5.0f32.sqrt()).abs() < 1e-6);
+        assert_eq!(BoundingSphere::from_points([]).radius, 0.0);
+
+        let unit = |x| BoundingSphere {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let merged = unit(0.0).merge(unit(4.0));
+        assert_eq!(
+            (merged.center, merged.radius),
+            (Vec3::new(2.0, 0.0, 0.0), 3.0)
+        );
+        let large = BoundingSphere {
+            center: Vec3::ZERO,
+            radius: 10.0,
+        };
+        assert_eq!(large.merge(unit(4.0)), large);
+        assert_eq!(unit(4.0).merge(large), large);
+
+        let moved = unit(1.0).transformed(Mat4::from_scale_rotation_translation(
+            Vec3::new(1.0, 3.0, 2.0),
+            Quat::IDENTITY,
+            Vec3::Y,
+        ));
+        assert_eq!(
+            (moved.center, moved.radius),
+            (Vec3::new(1.0, 1.0, 0.0), 3.0)
+        );
+    }
+
+    /// Reads back the instances the last GPU cull left, sorted by x.
+    fn read_visible(gpu: &crate::context::GpuContext, culler: &InstanceCuller) -> Vec<Vec3> {
+        let read = |buffer: &wgpu::Buffer| {
+            let staging = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: buffer.size(),
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            let mut encoder = gpu.device.create_command_encoder(&Default::default());
+            encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+            gpu.queue.submit(Some(encoder.finish()));
+            staging
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+            gpu.device
+                .poll(wgpu::PollType::wait_indefinitely())
+                .unwrap();
+            let bytes = staging.slice(..).get_mapped_range().to_vec();
+            bytes
+        };
+        let count: u32 = bytemuck::pod_read_unaligned(&read(&culler.counter));
+        let visible: Vec<InstanceRaw> = bytemuck::pod_collect_to_vec(&read(&culler.visible));
+        let mut positions: Vec<Vec3> = visible[..count as usize]
+            .iter()
+            .map(|raw| Vec3::from_slice(&raw.model[3]))
+            .collect();
+        positions.sort_by(|a, b| a.x.total_cmp(&b.x));
+        positions
+    }
+
+    /// Covers the left half of `depth` at depth `value`, like an occluder
+    /// drawn in front of everything there.
+    fn draw_left_half(gpu: &crate::context::GpuContext, depth: &Texture, value: f32) {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(
+                    "@vertex fn vs(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                     let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+                     return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+                 }"
+                    .into(),
+                ),
+            });
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: None,
+                primitive: Default::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            });
+        let mut frame = FrameEncoder::new(&gpu.device, &gpu.queue, None);
+        {
+            let mut pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            // The triangle's depth of 0 maps to the viewport's depth range.
+            pass.set_viewport(0.0, 0.0, SIZE as f32 / 2.0, SIZE as f32, value, value);
+            pass.draw(0..3, 0..1);
+        }
+        frame.submit();
+    }
+
+    #[test]
+    fn gpu_culling_matches_the_cpu_and_skips_occluded_instances() {
+        let gpu = test_gpu!();
+        if !InstanceCuller::is_supported(&gpu.adapter) || !DepthPyramid::is_supported(&gpu.adapter)
+        {
+            eprintln!("skipping: the adapter can't cull on the GPU");
+            return;
+        }
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let model = pentagon_model(device, queue);
+        let instances = [
+            // Behind the occluder, beside it, in front of it and out of view.
+            instance(-1.5, 0.0),
+            instance(1.5, 0.0),
+            instance(-0.5, 4.0),
+            instance(10.0, 0.0),
+        ];
+        let mut culler = InstanceCuller::new(device, &gpu.adapter, CullMode::Gpu, true);
+        assert!(culler.occlusion());
+        culler.set_model(device, queue, &model);
+        culler.set_instances(device, queue, &instances);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width: SIZE,
+            height: SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: Vec::new(),
+        };
+        let depth = Texture::create_depth_texture(device, &config, 1, "culling::tests::depth");
+        let view_proj = view_projection();
+        let cull = |culler: &mut InstanceCuller| {
+            let mut frame = FrameEncoder::new(device, queue, None);
+            culler.cull(device, queue, &mut frame, view_proj, Some(&depth.texture));
+            frame.submit();
+            read_visible(&gpu, culler)
+        };
+
+        // Without a previous frame only the frustum culls.
+        let bounds = model.bounding_sphere().unwrap();
+        let frustum = Frustum::from_view_projection(view_proj);
+        let mut expected: Vec<Vec3> = cull_instances(&instances, bounds, &frustum)
+            .iter()
+            .map(|instance| instance.position)
+            .collect();
+        expected.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(expected.len(), 3);
+        assert_eq!(cull(&mut culler), expected);
+
+        // An occluder 2 units from the camera hides the instance behind it.
+        let depth_at = |distance: f32| {
+            view_proj
+                .project_point3(Vec3::new(0.0, 0.0, 5.0 - distance))
+                .z
+        };
+        draw_left_half(&gpu, &depth, depth_at(2.0));
+        assert_eq!(
+            cull(&mut culler),
+            [Vec3::new(-0.5, 0.0, 4.0), Vec3::new(1.5, 0.0, 0.0)]
+        );
+
+        // Forgetting the previous frame turns occlusion culling off again.
+        culler.reset_history();
+        assert_eq!(cull(&mut culler), expected);
+    }
+}
@@ -0,0 +1,115 @@
+// Must match WORKGROUP_SIZE in compute.rs.
+const WORKGROUP_SIZE: u32 = 64u;
+
+// Same layout as `InstanceData` in shader.wgsl.
+struct InstanceData {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+    material: u32,
+    layer: u32,
+}
+
+struct CullParams {
+    // Frustum planes as (normal, distance), normals pointing inward.
+    planes: array<vec4<f32>, 6>,
+    // The camera the depth pyramid was drawn with.
+    occlusion_view_proj: mat4x4<f32>,
+    // The mesh's bounding sphere in model space: center and radius.
+    sphere: vec4<f32>,
+    // Size in pixels of the depth buffer the pyramid was built from.
+    depth_size: vec2<f32>,
+    instance_count: u32,
+    // Levels of the depth pyramid; 0 turns occlusion culling off.
+    pyramid_levels: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> instances: array<InstanceData>;
+@group(0) @binding(1)
+var<storage, read_write> visible: array<InstanceData>;
+@group(0) @binding(2)
+var<storage, read_write> visible_count: atomic<u32>;
+
+@group(1) @binding(0)
+var<uniform> params: CullParams;
+
+@group(2) @binding(0)
+var pyramid: texture_2d<f32>;
+
+// Whether the sphere is hidden behind the depth pyramid, testing the screen
+// rectangle around its bounding box against the farthest depth under it.
+fn occluded(center: vec3<f32>, radius: f32) -> bool {
+    if params.pyramid_levels == 0u {
+        return false;
+    }
+    var lower = vec2<f32>(1.0);
+    var upper = vec2<f32>(-1.0);
+    var nearest = 1.0;
+    for (var i = 0u; i < 8u; i++) {
+        let corner = center + radius * vec3<f32>(
+            select(-1.0, 1.0, (i & 1u) != 0u),
+            select(-1.0, 1.0, (i & 2u) != 0u),
+            select(-1.0, 1.0, (i & 4u) != 0u),
+        );
+        let clip = params.occlusion_view_proj * vec4<f32>(corner, 1.0);
+        // Reaching behind the camera, where the projection wraps around.
+        if clip.w <= 0.0 {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        lower = min(lower, ndc.xy);
+        upper = max(upper, ndc.xy);
+        nearest = min(nearest, ndc.z);
+    }
+    if nearest <= 0.0 {
+        return false;
+    }
+    // NDC y points up and texel rows down. The rectangle is padded by a
+    // pixel for TAA's sub-pixel jitter.
+    let uv_min = clamp(vec2<f32>(lower.x, -upper.y) * 0.5 + 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+    let uv_max = clamp(vec2<f32>(upper.x, -lower.y) * 0.5 + 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+    let pixel_min = max(uv_min * params.depth_size - 1.0, vec2<f32>(0.0));
+    let pixel_max = uv_max * params.depth_size + 1.0;
+    // Pyramid level `n` is 2^(n + 1) pixels per texel, so at this level the
+    // rectangle spans at most 2x2 texels.
+    let extent = max(pixel_max.x - pixel_min.x, pixel_max.y - pixel_min.y);
+    let level = min(max(u32(ceil(log2(max(extent, 1.0)))), 1u) - 1u, params.pyramid_levels - 1u);
+    let last = textureDimensions(pyramid, level) - 1u;
+    let first_texel = min(vec2<u32>(pixel_min) >> vec2<u32>(level + 1u), last);
+    let last_texel = min(vec2<u32>(pixel_max) >> vec2<u32>(level + 1u), last);
+    let farthest = max(
+        max(
+            textureLoad(pyramid, first_texel, i32(level)).r,
+            textureLoad(pyramid, vec2<u32>(last_texel.x, first_texel.y), i32(level)).r,
+        ),
+        max(
+            textureLoad(pyramid, vec2<u32>(first_texel.x, last_texel.y), i32(level)).r,
+            textureLoad(pyramid, last_texel, i32(level)).r,
+        ),
+    );
+    return nearest > farthest;
+}
+
+// Appends each instance whose bounding sphere is inside the frustum and not
+// occluded to `visible`, counting them in `visible_count`.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.instance_count {
+        return;
+    }
+    let instance = instances[id.x];
+    let model = instance.model;
+    let center = (model * vec4<f32>(params.sphere.xyz, 1.0)).xyz;
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+    let radius = params.sphere.w * scale;
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return;
+        }
+    }
+    if occluded(center, radius) {
+        return;
+    }
+    visible[atomicAdd(&visible_count, 1u)] = instance;
+}
@@ -0,0 +1,78 @@
+use winit::window::{CursorGrabMode, CursorIcon, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InteractionMode {
+    #[default]
+    Idle,
+    /// A mouse button is held to drag the view.
+    Dragging,
+    /// The cursor is hidden while looking around.
+    MouseLook,
+}
+
+impl InteractionMode {
+    /// The cursor shown in this mode, or `None` to hide it.
+    pub fn cursor_icon(self) -> Option<CursorIcon> {
+        match self {
+            Self::Idle => Some(CursorIcon::Default),
+            Self::Dragging => Some(CursorIcon::Grabbing),
+            Self::MouseLook => None,
+        }
+    }
+}
+
+/// Keeps the window cursor in sync with the current interaction mode.
+///
+/// Platforms without custom cursor support ignore the icon changes.
+#[derive(Default)]
+pub struct CursorController {
+    mode: InteractionMode,
+    grabbed: bool,
+}
+
+impl CursorController {
+    pub fn mode(&self) -> InteractionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, window: &Window, mode: InteractionMode) {
+        if self.mode == mode {
+            return;
+        }
+        self.mode = mode;
+        if let Some(icon) = mode.cursor_icon() {
+            window.set_cursor(icon);
+        }
+        self.update_visibility(window);
+    }
+
+    pub fn grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Locks the cursor in place and hides it, or releases it.
+    ///
+    /// Where locking is unsupported (macOS, wasm) the cursor is confined to
+    /// the window instead.
+    pub fn set_grabbed(&mut self, window: &Window, grabbed: bool) {
+        if self.grabbed == grabbed {
+            return;
+        }
+        let result = if grabbed {
+            window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(e) = result {
+            log::warn!("failed to change the cursor grab: {e}");
+        }
+        self.grabbed = grabbed;
+        self.update_visibility(window);
+    }
+
+    fn update_visibility(&self, window: &Window) {
+        window.set_cursor_visible(!self.grabbed && self.mode.cursor_icon().is_some());
+    }
+}
@@ -0,0 +1,183 @@
+use glam::{Mat4, Vec3};
+
+use crate::label;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::uniform::UniformBuffer;
+use crate::vertex::Vertex;
+
+const SHADER: &str = include_str!("debug_lines.wgsl");
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LinesUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+/// One end of a debug line, with a linear color.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl Vertex for LineVertex {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Immediate-mode colored line segments, e.g. for gizmos and bounds.
+///
+/// Lines are queued with [`line`](Self::line), uploaded with their
+/// view-projection by [`prepare`](Self::prepare) and drawn into an
+/// existing single-sampled pass without depth, so they always show on top.
+pub struct DebugLines {
+    vertices: Vec<LineVertex>,
+    /// Vertices drawn by [`draw`](Self::draw), as of the last `prepare`.
+    len: u32,
+    buffer: wgpu::Buffer,
+    /// Vertices `buffer` can hold.
+    capacity: usize,
+    uniform: UniformBuffer<LinesUniform>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Lines",
+            &crate::struct_layout!(LinesUniform { view_proj }),
+        );
+        let uniform = UniformBuffer::new(
+            device,
+            label!("debug_lines::uniform"),
+            wgpu::ShaderStages::VERTEX,
+        );
+        let capacity = 64;
+        Self {
+            vertices: Vec::new(),
+            len: 0,
+            buffer: create_buffer(device, capacity),
+            capacity,
+            pipeline: create_pipeline(device, uniform.layout(), format),
+            uniform,
+        }
+    }
+
+    /// Rebuilds the pipeline for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.pipeline = create_pipeline(device, self.uniform.layout(), format);
+    }
+
+    /// Queues a segment from `from` to `to`.
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: [f32; 4]) {
+        self.vertices.extend([
+            LineVertex {
+                position: from.to_array(),
+                color,
+            },
+            LineVertex {
+                position: to.to_array(),
+                color,
+            },
+        ]);
+    }
+
+    /// Uploads the queued lines and `view_proj`, and empties the queue for
+    /// the next frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view_proj: Mat4) {
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.buffer = create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.uniform.update(
+            queue,
+            &LinesUniform {
+                view_proj: view_proj.to_cols_array_2d(),
+            },
+        );
+        self.len = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.len == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.uniform.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.len, 0..1);
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: label!("debug_lines::vertex_buffer"),
+        size: (capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    uniform_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("debug_lines::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: label!("debug_lines::pipeline_layout"),
+        bind_group_layouts: &[uniform_layout],
+        push_constant_ranges: &[],
+    });
+    let mut constants = ShaderConstants::default();
+    let encode = if is_linear_output(format) { 0.0 } else { 1.0 };
+    constants.set("ENCODE_SRGB", encode);
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("debug_lines::pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[LineVertex::layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum InitError {
+    /// The window can't be drawn to, e.g. a canvas without a WebGL context.
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// Neither a hardware nor a fallback adapter is available.
+    NoAdapter(wgpu::RequestAdapterError),
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The adapter lacks downlevel capabilities the app requires.
+    MissingDownlevelFlags {
+        adapter: String,
+        missing: wgpu::DownlevelFlags,
+    },
+    /// Creating the app's pipelines or other resources failed validation;
+    /// the message names the resource by its label.
+    Validation(wgpu::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateSurface(e) => write!(f, "failed to create the window surface: {e}"),
+            Self::NoAdapter(e) => {
+                write!(f, "no GPU adapter found, not even a software fallback: {e}")
+            }
+            Self::RequestDevice(e) => write!(f, "failed to open the GPU device: {e}"),
+            Self::MissingDownlevelFlags { adapter, missing } => {
+                let names: Vec<&str> = missing.iter_names().map(|(name, _)| name).collect();
+                write!(
+                    f,
+                    "adapter `{adapter}` is missing required downlevel capabilities: {}",
+                    names.join(", ")
+                )
+            }
+            Self::Validation(e) => write!(f, "failed to create GPU resources: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+/// Why [`Context::spawn_window`](crate::app::Context::spawn_window) couldn't
+/// open a window.
+#[derive(Debug)]
+pub enum SpawnWindowError {
+    CreateWindow(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// The new window can't present the main window's format, which the
+    /// app's pipelines are built for.
+    UnsupportedFormat(wgpu::TextureFormat),
+}
+
+impl fmt::Display for SpawnWindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateWindow(e) => write!(f, "failed to create the window: {e}"),
+            Self::CreateSurface(e) => write!(f, "failed to create the window surface: {e}"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "the window can't present {format:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpawnWindowError {}
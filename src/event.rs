@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+/// Custom events delivered to the winit event loop from other threads.
+///
+/// Apps get a sender as [`Context::proxy`](crate::app::Context::proxy);
+/// clone it into the thread and call `EventLoopProxy::send_event`. The app
+/// receives the events in
+/// [`Application::user_event`](crate::app::Application::user_event).
+#[derive(Debug, Clone)]
+pub enum UserEvent {
+    /// A background loader finished loading the named asset.
+    AssetReady(String),
+    /// A watched shader file changed on disk.
+    ShaderChanged(PathBuf),
+    /// Wake the event loop and schedule a redraw.
+    RequestRedraw,
+}
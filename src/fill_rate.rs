@@ -0,0 +1,213 @@
+use winit::keyboard::KeyCode;
+
+use crate::label;
+use crate::scene::Scene;
+
+const SHADER: &str = include_str!("fill_rate.wgsl");
+
+/// Layers drawn when the scene starts.
+const DEFAULT_LAYERS: u32 = 16;
+const MAX_LAYERS: u32 = 4096;
+
+/// A fill-rate stress test: `layers` alpha-blended quads covering the whole
+/// target, so frame time tracks overdraw rather than draw calls. Up and Down
+/// double and halve the count.
+pub struct FillRateScene {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    layers: u32,
+}
+
+impl FillRateScene {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("fill_rate::shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("fill_rate::pipeline_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format, sample_count);
+        Self {
+            shader,
+            pipeline_layout,
+            pipeline,
+            layers: DEFAULT_LAYERS,
+        }
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Sets the number of fullscreen layers, clamped to `1..=4096`.
+    pub fn set_layers(&mut self, layers: u32) {
+        self.layers = layers.clamp(1, MAX_LAYERS);
+    }
+}
+
+impl Scene for FillRateScene {
+    fn name(&self) -> &str {
+        "fill rate"
+    }
+
+    fn needs_depth(&self) -> bool {
+        false
+    }
+
+    /// Black, so the layers' brightness shows the overdraw.
+    fn preferred_clear_color(&self) -> wgpu::Color {
+        wgpu::Color::BLACK
+    }
+
+    fn status(&self) -> String {
+        format!("{} layers", self.layers)
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::ArrowUp => self.set_layers(self.layers.saturating_mul(2)),
+            KeyCode::ArrowDown => self.set_layers(self.layers / 2),
+            _ => return false,
+        }
+        log::info!("fill rate: {} layers", self.layers);
+        true
+    }
+
+    fn set_target(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.pipeline = create_pipeline(
+            device,
+            &self.shader,
+            &self.pipeline_layout,
+            format,
+            sample_count,
+        );
+    }
+
+    fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..self.layers);
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("fill_rate::pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::read_texture_rgba;
+    use crate::frame::FrameEncoder;
+    use crate::test_support::test_gpu;
+
+    #[test]
+    fn keys_double_and_halve_the_layers_within_bounds() {
+        let gpu = test_gpu!();
+        let mut scene = FillRateScene::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, 1);
+        assert!(scene.key_pressed(KeyCode::ArrowUp));
+        assert_eq!(scene.layers(), DEFAULT_LAYERS * 2);
+        for _ in 0..16 {
+            scene.key_pressed(KeyCode::ArrowDown);
+        }
+        assert_eq!(scene.layers(), 1);
+        for _ in 0..16 {
+            scene.key_pressed(KeyCode::ArrowUp);
+        }
+        assert_eq!(scene.layers(), MAX_LAYERS);
+        assert!(!scene.key_pressed(KeyCode::KeyQ));
+    }
+
+    #[test]
+    fn layers_blend_in_a_pass_without_depth() {
+        let gpu = test_gpu!();
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let mut scene = FillRateScene::new(device, format, 1);
+        let mut alphas = Vec::new();
+        for layers in [1, 8] {
+            scene.set_layers(layers);
+            let mut frame = FrameEncoder::new(device, queue, None);
+            {
+                let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                scene.draw(&mut render_pass);
+            }
+            frame.submit();
+            let pixels = read_texture_rgba(device, queue, &texture).unwrap();
+            alphas.push(pixels[3]);
+        }
+        // Each layer covers the whole target, so more layers build up more
+        // coverage.
+        assert!(alphas[0] > 0 && alphas[1] > alphas[0], "{alphas:?}");
+    }
+}
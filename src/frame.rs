@@ -0,0 +1,51 @@
+/// Records every pass of a frame into a single `CommandEncoder` and submits it once.
+///
+/// Each `queue.submit` carries fixed driver overhead and splits the frame
+/// into batches the GPU scheduler can't overlap, so a frame should be one
+/// submission. Passes only ever see the encoder; the queue is reachable
+/// solely through `submit`, which consumes the frame.
+pub struct FrameEncoder<'a> {
+    queue: &'a wgpu::Queue,
+    encoder: Option<wgpu::CommandEncoder>,
+}
+
+impl<'a> FrameEncoder<'a> {
+    pub fn new(device: &wgpu::Device, queue: &'a wgpu::Queue, label: Option<&str>) -> Self {
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+        Self {
+            queue,
+            encoder: Some(encoder),
+        }
+    }
+
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        self.encoder
+            .as_mut()
+            .expect("encoder is present until submit")
+    }
+
+    pub fn begin_render_pass(
+        &mut self,
+        descriptor: &wgpu::RenderPassDescriptor<'_>,
+    ) -> wgpu::RenderPass<'_> {
+        self.encoder().begin_render_pass(descriptor)
+    }
+
+    pub fn submit(mut self) -> wgpu::SubmissionIndex {
+        let encoder = self
+            .encoder
+            .take()
+            .expect("encoder is present until submit");
+        self.queue.submit(Some(encoder.finish()))
+    }
+}
+
+impl Drop for FrameEncoder<'_> {
+    fn drop(&mut self) {
+        if self.encoder.is_some() {
+            log::warn!(
+                "FrameEncoder dropped without submitting; the frame's passes were discarded"
+            );
+        }
+    }
+}
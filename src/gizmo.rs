@@ -0,0 +1,188 @@
+use glam::{Mat4, Vec3};
+
+use crate::camera::Camera;
+use crate::debug_lines::DebugLines;
+
+/// Linear colors of the X, Y and Z axes.
+pub const AXIS_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.3, 0.5, 1.0, 1.0],
+];
+
+/// Gap in physical pixels between the gizmo and the target's edges.
+const MARGIN: u32 = 8;
+
+/// The world axes in a corner of the screen, turned with the camera so they
+/// show which way X, Y and Z point.
+///
+/// The gizmo draws with [`DebugLines`] into its own square viewport and
+/// scissor rectangle in the bottom-left corner, without depth, so nothing
+/// in the scene can hide it.
+pub struct AxisGizmo {
+    lines: DebugLines,
+    /// Side of the viewport in physical pixels.
+    size: u32,
+    /// Size of the target drawn into, in physical pixels.
+    target_size: [u32; 2],
+}
+
+impl AxisGizmo {
+    /// Creates a gizmo `size` physical pixels across, for a `width` by
+    /// `height` target.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: u32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            lines: DebugLines::new(device, format),
+            size,
+            target_size: [width, height],
+        }
+    }
+
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.lines.set_format(device, format);
+    }
+
+    /// Sets the side of the viewport, e.g. after a scale factor change.
+    pub fn set_size(&mut self, size: u32) {
+        self.size = size;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.target_size = [width, height];
+    }
+
+    /// Uploads the axes as seen by `camera`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera) {
+        for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().zip(AXIS_COLORS) {
+            self.lines.line(Vec3::ZERO, axis, color);
+        }
+        self.lines
+            .prepare(device, queue, Self::view_projection(camera));
+    }
+
+    /// The camera's orientation without its position, looking at unit axes
+    /// from outside a unit sphere, with an orthographic projection so every
+    /// axis keeps its length when seen side-on.
+    pub fn view_projection(camera: &Camera) -> Mat4 {
+        let forward = (camera.target - camera.eye).normalize_or(Vec3::NEG_Z);
+        let view = Mat4::look_at_rh(Vec3::ZERO, forward, camera.up);
+        let extent = 1.1;
+        Mat4::orthographic_rh(-extent, extent, -extent, extent, -extent, extent) * view
+    }
+
+    /// The gizmo's viewport as x, y, width and height in physical pixels,
+    /// in the bottom-left corner of the target.
+    pub fn viewport(&self) -> [u32; 4] {
+        let [width, height] = self.target_size;
+        let size = self.size.min(width).min(height);
+        let x = MARGIN.min(width - size);
+        let y = height - size - MARGIN.min(height - size);
+        [x, y, size, size]
+    }
+
+    /// Draws the axes into the target's corner, then restores the full
+    /// viewport and scissor for whatever is drawn next.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let [x, y, width, height] = self.viewport();
+        if width == 0 {
+            return;
+        }
+        render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(x, y, width, height);
+        self.lines.draw(render_pass);
+        let [width, height] = self.target_size;
+        render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(0, 0, width, height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3Swizzles, Vec4Swizzles};
+
+    use super::*;
+    use crate::test_support::{test_gpu, TestRenderer};
+
+    fn camera_at(eye: Vec3) -> Camera {
+        Camera {
+            eye,
+            target: Vec3::ZERO,
+            ..Camera::new(1.0)
+        }
+    }
+
+    #[test]
+    fn axes_turn_with_the_camera() {
+        let project = |camera: &Camera, axis: Vec3| {
+            (AxisGizmo::view_projection(camera) * axis.extend(1.0)).xyz()
+        };
+        // Looking down -Z, X points right and Y up.
+        let front = camera_at(Vec3::new(0.0, 0.0, 5.0));
+        assert!(project(&front, Vec3::X).abs_diff_eq(Vec3::new(1.0 / 1.1, 0.0, 0.5), 1e-5));
+        assert!(project(&front, Vec3::Y).abs_diff_eq(Vec3::new(0.0, 1.0 / 1.1, 0.5), 1e-5));
+        // From the +X side, X points at the viewer and Z to the left.
+        let side = camera_at(Vec3::new(5.0, 0.0, 0.0));
+        assert!(project(&side, Vec3::X)
+            .xy()
+            .abs_diff_eq(glam::Vec2::ZERO, 1e-5));
+        assert!(project(&side, Vec3::Z).x < -0.9);
+        // Moving the camera without turning it leaves the gizmo alone.
+        let moved = Camera {
+            eye: front.eye + Vec3::new(3.0, 1.0, 0.0),
+            target: front.target + Vec3::new(3.0, 1.0, 0.0),
+            ..front
+        };
+        assert!(AxisGizmo::view_projection(&moved)
+            .abs_diff_eq(AxisGizmo::view_projection(&front), 1e-6));
+    }
+
+    #[test]
+    fn draws_only_in_its_corner() {
+        const SIZE: u32 = 64;
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = TestRenderer::new(test_gpu!(), SIZE, SIZE, format);
+        let (device, queue) = (&renderer.gpu.device, &renderer.gpu.queue);
+        let mut gizmo = AxisGizmo::new(device, format, 24, SIZE, SIZE);
+        gizmo.prepare(device, queue, &camera_at(Vec3::new(0.0, 0.0, 5.0)));
+        let image = renderer.render(|frame, view| {
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            gizmo.draw(&mut render_pass);
+        });
+        let [x, y, width, height] = gizmo.viewport();
+        let inside =
+            |px: u32, py: u32| (x..x + width).contains(&px) && (y..y + height).contains(&py);
+        let mut red = 0;
+        for (px, py, pixel) in image.enumerate_pixels() {
+            if pixel.0[..3] == [0, 0, 0] {
+                continue;
+            }
+            assert!(inside(px, py), "drawn outside the corner at ({px}, {py})");
+            if pixel[0] > pixel[1] && pixel[0] > pixel[2] {
+                // The X axis runs right from the viewport's center.
+                assert!(px >= x + width / 2 - 1, "X axis at ({px}, {py})");
+                red += 1;
+            }
+        }
+        assert!(red > 5, "{red} X axis pixels");
+    }
+}
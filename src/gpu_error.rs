@@ -0,0 +1,26 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Collects uncaptured device errors so the event loop can react to them.
+///
+/// Without a handler wgpu panics on the first validation error with little
+/// context; this logs the full message instead and queues it for the app.
+#[derive(Clone, Default)]
+pub struct GpuErrorMonitor {
+    pending: Arc<Mutex<Vec<String>>>,
+}
+
+impl GpuErrorMonitor {
+    pub fn install(&self, device: &wgpu::Device) {
+        let pending = self.pending.clone();
+        device.on_uncaptured_error(Arc::new(move |error: wgpu::Error| {
+            log::error!("uncaptured GPU error: {error}");
+            pending.lock().push(error.to_string());
+        }));
+    }
+
+    /// Errors reported since the last call.
+    pub fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut *self.pending.lock())
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use nvml_wrapper::enum_wrappers::device::{Clock, TemperatureSensor};
+use nvml_wrapper::error::NvmlError;
+use nvml_wrapper::Nvml;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuStats {
+    pub temperature_celsius: u32,
+    pub graphics_clock_mhz: u32,
+}
+
+impl fmt::Display for GpuStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}°C {} MHz",
+            self.temperature_celsius, self.graphics_clock_mhz
+        )
+    }
+}
+
+/// Polls the temperature and graphics clock of the first NVIDIA GPU via NVML.
+///
+/// Without an NVIDIA driver (or on other vendors) NVML fails to load and
+/// [`stats`](Self::stats) stays `None`.
+pub struct GpuStatsMonitor {
+    nvml: Option<Nvml>,
+    stats: Option<GpuStats>,
+    last_poll: Option<Instant>,
+}
+
+impl Default for GpuStatsMonitor {
+    fn default() -> Self {
+        let nvml = Nvml::init()
+            .inspect_err(|e| log::info!("GPU stats unavailable: {e}"))
+            .ok();
+        Self {
+            nvml,
+            stats: None,
+            last_poll: None,
+        }
+    }
+}
+
+impl GpuStatsMonitor {
+    pub fn stats(&self) -> Option<GpuStats> {
+        self.stats
+    }
+
+    /// Re-reads the stats if a poll is due, returning whether it polled.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if self
+            .last_poll
+            .is_some_and(|last| now.duration_since(last) < POLL_INTERVAL)
+        {
+            return false;
+        }
+        self.last_poll = Some(now);
+        let Some(nvml) = &self.nvml else {
+            return true;
+        };
+        self.stats = read_stats(nvml)
+            .inspect_err(|e| log::debug!("failed to read GPU stats: {e}"))
+            .ok();
+        true
+    }
+}
+
+fn read_stats(nvml: &Nvml) -> Result<GpuStats, NvmlError> {
+    let device = nvml.device_by_index(0)?;
+    Ok(GpuStats {
+        temperature_celsius: device.temperature(TemperatureSensor::Gpu)?,
+        graphics_clock_mhz: device.clock_info(Clock::Graphics)?,
+    })
+}
@@ -0,0 +1,350 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+use crate::label;
+
+/// Timestamps written per frame: the start and end of each timed pass.
+const QUERY_COUNT: u32 = 2 * TimedPass::ALL.len() as u32;
+const BUFFER_SIZE: wgpu::BufferAddress = QUERY_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+/// Read-back frames the rolling averages are taken over.
+const AVERAGE_WINDOW: usize = 60;
+
+/// A render pass the [`GpuTimer`] can time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimedPass {
+    Scene,
+    Overlay,
+}
+
+impl TimedPass {
+    pub const ALL: [Self; 2] = [Self::Scene, Self::Overlay];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scene => "scene",
+            Self::Overlay => "overlay",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for TimedPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// GPU time spent in each timed pass of a frame; `None` for passes that
+/// weren't drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GpuTimings {
+    passes: [Option<Duration>; TimedPass::ALL.len()],
+}
+
+impl GpuTimings {
+    pub fn pass(&self, pass: TimedPass) -> Option<Duration> {
+        self.passes[pass.index()]
+    }
+
+    pub fn set_pass(&mut self, pass: TimedPass, time: Duration) {
+        self.passes[pass.index()] = Some(time);
+    }
+
+    /// Sum of the passes that were drawn.
+    pub fn total(&self) -> Duration {
+        self.passes.iter().flatten().sum()
+    }
+}
+
+impl fmt::Display for GpuTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        for pass in TimedPass::ALL {
+            if let Some(time) = self.pass(pass) {
+                write!(
+                    f,
+                    "{separator}{pass} pass {:.3}ms",
+                    time.as_secs_f64() * 1000.0
+                )?;
+                separator = ", ";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Measures render passes on the GPU with timestamp queries.
+///
+/// Results are read back asynchronously, so [`timings`](Self::timings) lags
+/// a frame or more behind. Frames encoded while the previous readback is
+/// still in flight are not timed.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// Bit per [`TimedPass`] given timestamp writes this frame; passes that
+    /// weren't drawn have no timestamps to read.
+    written: Cell<u32>,
+    readback: Option<(Receiver<Result<(), wgpu::BufferAsyncError>>, u32)>,
+    timings: Option<GpuTimings>,
+    history: TimingHistory,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the device was created without
+    /// `Features::TIMESTAMP_QUERY`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            log::info!("GPU timings unavailable: the adapter has no timestamp queries");
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: label!("gpu_timer::query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("gpu_timer::resolve_buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: label!("gpu_timer::readback_buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            written: Cell::new(0),
+            readback: None,
+            timings: None,
+            history: TimingHistory::default(),
+        })
+    }
+
+    /// The most recently read back timings.
+    pub fn timings(&self) -> Option<GpuTimings> {
+        self.timings
+    }
+
+    /// Each pass's mean time over the last read-back frames it was drawn in.
+    pub fn averages(&self) -> GpuTimings {
+        self.history.average()
+    }
+
+    /// Timestamp writes for `pass`, or `None` while the previous frame's
+    /// results are still being read back. Each pass should be timed at most
+    /// once per frame.
+    pub fn pass_writes(&self, pass: TimedPass) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        if self.readback.is_some() {
+            return None;
+        }
+        self.written.set(self.written.get() | 1 << pass.index());
+        let first = 2 * pass.index() as u32;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(first),
+            end_of_pass_write_index: Some(first + 1),
+        })
+    }
+
+    /// Copies this frame's timestamps to the readback buffer. Call after the
+    /// timed passes are encoded, then [`map`](Self::map) after submitting.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.readback.is_some() || self.written.get() == 0 {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            BUFFER_SIZE,
+        );
+    }
+
+    /// Starts reading back the timestamps resolved this frame.
+    pub fn map(&mut self) {
+        if self.readback.is_some() {
+            return;
+        }
+        let written = self.written.replace(0);
+        if written == 0 {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Some((receiver, written));
+    }
+
+    /// Picks up a finished readback without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some((receiver, written)) = &self.readback else {
+            return;
+        };
+        let _ = device.poll(wgpu::PollType::Poll);
+        match receiver.try_recv() {
+            Ok(Ok(())) => {
+                {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    let ticks: &[u64] = bytemuck::cast_slice(&data);
+                    let mut timings = GpuTimings::default();
+                    for pass in TimedPass::ALL {
+                        if written & 1 << pass.index() == 0 {
+                            continue;
+                        }
+                        let [start, end] = [ticks[2 * pass.index()], ticks[2 * pass.index() + 1]];
+                        let nanos = end.saturating_sub(start) as f64 * self.period as f64;
+                        timings.set_pass(pass, Duration::from_nanos(nanos as u64));
+                    }
+                    self.timings = Some(timings);
+                    self.history.push(timings);
+                }
+                self.readback_buffer.unmap();
+            }
+            Ok(Err(e)) => log::warn!("failed to read back GPU timings: {e}"),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => log::warn!("GPU timings readback was dropped"),
+        }
+        self.readback = None;
+    }
+}
+
+/// The timings of the last [`AVERAGE_WINDOW`] read-back frames.
+#[derive(Default)]
+struct TimingHistory {
+    frames: VecDeque<GpuTimings>,
+}
+
+impl TimingHistory {
+    fn push(&mut self, timings: GpuTimings) {
+        if self.frames.len() == AVERAGE_WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(timings);
+    }
+
+    fn average(&self) -> GpuTimings {
+        let mut average = GpuTimings::default();
+        for pass in TimedPass::ALL {
+            let times: Vec<_> = self.frames.iter().filter_map(|t| t.pass(pass)).collect();
+            if !times.is_empty() {
+                average.set_pass(pass, times.iter().sum::<Duration>() / times.len() as u32);
+            }
+        }
+        average
+    }
+}
+
+/// Per-pass GPU time budgets, checked against [`GpuTimer::averages`] to
+/// point at the pass that regressed rather than just the whole frame.
+pub struct PassBudgets {
+    budgets: Vec<(TimedPass, Duration)>,
+    /// Passes over budget at the last check, warned about once until they
+    /// drop back under.
+    over: Vec<TimedPass>,
+}
+
+impl PassBudgets {
+    pub fn new(budgets: &[(TimedPass, Duration)]) -> Self {
+        Self {
+            budgets: budgets.to_vec(),
+            over: Vec::new(),
+        }
+    }
+
+    /// Logs a warning naming each pass whose average went over its budget
+    /// since the last check, and returns the passes that are over.
+    pub fn check(&mut self, averages: &GpuTimings) -> &[TimedPass] {
+        for &(pass, budget) in &self.budgets {
+            let Some(average) = averages.pass(pass) else {
+                continue;
+            };
+            let was_over = self.over.contains(&pass);
+            if average > budget && !was_over {
+                log::warn!(
+                    "{pass} pass over its GPU budget: {:.3}ms average vs {:.3}ms",
+                    average.as_secs_f64() * 1000.0,
+                    budget.as_secs_f64() * 1000.0
+                );
+                self.over.push(pass);
+            } else if average <= budget && was_over {
+                log::info!("{pass} pass back within its GPU budget");
+                self.over.retain(|&p| p != pass);
+            }
+        }
+        &self.over
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn timings(scene: u64, overlay: Option<u64>) -> GpuTimings {
+        let mut timings = GpuTimings::default();
+        timings.set_pass(TimedPass::Scene, ms(scene));
+        if let Some(overlay) = overlay {
+            timings.set_pass(TimedPass::Overlay, ms(overlay));
+        }
+        timings
+    }
+
+    #[test]
+    fn averages_skip_frames_without_the_pass() {
+        let mut history = TimingHistory::default();
+        history.push(timings(2, Some(1)));
+        history.push(timings(4, None));
+        history.push(timings(6, Some(3)));
+        let average = history.average();
+        assert_eq!(average.pass(TimedPass::Scene), Some(ms(4)));
+        assert_eq!(average.pass(TimedPass::Overlay), Some(ms(2)));
+        assert_eq!(
+            average.to_string(),
+            "scene pass 4.000ms, overlay pass 2.000ms"
+        );
+    }
+
+    #[test]
+    fn averages_cover_the_rolling_window() {
+        let mut history = TimingHistory::default();
+        history.push(timings(100, None));
+        for _ in 0..AVERAGE_WINDOW {
+            history.push(timings(1, None));
+        }
+        assert_eq!(history.average(), timings(1, None));
+    }
+
+    #[test]
+    fn budgets_flag_passes_over_budget_until_they_recover() {
+        let mut budgets = PassBudgets::new(&[(TimedPass::Scene, ms(5))]);
+        assert!(budgets.check(&timings(4, Some(50))).is_empty());
+        assert_eq!(budgets.check(&timings(6, None)), [TimedPass::Scene]);
+        assert_eq!(budgets.check(&timings(7, None)), [TimedPass::Scene]);
+        // Frames without the pass leave it as it was.
+        assert_eq!(budgets.check(&GpuTimings::default()), [TimedPass::Scene]);
+        assert!(budgets.check(&timings(5, None)).is_empty());
+    }
+}
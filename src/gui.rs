@@ -0,0 +1,448 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+
+#[cfg(feature = "text")]
+use crate::frame::FrameEncoder;
+#[cfg(feature = "text")]
+use crate::sprite::SpriteBatch;
+#[cfg(feature = "text")]
+use crate::text::TextRenderer;
+#[cfg(feature = "text")]
+use crate::texture::Texture;
+
+/// Width of the widgets in the stack, in logical pixels.
+const WIDGET_WIDTH: f32 = 200.0;
+const ROW_HEIGHT: f32 = 22.0;
+/// Gap between rows and around the stack.
+const SPACING: f32 = 6.0;
+const FONT_SIZE: f32 = 14.0;
+const HANDLE_WIDTH: f32 = 8.0;
+
+// sRGB with straight alpha.
+const PANEL_COLOR: [f32; 4] = [0.1, 0.1, 0.12, 0.8];
+const WIDGET_COLOR: [f32; 4] = [0.25, 0.25, 0.3, 1.0];
+const HOT_COLOR: [f32; 4] = [0.35, 0.35, 0.42, 1.0];
+const ACTIVE_COLOR: [f32; 4] = [0.2, 0.4, 0.7, 1.0];
+const HANDLE_COLOR: [f32; 4] = [0.85, 0.85, 0.9, 1.0];
+#[cfg(feature = "text")]
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Identifies a widget across frames; made from the name it's built with.
+pub type WidgetId = u64;
+
+fn widget_id(name: &str) -> WidgetId {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A filled rectangle, min x, min y, max x, max y in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quad {
+    pub rect: [f32; 4],
+    pub color: [f32; 4],
+}
+
+/// Text with its top-left corner at `position`, in logical pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub position: [f32; 2],
+    pub text: String,
+}
+
+/// A small immediate-mode GUI for debug controls: labels, buttons and
+/// sliders in a vertical stack.
+///
+/// Each frame the widgets are declared again between [`begin`](Self::begin)
+/// and [`end`](Self::end), and report what happened to them since the last
+/// frame, e.g. [`button`](Self::button) returns whether it was clicked.
+/// The only state kept between frames is which widget, by the name it was
+/// declared with, holds the pointer: the one it was pressed on, until it is
+/// released, so a slider keeps following a drag that leaves it.
+///
+/// Everything is in logical pixels. The result is a list of [`Quad`]s and
+/// [`Label`]s, drawn by a [`GuiRenderer`] with the `text` feature.
+#[derive(Debug, Default)]
+pub struct Gui {
+    pointer: Option<[f32; 2]>,
+    down: bool,
+    /// The button went down or up since the last frame.
+    pressed: bool,
+    released: bool,
+    active: Option<WidgetId>,
+    /// Top-left corner of the next widget.
+    cursor: [f32; 2],
+    quads: Vec<Quad>,
+    labels: Vec<Label>,
+    /// The area the last finished frame's widgets cover, if any.
+    bounds: Option<[f32; 4]>,
+}
+
+impl Gui {
+    /// Follows the pointer to `position`.
+    pub fn handle_pointer_moved(&mut self, position: [f32; 2]) {
+        self.pointer = Some(position);
+    }
+
+    /// Follows the primary button; returns whether the GUI takes the event,
+    /// in which case the app should ignore it.
+    pub fn handle_button(&mut self, pressed: bool) -> bool {
+        let captured = if pressed {
+            self.is_over()
+        } else {
+            self.active.is_some()
+        };
+        if pressed && !self.down {
+            self.pressed = true;
+        } else if !pressed && self.down {
+            self.released = true;
+        }
+        self.down = pressed;
+        captured
+    }
+
+    /// Forgets the pointer, e.g. when it leaves the window or focus is lost
+    /// and the button's release may never arrive.
+    pub fn handle_pointer_left(&mut self) {
+        self.pointer = None;
+        self.down = false;
+        self.pressed = false;
+        self.released = false;
+        self.active = None;
+    }
+
+    /// Whether the pointer is over the widgets or dragging one of them.
+    pub fn wants_pointer(&self) -> bool {
+        self.active.is_some() || self.is_over()
+    }
+
+    fn is_over(&self) -> bool {
+        matches!((self.pointer, self.bounds), (Some(p), Some(r)) if contains(r, p))
+    }
+
+    /// Starts a frame's widgets, stacked down from `origin`.
+    pub fn begin(&mut self, origin: [f32; 2]) {
+        self.cursor = [origin[0] + SPACING, origin[1] + SPACING];
+        self.quads.clear();
+        self.labels.clear();
+        // Room for the panel behind the widgets, filled in by `end`.
+        self.quads.push(Quad {
+            rect: [origin[0], origin[1], origin[0], origin[1]],
+            color: PANEL_COLOR,
+        });
+    }
+
+    /// Finishes the frame's widgets, sizing the panel behind them.
+    pub fn end(&mut self) {
+        let [x, y, ..] = self.quads[0].rect;
+        let bounds = [x, y, x + WIDGET_WIDTH + 2.0 * SPACING, self.cursor[1]];
+        self.quads[0].rect = bounds;
+        self.bounds = (self.quads.len() > 1).then_some(bounds);
+        if self.released {
+            self.active = None;
+        }
+        self.pressed = false;
+        self.released = false;
+    }
+
+    /// The frame's rectangles, back to front.
+    pub fn quads(&self) -> &[Quad] {
+        &self.quads
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let rect = self.next_row();
+        self.add_label(rect, text);
+    }
+
+    /// A button named `name` showing `text`; returns whether it was clicked,
+    /// pressed and released over it.
+    pub fn button(&mut self, name: &str, text: &str) -> bool {
+        let rect = self.next_row();
+        let (hovered, held) = self.interact(widget_id(name), rect);
+        let clicked = held && self.released && hovered;
+        let color = if held {
+            ACTIVE_COLOR
+        } else if hovered {
+            HOT_COLOR
+        } else {
+            WIDGET_COLOR
+        };
+        self.quads.push(Quad { rect, color });
+        self.add_label(rect, text);
+        clicked
+    }
+
+    /// A slider named `name` setting `value` within `range`, labeled with
+    /// `text` and the value; returns whether the value changed.
+    pub fn slider(
+        &mut self,
+        name: &str,
+        text: &str,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+    ) -> bool {
+        let rect = self.next_row();
+        let (hovered, held) = self.interact(widget_id(name), rect);
+        let (low, high) = (*range.start(), *range.end());
+        let old = *value;
+        if let (true, Some([x, _])) = (held, self.pointer) {
+            let t = (x - rect[0]) / (rect[2] - rect[0]);
+            *value = low + t.clamp(0.0, 1.0) * (high - low);
+        }
+        let t = if high > low {
+            ((*value - low) / (high - low)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let handle_x = rect[0] + t * (rect[2] - rect[0] - HANDLE_WIDTH);
+        self.quads.push(Quad {
+            rect,
+            color: if hovered || held {
+                HOT_COLOR
+            } else {
+                WIDGET_COLOR
+            },
+        });
+        self.quads.push(Quad {
+            rect: [handle_x, rect[1], handle_x + HANDLE_WIDTH, rect[3]],
+            color: if held { ACTIVE_COLOR } else { HANDLE_COLOR },
+        });
+        self.add_label(rect, &format!("{text}: {value:.2}"));
+        *value != old
+    }
+
+    /// Returns whether the pointer is over `rect`, and whether the widget
+    /// holds the pointer, taking it if it was pressed over `rect`.
+    fn interact(&mut self, id: WidgetId, rect: [f32; 4]) -> (bool, bool) {
+        let hovered = self.pointer.is_some_and(|p| contains(rect, p));
+        if self.pressed && hovered && self.active.is_none() {
+            self.active = Some(id);
+        }
+        (hovered, self.active == Some(id))
+    }
+
+    fn next_row(&mut self) -> [f32; 4] {
+        let [x, y] = self.cursor;
+        self.cursor[1] += ROW_HEIGHT + SPACING;
+        [x, y, x + WIDGET_WIDTH, y + ROW_HEIGHT]
+    }
+
+    fn add_label(&mut self, rect: [f32; 4], text: &str) {
+        self.labels.push(Label {
+            position: [
+                rect[0] + 4.0,
+                rect[1] + (ROW_HEIGHT - FONT_SIZE) / 2.0 - 1.0,
+            ],
+            text: text.to_owned(),
+        });
+    }
+}
+
+fn contains(rect: [f32; 4], [x, y]: [f32; 2]) -> bool {
+    x >= rect[0] && x < rect[2] && y >= rect[1] && y < rect[3]
+}
+
+/// Draws a [`Gui`] with a [`SpriteBatch`] for its quads and the app's
+/// [`TextRenderer`] for its labels.
+#[cfg(feature = "text")]
+pub struct GuiRenderer {
+    sprites: SpriteBatch,
+    white: wgpu::BindGroup,
+    scale_factor: f32,
+}
+
+#[cfg(feature = "text")]
+impl GuiRenderer {
+    /// Creates a renderer for a `width` by `height` target of `format` on a
+    /// display with `scale_factor` physical pixels per logical pixel.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    ) -> Self {
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255; 4]),
+        ));
+        let white = Texture::from_image(device, queue, &white, crate::label!("gui::white"));
+        Self {
+            sprites: SpriteBatch::new(device, format, width, height),
+            white: white.bind_group(device, &Texture::bind_group_layout(device)),
+            scale_factor: scale_factor as f32,
+        }
+    }
+
+    /// Rebuilds the pipelines for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.sprites.set_format(device, format);
+    }
+
+    /// Follows a resize of the target to `width` by `height` physical pixels.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.sprites.resize(device, width, height);
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+    }
+
+    /// Paints `gui`'s quads over `view` and queues its labels on `text`,
+    /// whose next draw must come after this one.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+        gui: &Gui,
+        text: &mut TextRenderer,
+    ) {
+        for (layer, quad) in gui.quads().iter().enumerate() {
+            let rect = quad.rect.map(|v| v * self.scale_factor);
+            self.sprites
+                .draw_sprite(rect, [0.0, 0.0, 1.0, 1.0], quad.color, layer as u16);
+        }
+        self.sprites.draw(device, queue, frame, view, &self.white);
+        for label in gui.labels() {
+            let [x, y] = label.position;
+            text.queue(x, y, &label.text, FONT_SIZE, TEXT_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a frame with a label, a button and a slider, returning whether
+    /// the button was clicked and the slider's value.
+    fn frame(gui: &mut Gui, value: &mut f32) -> bool {
+        gui.begin([0.0, 0.0]);
+        gui.label("Controls");
+        let clicked = gui.button("reset", "Reset");
+        gui.slider("red", "Red", value, 0.0..=1.0);
+        gui.end();
+        clicked
+    }
+
+    /// Centers of the rows, in order.
+    fn row(index: usize) -> f32 {
+        SPACING + index as f32 * (ROW_HEIGHT + SPACING) + ROW_HEIGHT / 2.0
+    }
+
+    #[test]
+    fn widgets_stack_vertically_inside_the_panel() {
+        let mut gui = Gui::default();
+        frame(&mut gui, &mut 0.5);
+        let labels: Vec<_> = gui.labels().iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(labels, ["Controls", "Reset", "Red: 0.50"]);
+        let rows: Vec<f32> = gui.labels().iter().map(|l| l.position[1]).collect();
+        assert!(rows
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] == ROW_HEIGHT + SPACING));
+        let panel = gui.quads()[0].rect;
+        assert_eq!(
+            panel,
+            [
+                0.0,
+                0.0,
+                WIDGET_WIDTH + 2.0 * SPACING,
+                row(3) - ROW_HEIGHT / 2.0
+            ]
+        );
+        for quad in &gui.quads()[1..] {
+            assert!(contains(panel, [quad.rect[0], quad.rect[1]]));
+        }
+    }
+
+    #[test]
+    fn buttons_click_on_release_over_them() {
+        let mut gui = Gui::default();
+        let mut value = 0.0;
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_moved([50.0, row(1)]);
+        assert!(gui.handle_button(true));
+        assert!(!frame(&mut gui, &mut value));
+        assert!(gui.handle_button(false));
+        assert!(frame(&mut gui, &mut value));
+        assert!(!frame(&mut gui, &mut value));
+
+        // Press and release within one frame still clicks.
+        gui.handle_button(true);
+        gui.handle_button(false);
+        assert!(frame(&mut gui, &mut value));
+
+        // Releasing away from the button doesn't.
+        gui.handle_button(true);
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_moved([50.0, row(2)]);
+        gui.handle_button(false);
+        assert!(!frame(&mut gui, &mut value));
+    }
+
+    #[test]
+    fn presses_outside_are_left_to_the_app() {
+        let mut gui = Gui::default();
+        let mut value = 0.0;
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_moved([400.0, row(1)]);
+        assert!(!gui.wants_pointer());
+        assert!(!gui.handle_button(true));
+        assert!(!frame(&mut gui, &mut value));
+        // Dragging onto the button doesn't press it.
+        gui.handle_pointer_moved([50.0, row(1)]);
+        assert!(!gui.handle_button(false));
+        assert!(!frame(&mut gui, &mut value));
+    }
+
+    #[test]
+    fn sliders_follow_a_drag_that_leaves_them() {
+        let mut gui = Gui::default();
+        let mut value = 0.0;
+        frame(&mut gui, &mut value);
+        let x = |t: f32| SPACING + t * WIDGET_WIDTH;
+        gui.handle_pointer_moved([x(0.25), row(2)]);
+        gui.handle_button(true);
+        frame(&mut gui, &mut value);
+        assert_eq!(value, 0.25);
+
+        gui.handle_pointer_moved([x(0.75), row(2) + 100.0]);
+        assert!(gui.wants_pointer());
+        frame(&mut gui, &mut value);
+        assert_eq!(value, 0.75);
+        gui.handle_pointer_moved([x(2.0), row(2) + 100.0]);
+        frame(&mut gui, &mut value);
+        assert_eq!(value, 1.0);
+
+        assert!(gui.handle_button(false));
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_moved([x(0.5), row(2)]);
+        frame(&mut gui, &mut value);
+        assert_eq!(value, 1.0);
+    }
+
+    #[test]
+    fn losing_the_pointer_drops_the_drag() {
+        let mut gui = Gui::default();
+        let mut value = 0.0;
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_moved([SPACING + 10.0, row(2)]);
+        gui.handle_button(true);
+        frame(&mut gui, &mut value);
+        gui.handle_pointer_left();
+        assert!(!gui.wants_pointer());
+        gui.handle_pointer_moved([SPACING + 100.0, row(2)]);
+        frame(&mut gui, &mut value);
+        assert_eq!(value, 10.0 / WIDGET_WIDTH);
+    }
+}
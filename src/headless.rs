@@ -0,0 +1,451 @@
+use crate::bundle::{encode_bundles, BundleTarget};
+use crate::camera::{Camera, CameraUniform};
+use crate::capture::{read_texture_r32float, read_texture_rgba, CaptureError};
+use crate::config::AppConfig;
+use crate::context::GpuContext;
+use crate::error::InitError;
+use crate::frame::FrameEncoder;
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::label;
+use crate::linear_depth::LinearDepth;
+use crate::model::Model;
+use crate::pipeline::ScenePipeline;
+use crate::pulling;
+use crate::scene;
+use crate::texture::{Texture, DEPTH_FORMAT};
+use crate::uniform::UniformBuffer;
+use crate::vertex::{ModelVertex, Vertex};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// A frame read back by [`HeadlessRenderer::render`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessFrame {
+    /// Tightly packed sRGB RGBA, row by row from the top.
+    pub color: Vec<u8>,
+    /// View-space distance per pixel, in the same order, if it was asked
+    /// for; see [`LinearDepth`].
+    pub linear_depth: Option<Vec<f32>>,
+}
+
+/// Renders the demo scene into offscreen textures, without a window or
+/// surface, e.g. for CI or batch rendering.
+pub struct HeadlessRenderer {
+    context: GpuContext,
+    camera_uniform: UniformBuffer<CameraUniform>,
+    scene_pipeline: ScenePipeline,
+    model: Model,
+    instances: InstanceBuffer,
+    linear_depth: LinearDepth,
+    encode_threads: usize,
+    vertex_pulling: bool,
+}
+
+impl HeadlessRenderer {
+    pub async fn new(config: &AppConfig) -> Result<Self, InitError> {
+        let instance = GpuContext::create_instance(config);
+        let context = GpuContext::new(instance, None, config).await?;
+        let GpuContext { device, queue, .. } = &context;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let camera_uniform = UniformBuffer::new(
+            device,
+            label!("camera::uniform"),
+            wgpu::ShaderStages::VERTEX,
+        );
+        let texture_layout = Texture::array_bind_group_layout(device);
+        let vertex_pulling = config.vertex_pulling && pulling::is_supported(&context.adapter);
+        let scene_pipeline = if vertex_pulling {
+            ScenePipeline::new(
+                device,
+                FORMAT,
+                1,
+                wgpu::DepthBiasState::default(),
+                &[
+                    camera_uniform.layout(),
+                    &texture_layout,
+                    &InstanceBuffer::pulling_bind_group_layout(device),
+                    &pulling::bind_group_layout(device),
+                ],
+                Vec::new(),
+            )
+        } else {
+            ScenePipeline::new(
+                device,
+                FORMAT,
+                1,
+                wgpu::DepthBiasState::default(),
+                &[
+                    camera_uniform.layout(),
+                    &texture_layout,
+                    &InstanceBuffer::bind_group_layout(device),
+                ],
+                vec![ModelVertex::layout(), InstanceRaw::layout()],
+            )
+        };
+        let model = scene::pentagon_model(device, queue);
+        let mut instances = InstanceBuffer::new(device);
+        instances.set_materials(device, queue, scene::MATERIALS);
+        instances
+            .update(
+                device,
+                queue,
+                &scene::grid_instances(scene::INSTANCES_PER_ROW),
+            )
+            .expect("the grid only uses the scene's materials");
+        let linear_depth = LinearDepth::new(device);
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(InitError::Validation(error));
+        }
+        Ok(Self {
+            context,
+            camera_uniform,
+            scene_pipeline,
+            model,
+            instances,
+            linear_depth,
+            encode_threads: config.encode_threads,
+            vertex_pulling,
+        })
+    }
+
+    /// Renders one frame at `width` by `height`, at least 1x1, and returns
+    /// it as tightly packed sRGB RGBA, row by row from the top.
+    pub fn render_frame(&mut self, width: u32, height: u32) -> Result<Vec<u8>, CaptureError> {
+        Ok(self.render(width, height, false)?.color)
+    }
+
+    /// Like [`render_frame`](Self::render_frame), also reading back the
+    /// linear depth of the frame if `linear_depth` is set, e.g. for
+    /// compositing the frame with another renderer's by depth.
+    pub fn render(
+        &mut self,
+        width: u32,
+        height: u32,
+        linear_depth: bool,
+    ) -> Result<HeadlessFrame, CaptureError> {
+        let GpuContext { device, queue, .. } = &self.context;
+        let (width, height) = (width.max(1), height.max(1));
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(CaptureError::TooLarge { width, height, max });
+        }
+
+        let mut camera = Camera::new(width as f32 / height as f32);
+        camera.eye = scene::CAMERA_EYE;
+        self.camera_uniform
+            .update(queue, &CameraUniform::from(&camera));
+        self.linear_depth
+            .set_depth_range(queue, camera.znear, camera.zfar);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("headless::target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = Texture::create_depth_texture(
+            device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: FORMAT,
+                width,
+                height,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+                view_formats: vec![],
+                desired_maximum_frame_latency: 2,
+            },
+            1,
+            "headless::depth_texture",
+        );
+
+        let bundles = (self.encode_threads > 1 && !self.vertex_pulling).then(|| {
+            let target = BundleTarget {
+                color_format: FORMAT,
+                depth_format: Some(DEPTH_FORMAT),
+                sample_count: 1,
+            };
+            let (pipeline, camera, instances, model) = (
+                self.scene_pipeline.pipeline(),
+                self.camera_uniform.bind_group(),
+                &self.instances,
+                &self.model,
+            );
+            encode_bundles(
+                device,
+                &target,
+                instances.len() as usize,
+                self.encode_threads,
+                |encoder, range| {
+                    encoder.set_pipeline(pipeline);
+                    encoder.set_bind_group(0, camera, &[]);
+                    encoder.set_bind_group(2, instances.bind_group(), &[]);
+                    encoder.set_vertex_buffer(1, instances.slice());
+                    model.draw_bundle(encoder, range.start as u32..range.end as u32);
+                },
+            )
+        });
+
+        let mut frame = FrameEncoder::new(device, queue, label!("headless::encoder"));
+        {
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: label!("headless::render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(scene::CLEAR_COLOR),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            if let Some(bundles) = &bundles {
+                render_pass.execute_bundles(bundles);
+            } else if let (true, Some(instances)) =
+                (self.vertex_pulling, self.instances.pulling_bind_group())
+            {
+                render_pass.set_pipeline(self.scene_pipeline.pipeline());
+                render_pass.set_bind_group(0, self.camera_uniform.bind_group(), &[]);
+                render_pass.set_bind_group(2, instances, &[]);
+                self.model
+                    .draw_pulled(&mut render_pass, 0..self.instances.len());
+            } else {
+                render_pass.set_pipeline(self.scene_pipeline.pipeline());
+                render_pass.set_bind_group(0, self.camera_uniform.bind_group(), &[]);
+                render_pass.set_bind_group(2, self.instances.bind_group(), &[]);
+                render_pass.set_vertex_buffer(1, self.instances.slice());
+                self.model.draw(&mut render_pass, 0..self.instances.len());
+            }
+        }
+        let depth_target = linear_depth.then(|| {
+            let target = LinearDepth::create_target(device, width, height);
+            self.linear_depth
+                .resolve(device, &mut frame, &depth_texture.view, &target);
+            target
+        });
+        frame.submit();
+
+        Ok(HeadlessFrame {
+            color: read_texture_rgba(device, queue, &texture)?,
+            linear_depth: depth_target
+                .map(|target| read_texture_r32float(device, queue, &target))
+                .transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::{Instance, MaterialProperties};
+    use crate::model::Material;
+    use crate::test_support::{assert_matches_reference, skip_without_adapter};
+
+    /// A renderer for `config`, or `None` when GPU tests may be skipped.
+    fn renderer(config: &AppConfig) -> Option<HeadlessRenderer> {
+        pollster::block_on(HeadlessRenderer::new(config))
+            .map_err(skip_without_adapter)
+            .ok()
+    }
+
+    #[test]
+    fn renders_the_reference_frame() {
+        let Some(mut renderer) = renderer(&AppConfig::default()) else {
+            return;
+        };
+        let pixels = renderer.render_frame(64, 48).unwrap();
+        let image = image::RgbaImage::from_raw(64, 48, pixels).unwrap();
+        // Room for differences in rasterization and sRGB encoding between
+        // adapters; edge pixels that flip between the grid and the clear
+        // color still fail.
+        assert_matches_reference(&image, "headless_scene", 8);
+    }
+
+    #[test]
+    fn bundles_render_the_same_frame() {
+        let render = |encode_threads| {
+            let config = AppConfig {
+                encode_threads,
+                ..AppConfig::default()
+            };
+            let mut renderer = renderer(&config)?;
+            Some(renderer.render_frame(64, 64).unwrap())
+        };
+        let Some(direct) = render(1) else {
+            return;
+        };
+        assert_eq!(render(4).unwrap(), direct);
+    }
+
+    #[test]
+    fn pulled_vertices_render_the_same_frame() {
+        let render = |vertex_pulling| {
+            let config = AppConfig {
+                vertex_pulling,
+                ..AppConfig::default()
+            };
+            let mut renderer = renderer(&config)?;
+            if vertex_pulling && !renderer.vertex_pulling {
+                eprintln!("skipping: the adapter can't pull vertices");
+                return None;
+            }
+            Some(renderer.render_frame(64, 64).unwrap())
+        };
+        let (Some(buffers), Some(pulled)) = (render(false), render(true)) else {
+            return;
+        };
+        assert_eq!(pulled, buffers);
+    }
+
+    #[test]
+    fn instances_in_one_draw_use_their_own_materials() {
+        let Some(mut renderer) = renderer(&AppConfig::default()) else {
+            return;
+        };
+        let GpuContext { device, queue, .. } = &renderer.context;
+        // The grid cycles through the table, so every channel gets a tint.
+        renderer.instances.set_materials(
+            device,
+            queue,
+            &[
+                MaterialProperties {
+                    tint: [1.0, 0.0, 0.0, 1.0],
+                },
+                MaterialProperties {
+                    tint: [0.0, 1.0, 0.0, 1.0],
+                },
+                MaterialProperties {
+                    tint: [0.0, 0.0, 1.0, 1.0],
+                },
+            ],
+        );
+        let pixels = renderer.render_frame(64, 64).unwrap();
+        let only = |channel: usize| {
+            pixels
+                .chunks(4)
+                .any(|pixel| (0..3).all(|c| (pixel[c] > 0) == (c == channel)))
+        };
+        assert!(only(0) && only(1) && only(2));
+    }
+
+    #[test]
+    fn instances_of_one_material_use_their_own_tints_and_layers() {
+        let Some(mut renderer) = renderer(&AppConfig::default()) else {
+            return;
+        };
+        let GpuContext { device, queue, .. } = &renderer.context;
+        let solid = |color| image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        let layers = Texture::from_layers(
+            device,
+            queue,
+            &[solid([255; 4]), solid([0, 0, 255, 255])],
+            None,
+        );
+        renderer.model.materials[0] = Material::new(
+            device,
+            &Texture::array_bind_group_layout(device),
+            "layers",
+            &layers,
+        );
+        renderer.instances.set_materials(device, queue, &[]);
+        let render = |renderer: &mut HeadlessRenderer, tint_and_layer: &dyn Fn(u32) -> _| {
+            let instances: Vec<_> = scene::grid_instances(scene::INSTANCES_PER_ROW)
+                .into_iter()
+                .zip(0..)
+                .map(|(instance, i)| {
+                    let (tint, layer) = tint_and_layer(i);
+                    Instance {
+                        material: 0,
+                        tint,
+                        layer,
+                        ..instance
+                    }
+                })
+                .collect();
+            let GpuContext { device, queue, .. } = &renderer.context;
+            renderer
+                .instances
+                .update(device, queue, &instances)
+                .unwrap();
+            renderer.render_frame(64, 64).unwrap()
+        };
+        let has = |pixels: &[u8], color: [u8; 3]| pixels.chunks(4).any(|pixel| pixel[..3] == color);
+
+        // White layer 0, tinted red or green per instance.
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let green = [0.0, 1.0, 0.0, 1.0];
+        let pixels = render(&mut renderer, &|i| {
+            (if i % 2 == 0 { red } else { green }, 0)
+        });
+        assert!(has(&pixels, [255, 0, 0]) && has(&pixels, [0, 255, 0]));
+
+        // Untinted, alternating between the white and the blue layer.
+        let pixels = render(&mut renderer, &|i| ([1.0; 4], i % 2));
+        assert!(has(&pixels, [255, 255, 255]) && has(&pixels, [0, 0, 255]));
+    }
+
+    #[test]
+    fn a_red_write_mask_leaves_the_other_channels_cleared() {
+        let Some(mut renderer) = renderer(&AppConfig::default()) else {
+            return;
+        };
+        let full = renderer.render_frame(64, 64).unwrap();
+        renderer
+            .scene_pipeline
+            .set_write_mask(&renderer.context.device, wgpu::ColorWrites::RED);
+        let red = renderer.render_frame(64, 64).unwrap();
+        // Nothing is drawn in the top-left corner.
+        let clear = &full[..4];
+        assert!(red.chunks(4).all(|pixel| pixel[1..] == clear[1..]));
+        let red_channel = |pixels: &[u8]| -> Vec<u8> { pixels.chunks(4).map(|p| p[0]).collect() };
+        assert_eq!(red_channel(&red), red_channel(&full));
+        assert_ne!(red, full);
+    }
+
+    #[test]
+    fn linear_depth_is_the_view_distance() {
+        let Some(mut renderer) = renderer(&AppConfig::default()) else {
+            return;
+        };
+        // 30 floats is 120 bytes, so each row is padded in the copy.
+        let (width, height) = (30, 20);
+        let frame = renderer.render(width, height, true).unwrap();
+        assert_eq!(frame.color, renderer.render_frame(width, height).unwrap());
+        let depth = frame.linear_depth.unwrap();
+        assert_eq!(depth.len(), (width * height) as usize);
+
+        let camera = Camera::new(1.0);
+        // Nothing is drawn in the top-left corner.
+        assert!((depth[0] - camera.zfar).abs() < 0.1, "{}", depth[0]);
+        // The middle looks at the center instance, at the origin.
+        let center = depth[(height / 2 * width + width / 2) as usize];
+        let distance = scene::CAMERA_EYE.length();
+        assert!((center - distance).abs() < 0.1, "{center} vs {distance}");
+        assert!(renderer
+            .render(width, height, false)
+            .unwrap()
+            .linear_depth
+            .is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::frame::FrameEncoder;
+use crate::label;
+
+const SHADER: &str = include_str!("hiz.wgsl");
+
+/// Format of the pyramid's levels, written as storage textures.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+/// Width and height of a workgroup; `hiz.wgsl` must declare the same.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// A hierarchical depth (Hi-Z) pyramid: a mip chain built from a depth
+/// buffer in which each texel holds the farthest depth of the pixels under
+/// it, for testing whether a screen-space rectangle is hidden behind what
+/// was drawn.
+///
+/// The first level is half the depth buffer's size, rounded down, and each
+/// level after it halves the one before down to 1x1. Texels on the right and
+/// bottom edges also cover the odd column or row the halving leaves over.
+///
+/// Each level is written into a texture of its own and then copied into the
+/// pyramid's mip chain: GL drops storage writes to one level of a texture
+/// while another of its levels is bound for sampling.
+pub struct DepthPyramid {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    texture: Option<wgpu::Texture>,
+    /// All the levels, for sampling.
+    view: Option<wgpu::TextureView>,
+    /// One texture per level, written by its dispatch and read by the next.
+    levels: Vec<(wgpu::Texture, wgpu::TextureView)>,
+    /// Reads each level from the one before it, for every level but the
+    /// first, which reads the depth buffer.
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl DepthPyramid {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("hiz::layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("hiz::pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("hiz::shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: label!("hiz::pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("downsample"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Self {
+            layout,
+            pipeline,
+            texture: None,
+            view: None,
+            levels: Vec::new(),
+            bind_groups: Vec::new(),
+        }
+    }
+
+    /// Whether `adapter` can build a pyramid: it needs compute shaders that
+    /// write storage textures.
+    pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+        adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            && adapter.limits().max_storage_textures_per_shader_stage >= 1
+    }
+
+    /// The pyramid built last, with all its levels; `None` before the first
+    /// [`build`](Self::build).
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        self.view.as_ref()
+    }
+
+    /// Number of levels, 0 before the first [`build`](Self::build).
+    pub fn level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    /// Records the passes building the pyramid from `depth`, a
+    /// single-sampled depth buffer with `TEXTURE_BINDING` usage. The
+    /// pyramid is reallocated when the depth buffer's size changes.
+    pub fn build(
+        &mut self,
+        device: &wgpu::Device,
+        frame: &mut FrameEncoder,
+        depth: &wgpu::Texture,
+    ) {
+        let size = [(depth.width() / 2).max(1), (depth.height() / 2).max(1)];
+        if self
+            .texture
+            .as_ref()
+            .is_none_or(|texture| [texture.width(), texture.height()] != size)
+        {
+            self.allocate(device, size);
+        }
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+        let first = self.bind_group(device, &depth_view, &self.levels[0].1);
+        let mut pass = frame
+            .encoder()
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: label!("hiz::pass"),
+                timestamp_writes: None,
+            });
+        pass.set_pipeline(&self.pipeline);
+        let [width, height] = size;
+        for (level, bind_group) in std::iter::once(&first).chain(&self.bind_groups).enumerate() {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(
+                (width >> level).max(1).div_ceil(WORKGROUP_SIZE),
+                (height >> level).max(1).div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        drop(pass);
+        let texture = self.texture.as_ref().expect("allocated above");
+        for (level, (source, _)) in self.levels.iter().enumerate() {
+            frame.encoder().copy_texture_to_texture(
+                source.as_image_copy(),
+                wgpu::TexelCopyTextureInfo {
+                    mip_level: level as u32,
+                    ..texture.as_image_copy()
+                },
+                source.size(),
+            );
+        }
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device, [width, height]: [u32; 2]) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("hiz::pyramid"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: level_count(width, height),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.levels = (0..texture.mip_level_count())
+            .map(|level| {
+                let level = device.create_texture(&wgpu::TextureDescriptor {
+                    label: label!("hiz::level"),
+                    size: texture
+                        .size()
+                        .mip_level_size(level, wgpu::TextureDimension::D2),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: FORMAT,
+                    usage: wgpu::TextureUsages::STORAGE_BINDING
+                        | wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_SRC,
+                    view_formats: &[],
+                });
+                let view = level.create_view(&wgpu::TextureViewDescriptor::default());
+                (level, view)
+            })
+            .collect();
+        self.bind_groups = self
+            .levels
+            .windows(2)
+            .map(|pair| self.bind_group(device, &pair[0].1, &pair[1].1))
+            .collect();
+        self.view = Some(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        self.texture = Some(texture);
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        destination: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: label!("hiz::bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(destination),
+                },
+            ],
+        })
+    }
+}
+
+/// Levels from a `width` by `height` first level down to 1x1.
+fn level_count(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_go_down_to_one_texel() {
+        assert_eq!(level_count(1, 1), 1);
+        assert_eq!(level_count(2, 1), 2);
+        assert_eq!(level_count(3, 7), 3);
+        assert_eq!(level_count(640, 360), 10);
+    }
+}
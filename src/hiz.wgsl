@@ -0,0 +1,31 @@
+// Must match WORKGROUP_SIZE in hiz.rs.
+const WORKGROUP_SIZE: u32 = 8u;
+
+// The depth buffer for the first level, the level above for the others.
+// Bound as unfilterable float rather than `texture_depth_2d`, which GL can't
+// `textureLoad` from.
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var destination: texture_storage_2d<r32float, write>;
+
+// Each texel keeps the farthest depth of the 2x2 texels under it. A level
+// halves its source rounding down, so the texels at the right and bottom
+// edges also take the odd column or row left over, and every source texel
+// is covered.
+@compute @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination);
+    if any(id.xy >= size) {
+        return;
+    }
+    let first = id.xy * 2u;
+    let last = select(first + 1u, textureDimensions(source) - 1u, id.xy == size - 1u);
+    var farthest = 0.0;
+    for (var y = first.y; y <= last.y; y++) {
+        for (var x = first.x; x <= last.x; x++) {
+            farthest = max(farthest, textureLoad(source, vec2<u32>(x, y), 0).r);
+        }
+    }
+    textureStore(destination, id.xy, vec4<f32>(farthest, 0.0, 0.0, 0.0));
+}
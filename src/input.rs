@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use winit::event::{MouseButton, MouseScrollDelta};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::replay::InputEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ToggleCrosshair,
+    ToggleCrosshairGap,
+    SaveScreenshot,
+    CaptureFrame,
+    CopySettings,
+    ToggleTrace,
+    CycleTestPattern,
+    CycleDebugTexture,
+    /// Steps through the antialiasing modes: none, MSAA, FXAA and TAA.
+    CycleAntialiasing,
+    /// Draws the model's triangle edges over its shaded surface.
+    ToggleWireframe,
+    /// Shows the world axes in a corner of the screen.
+    ToggleAxisGizmo,
+    /// Pauses or resumes the loaded model's animation.
+    ToggleAnimation,
+    ToggleClearColorDemo,
+    /// Switches the model's textures between linear and nearest filtering.
+    ToggleTextureFilter,
+    /// Samples textures from finer mip levels, sharpening them.
+    DecreaseLodBias,
+    /// Samples textures from coarser mip levels, blurring them.
+    IncreaseLodBias,
+    ToggleVsync,
+    ToggleFullscreen,
+    OpenWindow,
+    /// Opens a window showing the scene from above, following the camera.
+    OpenMinimap,
+    NextScene,
+    /// Steps through culling the model grid off, on the CPU and on the GPU.
+    CycleCulling,
+    /// Shows the built-in debug panel (with the `text` feature).
+    ToggleGui,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub modifiers: ModifiersState,
+}
+
+/// Maps key presses, including their modifiers, to actions.
+///
+/// A binding matches only when the held modifiers are exactly the ones it
+/// requires, so `S` and `Ctrl+S` can be bound to different actions.
+pub struct InputMap {
+    bindings: Vec<(KeyBinding, Action)>,
+    modifiers: ModifiersState,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.bind(
+            KeyCode::KeyC,
+            ModifiersState::empty(),
+            Action::ToggleCrosshair,
+        );
+        map.bind(
+            KeyCode::KeyG,
+            ModifiersState::empty(),
+            Action::ToggleCrosshairGap,
+        );
+        map.bind(
+            KeyCode::KeyS,
+            ModifiersState::CONTROL,
+            Action::SaveScreenshot,
+        );
+        map.bind(KeyCode::F12, ModifiersState::empty(), Action::CaptureFrame);
+        map.bind(
+            KeyCode::KeyC,
+            ModifiersState::CONTROL | ModifiersState::SHIFT,
+            Action::CopySettings,
+        );
+        map.bind(KeyCode::F9, ModifiersState::empty(), Action::ToggleTrace);
+        map.bind(
+            KeyCode::KeyT,
+            ModifiersState::empty(),
+            Action::CycleTestPattern,
+        );
+        map.bind(
+            KeyCode::F3,
+            ModifiersState::empty(),
+            Action::CycleDebugTexture,
+        );
+        map.bind(
+            KeyCode::KeyM,
+            ModifiersState::empty(),
+            Action::CycleAntialiasing,
+        );
+        map.bind(
+            KeyCode::KeyF,
+            ModifiersState::empty(),
+            Action::ToggleWireframe,
+        );
+        map.bind(
+            KeyCode::KeyX,
+            ModifiersState::empty(),
+            Action::ToggleAxisGizmo,
+        );
+        map.bind(
+            KeyCode::KeyP,
+            ModifiersState::empty(),
+            Action::ToggleAnimation,
+        );
+        map.bind(
+            KeyCode::KeyB,
+            ModifiersState::empty(),
+            Action::ToggleClearColorDemo,
+        );
+        map.bind(
+            KeyCode::KeyL,
+            ModifiersState::empty(),
+            Action::ToggleTextureFilter,
+        );
+        map.bind(
+            KeyCode::BracketLeft,
+            ModifiersState::empty(),
+            Action::DecreaseLodBias,
+        );
+        map.bind(
+            KeyCode::BracketRight,
+            ModifiersState::empty(),
+            Action::IncreaseLodBias,
+        );
+        map.bind(KeyCode::KeyV, ModifiersState::empty(), Action::ToggleVsync);
+        map.bind(
+            KeyCode::F11,
+            ModifiersState::empty(),
+            Action::ToggleFullscreen,
+        );
+        map.bind(
+            KeyCode::Enter,
+            ModifiersState::ALT,
+            Action::ToggleFullscreen,
+        );
+        map.bind(KeyCode::KeyN, ModifiersState::CONTROL, Action::OpenWindow);
+        map.bind(
+            KeyCode::KeyN,
+            ModifiersState::CONTROL | ModifiersState::SHIFT,
+            Action::OpenMinimap,
+        );
+        map.bind(KeyCode::Tab, ModifiersState::empty(), Action::NextScene);
+        map.bind(KeyCode::F1, ModifiersState::empty(), Action::ToggleGui);
+        map.bind(KeyCode::KeyK, ModifiersState::empty(), Action::CycleCulling);
+        map
+    }
+}
+
+impl InputMap {
+    pub fn empty() -> Self {
+        Self {
+            bindings: Vec::new(),
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    pub fn bind(&mut self, key: KeyCode, modifiers: ModifiersState, action: Action) {
+        let binding = KeyBinding { key, modifiers };
+        self.bindings.retain(|(existing, _)| *existing != binding);
+        self.bindings.push((binding, action));
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Updates the held modifiers from `WindowEvent::ModifiersChanged`.
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn action_for(&self, key: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.key == key && binding.modifiers == self.modifiers)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Scroll distance counted as one line for pixel-precise devices.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Which keys and mouse buttons are held, which changed since the last
+/// [`begin_frame`](Self::begin_frame), and the mouse motion and scrolling
+/// accumulated since then.
+#[derive(Debug, Default)]
+pub struct InputState {
+    pressed: HashSet<KeyCode>,
+    just_pressed: HashSet<KeyCode>,
+    just_released: HashSet<KeyCode>,
+    buttons: HashSet<MouseButton>,
+    buttons_just_pressed: HashSet<MouseButton>,
+    buttons_just_released: HashSet<MouseButton>,
+    modifiers: ModifiersState,
+    mouse_delta: (f64, f64),
+    scroll_delta: (f64, f64),
+}
+
+impl InputState {
+    /// Updates the key state; repeats are ignored and losing focus releases
+    /// every key, since the release events go to another window.
+    pub fn handle(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Key {
+                code,
+                pressed: true,
+                repeat: false,
+            } => press(&mut self.pressed, &mut self.just_pressed, code),
+            InputEvent::Key {
+                code,
+                pressed: false,
+                ..
+            } => release(&mut self.pressed, &mut self.just_released, code),
+            InputEvent::MouseButton {
+                button,
+                pressed: true,
+            } => press(&mut self.buttons, &mut self.buttons_just_pressed, button),
+            InputEvent::MouseButton {
+                button,
+                pressed: false,
+            } => release(&mut self.buttons, &mut self.buttons_just_released, button),
+            InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+            InputEvent::Focused(false) => {
+                self.just_released.extend(self.pressed.drain());
+                self.buttons_just_released.extend(self.buttons.drain());
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds raw pointer motion from `DeviceEvent::MouseMotion`, which keeps
+    /// coming while the cursor is locked.
+    pub fn handle_mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        self.mouse_delta.0 += dx;
+        self.mouse_delta.1 += dy;
+    }
+
+    /// Adds scrolling from `WindowEvent::MouseWheel`, in lines.
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(position) => {
+                (position.x / PIXELS_PER_LINE, position.y / PIXELS_PER_LINE)
+            }
+        };
+        self.scroll_delta.0 += x;
+        self.scroll_delta.1 += y;
+    }
+
+    pub fn is_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn just_pressed(&self, key: KeyCode) -> bool {
+        self.just_pressed.contains(&key)
+    }
+
+    pub fn just_released(&self, key: KeyCode) -> bool {
+        self.just_released.contains(&key)
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn button_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_just_pressed.contains(&button)
+    }
+
+    pub fn button_just_released(&self, button: MouseButton) -> bool {
+        self.buttons_just_released.contains(&button)
+    }
+
+    /// The modifiers held, from the last `WindowEvent::ModifiersChanged`.
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Pointer motion since the last `begin_frame`, in unaccelerated device
+    /// units.
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    /// Scrolling since the last `begin_frame`, in lines.
+    pub fn scroll_delta(&self) -> (f64, f64) {
+        self.scroll_delta
+    }
+
+    /// Clears the per-frame changes and accumulated motion; call once the
+    /// frame's input has been handled, so motion from frames that weren't
+    /// drawn doesn't pile up into one large jump.
+    pub fn begin_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.buttons_just_pressed.clear();
+        self.buttons_just_released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = (0.0, 0.0);
+    }
+}
+
+fn press<T: Copy + Eq + Hash>(held: &mut HashSet<T>, just_pressed: &mut HashSet<T>, input: T) {
+    if held.insert(input) {
+        just_pressed.insert(input);
+    }
+}
+
+fn release<T: Copy + Eq + Hash>(held: &mut HashSet<T>, just_released: &mut HashSet<T>, input: T) {
+    if held.remove(&input) {
+        just_released.insert(input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctrl_s_saves_but_plain_s_does_not() {
+        let mut map = InputMap::default();
+        assert_eq!(map.action_for(KeyCode::KeyS), None);
+        map.set_modifiers(ModifiersState::CONTROL);
+        assert_eq!(map.action_for(KeyCode::KeyS), Some(Action::SaveScreenshot));
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let mut map = InputMap::default();
+        map.bind(
+            KeyCode::KeyC,
+            ModifiersState::CONTROL | ModifiersState::SHIFT,
+            Action::SaveScreenshot,
+        );
+        assert_eq!(map.action_for(KeyCode::KeyC), Some(Action::ToggleCrosshair));
+        map.set_modifiers(ModifiersState::CONTROL | ModifiersState::SHIFT);
+        assert_eq!(map.action_for(KeyCode::KeyC), Some(Action::SaveScreenshot));
+        map.set_modifiers(ModifiersState::CONTROL);
+        assert_eq!(map.action_for(KeyCode::KeyC), None);
+    }
+
+    #[test]
+    fn rebinding_replaces_the_old_action() {
+        let mut map = InputMap::empty();
+        map.bind(
+            KeyCode::KeyX,
+            ModifiersState::empty(),
+            Action::ToggleCrosshair,
+        );
+        map.bind(
+            KeyCode::KeyX,
+            ModifiersState::empty(),
+            Action::SaveScreenshot,
+        );
+        assert_eq!(map.action_for(KeyCode::KeyX), Some(Action::SaveScreenshot));
+    }
+
+    #[test]
+    fn losing_focus_releases_held_keys() {
+        let mut state = InputState::default();
+        let key = |pressed| InputEvent::Key {
+            code: KeyCode::KeyW,
+            pressed,
+            repeat: false,
+        };
+        state.handle(&key(true));
+        assert!(state.is_pressed(KeyCode::KeyW) && state.just_pressed(KeyCode::KeyW));
+        state.begin_frame();
+        assert!(!state.just_pressed(KeyCode::KeyW));
+        state.handle(&InputEvent::Focused(false));
+        assert!(!state.is_pressed(KeyCode::KeyW) && state.just_released(KeyCode::KeyW));
+    }
+}
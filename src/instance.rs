@@ -0,0 +1,472 @@
+use std::fmt;
+
+use glam::{Mat4, Quat, Vec3};
+
+use crate::label;
+use crate::pulling;
+use crate::vertex::Vertex;
+
+/// Placement of one copy of a mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// Index into the materials set with [`InstanceBuffer::set_materials`].
+    pub material: u32,
+    /// Multiplies the material's color, alpha included, for this instance
+    /// alone.
+    pub tint: [f32; 4],
+    /// Layer of the material's texture array this instance samples, clamped
+    /// to the last one; see [`Texture::from_layers`](crate::texture::Texture::from_layers).
+    pub layer: u32,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            material: 0,
+            tint: [1.0; 4],
+            layer: 0,
+        }
+    }
+}
+
+impl Instance {
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.position)
+    }
+}
+
+/// Per-instance vertex data: the model matrix as four column vectors at
+/// shader locations 5 to 8, the tint at 11, the material index at 9 and the
+/// texture layer at 10. Padded to the stride of `InstanceData` in
+/// `shader.wgsl`, which pulls the same buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub tint: [f32; 4],
+    pub material: u32,
+    pub layer: u32,
+    pub _padding: [u32; 2],
+}
+
+impl From<&Instance> for InstanceRaw {
+    fn from(instance: &Instance) -> Self {
+        Self {
+            model: instance.model_matrix().to_cols_array_2d(),
+            tint: instance.tint,
+            material: instance.material,
+            layer: instance.layer,
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl Vertex for InstanceRaw {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x4,
+            11 => Float32x4,
+            9 => Uint32,
+            10 => Uint32,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Per-material values the fragment shader looks up by the instance's
+/// material index; `Material` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialProperties {
+    /// Multiplies the sampled diffuse color, alpha included.
+    pub tint: [f32; 4],
+}
+
+impl Default for MaterialProperties {
+    fn default() -> Self {
+        Self { tint: [1.0; 4] }
+    }
+}
+
+/// An instance refers to a material past the end of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialIndexError {
+    /// The first instance with a bad index.
+    pub instance: usize,
+    pub material: u32,
+    pub materials: usize,
+}
+
+impl fmt::Display for MaterialIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "instance {} uses material {}, but only {} are set",
+            self.instance, self.material, self.materials
+        )
+    }
+}
+
+impl std::error::Error for MaterialIndexError {}
+
+/// A vertex buffer of `InstanceRaw`s that grows to fit when more instances
+/// are uploaded than it can hold, and the storage buffer of
+/// `MaterialProperties` their material indices point into.
+///
+/// All instances of a mesh are drawn with one instanced draw whatever their
+/// materials, tints and layers, which the fragment shader reads from the
+/// table at group 2 and the instance's own data.
+pub struct InstanceBuffer {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    len: usize,
+    materials: wgpu::Buffer,
+    material_count: usize,
+    /// The material table alone, for pipelines with vertex buffers.
+    bind_group: wgpu::BindGroup,
+    /// The material table and the instances, for vertex pulling where
+    /// supported.
+    pulling_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl InstanceBuffer {
+    /// Starts with a single default material, so instances with material 0
+    /// draw their textures unchanged.
+    pub fn new(device: &wgpu::Device) -> Self {
+        // Start with room for one instance: empty buffers can't be bound.
+        let capacity = 1;
+        let buffer = create_buffer(device, capacity);
+        let materials = create_materials_buffer(device, 1);
+        Self {
+            bind_group: create_bind_group(device, &materials),
+            pulling_bind_group: create_pulling_bind_group(device, &materials, &buffer),
+            buffer,
+            capacity,
+            len: 0,
+            materials,
+            material_count: 1,
+        }
+    }
+
+    /// Layout of [`bind_group`](Self::bind_group): the material table at
+    /// binding 0, visible to the fragment stage.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("instance::bind_group_layout"),
+            entries: &[materials_layout_entry()],
+        })
+    }
+
+    /// Layout of [`pulling_bind_group`](Self::pulling_bind_group): the
+    /// material table at binding 0 and the instances at binding 1, visible
+    /// to the vertex stage.
+    pub fn pulling_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("instance::pulling_bind_group_layout"),
+            entries: &[
+                materials_layout_entry(),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// Replaces the material table, keeping the default one if `materials`
+    /// is empty. Instances uploaded since must index into the new table.
+    pub fn set_materials(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        materials: &[MaterialProperties],
+    ) {
+        let default = [MaterialProperties::default()];
+        let materials = if materials.is_empty() {
+            &default[..]
+        } else {
+            materials
+        };
+        if materials.len() > self.materials_capacity() {
+            self.materials = create_materials_buffer(device, materials.len());
+            self.rebuild_bind_groups(device);
+        }
+        queue.write_buffer(&self.materials, 0, bytemuck::cast_slice(materials));
+        self.material_count = materials.len();
+    }
+
+    pub fn material_count(&self) -> usize {
+        self.material_count
+    }
+
+    /// Uploads `instances`, reallocating the buffer if they don't fit.
+    ///
+    /// Fails without uploading anything if an instance's material index is
+    /// out of range. The shader clamps the index too, but only to keep the
+    /// lookup in bounds.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[Instance],
+    ) -> Result<(), MaterialIndexError> {
+        if let Some((instance, bad)) = instances
+            .iter()
+            .enumerate()
+            .find(|(_, instance)| instance.material as usize >= self.material_count)
+        {
+            return Err(MaterialIndexError {
+                instance,
+                material: bad.material,
+                materials: self.material_count,
+            });
+        }
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = create_buffer(device, self.capacity);
+            self.rebuild_bind_groups(device);
+        }
+        let raw: Vec<InstanceRaw> = instances.iter().map(InstanceRaw::from).collect();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&raw));
+        self.len = instances.len();
+        Ok(())
+    }
+
+    fn materials_capacity(&self) -> usize {
+        self.materials.size() as usize / std::mem::size_of::<MaterialProperties>()
+    }
+
+    fn rebuild_bind_groups(&mut self, device: &wgpu::Device) {
+        self.bind_group = create_bind_group(device, &self.materials);
+        self.pulling_bind_group = create_pulling_bind_group(device, &self.materials, &self.buffer);
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+
+    /// The material table, bound at group 2 by callers of
+    /// [`Mesh::draw`](crate::mesh::Mesh::draw).
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// The material table and the instances as storage buffers, bound at
+    /// group 2 by callers of
+    /// [`Mesh::draw_pulled`](crate::mesh::Mesh::draw_pulled); `None` where
+    /// the device can't pull vertices.
+    pub fn pulling_bind_group(&self) -> Option<&wgpu::BindGroup> {
+        self.pulling_bind_group.as_ref()
+    }
+}
+
+fn materials_layout_entry() -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(
+                std::mem::size_of::<MaterialProperties>() as u64
+            ),
+        },
+        count: None,
+    }
+}
+
+fn create_materials_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: label!("instance::materials"),
+        size: (capacity * std::mem::size_of::<MaterialProperties>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(device: &wgpu::Device, materials: &wgpu::Buffer) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("instance::bind_group"),
+        layout: &InstanceBuffer::bind_group_layout(device),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: materials.as_entire_binding(),
+        }],
+    })
+}
+
+fn create_pulling_bind_group(
+    device: &wgpu::Device,
+    materials: &wgpu::Buffer,
+    instances: &wgpu::Buffer,
+) -> Option<wgpu::BindGroup> {
+    if !instances.usage().contains(wgpu::BufferUsages::STORAGE) {
+        return None;
+    }
+    Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("instance::pulling_bind_group"),
+        layout: &InstanceBuffer::pulling_bind_group_layout(device),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: materials.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: instances.as_entire_binding(),
+            },
+        ],
+    }))
+}
+
+fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: label!("instance::buffer"),
+        size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX
+            | wgpu::BufferUsages::COPY_DST
+            | pulling::storage_usage(device),
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_gpu;
+
+    #[test]
+    fn raw_matrix_round_trips_the_transform() {
+        let instance = Instance {
+            position: Vec3::new(1.0, -2.0, 3.0),
+            rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            material: 2,
+            tint: [0.5, 1.0, 0.25, 1.0],
+            layer: 3,
+        };
+        let raw = InstanceRaw::from(&instance);
+        let (scale, rotation, position) =
+            Mat4::from_cols_array_2d(&raw.model).to_scale_rotation_translation();
+        assert!(scale.abs_diff_eq(Vec3::ONE, 1e-6));
+        assert!(rotation.abs_diff_eq(instance.rotation, 1e-6));
+        assert_eq!(position, instance.position);
+        // The translation lives in the last column, as WGSL expects.
+        assert_eq!(raw.model[3], [1.0, -2.0, 3.0, 1.0]);
+        assert_eq!((raw.material, raw.layer), (2, 3));
+        assert_eq!(raw.tint, instance.tint);
+    }
+
+    #[test]
+    fn layout_covers_the_matrix_tint_material_and_layer() {
+        let layout = InstanceRaw::layout();
+        assert_eq!(layout.array_stride, 96);
+        assert_eq!(layout.step_mode, wgpu::VertexStepMode::Instance);
+        let offsets: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|a| (a.shader_location, a.offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            [
+                (5, 0),
+                (6, 16),
+                (7, 32),
+                (8, 48),
+                (11, 64),
+                (9, 80),
+                (10, 84)
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_layout_matches_the_shader() {
+        assert!(crate::shader::check_struct_layout(
+            include_str!("shader.wgsl"),
+            "InstanceData",
+            &crate::struct_layout!(InstanceRaw {
+                model,
+                tint,
+                material,
+                layer,
+            }),
+        ));
+    }
+
+    #[test]
+    fn buffer_grows_to_fit() {
+        let gpu = test_gpu!();
+        let instances = |n| vec![Instance::default(); n];
+        let mut buffer = InstanceBuffer::new(&gpu.device);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity, 1);
+
+        buffer
+            .update(&gpu.device, &gpu.queue, &instances(5))
+            .unwrap();
+        assert_eq!((buffer.len(), buffer.capacity), (5, 8));
+        assert_eq!(buffer.buffer.size(), 8 * 96);
+
+        // Shrinking keeps the larger buffer.
+        buffer
+            .update(&gpu.device, &gpu.queue, &instances(2))
+            .unwrap();
+        assert_eq!((buffer.len(), buffer.capacity), (2, 8));
+    }
+
+    #[test]
+    fn material_indices_are_validated() {
+        let gpu = test_gpu!();
+        let instance = |material| Instance {
+            material,
+            ..Instance::default()
+        };
+        let mut buffer = InstanceBuffer::new(&gpu.device);
+        assert_eq!(buffer.material_count(), 1);
+        assert_eq!(
+            buffer.update(&gpu.device, &gpu.queue, &[instance(0), instance(1)]),
+            Err(MaterialIndexError {
+                instance: 1,
+                material: 1,
+                materials: 1,
+            })
+        );
+        assert!(buffer.is_empty());
+
+        buffer.set_materials(&gpu.device, &gpu.queue, &[MaterialProperties::default(); 3]);
+        buffer
+            .update(&gpu.device, &gpu.queue, &[instance(0), instance(2)])
+            .unwrap();
+        assert_eq!(buffer.len(), 2);
+
+        // An empty table falls back to the default material.
+        buffer.set_materials(&gpu.device, &gpu.queue, &[]);
+        assert_eq!(buffer.material_count(), 1);
+    }
+}
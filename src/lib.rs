@@ -1,2 +1,79 @@
+pub mod aa;
+pub mod adapter;
+pub mod animation;
+pub mod app;
+pub mod bundle;
+pub mod camera;
+pub mod capture;
+pub mod compute;
+pub mod config;
+pub mod context;
+pub mod crosshair;
+pub mod cubemap;
+pub mod culling;
+pub mod cursor;
+pub mod debug_lines;
+pub mod error;
+pub mod event;
+pub mod fill_rate;
+pub mod frame;
+pub mod gizmo;
+pub mod gpu_error;
+#[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+pub mod gpu_stats;
+pub mod gpu_timer;
+pub mod gui;
+pub mod headless;
+pub mod hiz;
+pub mod input;
+pub mod instance;
+pub mod linear;
+pub mod linear_depth;
+pub mod mesh;
+pub mod model;
+pub mod msaa;
+pub mod offscreen;
+pub mod pacing;
+pub mod pipeline;
+#[cfg(feature = "battery")]
+pub mod power;
+pub mod present;
+pub mod pulling;
+pub mod ratelimit;
+pub mod render_scale;
+pub mod replay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod safe_mode;
+pub mod scene;
+pub mod settings;
+pub mod shader;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub mod shader_watcher;
+pub mod sprite;
+pub mod stencil;
+pub mod surface;
+pub mod test_pattern;
+#[cfg(any(test, feature = "test-support"))]
+#[doc(hidden)]
+pub mod test_support;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod texture;
+pub mod texture_viewer;
+pub mod throttle;
+pub mod timing;
+pub mod trace;
+#[cfg(feature = "egui")]
+pub mod ui;
+pub mod uniform;
 pub mod utils;
+pub mod vertex;
+pub mod warmup;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+pub mod window_target;
+pub mod wireframe;
+pub use app::{run, Application, WgpuAppHandler};
+pub use event::UserEvent;
+pub use headless::{HeadlessFrame, HeadlessRenderer};
 pub use utils::init_logger;
@@ -0,0 +1,31 @@
+use crate::surface::is_linear_output;
+
+/// Format of the [`OffscreenTarget`](crate::offscreen::OffscreenTarget) the
+/// scene is rendered into with a linear working space.
+///
+/// Blending into an 8-bit sRGB swapchain happens on quantized values, and
+/// into a non-sRGB one happens on gamma-encoded values; rendering here keeps
+/// all blending in linear space at full precision regardless of the surface.
+pub const WORKING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Converts a linear clear color for clearing an output of `format`
+/// directly, bypassing the offscreen target.
+pub fn output_clear_color(color: wgpu::Color, format: wgpu::TextureFormat) -> wgpu::Color {
+    if is_linear_output(format) {
+        return color;
+    }
+    let encode = |value: f64| {
+        let value = value.clamp(0.0, 1.0);
+        if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    wgpu::Color {
+        r: encode(color.r),
+        g: encode(color.g),
+        b: encode(color.b),
+        a: color.a,
+    }
+}
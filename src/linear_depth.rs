@@ -0,0 +1,175 @@
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("linear_depth.wgsl");
+
+/// Format of the linear depth target: one full-precision float per pixel.
+/// It is written as a storage texture, since not every adapter can render
+/// to it.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+/// Width and height of a workgroup; `linear_depth.wgsl` must declare the
+/// same.
+const WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DepthRange {
+    znear: f32,
+    zfar: f32,
+    _padding: [f32; 2],
+}
+
+/// A compute pass turning a depth buffer into linear depth, the view-space
+/// distance along the camera axis, for compositing with other renderers.
+///
+/// Hardware depth is nonlinear and depends on the clip planes, so they must
+/// match the camera the depth buffer was rendered with. Where nothing was
+/// drawn the result is the far plane.
+pub struct LinearDepth {
+    layout: wgpu::BindGroupLayout,
+    uniform_buffer: UniformBuffer<DepthRange>,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl LinearDepth {
+    pub fn new(device: &wgpu::Device) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "DepthRange",
+            &crate::struct_layout!(DepthRange {
+                znear,
+                zfar,
+                _padding,
+            }),
+        );
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("linear_depth::layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("linear_depth::uniform"),
+            wgpu::ShaderStages::COMPUTE,
+        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("linear_depth::pipeline_layout"),
+            bind_group_layouts: &[&layout, uniform_buffer.layout()],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("linear_depth::shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: label!("linear_depth::pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("resolve"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Self {
+            layout,
+            uniform_buffer,
+            pipeline,
+        }
+    }
+
+    /// A target for [`resolve`](Self::resolve) that can be read back with
+    /// [`read_texture_r32float`](crate::capture::read_texture_r32float).
+    pub fn create_target(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("linear_depth::target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Sets the clip planes of the camera the depth buffer is rendered with.
+    pub fn set_depth_range(&self, queue: &wgpu::Queue, znear: f32, zfar: f32) {
+        self.uniform_buffer.update(
+            queue,
+            &DepthRange {
+                znear,
+                zfar,
+                _padding: [0.0; 2],
+            },
+        );
+    }
+
+    /// Writes the linear depth of `depth`, a single-sampled depth buffer
+    /// with `TEXTURE_BINDING` usage, to `target`, a texture from
+    /// [`create_target`](Self::create_target) of the same size.
+    pub fn resolve(
+        &self,
+        device: &wgpu::Device,
+        frame: &mut FrameEncoder,
+        depth: &wgpu::TextureView,
+        target: &wgpu::Texture,
+    ) {
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: label!("linear_depth::bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&target_view),
+                },
+            ],
+        });
+        let mut pass = frame
+            .encoder()
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: label!("linear_depth::pass"),
+                timestamp_writes: None,
+            });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.set_bind_group(1, self.uniform_buffer.bind_group(), &[]);
+        pass.dispatch_workgroups(
+            target.width().div_ceil(WORKGROUP_SIZE),
+            target.height().div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
@@ -0,0 +1,33 @@
+// Must match WORKGROUP_SIZE in linear_depth.rs.
+const WORKGROUP_SIZE: u32 = 8u;
+
+struct DepthRange {
+    // Camera clip planes the depth buffer was rendered with.
+    znear: f32,
+    zfar: f32,
+    _padding: vec2<f32>,
+}
+
+// Bound as unfilterable float rather than `texture_depth_2d`, which GL can't
+// `textureLoad` from.
+@group(0) @binding(0)
+var depth_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var linear_depth: texture_storage_2d<r32float, write>;
+
+@group(1) @binding(0)
+var<uniform> range: DepthRange;
+
+// Undoes the perspective divide of wgpu's 0..1 depth: the result is the
+// view-space distance along the camera axis, in world units.
+@compute @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn resolve(@builtin(global_invocation_id) id: vec3<u32>) {
+    // The last workgroups run past the edges unless the size is a multiple
+    // of the workgroup size.
+    if any(id.xy >= textureDimensions(linear_depth)) {
+        return;
+    }
+    let depth = textureLoad(depth_texture, id.xy, 0).r;
+    let distance = range.znear * range.zfar / (range.zfar - depth * (range.zfar - range.znear));
+    textureStore(linear_depth, id.xy, vec4<f32>(distance, 0.0, 0.0, 0.0));
+}
@@ -1,183 +1,3062 @@
-use learn1::init_logger;
-use parking_lot::Mutex;
-use std::sync::Arc;
-use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
-use winit::window::Window;
+use image::RgbaImage;
+use learn1::aa::{AaMode, PostAa};
+use learn1::animation::{AnimationPlayer, Transform};
+use learn1::app::Context;
+use learn1::bundle::{encode_bundles, BundleCache, BundleTarget};
+use learn1::camera::{Camera, CameraController, CameraUniform, DEFAULT_FOVY};
+use learn1::capture::{downsample_rgba, force_opaque, read_texture_rgba, CaptureError};
+use learn1::config::AppConfig;
+use learn1::context::GpuContext;
+use learn1::crosshair::Crosshair;
+use learn1::cubemap::CubemapRenderer;
+use learn1::culling::InstanceCuller;
+use learn1::cursor::{CursorController, InteractionMode};
+use learn1::error::InitError;
+use learn1::fill_rate::FillRateScene;
+use learn1::frame::FrameEncoder;
+use learn1::gizmo::AxisGizmo;
+use learn1::gpu_error::GpuErrorMonitor;
+#[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+use learn1::gpu_stats::GpuStatsMonitor;
+use learn1::gpu_timer::{GpuTimer, GpuTimings, PassBudgets, TimedPass};
+#[cfg(feature = "text")]
+use learn1::gui::{Gui, GuiRenderer};
+use learn1::input::{Action, InputMap, InputState};
+use learn1::instance::{Instance, InstanceBuffer, InstanceRaw};
+use learn1::linear::{output_clear_color, WORKING_FORMAT};
+use learn1::model::{Model, ModelError};
+use learn1::msaa::{supported_sample_count, MsaaTarget};
+use learn1::offscreen::OffscreenTarget;
+use learn1::pacing::FramePacer;
+use learn1::pipeline::ScenePipeline;
+#[cfg(feature = "battery")]
+use learn1::power::{current_power_source, PowerMonitor, PowerSource};
+use learn1::present::{default_present_mode, vsync_present_mode};
+use learn1::pulling;
+use learn1::ratelimit::RateLimitedLogger;
+use learn1::render_scale::{self, AdaptiveRenderScale};
+use learn1::replay::{InputEvent, InputPlayer, InputRecorder};
+#[cfg(not(target_arch = "wasm32"))]
+use learn1::safe_mode::CrashSentinel;
+use learn1::scene::{
+    grid_instances, pentagon_model, ClearColorFade, Scene, CAMERA_EYE, CLEAR_COLOR,
+    INSTANCES_PER_ROW, MATERIALS,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use learn1::settings::copy_to_clipboard;
+use learn1::settings::{parse_present_mode, present_mode_name, RenderSettings};
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+use learn1::shader_watcher::ShaderWatcher;
+use learn1::surface::{max_output_value, select_format};
+use learn1::test_pattern::{Pattern, TestPattern};
+#[cfg(feature = "text")]
+use learn1::text::TextRenderer;
+use learn1::texture::{SamplerOptions, Texture, DEPTH_FORMAT};
+use learn1::texture_viewer::{DebugTexture, DebugTextureKind, TextureViewer};
+use learn1::throttle::ThrottleDetector;
+use learn1::timing::{FrameCounter, FrameLimiter, FrameStats};
+use learn1::trace::FrameTracer;
+#[cfg(feature = "egui")]
+use learn1::ui::Ui;
+use learn1::uniform::UniformBuffer;
+use learn1::vertex::{ModelVertex, Vertex};
+use learn1::warmup::{warm_up, WarmupDraw};
+use learn1::window_target::WindowTarget;
+use learn1::wireframe::{self, WireOverlay};
+#[cfg(not(target_arch = "wasm32"))]
+use learn1::HeadlessRenderer;
+use learn1::{init_logger, label, Application, UserEvent};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+use winit::event::{DeviceEvent, DeviceId, MouseButton, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::keyboard::KeyCode;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowId};
+
+const WINDOW_TITLE: &str = "tutorial2-surface";
+const VIEW_WINDOW_TITLE: &str = "tutorial2-surface (view)";
+const MINIMAP_WINDOW_TITLE: &str = "tutorial2-surface (minimap)";
+/// Inner size of a new minimap window, in logical pixels.
+const MINIMAP_WINDOW_SIZE: f64 = 256.0;
+/// How far above the main camera the minimap looks down from.
+const MINIMAP_HEIGHT: f32 = 8.0;
+const SCREENSHOT_SUPERSAMPLING: u32 = 2;
+/// Format screenshots are rendered in, whatever the surface's: readback
+/// only handles 8-bit RGBA, and an HDR surface's extended range is clipped.
+const CAPTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// Directory watched for shader edits, and the file the scene pipeline is
+/// built from.
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+const SCENE_SHADER: &str = "shader.wgsl";
+/// Field of view eased to while the middle mouse button is held, in degrees.
+const ZOOM_FOVY: f32 = 20.0;
+const MONITOR_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Crosshair gap in logical pixels.
+const CROSSHAIR_GAP: f64 = 4.0;
+/// Side of the axis gizmo in logical pixels.
+const AXIS_GIZMO_SIZE: f64 = 80.0;
+/// Mip levels each press of `[` or `]` shifts texture sampling by.
+const LOD_BIAS_STEP: f32 = 0.5;
+/// Face size of the cubemap shown in the texture viewer.
+const CUBEMAP_SIZE: u32 = 128;
+/// Where the cubemap is rendered from, above the model grid.
+const CUBEMAP_CENTER: glam::Vec3 = glam::Vec3::new(0.0, 1.0, 0.0);
+/// Clear colors cycled through with Space.
+const CLEAR_COLORS: &[wgpu::Color] = &[
+    CLEAR_COLOR,
+    wgpu::Color {
+        r: 0.3,
+        g: 0.1,
+        b: 0.1,
+        a: 1.0,
+    },
+    wgpu::Color {
+        r: 0.1,
+        g: 0.3,
+        b: 0.1,
+        a: 1.0,
+    },
+    wgpu::Color::BLACK,
+];
+
+/// How a [`SceneView`] places its camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViewCamera {
+    /// The main camera, at the view's aspect ratio.
+    Main,
+    /// Looking straight down on the main camera's position.
+    Minimap,
+}
+
+/// An extra window showing the scene. It draws the main window's model,
+/// instances and pipelines with its own camera; only the camera uniform and
+/// the size-dependent targets are its own. Overlays are only drawn in the
+/// main window.
+struct SceneView {
+    target: WindowTarget,
+    camera: ViewCamera,
+    camera_uniform: UniformBuffer<CameraUniform>,
+    depth_texture: Texture,
+    msaa: Option<MsaaTarget>,
+    offscreen: Option<OffscreenTarget>,
+    /// Sample count the targets were created with.
+    sample_count: u32,
+}
+
+/// Everything the main window's scene bundles were recorded with. Handles
+/// compare by identity, so rebuilding the pipeline (e.g. for a depth or LOD
+/// bias) or a material's bind group (e.g. for a new sampler) records the
+/// bundles again, while writing new contents into the buffers doesn't.
+#[derive(PartialEq)]
+struct SceneBundleKey {
+    pipeline: wgpu::RenderPipeline,
+    camera: wgpu::BindGroup,
+    instances: wgpu::BindGroup,
+    instance_buffer: wgpu::Buffer,
+    instance_count: u32,
+    materials: Vec<wgpu::BindGroup>,
+    meshes: Vec<wgpu::Buffer>,
+    threads: usize,
+}
+
+/// Startup options from the command line.
+#[derive(Default)]
+struct DemoOptions {
+    settings: Option<String>,
+    /// OBJ or glTF file drawn instead of the pentagon grid once loaded.
+    model: Option<PathBuf>,
+    recorder: Option<InputRecorder>,
+    player: Option<InputPlayer>,
+}
+
+/// A model loaded on a background thread, and the file it came from.
+type LoadedModel = (PathBuf, Result<Model, ModelError>);
 
 struct WgpuApp {
-    window: Arc<Window>,
-    surface: wgpu::Surface<'static>,
+    /// Extra windows showing the same scene, e.g. as a debug view.
+    views: HashMap<WindowId, SceneView>,
+    /// Surface errors from the extra windows, which can repeat every frame.
+    view_error_log: RateLimitedLogger,
+    /// Window size before entering fullscreen, restored when leaving it.
+    windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
+    /// Physical pixels per logical pixel of the main window.
+    scale_factor: f64,
+    present_modes: Vec<wgpu::PresentMode>,
+    hdr_output: bool,
+    /// Peak luminance of the display in nits, which HDR output is clipped to.
+    max_luminance: f32,
+    safe_mode: bool,
+    monitor: Option<MonitorHandle>,
+    last_monitor_check: Option<Instant>,
+    monitor_check_pending: bool,
+    clear_color: wgpu::Color,
+    /// Map the cursor position to the clear color's red and green channels.
+    clear_color_demo: bool,
+    /// Eases `clear_color` to the scene's background on a scene switch.
+    clear_fade: ClearColorFade,
+    offscreen: Option<OffscreenTarget>,
+    /// Fraction of the window's resolution the scene is drawn at.
+    render_scale: f32,
+    /// The scene below full resolution, upscaled to the window before the
+    /// overlays are drawn. `None` at a `render_scale` of 1.
+    upscaler: Option<OffscreenTarget>,
+    /// Adjusts `render_scale` to the GPU frame time budget, when one is set.
+    adaptive_scale: Option<AdaptiveRenderScale>,
+    camera: Camera,
+    camera_controller: CameraController,
+    camera_uniform: UniformBuffer<CameraUniform>,
+    /// `None` while the active scene doesn't use depth.
+    depth_texture: Option<Texture>,
+    aa_mode: AaMode,
+    /// Samples per pixel of the scene pass, from `aa_mode`.
+    sample_count: u32,
+    msaa: Option<MsaaTarget>,
+    /// The FXAA or TAA pass and its textures, in those modes.
+    post_aa: Option<PostAa>,
+    scene_pipeline: ScenePipeline,
+    wire_overlay: WireOverlay,
+    /// Draw the model's triangle edges over it, toggled with F.
+    wireframe: bool,
+    axis_gizmo: AxisGizmo,
+    /// Show the axis gizmo, toggled with X.
+    axis_gizmo_visible: bool,
+    /// The scene pipeline reads vertices from storage buffers; see
+    /// `AppConfig::vertex_pulling`.
+    vertex_pulling: bool,
+    /// The shared device, for recording render bundles while drawing.
     device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
-    size_changed: bool,
+    /// The main window's scene bundles, with `encode_threads` above 1.
+    scene_bundles: RefCell<BundleCache<SceneBundleKey>>,
+    model: Model,
+    /// Filtering of the model's textures, toggled with L.
+    texture_filter: wgpu::FilterMode,
+    /// Plays the model's first animation clip, paused with P.
+    animation_player: Option<AnimationPlayer>,
+    /// The model's node transforms, posed by `animation_player`.
+    node_transforms: Vec<Transform>,
+    instances: InstanceBuffer,
+    /// Culls `instances` before the plain vertex-buffer draw, cycled with K.
+    culler: InstanceCuller,
+    /// Scenes shown instead of the model grid, cycled with Tab.
+    scenes: Vec<Box<dyn Scene>>,
+    /// Index into `scenes`; `None` shows the model grid.
+    active_scene: Option<usize>,
+    test_pattern: TestPattern,
+    test_pattern_visible: bool,
+    crosshair: Crosshair,
+    texture_viewer: TextureViewer,
+    /// The model grid seen from `CUBEMAP_CENTER`, rendered only while the
+    /// texture viewer shows it.
+    cubemap: CubemapRenderer,
+    crosshair_enabled: bool,
+    mouse_look: bool,
+    zoomed: bool,
+    dragging: bool,
+    cursor: CursorController,
+    cursor_position: Option<[f32; 2]>,
+    pacer: FramePacer,
+    limiter: FrameLimiter,
+    throttle: ThrottleDetector,
+    gpu_errors: GpuErrorMonitor,
+    gpu_error_dumped: bool,
+    tracer: FrameTracer,
+    frame_counter: FrameCounter,
+    gpu_timer: Option<GpuTimer>,
+    pass_budgets: PassBudgets,
+    #[cfg(feature = "egui")]
+    ui: Ui,
+    #[cfg(feature = "text")]
+    text: TextRenderer,
+    /// The built-in debug controls, toggled with F1.
+    #[cfg(feature = "text")]
+    gui: Gui,
+    #[cfg(feature = "text")]
+    gui_renderer: GuiRenderer,
+    #[cfg(feature = "text")]
+    gui_visible: bool,
+    /// Kept alive to keep sending `UserEvent::ShaderChanged`.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    _shader_watcher: Option<ShaderWatcher>,
+    /// Shaders reported changed since the last frame, each listed once.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    changed_shaders: Vec<PathBuf>,
+    /// Models finished loading in the background, announced with
+    /// `UserEvent::AssetReady`.
+    loaded_models: mpsc::Receiver<LoadedModel>,
+    model_sender: mpsc::Sender<LoadedModel>,
+    capture_with_alpha: bool,
+    config: AppConfig,
+    input_map: InputMap,
+    input_state: InputState,
+    last_input_time: Option<Instant>,
+    idle: bool,
+    recorder: Option<InputRecorder>,
+    player: Option<InputPlayer>,
+    /// Set by events that need a frame; the single `request_redraw` for the
+    /// event-loop iteration is issued in `about_to_wait`.
+    redraw_requested: bool,
+    #[cfg(feature = "battery")]
+    power: PowerMonitor,
+    #[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+    gpu_stats: GpuStatsMonitor,
+    /// When the frame being drawn started, for the frame trace.
+    frame_start: Instant,
+    /// CPU-side steps of the frame just rendered, recorded in the frame
+    /// trace once it is presented.
+    frame_marks: Option<[(&'static str, Instant); 3]>,
 }
 
-impl WgpuApp {
-    async fn new(window: Arc<Window>) -> Self {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
+impl WgpuApp {
+    /// Loads the OBJ or glTF model at `path` on a background thread, which
+    /// sends `UserEvent::AssetReady` when it is done. The current scene is
+    /// drawn until then.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_model(&mut self, ctx: &mut Context<'_>, path: PathBuf) {
+        let (device, queue) = (ctx.gpu.device.clone(), ctx.gpu.queue.clone());
+        let (sender, proxy) = (self.model_sender.clone(), ctx.proxy.clone());
+        let spawned = std::thread::Builder::new()
+            .name("model loader".to_owned())
+            .spawn(move || {
+                let name = path.display().to_string();
+                let model = Model::load(&device, &queue, &path);
+                if sender.send((path, model)).is_ok() {
+                    // Fails only once the event loop has exited.
+                    let _ = proxy.send_event(UserEvent::AssetReady(name));
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("failed to start loading a model: {e}");
+        }
+    }
+
+    /// Loads the model at `path` right away, as the browser has no threads
+    /// to load it on.
+    #[cfg(target_arch = "wasm32")]
+    fn load_model(&mut self, ctx: &mut Context<'_>, path: PathBuf) {
+        let model = Model::load(&ctx.gpu.device, &ctx.gpu.queue, &path);
+        self.set_model(ctx, &path, model);
+    }
+
+    /// Shows the models loaded in the background so far.
+    fn receive_models(&mut self, ctx: &mut Context<'_>) {
+        while let Ok((path, model)) = self.loaded_models.try_recv() {
+            self.set_model(ctx, &path, model);
+        }
+    }
+
+    /// Replaces the scene with `model`, loaded from `path` and drawn once at
+    /// the origin. If it failed to load the current scene is kept.
+    fn set_model(&mut self, ctx: &mut Context<'_>, path: &Path, model: Result<Model, ModelError>) {
+        match model {
+            Ok(model) => {
+                log::info!(
+                    "loaded {} with {} meshes and {} materials",
+                    path.display(),
+                    model.meshes.len(),
+                    model.materials.len()
+                );
+                self.model = model;
+                self.model.set_sampler(
+                    &ctx.gpu.device,
+                    SamplerOptions::filtered(self.texture_filter),
+                );
+                self.culler
+                    .set_model(&ctx.gpu.device, &ctx.gpu.queue, &self.model);
+                self.throttle.reset();
+                self.start_animation(&ctx.gpu.queue);
+                self.set_instances(ctx, &[Instance::default()]);
+            }
+            Err(e) => log::error!("{e}"),
+        }
+    }
+
+    /// Replaces the instances each mesh is drawn with.
+    fn set_instances(&mut self, ctx: &mut Context<'_>, instances: &[Instance]) {
+        match self
+            .instances
+            .update(&ctx.gpu.device, &ctx.gpu.queue, instances)
+        {
+            Ok(()) => self
+                .culler
+                .set_instances(&ctx.gpu.device, &ctx.gpu.queue, instances),
+            Err(e) => log::error!("keeping the current instances: {e}"),
+        }
+    }
+
+    fn set_window_resized(
+        &mut self,
+        ctx: &mut Context<'_>,
+        new_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        ctx.target.set_resized(new_size, Instant::now());
+    }
+
+    /// Physical pixels per logical pixel, for converting logical sizes such
+    /// as UI or text metrics. Surface, camera and cursor math is all in
+    /// physical pixels.
+    fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Follows a scale factor change, e.g. after moving to a monitor with a
+    /// different DPI. The window's physical size usually changes with it.
+    fn set_scale_factor(&mut self, ctx: &mut Context<'_>, scale_factor: f64) {
+        if scale_factor == self.scale_factor {
+            return;
+        }
+        log::info!(
+            "scale factor changed from {} to {scale_factor}",
+            self.scale_factor
+        );
+        self.scale_factor = scale_factor;
+        #[cfg(feature = "text")]
+        {
+            self.text.set_scale_factor(scale_factor);
+            self.gui_renderer.set_scale_factor(scale_factor);
+        }
+        self.axis_gizmo
+            .set_size((AXIS_GIZMO_SIZE * scale_factor).round() as u32);
+        let size = ctx.target.window.inner_size();
+        if size.width > 0 && size.height > 0 {
+            self.set_window_resized(ctx, size);
+        }
+        if self.crosshair.constants().get("GAP").unwrap_or(0.0) > 0.0 {
+            self.set_crosshair_gap(ctx, true);
+        }
+    }
+
+    fn is_fullscreen(&self, ctx: &Context<'_>) -> bool {
+        ctx.target.window.fullscreen().is_some()
+    }
+
+    /// Switches between the window and borderless fullscreen on the current
+    /// monitor. The surface follows through the `Resized` events this causes.
+    fn toggle_fullscreen(&mut self, ctx: &mut Context<'_>) {
+        if self.is_fullscreen(ctx) {
+            ctx.target.window.set_fullscreen(None);
+            if let Some(size) = self.windowed_size.take() {
+                // A size applied immediately produces no `Resized` event.
+                if let Some(size) = ctx.target.window.request_inner_size(size) {
+                    self.set_window_resized(ctx, size);
+                }
+            }
+        } else {
+            self.windowed_size = Some(ctx.target.window.inner_size());
+            ctx.target
+                .window
+                .set_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+    }
+
+    /// Applies the current configuration to the surface, e.g. after it was
+    /// lost or became outdated. A window size still settling is applied
+    /// right away, since the old one may be why it was outdated.
+    fn reconfigure_surface(&mut self, ctx: &mut Context<'_>) {
+        if ctx.target.apply_resize(&ctx.gpu.device) {
+            self.surface_resized(ctx);
+        } else {
+            ctx.target.reconfigure(&ctx.gpu.device);
+        }
+    }
+
+    /// Updates everything sized to the surface after it was resized.
+    fn surface_resized(&mut self, ctx: &mut Context<'_>) {
+        self.create_scene_targets(ctx);
+        if let Some(offscreen) = &mut self.offscreen {
+            offscreen.resize(
+                &ctx.gpu.device,
+                ctx.target.config.width,
+                ctx.target.config.height,
+            );
+        }
+        self.camera.aspect = ctx.target.config.width as f32 / ctx.target.config.height as f32;
+        self.upload_camera(&ctx.gpu.queue);
+        self.test_pattern.resize(
+            &ctx.gpu.queue,
+            ctx.target.config.width,
+            ctx.target.config.height,
+        );
+        self.crosshair.resize(
+            &ctx.gpu.queue,
+            ctx.target.config.width,
+            ctx.target.config.height,
+        );
+        self.axis_gizmo
+            .resize(ctx.target.config.width, ctx.target.config.height);
+        #[cfg(feature = "text")]
+        {
+            let (width, height) = (ctx.target.config.width, ctx.target.config.height);
+            self.text.resize(width, height);
+            self.gui_renderer.resize(&ctx.gpu.device, width, height);
+        }
+    }
+
+    /// Format the scene pass renders in: the offscreen target's format, or
+    /// the surface format. The scene pipeline is kept built for it.
+    fn scene_format(&self) -> wgpu::TextureFormat {
+        self.scene_pipeline.format()
+    }
+
+    /// A new offscreen target like the main window's, for another output of
+    /// the given size.
+    fn create_offscreen(
+        &self,
+        ctx: &Context<'_>,
+        width: u32,
+        height: u32,
+    ) -> Option<OffscreenTarget> {
+        self.offscreen.as_ref().map(|offscreen| {
+            let offscreen = OffscreenTarget::new(
+                &ctx.gpu.device,
+                &ctx.gpu.queue,
+                width,
+                height,
+                offscreen.format(),
+                ctx.target.config.format,
+            );
+            offscreen.set_max_output_value(
+                &ctx.gpu.queue,
+                max_output_value(ctx.target.config.format, self.max_luminance),
+            );
+            offscreen
+        })
+    }
+
+    /// Uploads the camera, jittered for TAA.
+    fn upload_camera(&self, queue: &wgpu::Queue) {
+        let mut view_proj = self.camera.build_view_projection_matrix();
+        if let Some(post_aa) = &self.post_aa {
+            view_proj = post_aa.jitter_view_projection(view_proj);
+        }
+        self.camera_uniform.update(
+            queue,
+            &CameraUniform {
+                view_proj: view_proj.to_cols_array_2d(),
+            },
+        );
+    }
+
+    /// Recreates the depth, MSAA, post-processing and upscaling targets for
+    /// the current size, render scale, format and sample count.
+    fn create_scene_targets(&mut self, ctx: &mut Context<'_>) {
+        let [width, height] = self.scene_size(ctx);
+        self.create_depth_texture(ctx);
+        if let Some(post_aa) = &mut self.post_aa {
+            post_aa.set_target(&ctx.gpu.device, self.scene_pipeline.format(), width, height);
+        }
+        self.msaa = (self.sample_count > 1).then(|| {
+            MsaaTarget::new(
+                &ctx.gpu.device,
+                self.scene_format(),
+                width,
+                height,
+                self.sample_count,
+                self.config.msaa_resolve,
+            )
+        });
+        self.upscaler = (self.render_scale < 1.0).then(|| {
+            let format = self.scene_format();
+            let upscaler = OffscreenTarget::new(
+                &ctx.gpu.device,
+                &ctx.gpu.queue,
+                width,
+                height,
+                format,
+                format,
+            );
+            // The scene is already in the output's range; only the final
+            // blit to the surface clips it.
+            upscaler.set_max_output_value(&ctx.gpu.queue, f32::MAX);
+            upscaler
+        });
+    }
+
+    /// Size of the main window's scene targets: the surface's size at the
+    /// render scale.
+    fn scene_size(&self, ctx: &Context<'_>) -> [u32; 2] {
+        render_scale::scaled_size(
+            ctx.target.config.width,
+            ctx.target.config.height,
+            self.render_scale,
+        )
+    }
+
+    /// Nudges the render scale toward the GPU frame time budget, from the
+    /// timed passes' averages.
+    fn adapt_render_scale(&mut self, ctx: &mut Context<'_>, now: Instant) {
+        let (Some(adaptive), Some(gpu_timer)) = (&mut self.adaptive_scale, &self.gpu_timer) else {
+            return;
+        };
+        let averages = gpu_timer.averages();
+        // Nothing was timed, e.g. while only clearing.
+        if averages.pass(TimedPass::Scene).is_none() {
+            return;
+        }
+        let gpu_time = averages.total();
+        if let Some(scale) = adaptive.update(now, gpu_time, self.render_scale) {
+            log::info!(
+                "GPU frame time {:.2}ms vs a {:.2}ms budget",
+                gpu_time.as_secs_f64() * 1000.0,
+                adaptive.budget().as_secs_f64() * 1000.0
+            );
+            self.set_render_scale(ctx, scale);
+        }
+    }
+
+    /// Draws the scene at `scale` of the window's resolution, clamped to
+    /// [`render_scale::RENDER_SCALE_RANGE`].
+    fn set_render_scale(&mut self, ctx: &mut Context<'_>, scale: f32) {
+        let scale = render_scale::clamp(scale);
+        if scale == self.render_scale {
+            return;
+        }
+        self.render_scale = scale;
+        log::info!("render scale: {scale:.2}");
+        self.create_scene_targets(ctx);
+    }
+
+    /// Switches the scene pass to `sample_count` samples (1 or 4), falling
+    /// back to 1 if the adapter can't multisample the scene formats.
+    fn set_sample_count(&mut self, ctx: &mut Context<'_>, sample_count: u32) {
+        let sample_count = supported_sample_count(
+            &ctx.gpu.adapter,
+            &[self.scene_format(), DEPTH_FORMAT],
+            sample_count,
+        );
+        if sample_count == self.sample_count {
+            return;
+        }
+        log::info!("MSAA: {sample_count}x");
+        self.sample_count = sample_count;
+        self.scene_pipeline
+            .set_sample_count(&ctx.gpu.device, sample_count);
+        self.set_scene_targets(ctx);
+        self.create_scene_targets(ctx);
+    }
+
+    /// Rebuilds the registered scenes' pipelines for the current scene
+    /// format and sample count.
+    fn set_scene_targets(&mut self, ctx: &Context<'_>) {
+        let format = self.scene_format();
+        self.wire_overlay
+            .set_target(&ctx.gpu.device, format, self.sample_count);
+        self.axis_gizmo.set_format(&ctx.gpu.device, format);
+        for scene in &mut self.scenes {
+            scene.set_target(&ctx.gpu.device, format, self.sample_count);
+        }
+    }
+
+    /// Creates the depth buffer if the active scene uses one, and frees it
+    /// otherwise.
+    fn create_depth_texture(&mut self, ctx: &Context<'_>) {
+        let [width, height] = self.scene_size(ctx);
+        let config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..ctx.target.config.clone()
+        };
+        self.depth_texture = self.scene_needs_depth().then(|| {
+            Texture::create_depth_texture(
+                &ctx.gpu.device,
+                &config,
+                self.sample_count,
+                "depth_texture",
+            )
+        });
+        self.culler.reset_history();
+    }
+
+    /// Switches to the next registered scene, then back to the model grid.
+    fn next_scene(&mut self, ctx: &Context<'_>) {
+        let needed_depth = self.scene_needs_depth();
+        self.active_scene = match self.active_scene {
+            None if !self.scenes.is_empty() => Some(0),
+            Some(index) if index + 1 < self.scenes.len() => Some(index + 1),
+            _ => None,
+        };
+        log::info!(
+            "scene: {}{}",
+            self.scene_name(),
+            if self.scene_needs_depth() {
+                ""
+            } else {
+                " (no depth buffer)"
+            }
+        );
+        if self.scene_needs_depth() != needed_depth {
+            self.create_depth_texture(ctx);
+        }
+        self.culler.reset_history();
+        let background = self
+            .active_scene()
+            .map_or(CLEAR_COLOR, |scene| scene.preferred_clear_color());
+        self.clear_fade
+            .start(Instant::now(), self.clear_color, background);
+        self.throttle.reset();
+    }
+
+    /// Whether the active scene draws with a depth buffer; the model grid
+    /// always does.
+    fn scene_needs_depth(&self) -> bool {
+        self.active_scene().is_none_or(|scene| scene.needs_depth())
+    }
+
+    fn active_scene(&self) -> Option<&dyn Scene> {
+        Some(self.scenes.get(self.active_scene?)?.as_ref())
+    }
+
+    fn scene_name(&self) -> String {
+        match self.active_scene() {
+            Some(scene) => scene.name().to_owned(),
+            None => "model".to_owned(),
+        }
+    }
+
+    /// Switches to `mode`, rebuilding only what it changes: the MSAA target,
+    /// depth buffer and pipelines when the sample count does, and the
+    /// post-processing pass when entering or leaving FXAA or TAA. MSAA the
+    /// adapter can't do falls back to no antialiasing.
+    fn set_aa_mode(&mut self, ctx: &mut Context<'_>, mode: AaMode) {
+        self.set_sample_count(ctx, mode.sample_count());
+        let mode = match mode {
+            AaMode::Msaa(_) if self.sample_count == 1 => AaMode::None,
+            mode => mode,
+        };
+        if mode == self.aa_mode {
+            return;
+        }
+        self.aa_mode = mode;
+        self.throttle.reset();
+        match (&mut self.post_aa, mode.is_post_process()) {
+            (Some(post_aa), true) => post_aa.set_mode(mode),
+            (None, true) => {
+                let [width, height] = self.scene_size(ctx);
+                self.post_aa = Some(PostAa::new(
+                    &ctx.gpu.device,
+                    self.scene_format(),
+                    width,
+                    height,
+                    mode,
+                ));
+            }
+            (_, false) => self.post_aa = None,
+        }
+        // Drops the jitter when leaving TAA.
+        self.upload_camera(&ctx.gpu.queue);
+        log::info!("antialiasing: {mode}");
+    }
+
+    fn cycle_aa_mode(&mut self, ctx: &mut Context<'_>) {
+        self.set_aa_mode(ctx, self.aa_mode.next());
+    }
+
+    /// Schedules a monitor check, throttled because dragging a window emits a
+    /// `Moved` event per step.
+    fn request_monitor_check(&mut self, ctx: &mut Context<'_>, now: Instant) {
+        self.monitor_check_pending = true;
+        self.poll_monitor_check(ctx, now);
+    }
+
+    /// Runs a pending monitor check once the throttle interval has passed,
+    /// returning when it will next be due otherwise.
+    fn poll_monitor_check(&mut self, ctx: &mut Context<'_>, now: Instant) -> Option<Instant> {
+        if !self.monitor_check_pending {
+            return None;
+        }
+        if let Some(due) = self
+            .last_monitor_check
+            .map(|last| last + MONITOR_CHECK_INTERVAL)
+            .filter(|&due| due > now)
+        {
+            return Some(due);
+        }
+        self.monitor_check_pending = false;
+        self.last_monitor_check = Some(now);
+        self.check_monitor_change(ctx);
+        None
+    }
+
+    /// Re-queries the surface after the window lands on another monitor,
+    /// which may prefer a different format (e.g. SDR vs HDR) or support
+    /// different present modes, and reconfigures it if needed.
+    fn check_monitor_change(&mut self, ctx: &mut Context<'_>) {
+        let monitor = ctx.target.window.current_monitor();
+        if monitor == self.monitor || ctx.target.surface.is_none() {
+            return;
+        }
+        self.monitor = monitor;
+        let pacer = FramePacer::for_monitor(self.monitor.clone());
+        self.pacer.set_target_interval(pacer.target_interval());
+
+        let Some(surface) = &ctx.target.surface else {
+            return;
+        };
+        let caps = surface.get_capabilities(&ctx.gpu.adapter);
+        let format = select_format(&caps.formats, self.hdr_output);
+        let present_mode = if caps.present_modes.contains(&ctx.target.config.present_mode) {
+            ctx.target.config.present_mode
+        } else {
+            default_present_mode(&caps.present_modes, self.safe_mode)
+        };
+        self.present_modes = caps.present_modes;
+        if format == ctx.target.config.format && present_mode == ctx.target.config.present_mode {
+            return;
+        }
+
+        log::info!(
+            "monitor changed to {:?}: reconfiguring surface from {:?}/{:?} to {:?}/{:?}",
+            self.monitor.as_ref().and_then(MonitorHandle::name),
+            ctx.target.config.format,
+            ctx.target.config.present_mode,
+            format,
+            present_mode
+        );
+        let format_changed = format != ctx.target.config.format;
+        ctx.target.config.format = format;
+        ctx.target.config.present_mode = present_mode;
+        self.reconfigure_surface(ctx);
+        #[cfg(feature = "egui")]
+        if format_changed {
+            self.ui.set_format(&ctx.gpu.device, &ctx.gpu.queue, format);
+        }
+        #[cfg(feature = "text")]
+        if format_changed {
+            self.text.set_format(&ctx.gpu.device, format);
+            self.gui_renderer.set_format(&ctx.gpu.device, format);
+        }
+        if format_changed {
+            self.texture_viewer.set_format(&ctx.gpu.device, format);
+        }
+        if let (true, Some(offscreen)) = (format_changed, &mut self.offscreen) {
+            offscreen.set_output_format(&ctx.gpu.device, format);
+            offscreen
+                .set_max_output_value(&ctx.gpu.queue, max_output_value(format, self.max_luminance));
+        } else if format_changed {
+            self.scene_pipeline.set_format(&ctx.gpu.device, format);
+            let sample_count = self.sample_count;
+            self.sample_count =
+                supported_sample_count(&ctx.gpu.adapter, &[format, DEPTH_FORMAT], sample_count);
+            if self.sample_count != sample_count {
+                self.scene_pipeline
+                    .set_sample_count(&ctx.gpu.device, self.sample_count);
+            }
+            self.set_scene_targets(ctx);
+            self.create_scene_targets(ctx);
+            self.test_pattern.set_format(&ctx.gpu.device, format);
+            let constants = self.crosshair.constants().clone();
+            self.crosshair
+                .set_constants(&ctx.gpu.device, format, constants);
+        }
+    }
+
+    fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    /// Sets the color the scene is cleared to, clamping each channel to 0..1.
+    fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_fade.cancel();
+        self.clear_color = wgpu::Color {
+            r: color.r.clamp(0.0, 1.0),
+            g: color.g.clamp(0.0, 1.0),
+            b: color.b.clamp(0.0, 1.0),
+            a: color.a.clamp(0.0, 1.0),
+        };
+    }
+
+    /// Switches to the next of `CLEAR_COLORS`.
+    fn cycle_clear_color(&mut self) {
+        let next = CLEAR_COLORS
+            .iter()
+            .position(|color| *color == self.clear_color)
+            .map_or(0, |index| (index + 1) % CLEAR_COLORS.len());
+        self.set_clear_color(CLEAR_COLORS[next]);
+    }
+
+    fn cycle_culling(&mut self) {
+        self.culler.set_mode(self.culler.mode().next());
+        log::info!(
+            "culling: {}{}",
+            self.culler.mode(),
+            if self.culler.occlusion() {
+                " with occlusion"
+            } else {
+                ""
+            }
+        );
+    }
+
+    fn toggle_wireframe(&mut self, ctx: &Context<'_>) {
+        self.set_wireframe(ctx, !self.wireframe);
+    }
+
+    fn set_wireframe(&mut self, ctx: &Context<'_>, enabled: bool) {
+        self.wireframe = enabled;
+        let bias = if self.wireframe {
+            wireframe::SURFACE_DEPTH_BIAS
+        } else {
+            wgpu::DepthBiasState::default()
+        };
+        self.scene_pipeline.set_depth_bias(&ctx.gpu.device, bias);
+        log::info!(
+            "wireframe overlay: {}",
+            if self.wireframe { "on" } else { "off" }
+        );
+    }
+
+    fn toggle_texture_filter(&mut self, ctx: &Context<'_>) {
+        self.texture_filter = match self.texture_filter {
+            wgpu::FilterMode::Linear => wgpu::FilterMode::Nearest,
+            wgpu::FilterMode::Nearest => wgpu::FilterMode::Linear,
+        };
+        self.model.set_sampler(
+            &ctx.gpu.device,
+            SamplerOptions::filtered(self.texture_filter),
+        );
+        log::info!("texture filtering: {:?}", self.texture_filter);
+    }
+
+    /// Shifts the scene's texture LOD bias by `step` mip levels.
+    fn adjust_lod_bias(&mut self, ctx: &Context<'_>, step: f32) {
+        let bias = self.scene_pipeline.lod_bias() + step;
+        self.scene_pipeline.set_lod_bias(&ctx.gpu.device, bias);
+        log::info!("texture LOD bias: {:+}", self.scene_pipeline.lod_bias());
+    }
+
+    fn toggle_axis_gizmo(&mut self) {
+        self.axis_gizmo_visible = !self.axis_gizmo_visible;
+        log::info!(
+            "axis gizmo: {}",
+            if self.axis_gizmo_visible { "on" } else { "off" }
+        );
+    }
+
+    /// Poses the model at the start of its first animation clip and plays
+    /// it, if it has one.
+    fn start_animation(&mut self, queue: &wgpu::Queue) {
+        self.animation_player = None;
+        let Some(animation) = &self.model.animation else {
+            return;
+        };
+        let clip = &animation.clips[0];
+        log::info!(
+            "playing animation {:?} ({:.2}s)",
+            clip.name,
+            clip.duration()
+        );
+        self.node_transforms = animation.nodes.rest.clone();
+        clip.apply(0.0, &mut self.node_transforms);
+        self.model.pose(queue, &self.node_transforms);
+        let mut player = AnimationPlayer::new(clip.duration());
+        player.play();
+        self.animation_player = Some(player);
+    }
+
+    /// Advances the model's animation by `dt` and poses the model.
+    fn animate(&mut self, queue: &wgpu::Queue, dt: Duration) {
+        let (Some(player), Some(animation)) = (&mut self.animation_player, &self.model.animation)
+        else {
+            return;
+        };
+        if player.advance(dt) {
+            animation.clips[0].apply(player.time(), &mut self.node_transforms);
+            self.model.pose(queue, &self.node_transforms);
+        }
+    }
+
+    fn toggle_animation(&mut self) {
+        let Some(player) = &mut self.animation_player else {
+            return;
+        };
+        if player.is_playing() {
+            player.pause();
+        } else {
+            player.play();
+        }
+        log::info!(
+            "animation: {}",
+            if player.is_playing() {
+                "playing"
+            } else {
+                "paused"
+            }
+        );
+    }
+
+    fn toggle_clear_color_demo(&mut self) {
+        self.clear_color_demo = !self.clear_color_demo;
+    }
+
+    fn encode_settings(&self, ctx: &Context<'_>) -> String {
+        let color = self.clear_color();
+        RenderSettings {
+            camera_eye: Some(self.camera.eye.to_array()),
+            camera_target: Some(self.camera.target.to_array()),
+            camera_fovy: Some(self.camera.fovy),
+            clear_color: Some([color.r, color.g, color.b, color.a]),
+            present_mode: Some(present_mode_name(ctx.target.config.present_mode)),
+            aa_mode: Some(self.aa_mode.name()),
+            crosshair: Some(self.crosshair_enabled),
+            wireframe: Some(self.wireframe),
+            axis_gizmo: Some(self.axis_gizmo_visible),
+        }
+        .encode()
+    }
+
+    fn present_mode(&self, ctx: &Context<'_>) -> wgpu::PresentMode {
+        ctx.target.config.present_mode
+    }
+
+    /// Switches to `mode`, or to `Fifo` if the surface doesn't support it.
+    /// The surface is reconfigured before the next frame, like on a resize.
+    fn set_present_mode(&mut self, ctx: &mut Context<'_>, mode: wgpu::PresentMode) {
+        let mode = if self.present_modes.contains(&mode) {
+            mode
+        } else {
+            log::warn!("present mode {mode:?} is not supported, using Fifo");
+            wgpu::PresentMode::Fifo
+        };
+        if mode != ctx.target.config.present_mode {
+            log::info!("present mode: {mode:?}");
+            ctx.target.config.present_mode = mode;
+            ctx.target.request_reconfigure();
+        }
+    }
+
+    /// Switches between vsync (`FifoRelaxed` where supported, as at startup,
+    /// or else `Fifo`) and the lowest-latency mode without it, `Mailbox` or
+    /// else `Immediate`.
+    fn toggle_vsync(&mut self, ctx: &mut Context<'_>) {
+        use wgpu::PresentMode;
+        let mode = match self.present_mode(ctx) {
+            PresentMode::Fifo | PresentMode::FifoRelaxed => {
+                [PresentMode::Mailbox, PresentMode::Immediate]
+                    .into_iter()
+                    .find(|mode| self.present_modes.contains(mode))
+                    .unwrap_or(PresentMode::Immediate)
+            }
+            _ => vsync_present_mode(&self.present_modes, self.safe_mode),
+        };
+        self.set_present_mode(ctx, mode);
+    }
+
+    fn apply_settings(&mut self, ctx: &mut Context<'_>, encoded: &str) {
+        let settings = match RenderSettings::decode(encoded) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+        if let Some([r, g, b, a]) = settings.clear_color {
+            self.set_clear_color(wgpu::Color { r, g, b, a });
+        }
+        if let Some(name) = settings.present_mode {
+            match parse_present_mode(&name) {
+                Some(mode) if self.present_modes.contains(&mode) => {
+                    ctx.target.config.present_mode = mode;
+                    ctx.target.request_reconfigure();
+                }
+                _ => log::warn!("settings: present mode `{name}` is not available"),
+            }
+        }
+        if let Some(name) = settings.aa_mode {
+            match name.parse() {
+                Ok(mode) => self.set_aa_mode(ctx, mode),
+                Err(e) => log::warn!("settings: {e}"),
+            }
+        }
+        if let Some(crosshair) = settings.crosshair {
+            self.crosshair_enabled = crosshair;
+        }
+        if let Some(wireframe) = settings.wireframe {
+            self.set_wireframe(ctx, wireframe);
+        }
+        if let Some(axis_gizmo) = settings.axis_gizmo {
+            self.axis_gizmo_visible = axis_gizmo;
+        }
+        let eye = settings.camera_eye.map(glam::Vec3::from);
+        let target = settings.camera_target.map(glam::Vec3::from);
+        if eye.is_some() || target.is_some() {
+            self.camera.eye = eye.unwrap_or(self.camera.eye);
+            self.camera.target = target.unwrap_or(self.camera.target);
+            self.camera_controller.set_pose(&self.camera);
+        }
+        if let Some(fovy) = settings.camera_fovy {
+            self.camera.set_target_fovy(fovy);
+            self.camera.fovy = self.camera.target_fovy;
+        }
+        self.upload_camera(&ctx.gpu.queue);
+    }
+
+    fn copy_settings(&self, ctx: &Context<'_>) {
+        let encoded = self.encode_settings(ctx);
+        log::info!("settings: {encoded}");
+        #[cfg(not(target_arch = "wasm32"))]
+        match copy_to_clipboard(&encoded) {
+            Ok(()) => log::info!("settings copied to clipboard"),
+            Err(e) => log::warn!("failed to copy settings to clipboard: {e}"),
+        }
+    }
+
+    fn toggle_crosshair(&mut self) {
+        self.crosshair_enabled = !self.crosshair_enabled;
+    }
+
+    /// Cycles through off, SMPTE bars and a checkerboard.
+    fn cycle_test_pattern(&mut self, ctx: &mut Context<'_>) {
+        match (self.test_pattern_visible, self.test_pattern.pattern()) {
+            (false, _) => {
+                self.test_pattern
+                    .set_pattern(&ctx.gpu.queue, Pattern::SmpteBars);
+                self.test_pattern_visible = true;
+            }
+            (true, Pattern::SmpteBars) => {
+                self.test_pattern
+                    .set_pattern(&ctx.gpu.queue, Pattern::Checkerboard);
+            }
+            (true, Pattern::Checkerboard) => self.test_pattern_visible = false,
+        }
+    }
+
+    /// The intermediate textures of the main window's frame that the texture
+    /// viewer can show.
+    fn debug_textures(&self) -> Vec<DebugTexture<'_>> {
+        let mut textures = Vec::new();
+        if let Some(offscreen) = &self.offscreen {
+            textures.push(DebugTexture {
+                name: "offscreen",
+                view: offscreen.view(),
+                size: offscreen.size(),
+                kind: DebugTextureKind::Color,
+            });
+        }
+        // A multisampled depth buffer can't be bound for sampling.
+        if let (1, Some(depth_texture)) = (self.sample_count, &self.depth_texture) {
+            let texture = &depth_texture.texture;
+            textures.push(DebugTexture {
+                name: "depth",
+                view: &depth_texture.view,
+                size: [texture.width(), texture.height()],
+                kind: DebugTextureKind::Depth,
+            });
+        }
+        textures.push(DebugTexture {
+            name: "cubemap",
+            view: self.cubemap.strip_view(),
+            size: [self.cubemap.strip().width(), self.cubemap.strip().height()],
+            kind: DebugTextureKind::Color,
+        });
+        textures
+    }
+
+    /// Renders the cubemap if the texture viewer is showing it.
+    fn render_cubemap(&self, frame: &mut FrameEncoder, queue: &wgpu::Queue) {
+        let selected = self
+            .texture_viewer
+            .selected()
+            .map(|index| self.debug_textures()[index].name);
+        if selected != Some("cubemap") || !self.has_meshes() {
+            return;
+        }
+        self.cubemap.render(
+            queue,
+            frame,
+            CUBEMAP_CENTER,
+            &self.model,
+            &self.instances,
+            self.clear_color(),
+        );
+        self.cubemap.unfold(frame);
+    }
+
+    fn cycle_debug_texture(&mut self) {
+        let textures: Vec<_> = self
+            .debug_textures()
+            .iter()
+            .map(|texture| texture.name.to_owned())
+            .collect();
+        self.texture_viewer.cycle(textures.len());
+        match self.texture_viewer.selected() {
+            Some(index) => log::info!("texture viewer: {}", textures[index]),
+            None => log::info!("texture viewer: off"),
+        }
+    }
+
+    /// Draws the selected debug texture, with its name when text is enabled.
+    fn draw_texture_viewer(
+        &mut self,
+        ctx: &Context<'_>,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let Some(index) = self.texture_viewer.selected() else {
+            return;
+        };
+        self.texture_viewer
+            .set_depth_range(&ctx.gpu.queue, self.camera.znear, self.camera.zfar);
+        let output_size = [ctx.target.config.width, ctx.target.config.height];
+        let Some((name, [x, y, _, height])) = self.debug_textures().get(index).map(|texture| {
+            let rect = self
+                .texture_viewer
+                .draw(&ctx.gpu.device, frame, view, output_size, texture);
+            (texture.name.to_owned(), rect)
+        }) else {
+            return;
+        };
+        #[cfg(feature = "text")]
+        {
+            let scale = self.scale_factor() as f32;
+            self.text.queue(
+                x / scale,
+                (y + height) / scale - 20.0,
+                &name,
+                14.0,
+                [1.0, 1.0, 0.0, 1.0],
+            );
+        }
+        #[cfg(not(feature = "text"))]
+        let _ = (name, x, y, height);
+    }
+
+    fn toggle_crosshair_gap(&mut self, ctx: &mut Context<'_>) {
+        let enabled = self.crosshair.constants().get("GAP").unwrap_or(0.0) > 0.0;
+        self.set_crosshair_gap(ctx, !enabled);
+    }
+
+    fn set_crosshair_gap(&mut self, ctx: &mut Context<'_>, enabled: bool) {
+        let gap = if enabled {
+            CROSSHAIR_GAP * self.scale_factor()
+        } else {
+            0.0
+        };
+        let mut constants = self.crosshair.constants().clone();
+        constants.set("GAP", gap);
+        self.crosshair
+            .set_constants(&ctx.gpu.device, self.scene_format(), constants);
+        log::info!("crosshair gap: {gap}px");
+    }
+
+    fn set_mouse_look(&mut self, ctx: &mut Context<'_>, enabled: bool) {
+        if self.mouse_look == enabled {
+            return;
+        }
+        self.mouse_look = enabled;
+        // While looking around the OS cursor is hidden and the crosshair marks the screen center.
+        self.set_cursor_grabbed(ctx, enabled);
+        self.update_cursor(ctx);
+        self.update_crosshair_position(ctx);
+    }
+
+    /// Zooms in while `enabled` and back out after, easing there over the
+    /// next frames.
+    fn set_zoomed(&mut self, enabled: bool) {
+        if self.zoomed == enabled {
+            return;
+        }
+        self.zoomed = enabled;
+        self.camera
+            .set_target_fovy(if enabled { ZOOM_FOVY } else { DEFAULT_FOVY });
+    }
+
+    /// Locks and hides the cursor so mouse motion can turn the view without
+    /// the cursor leaving the window.
+    fn set_cursor_grabbed(&mut self, ctx: &mut Context<'_>, grabbed: bool) {
+        self.cursor.set_grabbed(&ctx.target.window, grabbed);
+    }
+
+    fn set_dragging(&mut self, ctx: &mut Context<'_>, dragging: bool) {
+        self.dragging = dragging;
+        self.update_cursor(ctx);
+    }
+
+    fn update_cursor(&mut self, ctx: &mut Context<'_>) {
+        let mode = if self.mouse_look {
+            InteractionMode::MouseLook
+        } else if self.dragging {
+            InteractionMode::Dragging
+        } else {
+            InteractionMode::Idle
+        };
+        self.cursor.set_mode(&ctx.target.window, mode);
+    }
+
+    fn set_cursor_position(
+        &mut self,
+        ctx: &mut Context<'_>,
+        position: winit::dpi::PhysicalPosition<f64>,
+    ) {
+        self.cursor_position = Some([position.x as f32, position.y as f32]);
+        #[cfg(feature = "text")]
+        {
+            let scale = self.scale_factor() as f32;
+            self.gui
+                .handle_pointer_moved([position.x as f32 / scale, position.y as f32 / scale]);
+        }
+        self.update_crosshair_position(ctx);
+        if self.clear_color_demo {
+            self.set_clear_color(wgpu::Color {
+                r: position.x / ctx.target.size.width as f64,
+                g: position.y / ctx.target.size.height as f64,
+                ..self.clear_color
+            });
+        }
+    }
+
+    fn update_crosshair_position(&mut self, ctx: &mut Context<'_>) {
+        let position = if self.mouse_look {
+            None
+        } else {
+            self.cursor_position
+        };
+        self.crosshair.set_position(&ctx.gpu.queue, position);
+    }
+
+    /// Draws the scene into `view`, rendering into `msaa` and resolving into
+    /// `view` when multisampling. Overlays are drawn separately, with
+    /// [`draw_overlays`](Self::draw_overlays).
+    ///
+    /// `view_camera` is the camera of a [`SceneView`]. `None` draws the main
+    /// window's frame.
+    fn draw_scene(
+        &self,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+        depth_view: Option<&wgpu::TextureView>,
+        msaa: Option<&MsaaTarget>,
+        view_camera: Option<&wgpu::BindGroup>,
+    ) {
+        let camera = view_camera.unwrap_or(self.camera_uniform.bind_group());
+        let bundles = (self.has_meshes() && self.config.encode_threads > 1 && !self.vertex_pulling)
+            .then(|| {
+                let record = || {
+                    let target = BundleTarget {
+                        color_format: self.scene_pipeline.format(),
+                        depth_format: Some(DEPTH_FORMAT),
+                        sample_count: self.sample_count,
+                    };
+                    // `self` isn't `Sync`, so the threads only get what they
+                    // draw.
+                    let (pipeline, instances, model) =
+                        (self.scene_pipeline.pipeline(), &self.instances, &self.model);
+                    encode_bundles(
+                        &self.device,
+                        &target,
+                        instances.len() as usize,
+                        self.config.encode_threads,
+                        |encoder, range| {
+                            encoder.set_pipeline(pipeline);
+                            encoder.set_bind_group(0, camera, &[]);
+                            encoder.set_bind_group(2, instances.bind_group(), &[]);
+                            encoder.set_vertex_buffer(1, instances.slice());
+                            model.draw_bundle(encoder, range.start as u32..range.end as u32);
+                        },
+                    )
+                };
+                // Other windows' cameras would replace the main window's
+                // bundles every frame, so theirs aren't kept.
+                match view_camera {
+                    Some(_) => record(),
+                    None => self
+                        .scene_bundles
+                        .borrow_mut()
+                        .get_or_record(self.scene_bundle_key(), record)
+                        .to_vec(),
+                }
+            });
+        {
+            let (color_view, resolve_target, store) = match msaa {
+                Some(msaa) => (msaa.view(), msaa.resolve_target(view), msaa.store_op()),
+                None => (view, None, wgpu::StoreOp::Store),
+            };
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: label!("frame::render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(output_clear_color(
+                            self.clear_color,
+                            self.scene_format(),
+                        )),
+                        store,
+                    },
+                })],
+                // Scenes without depth have pipelines built without it too.
+                depth_stencil_attachment: depth_view.filter(|_| self.scene_needs_depth()).map(
+                    |view| wgpu::RenderPassDepthStencilAttachment {
+                        view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: view_camera
+                    .is_none()
+                    .then(|| self.gpu_timer.as_ref()?.pass_writes(TimedPass::Scene))
+                    .flatten(),
+            });
+            if let Some(scene) = self.active_scene() {
+                scene.draw(&mut render_pass);
+            } else if let Some(bundles) = &bundles {
+                render_pass.execute_bundles(bundles);
+            } else if let (true, true, Some(instances)) = (
+                self.has_meshes(),
+                self.vertex_pulling,
+                self.instances.pulling_bind_group(),
+            ) {
+                render_pass.set_pipeline(self.scene_pipeline.pipeline());
+                render_pass.set_bind_group(0, camera, &[]);
+                render_pass.set_bind_group(2, instances, &[]);
+                self.model
+                    .draw_pulled(&mut render_pass, 0..self.instances.len());
+            } else if self.has_meshes() {
+                render_pass.set_pipeline(self.scene_pipeline.pipeline());
+                render_pass.set_bind_group(0, camera, &[]);
+                render_pass.set_bind_group(2, self.instances.bind_group(), &[]);
+                if view_camera.is_none() && self.culls_grid() {
+                    self.culler.draw(&mut render_pass, &self.model);
+                } else {
+                    render_pass.set_vertex_buffer(1, self.instances.slice());
+                    self.model.draw(&mut render_pass, 0..self.instances.len());
+                }
+            }
+            if self.wireframe && self.has_meshes() {
+                self.wire_overlay
+                    .draw(&mut render_pass, camera, &self.model, &self.instances);
+            }
+        }
+        if let Some(msaa) = msaa {
+            msaa.resolve(frame, view);
+        }
+    }
+
+    fn has_overlays(&self) -> bool {
+        self.test_pattern_visible
+            || self.crosshair_enabled
+            || self.mouse_look
+            || self.axis_gizmo_visible
+    }
+
+    /// What the main window's scene bundles bind and draw.
+    fn scene_bundle_key(&self) -> SceneBundleKey {
+        SceneBundleKey {
+            pipeline: self.scene_pipeline.pipeline().clone(),
+            camera: self.camera_uniform.bind_group().clone(),
+            instances: self.instances.bind_group().clone(),
+            instance_buffer: self.instances.slice().buffer().clone(),
+            instance_count: self.instances.len(),
+            materials: self
+                .model
+                .materials
+                .iter()
+                .map(|material| material.bind_group.clone())
+                .collect(),
+            meshes: self
+                .model
+                .meshes
+                .iter()
+                .map(|mesh| mesh.mesh.vertex_buffer().clone())
+                .collect(),
+            threads: self.config.encode_threads,
+        }
+    }
+
+    /// Draws screen-space overlays on top of the scene, without depth.
+    fn draw_overlays(&self, frame: &mut FrameEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("frame::overlay_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: self
+                .gpu_timer
+                .as_ref()
+                .and_then(|timer| timer.pass_writes(TimedPass::Overlay)),
+        });
+        if self.test_pattern_visible {
+            self.test_pattern.draw(&mut render_pass);
+        }
+        if self.crosshair_enabled || self.mouse_look {
+            self.crosshair.draw(&mut render_pass);
+        }
+        // Last, as it narrows the viewport.
+        if self.axis_gizmo_visible {
+            self.axis_gizmo.draw(&mut render_pass);
+        }
+    }
+
+    fn has_meshes(&self) -> bool {
+        self.active_scene.is_none() && !self.instances.is_empty() && self.model.has_indices()
+    }
+
+    /// Whether this frame draws the model grid through the culler, rather
+    /// than from render bundles or with vertex pulling, which draw every
+    /// instance.
+    fn culls_grid(&self) -> bool {
+        self.culler.is_active()
+            && self.has_meshes()
+            && self.config.encode_threads <= 1
+            && !self.vertex_pulling
+    }
+
+    fn has_draws(&self) -> bool {
+        self.has_meshes() || self.active_scene.is_some() || self.has_overlays()
+    }
+
+    /// Clears `view` without binding any pipeline, and without the offscreen
+    /// target and its blit pass, for frames with nothing to draw.
+    fn render_clear_only(
+        &self,
+        ctx: &Context<'_>,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let clear_color = output_clear_color(self.clear_color, ctx.target.config.format);
+        frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("frame::clear_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+    }
+
+    /// Draws the scene to `view`, going through `offscreen` when it is set:
+    /// the scene pass renders into it and a second pass blits it to `view`.
+    ///
+    /// `view_camera` is the camera of a [`SceneView`]; overlays are left out
+    /// there. `None` draws the main window's frame, overlays included.
+    fn draw_to(
+        &self,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+        depth_view: Option<&wgpu::TextureView>,
+        msaa: Option<&MsaaTarget>,
+        offscreen: Option<&OffscreenTarget>,
+        view_camera: Option<&wgpu::BindGroup>,
+    ) {
+        let target = offscreen.map_or(view, OffscreenTarget::view);
+        self.draw_scene(frame, target, depth_view, msaa, view_camera);
+        if view_camera.is_none() && self.has_overlays() {
+            self.draw_overlays(frame, target);
+        }
+        if let Some(offscreen) = offscreen {
+            offscreen.blit(frame, view);
+        }
+    }
+
+    /// Opens another window showing the scene.
+    fn open_view(
+        &mut self,
+        ctx: &mut Context<'_>,
+        event_loop: &ActiveEventLoop,
+        camera: ViewCamera,
+    ) {
+        let window_attributes = match camera {
+            ViewCamera::Main => Window::default_attributes().with_title(VIEW_WINDOW_TITLE),
+            ViewCamera::Minimap => Window::default_attributes()
+                .with_title(MINIMAP_WINDOW_TITLE)
+                .with_inner_size(winit::dpi::LogicalSize::new(
+                    MINIMAP_WINDOW_SIZE,
+                    MINIMAP_WINDOW_SIZE,
+                )),
+        };
+        match ctx.spawn_window(event_loop, window_attributes) {
+            Ok(target) => self.add_view(ctx, target, camera),
+            Err(e) => log::error!("failed to open a window: {e}"),
+        }
+    }
+
+    /// Shows the scene in `target`, a window opened as an extra view.
+    fn add_view(&mut self, ctx: &mut Context<'_>, target: WindowTarget, camera: ViewCamera) {
+        let (depth_texture, msaa, offscreen) = self.create_view_targets(ctx, &target.config);
+        let view = SceneView {
+            camera,
+            camera_uniform: UniformBuffer::new(
+                &ctx.gpu.device,
+                label!("view::camera_uniform"),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            depth_texture,
+            msaa,
+            offscreen,
+            sample_count: self.sample_count,
+            target,
+        };
+        self.views.insert(view.target.window.id(), view);
+    }
+
+    /// Depth, MSAA and offscreen targets for a [`SceneView`] of `config`'s
+    /// size.
+    fn create_view_targets(
+        &self,
+        ctx: &Context<'_>,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (Texture, Option<MsaaTarget>, Option<OffscreenTarget>) {
+        let depth_texture = Texture::create_depth_texture(
+            &ctx.gpu.device,
+            config,
+            self.sample_count,
+            "view::depth_texture",
+        );
+        let msaa = (self.sample_count > 1).then(|| {
+            MsaaTarget::new(
+                &ctx.gpu.device,
+                self.scene_format(),
+                config.width,
+                config.height,
+                self.sample_count,
+                self.config.msaa_resolve,
+            )
+        });
+        let offscreen = self.create_offscreen(ctx, config.width, config.height);
+        (depth_texture, msaa, offscreen)
+    }
+
+    /// Handles an event for an extra window: resizes, redraws and closing,
+    /// which drops just that window and its surface.
+    fn handle_view_event(&mut self, ctx: &Context<'_>, id: WindowId, event: &WindowEvent) {
+        let Some(mut view) = self.views.remove(&id) else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => return,
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                view.target.set_resized(*size, Instant::now());
+            }
+            WindowEvent::RedrawRequested => match self.render_view(ctx, &mut view) {
+                Ok(()) => {}
+                Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                    self.view_error_log.log(
+                        log::Level::Warn,
+                        &format!("{e}, reconfiguring the view's surface"),
+                    );
+                    view.target.reconfigure(&ctx.gpu.device);
+                }
+                Err(e) => self
+                    .view_error_log
+                    .log(log::Level::Warn, &format!("failed to draw a view: {e}")),
+            },
+            _ => {}
+        }
+        self.views.insert(id, view);
+    }
+
+    fn render_view(
+        &self,
+        ctx: &Context<'_>,
+        view: &mut SceneView,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let mut stale = view
+            .target
+            .resize_if_needed(&ctx.gpu.device, Instant::now());
+        // Follow the main window's format, which the pipelines are built for.
+        if view.target.config.format != ctx.target.config.format {
+            view.target.config.format = ctx.target.config.format;
+            view.target.reconfigure(&ctx.gpu.device);
+            stale = true;
+        }
+        if stale
+            || view.sample_count != self.sample_count
+            || view.offscreen.is_some() != self.offscreen.is_some()
+        {
+            (view.depth_texture, view.msaa, view.offscreen) =
+                self.create_view_targets(ctx, &view.target.config);
+            view.sample_count = self.sample_count;
+        }
+        let aspect = view.target.config.width as f32 / view.target.config.height as f32;
+        let camera = match view.camera {
+            ViewCamera::Main => Camera {
+                aspect,
+                ..self.camera
+            },
+            ViewCamera::Minimap => Camera::top_down(
+                self.camera.eye.with_y(0.0),
+                self.camera.eye.y + MINIMAP_HEIGHT,
+                aspect,
+            ),
+        };
+        view.camera_uniform
+            .update(&ctx.gpu.queue, &CameraUniform::from(&camera));
+
+        let Some(surface) = &view.target.surface else {
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
+        let surface_view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut frame = FrameEncoder::new(&ctx.gpu.device, &ctx.gpu.queue, label!("view::encoder"));
+        self.draw_to(
+            &mut frame,
+            &surface_view,
+            Some(&view.depth_texture.view),
+            view.msaa.as_ref(),
+            view.offscreen.as_ref(),
+            Some(view.camera_uniform.bind_group()),
+        );
+        frame.submit();
+        view.target.window.pre_present_notify();
+        output.present();
+        Ok(())
+    }
+
+    /// Asks every window for a redraw.
+    fn request_redraw(&self, ctx: &Context<'_>) {
+        ctx.target.window.request_redraw();
+        for view in self.views.values() {
+            view.target.window.request_redraw();
+        }
+    }
+
+    fn capture_supersampled(
+        &self,
+        ctx: &Context<'_>,
+        factor: u32,
+    ) -> Result<RgbaImage, CaptureError> {
+        let factor = factor.max(1);
+        let (width, height) = (
+            ctx.target.config.width * factor,
+            ctx.target.config.height * factor,
+        );
+        let max = ctx.gpu.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(CaptureError::TooLarge { width, height, max });
+        }
+
+        let texture = ctx.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("capture::supersampled"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CAPTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
         });
-        let surface = instance.create_surface(window.clone()).unwrap();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    label: None,
-                    memory_hints: wgpu::MemoryHints::Performance,
-                    trace: wgpu::Trace::Off,
-                },
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Always blit, so the scene is converted from the format its
+        // pipelines were built for.
+        let offscreen = OffscreenTarget::new(
+            &ctx.gpu.device,
+            &ctx.gpu.queue,
+            width,
+            height,
+            self.scene_format(),
+            CAPTURE_FORMAT,
+        );
+        let depth_texture = Texture::create_depth_texture(
+            &ctx.gpu.device,
+            &wgpu::SurfaceConfiguration {
+                width,
+                height,
+                ..ctx.target.config.clone()
+            },
+            self.sample_count,
+            "capture::depth_texture",
+        );
+        let msaa = (self.sample_count > 1).then(|| {
+            MsaaTarget::new(
+                &ctx.gpu.device,
+                self.scene_format(),
+                width,
+                height,
+                self.sample_count,
+                self.config.msaa_resolve,
             )
-            .await
-            .unwrap();
+        });
+        let mut frame = FrameEncoder::new(
+            &ctx.gpu.device,
+            &ctx.gpu.queue,
+            label!("capture::scene_encoder"),
+        );
+        self.draw_to(
+            &mut frame,
+            &view,
+            Some(&depth_texture.view),
+            msaa.as_ref(),
+            Some(&offscreen),
+            None,
+        );
+        frame.submit();
 
-        let caps = surface.get_capabilities(&adapter);
-        let mut size = window.inner_size();
-        size.width = size.width.max(1);
-        size.height = size.height.max(1);
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: caps.formats[0],
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(&device, &config);
-
-        Self {
-            window,
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            size_changed: false,
+        let pixels = read_texture_rgba(&ctx.gpu.device, &ctx.gpu.queue, &texture)?;
+        let mut pixels = downsample_rgba(&pixels, width, height, factor, CAPTURE_FORMAT.is_srgb());
+        if !self.capture_with_alpha {
+            force_opaque(&mut pixels);
+        }
+        let image = RgbaImage::from_raw(ctx.target.config.width, ctx.target.config.height, pixels)
+            .expect("downsampled buffer matches the output size");
+        Ok(image)
+    }
+
+    fn save_screenshot(&self, ctx: &Context<'_>) {
+        let path = format!("screenshot-{}.png", unix_timestamp());
+        self.save_capture(ctx, &path, SCREENSHOT_SUPERSAMPLING);
+    }
+
+    /// Renders the current frame at the window's size and saves it to
+    /// `path`, in the format given by its extension.
+    ///
+    /// The surface texture can't be copied from, so the scene is drawn again
+    /// into an offscreen texture of [`CAPTURE_FORMAT`].
+    fn capture_screenshot(&self, ctx: &Context<'_>, path: &Path) -> Result<(), CaptureError> {
+        self.capture_supersampled(ctx, 1)?.save(path)?;
+        Ok(())
+    }
+
+    fn capture_frame(&self, ctx: &Context<'_>) {
+        let path = format!("frame-{}.png", unix_timestamp());
+        match self.capture_screenshot(ctx, Path::new(&path)) {
+            Ok(()) => log::info!("saved frame to {path}"),
+            Err(e) => log::error!("frame capture failed: {e}"),
+        }
+    }
+
+    fn save_capture(&self, ctx: &Context<'_>, path: &str, factor: u32) {
+        match self
+            .capture_supersampled(ctx, factor)
+            .and_then(|image| image.save(path).map_err(CaptureError::from))
+        {
+            Ok(()) => log::info!("saved screenshot to {path}"),
+            Err(e) => log::error!("screenshot failed: {e}"),
+        }
+    }
+
+    fn handle_input(
+        &mut self,
+        ctx: &mut Context<'_>,
+        event_loop: &ActiveEventLoop,
+        input: InputEvent,
+    ) {
+        self.input_state.handle(&input);
+        match input {
+            InputEvent::Modifiers(modifiers) => self.input_map.set_modifiers(modifiers),
+            InputEvent::Key {
+                code,
+                pressed: true,
+                repeat: false,
+            } if self
+                .active_scene
+                .and_then(|index| self.scenes.get_mut(index))
+                .is_some_and(|scene| scene.key_pressed(code)) => {}
+            InputEvent::Key {
+                code,
+                pressed: true,
+                repeat: false,
+            } => match self.input_map.action_for(code) {
+                Some(Action::ToggleCrosshair) => self.toggle_crosshair(),
+                Some(Action::ToggleCrosshairGap) => self.toggle_crosshair_gap(ctx),
+                Some(Action::SaveScreenshot) => self.save_screenshot(ctx),
+                Some(Action::CaptureFrame) => self.capture_frame(ctx),
+                Some(Action::CopySettings) => self.copy_settings(ctx),
+                Some(Action::ToggleTrace) => self.toggle_trace(),
+                Some(Action::CycleTestPattern) => self.cycle_test_pattern(ctx),
+                Some(Action::CycleDebugTexture) => self.cycle_debug_texture(),
+                Some(Action::CycleAntialiasing) => self.cycle_aa_mode(ctx),
+                Some(Action::ToggleWireframe) => self.toggle_wireframe(ctx),
+                Some(Action::ToggleAxisGizmo) => self.toggle_axis_gizmo(),
+                Some(Action::ToggleAnimation) => self.toggle_animation(),
+                Some(Action::ToggleClearColorDemo) => self.toggle_clear_color_demo(),
+                Some(Action::ToggleTextureFilter) => self.toggle_texture_filter(ctx),
+                Some(Action::DecreaseLodBias) => self.adjust_lod_bias(ctx, -LOD_BIAS_STEP),
+                Some(Action::IncreaseLodBias) => self.adjust_lod_bias(ctx, LOD_BIAS_STEP),
+                Some(Action::ToggleVsync) => self.toggle_vsync(ctx),
+                Some(Action::ToggleFullscreen) => self.toggle_fullscreen(ctx),
+                Some(Action::OpenWindow) => self.open_view(ctx, event_loop, ViewCamera::Main),
+                Some(Action::OpenMinimap) => self.open_view(ctx, event_loop, ViewCamera::Minimap),
+                Some(Action::NextScene) => self.next_scene(ctx),
+                Some(Action::CycleCulling) => self.cycle_culling(),
+                #[cfg(feature = "text")]
+                Some(Action::ToggleGui) => self.toggle_gui(),
+                #[cfg(not(feature = "text"))]
+                Some(Action::ToggleGui) => log::warn!("the debug panel needs the `text` feature"),
+                None => {}
+            },
+            InputEvent::MouseButton {
+                button: MouseButton::Right,
+                pressed,
+            } => self.set_mouse_look(ctx, pressed),
+            InputEvent::MouseButton {
+                button: MouseButton::Middle,
+                pressed,
+            } => self.set_zoomed(pressed),
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                pressed,
+            } => self.handle_left_button(ctx, pressed),
+            InputEvent::CursorMoved(position) => self.set_cursor_position(ctx, position),
+            InputEvent::Focused(false) => {
+                #[cfg(feature = "text")]
+                self.gui.handle_pointer_left();
+                self.set_dragging(ctx, false);
+                self.set_mouse_look(ctx, false);
+                self.set_zoomed(false);
+            }
+            _ => {}
         }
     }
 
-    fn set_window_resized(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size == self.size {
+    /// Logs uncaptured GPU errors and, when enabled, dumps the first failing
+    /// frame (screenshot plus error and settings report) for bug reports.
+    fn handle_gpu_errors(&mut self, ctx: &Context<'_>) {
+        let errors = self.gpu_errors.take_errors();
+        if errors.is_empty() || !self.config.dump_on_gpu_error || self.gpu_error_dumped {
             return;
         }
-        self.size = new_size;
-        self.size_changed = true;
+        self.gpu_error_dumped = true;
+
+        let stamp = unix_timestamp();
+        self.save_capture(ctx, &format!("gpu-error-{stamp}.png"), 1);
+        let report_path = format!("gpu-error-{stamp}.txt");
+        let report = format!(
+            "{}\n\nsettings: {}\n",
+            errors.join("\n\n"),
+            self.encode_settings(ctx)
+        );
+        match std::fs::write(&report_path, report) {
+            Ok(()) => log::info!("wrote GPU error report to {report_path}"),
+            Err(e) => log::error!("failed to write {report_path}: {e}"),
+        }
     }
 
-    fn resize_surface_if_needed(&mut self) {
-        if self.size_changed {
-            self.config.width = self.size.width;
-            self.config.height = self.size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.size_changed = false;
+    fn toggle_trace(&mut self) {
+        if !self.tracer.is_recording() {
+            self.tracer.start();
+            log::info!("recording frame trace");
+            return;
+        }
+        let path = format!("trace-{}.json", unix_timestamp());
+        match self.tracer.stop(Path::new(&path)) {
+            Ok(()) => log::info!("saved frame trace to {path}"),
+            Err(e) => log::error!("failed to save frame trace: {e}"),
         }
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.resize_surface_if_needed();
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
+    fn frame_stats(&self) -> FrameStats {
+        self.frame_counter.stats()
+    }
 
-        {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    depth_slice: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
+    /// GPU time of the last frame whose timestamps were read back, if the
+    /// adapter supports timestamp queries.
+    fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.gpu_timer.as_ref().and_then(GpuTimer::timings)
+    }
+
+    /// Rebuilds the scene pipeline if `shader.wgsl` changed on disk. A
+    /// shader that fails to compile is logged and the old one kept.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn reload_changed_shaders(&mut self, ctx: &mut Context<'_>) {
+        for path in std::mem::take(&mut self.changed_shaders) {
+            if path.file_name() != Some(SCENE_SHADER.as_ref()) {
+                log::info!(
+                    "{} changed; only {SCENE_SHADER} is reloaded",
+                    path.display()
+                );
+                continue;
+            }
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| format!("{}: {e}", path.display()))
+                .and_then(|source| self.scene_pipeline.reload(&ctx.gpu.device, &path, &source));
+            match result {
+                Ok(()) => log::info!("reloaded {}", path.display()),
+                Err(e) => log::error!("shader reload failed, keeping the previous one:\n{e}"),
+            }
+        }
+    }
+
+    /// Passes `event` to the debug UI and returns whether the UI claims it,
+    /// in which case the app ignores it.
+    #[cfg(feature = "egui")]
+    fn ui_captures(&mut self, ctx: &mut Context<'_>, event: &WindowEvent) -> bool {
+        self.ui.on_window_event(&ctx.target.window, event);
+        InputEvent::from_window_event(event).is_some_and(|input| self.ui.wants_input(&input))
+    }
+
+    #[cfg(not(feature = "egui"))]
+    fn ui_captures(&mut self, _ctx: &mut Context<'_>, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    /// Builds the debug panel: frame stats, surface size and a clear color
+    /// picker.
+    #[cfg(feature = "egui")]
+    fn build_ui(&mut self, ctx: &mut Context<'_>) {
+        let stats = self.frame_stats();
+        let (width, height) = (ctx.target.config.width, ctx.target.config.height);
+        let format = ctx.target.config.format;
+        let present_mode = self.present_mode(ctx);
+        let fullscreen = self.is_fullscreen(ctx);
+        let color = self.clear_color();
+        let old_rgb = [color.r as f32, color.g as f32, color.b as f32];
+        let mut rgb = old_rgb;
+        self.ui.run(&ctx.target.window, |context| {
+            egui::Window::new("Debug").show(context, |ui| {
+                ui.label(stats.to_string());
+                ui.label(format!("Surface: {width}x{height} {format:?}"));
+                ui.label(format!("Present mode: {present_mode:?}"));
+                ui.label(if fullscreen { "Fullscreen" } else { "Windowed" });
+                ui.horizontal(|ui| {
+                    ui.label("Clear color");
+                    ui.color_edit_button_rgb(&mut rgb);
+                });
             });
+        });
+        if rgb != old_rgb {
+            let [r, g, b] = rgb.map(f64::from);
+            self.set_clear_color(wgpu::Color { r, g, b, ..color });
         }
+    }
 
-        self.queue.submit(Some(encoder.finish()));
-        output.present();
-        Ok(())
+    /// Builds the built-in debug panel, below the FPS readout: clear color
+    /// sliders and a button resetting it.
+    #[cfg(feature = "text")]
+    fn build_gui(&mut self) {
+        let color = self.clear_color();
+        let mut rgb = [color.r, color.g, color.b].map(|c| c as f32);
+        self.gui.begin([8.0, 32.0]);
+        self.gui.label("Clear color");
+        let mut changed = false;
+        for (channel, name) in rgb.iter_mut().zip(["Red", "Green", "Blue"]) {
+            changed |= self.gui.slider(name, name, channel, 0.0..=1.0);
+        }
+        let reset = self.gui.button("reset", "Reset");
+        self.gui.end();
+        if reset {
+            self.set_clear_color(CLEAR_COLOR);
+        } else if changed {
+            let [r, g, b] = rgb.map(f64::from);
+            self.set_clear_color(wgpu::Color { r, g, b, ..color });
+        }
     }
-}
 
-#[derive(Default)]
-struct WgpuAppHandler {
-    app: Arc<Mutex<Option<WgpuApp>>>,
-}
+    #[cfg(feature = "text")]
+    fn toggle_gui(&mut self) {
+        self.gui_visible = !self.gui_visible;
+        if !self.gui_visible {
+            self.gui.handle_pointer_left();
+        }
+    }
 
-impl ApplicationHandler for WgpuAppHandler {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.app.as_ref().lock().is_some() {
+    /// Passes the primary button to the built-in GUI first, then to
+    /// dragging if the GUI doesn't take it.
+    fn handle_left_button(&mut self, ctx: &mut Context<'_>, pressed: bool) {
+        #[cfg(feature = "text")]
+        if self.gui_visible && self.gui.handle_button(pressed) {
             return;
         }
+        self.set_dragging(ctx, pressed);
+    }
+
+    fn record_input(&mut self, now: Instant) {
+        self.last_input_time = Some(now);
+        if self.idle {
+            self.idle = false;
+            log::info!("input received, restoring full framerate");
+        }
+    }
+
+    #[cfg(feature = "battery")]
+    fn on_battery(&mut self, now: Instant) -> bool {
+        if let Some(source) = self.power.poll(now) {
+            apply_power_source(&mut self.config, source);
+        }
+        self.power.source() == PowerSource::Battery
+    }
+
+    #[cfg(not(feature = "battery"))]
+    fn on_battery(&mut self, _now: Instant) -> bool {
+        false
+    }
+
+    /// Polls the GPU stats, returning whether they were refreshed.
+    #[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+    fn poll_gpu_stats(&mut self, now: Instant) -> bool {
+        self.gpu_stats.poll(now)
+    }
+
+    #[cfg(not(all(feature = "gpu-stats", not(target_arch = "wasm32"))))]
+    fn poll_gpu_stats(&mut self, _now: Instant) -> bool {
+        false
+    }
+
+    /// GPU stats for the window title, if the `gpu-stats` feature is on.
+    #[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+    fn gpu_stats_title(&self) -> String {
+        match self.gpu_stats.stats() {
+            Some(stats) => format!(" | GPU {stats}"),
+            None => " | GPU unavailable".to_owned(),
+        }
+    }
+
+    #[cfg(not(all(feature = "gpu-stats", not(target_arch = "wasm32"))))]
+    fn gpu_stats_title(&self) -> String {
+        String::new()
+    }
+
+    fn update_idle(&mut self, now: Instant) -> bool {
+        let idle = match (self.config.idle_timeout, self.last_input_time) {
+            (Some(timeout), Some(last_input)) => now.duration_since(last_input) >= timeout,
+            _ => false,
+        };
+        if idle && !self.idle {
+            log::info!(
+                "no input for a while, dropping to {}fps",
+                self.config.idle_fps
+            );
+        }
+        self.idle = idle;
+        idle
+    }
+}
+
+impl Application for WgpuApp {
+    type Options = DemoOptions;
+
+    fn init(
+        ctx: &mut Context<'_>,
+        app_config: &AppConfig,
+        options: DemoOptions,
+    ) -> Result<Self, InitError> {
+        let GpuContext {
+            adapter,
+            device,
+            queue,
+            ..
+        } = ctx.gpu;
+
+        let gpu_errors = GpuErrorMonitor::default();
+        gpu_errors.install(device);
+
+        let config = &ctx.target.config;
+        if config.format == wgpu::TextureFormat::Rgba16Float {
+            log::info!(
+                "HDR output: peak value {:.2} ({} nits)",
+                max_output_value(config.format, app_config.max_luminance),
+                app_config.max_luminance
+            );
+        }
+        let present_modes = ctx
+            .target
+            .surface
+            .as_ref()
+            .map(|surface| surface.get_capabilities(adapter).present_modes)
+            .unwrap_or_default();
+
+        let window = &ctx.target.window;
+        let monitor = window.current_monitor();
+        let pacer = FramePacer::for_monitor(monitor.clone());
+        log::info!(
+            "frame pacing target: {:.2}ms",
+            pacer.target_interval().as_secs_f64() * 1000.0
+        );
+
+        let pipelines_start = Instant::now();
+        let offscreen_format = if app_config.linear_working_space {
+            Some(WORKING_FORMAT)
+        } else {
+            app_config.offscreen_format
+        };
+        let offscreen = offscreen_format.map(|format| {
+            let offscreen = OffscreenTarget::new(
+                device,
+                queue,
+                config.width,
+                config.height,
+                format,
+                config.format,
+            );
+            offscreen.set_max_output_value(
+                queue,
+                max_output_value(config.format, app_config.max_luminance),
+            );
+            offscreen
+        });
+        let scene_format = offscreen_format.unwrap_or(config.format);
+        let gpu_timer = GpuTimer::new(device, queue);
+        let adaptive_scale = match (app_config.render_scale_budget, &gpu_timer) {
+            (Some(budget), Some(_)) => Some(AdaptiveRenderScale::new(
+                budget,
+                app_config.render_scale_bounds,
+            )),
+            (Some(_), None) => {
+                log::warn!("no GPU timings to adapt the render scale to, keeping it fixed");
+                None
+            }
+            (None, _) => None,
+        };
+        let mut camera = Camera::new(config.width as f32 / config.height as f32);
+        camera.eye = CAMERA_EYE;
+        let camera_controller = CameraController::new(&camera, app_config.camera_smoothing);
+        let camera_uniform = UniformBuffer::new(
+            device,
+            label!("camera::uniform"),
+            wgpu::ShaderStages::VERTEX,
+        );
+        camera_uniform.update(queue, &CameraUniform::from(&camera));
+        let sample_count = supported_sample_count(
+            adapter,
+            &[scene_format, DEPTH_FORMAT],
+            app_config.aa_mode.sample_count(),
+        );
+        let aa_mode = match app_config.aa_mode {
+            AaMode::Msaa(_) if sample_count == 1 => AaMode::None,
+            mode => mode,
+        };
+        log::info!("antialiasing: {aa_mode}");
+        let post_aa = aa_mode
+            .is_post_process()
+            .then(|| PostAa::new(device, scene_format, config.width, config.height, aa_mode));
+        let depth_texture = Some(Texture::create_depth_texture(
+            device,
+            config,
+            sample_count,
+            "depth_texture",
+        ));
+        let msaa = (sample_count > 1).then(|| {
+            MsaaTarget::new(
+                device,
+                scene_format,
+                config.width,
+                config.height,
+                sample_count,
+                app_config.msaa_resolve,
+            )
+        });
+        let texture_layout = Texture::array_bind_group_layout(device);
+        let vertex_pulling = app_config.vertex_pulling && pulling::is_supported(adapter);
+        if app_config.vertex_pulling && !vertex_pulling {
+            log::warn!("vertex pulling is not supported by the adapter, using vertex buffers");
+        }
+        let scene_pipeline = if vertex_pulling {
+            ScenePipeline::new(
+                device,
+                scene_format,
+                sample_count,
+                wgpu::DepthBiasState::default(),
+                &[
+                    camera_uniform.layout(),
+                    &texture_layout,
+                    &InstanceBuffer::pulling_bind_group_layout(device),
+                    &pulling::bind_group_layout(device),
+                ],
+                Vec::new(),
+            )
+        } else {
+            ScenePipeline::new(
+                device,
+                scene_format,
+                sample_count,
+                wgpu::DepthBiasState::default(),
+                &[
+                    camera_uniform.layout(),
+                    &texture_layout,
+                    &InstanceBuffer::bind_group_layout(device),
+                ],
+                vec![ModelVertex::layout(), InstanceRaw::layout()],
+            )
+        };
+        let wire_overlay = WireOverlay::new(
+            device,
+            queue,
+            scene_format,
+            sample_count,
+            camera_uniform.layout(),
+        );
+        let mut model = pentagon_model(device, queue);
+        model.set_sampler(device, SamplerOptions::filtered(wgpu::FilterMode::Linear));
+        let mut instances = InstanceBuffer::new(device);
+        instances.set_materials(device, queue, MATERIALS);
+        let mut culler = InstanceCuller::new(
+            device,
+            adapter,
+            app_config.culling,
+            app_config.occlusion_culling,
+        );
+        culler.set_model(device, queue, &model);
+        let mut limiter = FrameLimiter::new(app_config.max_fps);
+        limiter.set_spin(app_config.spin_frame_limiter);
+        let test_pattern =
+            TestPattern::new(device, queue, scene_format, config.width, config.height);
+        let crosshair = Crosshair::new(device, queue, scene_format, config.width, config.height);
+        let texture_viewer =
+            TextureViewer::new(device, config.format, app_config.texture_viewer_size);
+        let cubemap = CubemapRenderer::new(device, WORKING_FORMAT, CUBEMAP_SIZE);
+        log::info!(
+            "cubemap: {}",
+            if cubemap.is_multiview() {
+                "one multiview pass"
+            } else {
+                "one pass per face"
+            }
+        );
+        let scenes: Vec<Box<dyn Scene>> = vec![Box::new(FillRateScene::new(
+            device,
+            scene_format,
+            sample_count,
+        ))];
+        log::info!(
+            "created pipelines in {:.1}ms",
+            pipelines_start.elapsed().as_secs_f64() * 1000.0
+        );
+        if app_config.warm_up_pipelines {
+            warm_up(
+                device,
+                queue,
+                scene_format,
+                &[
+                    WarmupDraw {
+                        name: "scene",
+                        depth_format: Some(DEPTH_FORMAT),
+                        sample_count,
+                        draw: &|render_pass| {
+                            render_pass.set_pipeline(scene_pipeline.pipeline());
+                            render_pass.set_bind_group(0, camera_uniform.bind_group(), &[]);
+                            match instances.pulling_bind_group() {
+                                Some(instances) if vertex_pulling => {
+                                    render_pass.set_bind_group(2, instances, &[]);
+                                    model.draw_pulled(render_pass, 0..1);
+                                }
+                                _ => {
+                                    render_pass.set_bind_group(2, instances.bind_group(), &[]);
+                                    render_pass.set_vertex_buffer(1, instances.slice());
+                                    model.draw(render_pass, 0..1);
+                                }
+                            }
+                        },
+                    },
+                    WarmupDraw {
+                        name: "test_pattern",
+                        depth_format: None,
+                        sample_count: 1,
+                        draw: &|render_pass| test_pattern.draw(render_pass),
+                    },
+                    WarmupDraw {
+                        name: "crosshair",
+                        depth_format: None,
+                        sample_count: 1,
+                        draw: &|render_pass| crosshair.draw(render_pass),
+                    },
+                ],
+            );
+        }
+
+        let (model_sender, loaded_models) = mpsc::channel();
+        #[cfg(feature = "egui")]
+        let ui = Ui::new(device, config.format, window);
+        let scale_factor = window.scale_factor();
+        let axis_gizmo = AxisGizmo::new(
+            device,
+            scene_format,
+            (AXIS_GIZMO_SIZE * scale_factor).round() as u32,
+            config.width,
+            config.height,
+        );
+        #[cfg(feature = "text")]
+        let text = TextRenderer::new(
+            device,
+            config.format,
+            config.width,
+            config.height,
+            scale_factor,
+        );
+        #[cfg(feature = "text")]
+        let gui_renderer = GuiRenderer::new(
+            device,
+            queue,
+            config.format,
+            config.width,
+            config.height,
+            scale_factor,
+        );
+        let mut app = Self {
+            views: HashMap::new(),
+            view_error_log: RateLimitedLogger::default(),
+            windowed_size: None,
+            scale_factor,
+            present_modes,
+            hdr_output: app_config.hdr_output,
+            max_luminance: app_config.max_luminance,
+            safe_mode: app_config.safe_mode,
+            monitor,
+            last_monitor_check: None,
+            monitor_check_pending: false,
+            clear_color: CLEAR_COLORS[0],
+            clear_color_demo: false,
+            clear_fade: ClearColorFade::new(app_config.scene_fade),
+            offscreen,
+            render_scale: 1.0,
+            upscaler: None,
+            adaptive_scale,
+            camera,
+            camera_controller,
+            camera_uniform,
+            depth_texture,
+            aa_mode,
+            sample_count,
+            msaa,
+            post_aa,
+            scene_pipeline,
+            wire_overlay,
+            wireframe: false,
+            axis_gizmo,
+            axis_gizmo_visible: false,
+            vertex_pulling,
+            device: device.clone(),
+            scene_bundles: RefCell::default(),
+            model,
+            texture_filter: wgpu::FilterMode::Linear,
+            animation_player: None,
+            node_transforms: Vec::new(),
+            instances,
+            culler,
+            scenes,
+            active_scene: None,
+            test_pattern,
+            test_pattern_visible: false,
+            crosshair,
+            crosshair_enabled: false,
+            texture_viewer,
+            cubemap,
+            mouse_look: false,
+            zoomed: false,
+            dragging: false,
+            cursor: CursorController::default(),
+            cursor_position: None,
+            pacer,
+            limiter,
+            throttle: ThrottleDetector::default(),
+            gpu_errors,
+            gpu_error_dumped: false,
+            tracer: FrameTracer::default(),
+            frame_counter: FrameCounter::default(),
+            gpu_timer,
+            pass_budgets: PassBudgets::new(&app_config.gpu_pass_budgets),
+            #[cfg(feature = "egui")]
+            ui,
+            #[cfg(feature = "text")]
+            text,
+            #[cfg(feature = "text")]
+            gui: Gui::default(),
+            #[cfg(feature = "text")]
+            gui_renderer,
+            #[cfg(feature = "text")]
+            gui_visible: false,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            _shader_watcher: ShaderWatcher::new(Path::new(SHADER_DIR), ctx.proxy.clone())
+                .inspect_err(|e| log::warn!("not watching {SHADER_DIR} for shader changes: {e}"))
+                .ok(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            changed_shaders: Vec::new(),
+            loaded_models,
+            model_sender,
+            capture_with_alpha: app_config.capture_with_alpha,
+            config: app_config.clone(),
+            input_map: InputMap::default(),
+            input_state: InputState::default(),
+            last_input_time: Some(Instant::now()),
+            idle: false,
+            recorder: options.recorder,
+            player: options.player,
+            redraw_requested: false,
+            #[cfg(feature = "battery")]
+            power: PowerMonitor::default(),
+            #[cfg(all(feature = "gpu-stats", not(target_arch = "wasm32")))]
+            gpu_stats: GpuStatsMonitor::default(),
+            frame_start: Instant::now(),
+            frame_marks: None,
+        };
+        app.set_render_scale(ctx, app_config.render_scale);
+        app.set_instances(ctx, &grid_instances(INSTANCES_PER_ROW));
+        if let Some(model) = options.model {
+            app.load_model(ctx, model);
+        }
+        if let Some(settings) = &options.settings {
+            app.apply_settings(ctx, settings);
+        }
+        let now = Instant::now();
+        if let Some(recorder) = &mut app.recorder {
+            recorder.start(now);
+        }
+        if let Some(player) = &mut app.player {
+            player.start(now);
+        }
+        Ok(app)
+    }
+
+    fn resize(&mut self, ctx: &mut Context<'_>, _width: u32, _height: u32) {
+        self.surface_resized(ctx);
+    }
+
+    fn update(&mut self, ctx: &mut Context<'_>, dt: Duration) {
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        self.reload_changed_shaders(ctx);
+        // TAA needs the next jitter every frame.
+        let taa = self.aa_mode == AaMode::Taa;
+        self.camera_controller
+            .handle_input(&self.input_state, self.mouse_look, dt);
+        let moved = self.camera_controller.update(&mut self.camera, dt);
+        let zoomed = self.camera.update_fovy(dt);
+        if moved || zoomed || taa {
+            self.upload_camera(&ctx.gpu.queue);
+        }
+        self.animate(&ctx.gpu.queue, dt);
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.poll(&ctx.gpu.device);
+        }
+    }
+
+    fn render(
+        &mut self,
+        ctx: &mut Context<'_>,
+        view: &wgpu::TextureView,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let acquired = Instant::now();
+        if let Some(color) = self.clear_fade.update(acquired) {
+            self.clear_color = color;
+        }
+        #[cfg(feature = "egui")]
+        self.build_ui(ctx);
+        let mut frame =
+            FrameEncoder::new(&ctx.gpu.device, &ctx.gpu.queue, label!("frame::encoder"));
+        let has_draws = self.has_draws();
+        if self.axis_gizmo_visible {
+            self.axis_gizmo
+                .prepare(&ctx.gpu.device, &ctx.gpu.queue, &self.camera);
+        }
+        if self.culls_grid() {
+            self.culler.cull(
+                &ctx.gpu.device,
+                &ctx.gpu.queue,
+                &mut frame,
+                self.camera.build_view_projection_matrix(),
+                self.depth_texture.as_ref().map(|texture| &texture.texture),
+            );
+        } else {
+            self.culler.reset_history();
+        }
+        let depth_view = self.depth_texture.as_ref().map(|texture| &texture.view);
+        if has_draws {
+            // The scene is drawn at the render scale and upscaled, then the
+            // overlays go on top at full resolution. With FXAA or TAA the
+            // scene goes into the post pass's input, which is filtered into
+            // wherever it would have been drawn otherwise, so FXAA doesn't
+            // blur the overlays and TAA doesn't smear them into its history.
+            let output = self.offscreen.as_ref().map_or(view, OffscreenTarget::view);
+            let scene_output = self.upscaler.as_ref().map_or(output, OffscreenTarget::view);
+            match &self.post_aa {
+                Some(post_aa) => {
+                    self.draw_scene(&mut frame, post_aa.input_view(), depth_view, None, None)
+                }
+                None => self.draw_scene(
+                    &mut frame,
+                    scene_output,
+                    depth_view,
+                    self.msaa.as_ref(),
+                    None,
+                ),
+            }
+            if let Some(post_aa) = &mut self.post_aa {
+                post_aa.draw(&ctx.gpu.queue, &mut frame, scene_output);
+            }
+            if let Some(upscaler) = &self.upscaler {
+                upscaler.blit(&mut frame, output);
+            }
+            if self.has_overlays() {
+                self.draw_overlays(&mut frame, output);
+            }
+            if let Some(offscreen) = &self.offscreen {
+                offscreen.blit(&mut frame, view);
+            }
+        }
+        if has_draws {
+            if let Some(gpu_timer) = &self.gpu_timer {
+                gpu_timer.resolve(frame.encoder());
+            }
+        } else {
+            self.render_clear_only(ctx, &mut frame, view);
+        }
+        self.render_cubemap(&mut frame, &ctx.gpu.queue);
+        self.draw_texture_viewer(ctx, &mut frame, view);
+        // The FPS readout, in the top-left corner.
+        #[cfg(feature = "text")]
+        {
+            if self.gui_visible {
+                self.build_gui();
+                self.gui_renderer.draw(
+                    &ctx.gpu.device,
+                    &ctx.gpu.queue,
+                    &mut frame,
+                    view,
+                    &self.gui,
+                    &mut self.text,
+                );
+            }
+            let stats = self.frame_stats().to_string();
+            self.text
+                .queue(8.0, 8.0, &stats, 16.0, [1.0, 1.0, 1.0, 1.0]);
+            self.text
+                .draw(&ctx.gpu.device, &ctx.gpu.queue, &mut frame, view);
+        }
+        #[cfg(feature = "egui")]
+        self.ui.draw(
+            &ctx.gpu.device,
+            &ctx.gpu.queue,
+            &mut frame,
+            view,
+            ctx.target.config.width,
+            ctx.target.config.height,
+        );
+        let encoded = Instant::now();
+        frame.submit();
+        if let (true, Some(gpu_timer)) = (has_draws, &mut self.gpu_timer) {
+            gpu_timer.map();
+        }
+        self.frame_marks = Some([
+            ("acquire", acquired),
+            ("encode", encoded),
+            ("submit", Instant::now()),
+        ]);
+        Ok(())
+    }
 
-        let window_attributes = Window::default_attributes().with_title("tutorial2-surface");
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-        let wgpu_app = pollster::block_on(WgpuApp::new(window));
-        self.app.lock().replace(wgpu_app);
+    fn frame_finished(&mut self, ctx: &mut Context<'_>) {
+        let now = Instant::now();
+        if let Some([acquire, encode, submit]) = self.frame_marks.take() {
+            self.tracer.record_frame(
+                self.frame_start,
+                &[acquire, encode, submit, ("present", now)],
+            );
+            self.frame_counter.tick(now);
+            // Waiting for a surface texture is vsync, not work, so the CPU
+            // side is timed from the acquire.
+            let cpu = submit.1.duration_since(acquire.1);
+            let gpu = self.gpu_timings().map_or(Duration::ZERO, |t| t.total());
+            self.throttle.record_frame(now, cpu.max(gpu));
+        }
+        self.handle_gpu_errors(ctx);
+        self.pacer.frame_presented(now);
+        self.limiter.frame_rendered(now);
+        self.input_state.begin_frame();
     }
 
     fn window_event(
         &mut self,
+        ctx: &mut Context<'_>,
         event_loop: &ActiveEventLoop,
-        _window_id: winit::window::WindowId,
-        event: WindowEvent,
+        window_id: WindowId,
+        event: &WindowEvent,
     ) {
-        let mut app_guard = self.app.lock();
-        if let Some(app) = app_guard.as_mut() {
-            match event {
-                WindowEvent::CloseRequested => {
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::Touch(_)
+        ) {
+            self.record_input(Instant::now());
+        }
+        if window_id != ctx.target.window.id() {
+            self.handle_view_event(ctx, window_id, event);
+            return;
+        }
+        // The debug UI sees events first; input it claims, like clicks on
+        // its panel, never reaches the app.
+        if self.ui_captures(ctx, event) {
+            return;
+        }
+        if let Some(input) = InputEvent::from_window_event(event) {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(Instant::now(), input);
+            }
+            // Live input is ignored while replaying so runs stay deterministic.
+            if self.player.is_none() {
+                self.handle_input(ctx, event_loop, input);
+            }
+            return;
+        }
+        match event {
+            WindowEvent::Moved(_) => {
+                self.request_monitor_check(ctx, Instant::now());
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.player.is_none() => {
+                self.input_state.handle_scroll(*delta);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.set_scale_factor(ctx, *scale_factor);
+                self.check_monitor_change(ctx);
+            }
+            WindowEvent::RedrawRequested => {
+                if self.input_state.just_pressed(KeyCode::Escape) {
                     event_loop.exit();
+                    return;
+                }
+                if self.input_state.just_pressed(KeyCode::Space) {
+                    self.cycle_clear_color();
                 }
-                WindowEvent::Resized(physical_size) => {
-                    if physical_size.width > 0 && physical_size.height > 0 {
-                        app.set_window_resized(physical_size);
-                    }
+                self.frame_start = Instant::now();
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _ctx: &mut Context<'_>,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: &DeviceEvent,
+    ) {
+        if self.player.is_some() {
+            return;
+        }
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.input_state.handle_mouse_motion(*delta);
+            // With the cursor locked for mouse-look no `CursorMoved` arrives,
+            // so this is the only sign the user is still active. Raw motion
+            // also comes in while the cursor is elsewhere, which is ignored.
+            if self.mouse_look {
+                self.record_input(Instant::now());
+            }
+        }
+    }
+
+    fn user_event(
+        &mut self,
+        ctx: &mut Context<'_>,
+        _event_loop: &ActiveEventLoop,
+        event: UserEvent,
+    ) {
+        match event {
+            UserEvent::AssetReady(name) => {
+                log::info!("asset ready: {name}");
+                self.receive_models(ctx);
+            }
+            // Reloaded before the next frame, once per path however many
+            // writes the save took.
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            UserEvent::ShaderChanged(path) => {
+                if !self.changed_shaders.contains(&path) {
+                    self.changed_shaders.push(path);
                 }
-                WindowEvent::RedrawRequested => {
-                    app.window.pre_present_notify();
-                    match app.render() {
-                        Ok(_) => {}
-                        Err(wgpu::SurfaceError::Lost) => eprintln!("Surface is lost"),
-                        Err(e) => eprintln!("{e:?}"),
-                    }
-                    app.window.request_redraw();
+            }
+            #[cfg(not(all(feature = "hot-reload", not(target_arch = "wasm32"))))]
+            UserEvent::ShaderChanged(_) => {}
+            UserEvent::RequestRedraw => {}
+        }
+        self.redraw_requested = true;
+    }
+
+    fn about_to_wait(&mut self, ctx: &mut Context<'_>, event_loop: &ActiveEventLoop) {
+        let now = Instant::now();
+        let idle = self.update_idle(now);
+        let on_battery = self.on_battery(now);
+        let gpu_stats_due = self.poll_gpu_stats(now);
+        let replayed = match &mut self.player {
+            Some(player) => player.due(now),
+            None => Vec::new(),
+        };
+        if !replayed.is_empty() {
+            self.record_input(now);
+        }
+        if self.player.as_ref().is_some_and(InputPlayer::is_finished) {
+            log::info!("input replay finished");
+            self.player = None;
+        }
+
+        for input in replayed {
+            self.handle_input(ctx, event_loop, input);
+        }
+        if self.frame_counter.report_due(now) || gpu_stats_due {
+            ctx.target.window.set_title(&format!(
+                "{WINDOW_TITLE} | {}{}{}",
+                self.frame_stats(),
+                self.gpu_stats_title(),
+                if self.throttle.throttling_likely() {
+                    " | throttling?"
+                } else {
+                    ""
                 }
-                _ => {}
+            ));
+            if let Some(timings) = self.gpu_timings() {
+                log::info!("GPU: {timings}");
+            }
+            if let Some(gpu_timer) = &self.gpu_timer {
+                self.pass_budgets.check(&gpu_timer.averages());
+            }
+            if let Some(scene) = self.active_scene() {
+                log::info!(
+                    "scene {} ({}): {}",
+                    scene.name(),
+                    scene.status(),
+                    self.frame_stats()
+                );
+            }
+        }
+        self.adapt_render_scale(ctx, now);
+        let monitor_check = self.poll_monitor_check(ctx, now);
+        let mut deadline = self.pacer.next_deadline();
+        if let (Some(min_frame_time), Some(last)) =
+            (self.config.min_frame_time, self.pacer.last_present())
+        {
+            deadline = deadline.max(Some(last + min_frame_time));
+        }
+        deadline = deadline.max(self.limiter.wake_time());
+        if on_battery {
+            let battery_interval =
+                Duration::from_secs_f64(1.0 / self.config.battery_fps.max(1) as f64);
+            let battery_deadline = self
+                .pacer
+                .last_present()
+                .map(|last| last + battery_interval);
+            deadline = deadline.max(battery_deadline);
+        }
+        if idle {
+            let idle_interval = Duration::from_secs_f64(1.0 / self.config.idle_fps.max(1) as f64);
+            let idle_deadline = self.pacer.last_present().map(|last| last + idle_interval);
+            deadline = deadline.max(idle_deadline);
+        }
+        if let Some(next_input) = self.player.as_ref().and_then(InputPlayer::next_deadline) {
+            deadline = deadline.map(|deadline| deadline.min(next_input));
+        }
+        if let Some(monitor_check) = monitor_check {
+            deadline = deadline.map(|deadline| deadline.min(monitor_check));
+        }
+        if let Some(resize) = ctx.target.resize_deadline() {
+            deadline = deadline.map(|deadline| deadline.min(resize));
+        }
+        match deadline {
+            Some(deadline) if deadline > now && !self.redraw_requested => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(deadline));
+            }
+            _ => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                self.limiter.spin(Instant::now());
+                self.request_redraw(ctx);
             }
         }
+        self.redraw_requested = false;
     }
+
+    /// Drops the extra windows' surfaces along with the main one. Pipelines,
+    /// buffers and other GPU resources are kept for `resumed`.
+    fn suspended(&mut self, _ctx: &mut Context<'_>) {
+        for view in self.views.values_mut() {
+            view.target.suspend();
+        }
+    }
+
+    /// Recreates the extra windows' surfaces against the existing device.
+    fn resumed(&mut self, ctx: &mut Context<'_>) {
+        let now = Instant::now();
+        for view in self.views.values_mut() {
+            if let Err(e) = view.target.resume(ctx.gpu) {
+                log::error!("failed to recreate a surface after resuming: {e}");
+                continue;
+            }
+            // The window may have been resized while suspended.
+            let size = view.target.window.inner_size();
+            if size.width > 0 && size.height > 0 {
+                view.target.set_resized(size, now);
+            }
+        }
+        self.request_redraw(ctx);
+    }
+
+    fn exiting(&mut self, _ctx: &mut Context<'_>) {
+        if let Some(recorder) = &self.recorder {
+            match recorder.save() {
+                Ok(()) => log::info!("saved input recording to {}", recorder.path().display()),
+                Err(e) => log::error!("failed to save input recording: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "battery")]
+fn apply_power_source(config: &mut AppConfig, source: PowerSource) {
+    match source {
+        PowerSource::Battery => {
+            log::info!(
+                "on battery: capping at {}fps, preferring a low-power adapter on next init",
+                config.battery_fps
+            );
+            config.power_preference = wgpu::PowerPreference::LowPower;
+        }
+        PowerSource::Ac => {
+            log::info!("on AC power: full framerate, high-performance adapter on next init");
+            config.power_preference = wgpu::PowerPreference::HighPerformance;
+        }
+    }
+}
+
+/// Parses a `WIDTHxHEIGHT` size, both non-zero.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Renders one frame of the scene without a window and saves it to `path`,
+/// and its linear depth to `depth_path` as raw native-endian `f32`s, row by
+/// row from the top.
+#[cfg(not(target_arch = "wasm32"))]
+fn render_headless(
+    config: &AppConfig,
+    width: u32,
+    height: u32,
+    path: &Path,
+    depth_path: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut renderer = pollster::block_on(HeadlessRenderer::new(config))?;
+    let frame = renderer.render(width, height, depth_path.is_some())?;
+    RgbaImage::from_raw(width, height, frame.color)
+        .expect("the frame matches the requested size")
+        .save(path)?;
+    log::info!("saved headless frame to {}", path.display());
+    if let (Some(depth_path), Some(depth)) = (depth_path, frame.linear_depth) {
+        std::fs::write(depth_path, bytemuck::cast_slice(&depth))?;
+        log::info!(
+            "saved {width}x{height} linear depth to {}",
+            depth_path.display()
+        );
+    }
+    Ok(())
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 fn main() -> Result<(), impl std::error::Error> {
     init_logger();
-    let events_loop = EventLoop::new()?;
-    let mut app = WgpuAppHandler::default();
-    events_loop.run_app(&mut app)
+    let mut config = AppConfig::default();
+    let mut options = DemoOptions::default();
+    #[cfg(not(target_arch = "wasm32"))]
+    let (mut headless_size, mut headless_out, mut headless_depth_out) = (None, None, None);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--settings" => options.settings = args.next(),
+            "--model" => options.model = args.next().map(PathBuf::from),
+            "--record" => options.recorder = args.next().map(InputRecorder::new),
+            "--replay" => match args.next().map(|path| InputPlayer::load(Path::new(&path))) {
+                Some(Ok(player)) => options.player = Some(player),
+                Some(Err(e)) => log::error!("failed to load input replay: {e}"),
+                None => log::warn!("--replay needs a path"),
+            },
+            "--vertex-pulling" => config.vertex_pulling = true,
+            "--aa" => match args.next().map(|mode| mode.parse()) {
+                Some(Ok(mode)) => config.aa_mode = mode,
+                Some(Err(e)) => log::warn!("{e}"),
+                None => log::warn!("--aa needs a mode: none, msaa, fxaa or taa"),
+            },
+            "--msaa-resolve" => match args.next().map(|resolve| resolve.parse()) {
+                Some(Ok(resolve)) => config.msaa_resolve = resolve,
+                Some(Err(e)) => log::warn!("{e}"),
+                None => log::warn!("--msaa-resolve needs implicit or explicit"),
+            },
+            "--render-scale" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(scale) => config.render_scale = scale,
+                None => log::warn!("--render-scale needs a fraction of the window resolution"),
+            },
+            "--render-scale-budget" => match args
+                .next()
+                .and_then(|s| s.parse::<f64>().ok())
+                .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+            {
+                Some(budget) => config.render_scale_budget = Some(budget),
+                None => log::warn!("--render-scale-budget needs a GPU frame time in milliseconds"),
+            },
+            "--camera-smoothing" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(seconds) => config.camera_smoothing = seconds,
+                None => log::warn!("--camera-smoothing needs a time in seconds"),
+            },
+            "--encode-threads" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(threads) => config.encode_threads = threads,
+                None => log::warn!("--encode-threads needs a thread count"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            "--headless" => match args.next().as_deref().and_then(parse_size) {
+                Some(size) => headless_size = Some(size),
+                None => log::warn!("--headless needs a size like 1280x720"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            "--out" => headless_out = args.next(),
+            #[cfg(not(target_arch = "wasm32"))]
+            "--depth-out" => headless_depth_out = args.next(),
+            other => log::warn!("ignoring unknown argument `{other}`"),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some((width, height)) = headless_size {
+        let path = headless_out.unwrap_or_else(|| "frame.png".to_string());
+        if let Err(e) = render_headless(
+            &config,
+            width,
+            height,
+            Path::new(&path),
+            headless_depth_out.as_deref().map(Path::new),
+        ) {
+            log::error!("headless render failed: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let sentinel = {
+        let (sentinel, crashed) = CrashSentinel::arm(&CrashSentinel::default_dir());
+        if crashed {
+            log::warn!("the previous run did not exit cleanly, starting in safe mode");
+            config.enable_safe_mode();
+        }
+        sentinel
+    };
+    #[cfg(feature = "battery")]
+    apply_power_source(&mut config, current_power_source());
+
+    // On the web this returns immediately; the browser drives the event loop.
+    let result = learn1::run::<WgpuApp>(WINDOW_TITLE, config, options);
+    #[cfg(not(target_arch = "wasm32"))]
+    sentinel.disarm();
+    result
 }
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::pulling;
+use crate::vertex::Vertex;
+
+/// An index type a [`Mesh`] can be built from.
+pub trait Index: bytemuck::Pod + Into<u32> {
+    const FORMAT: wgpu::IndexFormat;
+}
+
+impl Index for u16 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+}
+
+impl Index for u32 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+}
+
+/// Indexed geometry in GPU buffers, with `Uint16` or `Uint32` indices.
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    /// The vertex buffer bound for vertex pulling, where supported.
+    vertex_bind_group: Option<wgpu::BindGroup>,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    /// Each triangle edge once, as `Uint32` pairs for a `LineList`.
+    line_index_buffer: wgpu::Buffer,
+    num_vertices: u32,
+    num_indices: u32,
+    num_line_indices: u32,
+}
+
+impl Mesh {
+    pub fn new<V: Vertex, I: Index>(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        vertices: &[V],
+        indices: &[I],
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(vertices),
+            // COPY_DST lets animated meshes be posed; see `write_vertices`.
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | pulling::storage_usage(device),
+        });
+        let vertex_bind_group = pulling::bind_group(device, &vertex_buffer);
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let line_indices = edges(indices);
+        let line_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(&line_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            vertex_bind_group,
+            index_buffer,
+            index_format: I::FORMAT,
+            line_index_buffer,
+            num_vertices: vertices.len() as u32,
+            num_indices: indices.len() as u32,
+            num_line_indices: line_indices.len() as u32,
+        }
+    }
+
+    /// Replaces the vertices, which must be as many as the mesh was
+    /// created with.
+    pub fn write_vertices<V: Vertex>(&self, queue: &wgpu::Queue, vertices: &[V]) {
+        assert_eq!(vertices.len(), self.num_vertices as usize);
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+    }
+
+    pub fn num_vertices(&self) -> u32 {
+        self.num_vertices
+    }
+
+    pub fn num_indices(&self) -> u32 {
+        self.num_indices
+    }
+
+    /// The buffer the vertices are drawn from, e.g. to tell whether
+    /// something recorded with this mesh still refers to the current one.
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    /// Binds the buffers at vertex slot 0 and draws `instances`; any
+    /// per-instance buffers must already be bound. A mesh without indices is
+    /// skipped.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+        render_pass.draw_indexed(0..self.num_indices, 0, instances);
+    }
+
+    /// Like [`draw`](Self::draw), but for a vertex pulling pipeline: the
+    /// vertices are bound as a storage buffer at group 3 instead of at vertex
+    /// slot 0, and the instances must be bound at group 2. Skipped where the
+    /// device can't pull vertices.
+    pub fn draw_pulled(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        let Some(vertex_bind_group) = &self.vertex_bind_group else {
+            return;
+        };
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_bind_group(3, vertex_bind_group, &[]);
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+        render_pass.draw_indexed(0..self.num_indices, 0, instances);
+    }
+
+    /// Like [`draw`](Self::draw), but takes the instance count from the
+    /// `DrawIndexedIndirectArgs` at `offset` in `indirect`, e.g. written by
+    /// a culling pass. Its index count must be this mesh's.
+    pub fn draw_indirect(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        indirect: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+        render_pass.draw_indexed_indirect(indirect, offset);
+    }
+
+    /// Like [`draw`](Self::draw), but draws each triangle edge once with a
+    /// `LineList` pipeline, e.g. a
+    /// [`WireOverlay`](crate::wireframe::WireOverlay).
+    pub fn draw_edges(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        if self.num_line_indices == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.line_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_line_indices, 0, instances);
+    }
+
+    /// Like [`draw`](Self::draw), recording into a render bundle.
+    pub fn draw_bundle<'a>(
+        &'a self,
+        encoder: &mut wgpu::RenderBundleEncoder<'a>,
+        instances: Range<u32>,
+    ) {
+        if self.num_indices == 0 {
+            return;
+        }
+        encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        encoder.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+        encoder.draw_indexed(0..self.num_indices, 0, instances);
+    }
+}
+
+/// The edges of the triangles in `indices` as line list pairs, each shared
+/// edge once, in the order they first appear.
+fn edges<I: Index>(indices: &[I]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0].into(), triangle[1].into(), triangle[2].into()];
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            if seen.insert((from.min(to), from.max(to))) {
+                lines.extend([from, to]);
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_edges_are_listed_once() {
+        // A quad from two triangles sharing the 0-2 diagonal.
+        assert_eq!(
+            edges(&[0u16, 1, 2, 0, 2, 3]),
+            [0, 1, 1, 2, 2, 0, 2, 3, 3, 0]
+        );
+        // A trailing partial triangle draws nothing.
+        assert!(edges(&[0u32, 1]).is_empty());
+    }
+}
@@ -0,0 +1,841 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use glam::{Mat3, Mat4, Quat, Vec3, Vec4};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, Rgba, RgbaImage};
+
+use crate::animation::{AnimationClip, Channel, Interpolation, NodeHierarchy, Property, Transform};
+use crate::culling::BoundingSphere;
+use crate::label;
+use crate::mesh::Mesh;
+use crate::texture::{SamplerOptions, Texture};
+use crate::vertex::ModelVertex;
+
+#[derive(Debug)]
+pub enum ModelError {
+    /// The OBJ file couldn't be read or parsed.
+    Obj {
+        path: PathBuf,
+        error: tobj::LoadError,
+    },
+    /// The glTF file or one of its buffers couldn't be read or is invalid.
+    Gltf { path: PathBuf, error: gltf::Error },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Obj { path, error } => {
+                write!(f, "failed to load {}: {error}", path.display())
+            }
+            Self::Gltf { path, error } => {
+                write!(f, "failed to load {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// A diffuse texture made with [`Texture::from_layers`], bound as an array
+/// with [`Texture::array_bind_group_layout`].
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: impl Into<String>,
+        diffuse_texture: &Texture,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            diffuse_texture: diffuse_texture.clone(),
+            bind_group: diffuse_texture.array_bind_group(device, layout),
+        }
+    }
+
+    /// Samples the diffuse texture with `options` from now on; see
+    /// [`Texture::set_sampler`].
+    pub fn set_sampler(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        options: SamplerOptions,
+    ) {
+        self.diffuse_texture.set_sampler(device, options);
+        self.bind_group = self.diffuse_texture.array_bind_group(device, layout);
+    }
+}
+
+/// One object of a [`Model`] and the index of its material.
+pub struct ModelMesh {
+    pub name: String,
+    pub mesh: Mesh,
+    pub material: usize,
+    /// Encloses the vertices the mesh was created with.
+    pub bounds: BoundingSphere,
+}
+
+/// Meshes with their materials, e.g. loaded from a model file. Every mesh's
+/// material index is valid.
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+    /// The clips of an animated glTF model; see [`Model::pose`].
+    pub animation: Option<ModelAnimation>,
+}
+
+/// A glTF model's animation clips and the nodes they move.
+pub struct ModelAnimation {
+    pub clips: Vec<AnimationClip>,
+    pub nodes: NodeHierarchy,
+    /// Each mesh's node and its vertices relative to that node, indexed
+    /// like [`Model::meshes`].
+    meshes: Vec<(usize, Vec<ModelVertex>)>,
+}
+
+impl Model {
+    /// Loads a Wavefront OBJ file with one mesh per object or group, and the
+    /// diffuse textures of the materials in its MTL files.
+    ///
+    /// Missing normals are computed from the faces and missing texture
+    /// coordinates are zero. Materials without a diffuse map, or whose map
+    /// fails to load, and meshes without a material get a white texture.
+    pub fn from_obj(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let (objects, obj_materials) =
+            tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(|error| ModelError::Obj {
+                path: path.to_owned(),
+                error,
+            })?;
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            log::warn!("{}: failed to load materials: {e}", path.display());
+            Vec::new()
+        });
+
+        let layout = Texture::array_bind_group_layout(device);
+        let white = white_texture(device, queue);
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut materials: Vec<Material> = obj_materials
+            .into_iter()
+            .map(|material| {
+                let texture = material.diffuse_texture.as_ref().and_then(|file| {
+                    let texture_path = directory.join(file);
+                    image::open(&texture_path)
+                        .map(|image| {
+                            Texture::from_layers(
+                                device,
+                                queue,
+                                &[image.to_rgba8()],
+                                Some(&texture_path.display().to_string()),
+                            )
+                        })
+                        .inspect_err(|e| {
+                            log::warn!("failed to load {}: {e}", texture_path.display());
+                        })
+                        .ok()
+                });
+                Material::new(
+                    device,
+                    &layout,
+                    material.name,
+                    texture.as_ref().unwrap_or(&white),
+                )
+            })
+            .collect();
+
+        let mut default_material = None;
+        let meshes = objects
+            .into_iter()
+            .map(|object| {
+                let material = match object.mesh.material_id {
+                    Some(id) if id < materials.len() => id,
+                    _ => *default_material.get_or_insert_with(|| {
+                        materials.push(Material::new(device, &layout, "default", &white));
+                        materials.len() - 1
+                    }),
+                };
+                let vertices = obj_vertices(&object.mesh);
+                ModelMesh {
+                    bounds: BoundingSphere::from_vertices(&vertices),
+                    mesh: Mesh::new(
+                        device,
+                        label!("model::mesh"),
+                        &vertices,
+                        &object.mesh.indices,
+                    ),
+                    name: object.name,
+                    material,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            meshes,
+            materials,
+            animation: None,
+        })
+    }
+
+    /// Loads the default scene of a glTF 2.0 file, either `.gltf` with its
+    /// buffers and images or a self-contained `.glb`.
+    ///
+    /// The node hierarchy is flattened: each triangle primitive becomes one
+    /// mesh with its node's world transform baked into the vertices, so a
+    /// mesh used by several nodes is uploaded once per node. Base color
+    /// textures, embedded or external PNG and JPEG, become the materials'
+    /// diffuse textures. Skins, morph targets, other primitive modes and
+    /// extensions are skipped with a warning.
+    ///
+    /// Translation, rotation and scale channels of the file's animations are
+    /// read into [`Model::animation`], whose meshes can then be re-posed
+    /// with [`Model::pose`]. Morph target weight channels are skipped.
+    pub fn from_gltf(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let gltf_error = |error| ModelError::Gltf {
+            path: path.to_owned(),
+            error,
+        };
+        let (document, blob) = read_gltf(path).map_err(gltf_error)?;
+        for extension in document.extensions_used() {
+            log::warn!(
+                "{}: extension {extension} is not supported, ignoring it",
+                path.display()
+            );
+        }
+        let base = path.parent();
+        let buffers = gltf::import_buffers(&document, base, blob).map_err(gltf_error)?;
+
+        let layout = Texture::array_bind_group_layout(device);
+        let white = white_texture(device, queue);
+        let textures: Vec<Option<Texture>> = document
+            .images()
+            .map(|image| {
+                let data = gltf::image::Data::from_source(image.source(), base, &buffers)
+                    .inspect_err(|e| {
+                        log::warn!("{}: image {}: {e}", path.display(), image.index());
+                    })
+                    .ok()?;
+                let image = gltf_image(data).or_else(|| {
+                    log::warn!(
+                        "{}: image {} has an unsupported pixel format",
+                        path.display(),
+                        image.index()
+                    );
+                    None
+                })?;
+                Some(Texture::from_layers(
+                    device,
+                    queue,
+                    &[image.to_rgba8()],
+                    label!("model::texture"),
+                ))
+            })
+            .collect();
+        let mut materials: Vec<Material> = document
+            .materials()
+            .map(|material| {
+                let texture = material
+                    .pbr_metallic_roughness()
+                    .base_color_texture()
+                    .and_then(|info| textures[info.texture().source().index()].as_ref());
+                Material::new(
+                    device,
+                    &layout,
+                    material.name().unwrap_or_default(),
+                    texture.unwrap_or(&white),
+                )
+            })
+            .collect();
+        // For primitives without a material.
+        materials.push(Material::new(device, &layout, "default", &white));
+
+        let clips = gltf_clips(&document, &buffers, path);
+        let animation = (!clips.is_empty()).then(|| ModelAnimation {
+            clips,
+            nodes: NodeHierarchy::new(document.nodes().map(gltf_transform).collect()),
+            meshes: Vec::new(),
+        });
+        let mut loader = GltfLoader {
+            device,
+            path,
+            buffers: &buffers,
+            default_material: materials.len() - 1,
+            meshes: Vec::new(),
+            animation,
+        };
+        match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => {
+                for node in scene.nodes() {
+                    loader.add_node(node, None, Mat4::IDENTITY, 0);
+                }
+            }
+            None => log::warn!("{}: the file has no scene to show", path.display()),
+        }
+        Ok(Self {
+            meshes: loader.meshes,
+            materials,
+            animation: loader.animation,
+        })
+    }
+
+    /// Loads `path` as glTF if its extension is `.gltf` or `.glb`, as OBJ
+    /// otherwise.
+    pub fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gltf" | "glb") => Self::from_gltf(device, queue, path),
+            _ => Self::from_obj(device, queue, path),
+        }
+    }
+
+    /// Samples every material's texture with `options` from now on.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, options: SamplerOptions) {
+        let layout = Texture::array_bind_group_layout(device);
+        for material in &mut self.materials {
+            material.set_sampler(device, &layout, options);
+        }
+    }
+
+    /// Draws every mesh with its material's bind group at group 1. The
+    /// pipeline, the other bind groups and any per-instance buffers must
+    /// already be set.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        for mesh in &self.meshes {
+            render_pass.set_bind_group(1, &self.materials[mesh.material].bind_group, &[]);
+            mesh.mesh.draw(render_pass, instances.clone());
+        }
+    }
+
+    /// Like [`draw`](Self::draw), taking each mesh's instance count from
+    /// the `DrawIndexedIndirectArgs` at its index in `indirect`; see
+    /// [`Mesh::draw_indirect`].
+    pub fn draw_indirect(&self, render_pass: &mut wgpu::RenderPass<'_>, indirect: &wgpu::Buffer) {
+        let stride = std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64;
+        for (index, mesh) in self.meshes.iter().enumerate() {
+            render_pass.set_bind_group(1, &self.materials[mesh.material].bind_group, &[]);
+            mesh.mesh
+                .draw_indirect(render_pass, indirect, index as u64 * stride);
+        }
+    }
+
+    /// Like [`draw`](Self::draw), for a vertex pulling pipeline; see
+    /// [`Mesh::draw_pulled`].
+    pub fn draw_pulled(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        for mesh in &self.meshes {
+            render_pass.set_bind_group(1, &self.materials[mesh.material].bind_group, &[]);
+            mesh.mesh.draw_pulled(render_pass, instances.clone());
+        }
+    }
+
+    /// Draws every mesh's triangle edges; see [`Mesh::draw_edges`]. No
+    /// material is bound.
+    pub fn draw_edges(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        for mesh in &self.meshes {
+            mesh.mesh.draw_edges(render_pass, instances.clone());
+        }
+    }
+
+    /// Like [`draw`](Self::draw), recording into a render bundle.
+    pub fn draw_bundle<'a>(
+        &'a self,
+        encoder: &mut wgpu::RenderBundleEncoder<'a>,
+        instances: Range<u32>,
+    ) {
+        for mesh in &self.meshes {
+            encoder.set_bind_group(1, &self.materials[mesh.material].bind_group, &[]);
+            mesh.mesh.draw_bundle(encoder, instances.clone());
+        }
+    }
+
+    /// Moves the meshes of an animated model to where `transforms`, indexed
+    /// by node and e.g. posed with [`AnimationClip::apply`], put them. Does
+    /// nothing for a model without animations.
+    ///
+    /// Triangles keep the winding of the rest pose, so a node mirrored
+    /// differently from its rest pose is drawn inside out.
+    pub fn pose(&self, queue: &wgpu::Queue, transforms: &[Transform]) {
+        let Some(animation) = &self.animation else {
+            return;
+        };
+        let world = animation.nodes.world_matrices(transforms);
+        for (mesh, (node, vertices)) in self.meshes.iter().zip(&animation.meshes) {
+            mesh.mesh
+                .write_vertices(queue, &transform_vertices(vertices, world[*node]));
+        }
+    }
+
+    pub fn has_indices(&self) -> bool {
+        self.meshes.iter().any(|mesh| mesh.mesh.num_indices() > 0)
+    }
+
+    /// A sphere enclosing every mesh, or `None` for an animated model, whose
+    /// meshes move away from where they were created, or one without
+    /// meshes.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        if self.animation.is_some() {
+            return None;
+        }
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(BoundingSphere::merge)
+    }
+}
+
+/// Nodes nested deeper than this are skipped, so a cyclic hierarchy can't
+/// recurse forever.
+const MAX_NODE_DEPTH: usize = 256;
+
+/// Walks a glTF scene's nodes, collecting their primitives as meshes.
+struct GltfLoader<'a> {
+    device: &'a wgpu::Device,
+    path: &'a Path,
+    buffers: &'a [gltf::buffer::Data],
+    default_material: usize,
+    meshes: Vec<ModelMesh>,
+    /// Set for animated files, to record the node tree and each mesh's
+    /// unposed vertices.
+    animation: Option<ModelAnimation>,
+}
+
+impl GltfLoader<'_> {
+    fn add_node(
+        &mut self,
+        node: gltf::Node<'_>,
+        parent: Option<usize>,
+        parent_transform: Mat4,
+        depth: usize,
+    ) {
+        if depth > MAX_NODE_DEPTH {
+            log::warn!(
+                "{}: node {} is nested too deeply, skipping it",
+                self.path.display(),
+                node.index()
+            );
+            return;
+        }
+        if let Some(animation) = &mut self.animation {
+            animation.nodes.push(node.index(), parent);
+        }
+        let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            if node.skin().is_some() {
+                log::warn!(
+                    "{}: node {} is skinned; skinning is not supported, drawing its bind pose",
+                    self.path.display(),
+                    node.index()
+                );
+            }
+            let name = mesh.name().or(node.name()).unwrap_or_default();
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, node.index(), transform, name);
+            }
+        }
+        for child in node.children() {
+            self.add_node(child, Some(node.index()), transform, depth + 1);
+        }
+    }
+
+    fn add_primitive(
+        &mut self,
+        primitive: &gltf::Primitive<'_>,
+        node: usize,
+        transform: Mat4,
+        name: &str,
+    ) {
+        let path = self.path.display();
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!(
+                "{path}: skipping a primitive of {name:?} drawn as {:?}, only triangles are supported",
+                primitive.mode()
+            );
+            return;
+        }
+        if primitive.morph_targets().next().is_some() {
+            log::warn!("{path}: ignoring the morph targets of {name:?}");
+        }
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+        let Some(positions) = reader.read_positions() else {
+            log::warn!("{path}: skipping a primitive of {name:?} without positions");
+            return;
+        };
+        let positions: Vec<[f32; 3]> = positions.collect();
+        let mut indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices.iter().any(|&i| i as usize >= positions.len()) {
+            log::warn!("{path}: skipping a primitive of {name:?} with out of range indices");
+            return;
+        }
+        indices.truncate(indices.len() / 3 * 3);
+
+        let material = primitive.material();
+        let tex_coord_set = material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map_or(0, |info| info.tex_coord());
+        let tex_coords: Vec<[f32; 2]> = reader
+            .read_tex_coords(tex_coord_set)
+            .map(|tex_coords| tex_coords.into_f32().collect())
+            .filter(|tex_coords: &Vec<_>| tex_coords.len() == positions.len())
+            .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+        let normals: Vec<[f32; 3]> = reader
+            .read_normals()
+            .map(|normals| normals.collect())
+            .filter(|normals: &Vec<_>| normals.len() == positions.len())
+            .unwrap_or_else(|| face_normals(&positions, &indices));
+
+        let local: Vec<ModelVertex> = positions
+            .iter()
+            .zip(&tex_coords)
+            .zip(&normals)
+            .map(|((&position, &tex_coords), &normal)| ModelVertex {
+                position,
+                tex_coords,
+                normal,
+            })
+            .collect();
+        let vertices = transform_vertices(&local, transform);
+        // A mirroring transform turns counter-clockwise triangles clockwise.
+        if transform.determinant() < 0.0 {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        if let Some(animation) = &mut self.animation {
+            animation.meshes.push((node, local));
+        }
+        self.meshes.push(ModelMesh {
+            bounds: BoundingSphere::from_vertices(&vertices),
+            name: name.to_owned(),
+            mesh: Mesh::new(self.device, label!("model::mesh"), &vertices, &indices),
+            material: material.index().unwrap_or(self.default_material),
+        });
+    }
+}
+
+/// Moves `vertices` by `transform`, keeping their normals perpendicular to
+/// the surface.
+fn transform_vertices(vertices: &[ModelVertex], transform: Mat4) -> Vec<ModelVertex> {
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+    vertices
+        .iter()
+        .map(|vertex| ModelVertex {
+            position: transform
+                .transform_point3(vertex.position.into())
+                .to_array(),
+            tex_coords: vertex.tex_coords,
+            normal: (normal_matrix * Vec3::from(vertex.normal))
+                .normalize_or(Vec3::Y)
+                .to_array(),
+        })
+        .collect()
+}
+
+/// A glTF node's transform, with a matrix split into its parts.
+fn gltf_transform(node: gltf::Node<'_>) -> Transform {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    Transform {
+        translation: translation.into(),
+        rotation: Quat::from_array(rotation),
+        scale: scale.into(),
+    }
+}
+
+/// Reads the animations of a glTF document, skipping channels that can't be
+/// played and clips left without any.
+fn gltf_clips(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    path: &Path,
+) -> Vec<AnimationClip> {
+    document
+        .animations()
+        .filter_map(|animation| {
+            let name = animation
+                .name()
+                .map_or_else(|| format!("animation {}", animation.index()), str::to_owned);
+            let channels: Vec<Channel> = animation
+                .channels()
+                .filter_map(|channel| gltf_channel(&channel, buffers, path, &name))
+                .collect();
+            if channels.is_empty() {
+                log::warn!("{}: {name:?} has no playable channels", path.display());
+                return None;
+            }
+            Some(AnimationClip { name, channels })
+        })
+        .collect()
+}
+
+/// Reads one animation channel, if it drives a node's translation, rotation
+/// or scale with valid keyframes.
+fn gltf_channel(
+    channel: &gltf::animation::Channel<'_>,
+    buffers: &[gltf::buffer::Data],
+    path: &Path,
+    clip: &str,
+) -> Option<Channel> {
+    use gltf::animation::util::ReadOutputs;
+
+    let path = path.display();
+    let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+    let interpolation = match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+    };
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+    let (property, values): (_, Vec<Vec4>) = match reader.read_outputs()? {
+        ReadOutputs::Translations(values) => (
+            Property::Translation,
+            values.map(|value| Vec3::from(value).extend(0.0)).collect(),
+        ),
+        ReadOutputs::Rotations(values) => (
+            Property::Rotation,
+            values.into_f32().map(Vec4::from).collect(),
+        ),
+        ReadOutputs::Scales(values) => (
+            Property::Scale,
+            values.map(|value| Vec3::from(value).extend(0.0)).collect(),
+        ),
+        ReadOutputs::MorphTargetWeights(_) => {
+            log::warn!("{path}: skipping a morph target weight channel of {clip:?}");
+            return None;
+        }
+    };
+    let values_per_key = match interpolation {
+        Interpolation::CubicSpline => 3,
+        _ => 1,
+    };
+    let ascending = times.windows(2).all(|pair| pair[0] <= pair[1]);
+    if times.is_empty() || !ascending || values.len() != times.len() * values_per_key {
+        log::warn!("{path}: skipping a channel of {clip:?} with invalid keyframes");
+        return None;
+    }
+    Some(Channel {
+        node: channel.target().node().index(),
+        property,
+        interpolation,
+        times,
+        values,
+    })
+}
+
+/// Reads a `.gltf` or `.glb` document and the binary chunk of a `.glb`.
+///
+/// Extensions the document requires are dropped before it is validated, so
+/// files using them still load, without those features.
+fn read_gltf(path: &Path) -> Result<(gltf::Document, Option<Vec<u8>>), gltf::Error> {
+    let reader = BufReader::new(File::open(path).map_err(gltf::Error::Io)?);
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_reader_without_validation(reader)?;
+    let mut root = document.into_json();
+    root.extensions_required.clear();
+    Ok((gltf::Document::from_json(root)?, blob))
+}
+
+/// Converts decoded glTF image data with 8-bit channels.
+fn gltf_image(data: gltf::image::Data) -> Option<DynamicImage> {
+    use gltf::image::Format;
+
+    let gltf::image::Data {
+        pixels,
+        format,
+        width,
+        height,
+    } = data;
+    match format {
+        Format::R8 => GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => {
+            GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8)
+        }
+        Format::R8G8B8 => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => {
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        _ => None,
+    }
+}
+
+/// A 1x1 white texture for materials without a diffuse map.
+fn white_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+    let image = RgbaImage::from_pixel(1, 1, Rgba([255; 4]));
+    Texture::from_layers(device, queue, &[image], label!("model::white_texture"))
+}
+
+/// Interleaves an OBJ mesh's attributes, which share one index.
+fn obj_vertices(mesh: &tobj::Mesh) -> Vec<ModelVertex> {
+    let positions: Vec<[f32; 3]> = mesh
+        .positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let normals: Vec<[f32; 3]> = if mesh.normals.len() == mesh.positions.len() {
+        mesh.normals
+            .chunks_exact(3)
+            .map(|n| [n[0], n[1], n[2]])
+            .collect()
+    } else {
+        face_normals(&positions, &mesh.indices)
+    };
+    let has_tex_coords = mesh.texcoords.len() / 2 == positions.len();
+    positions
+        .iter()
+        .zip(normals)
+        .enumerate()
+        .map(|(i, (&position, normal))| ModelVertex {
+            position,
+            // OBJ's V axis points up, wgpu's down.
+            tex_coords: if has_tex_coords {
+                [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]]
+            } else {
+                [0.0; 2]
+            },
+            normal,
+        })
+        .collect()
+}
+
+/// Per-vertex normals averaged from the counter-clockwise triangles in
+/// `indices`, weighted by their area. Vertices on no triangle with an area
+/// get +Y.
+fn face_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let [pa, pb, pc] = [a, b, c].map(|i| Vec3::from(positions[i]));
+        // Twice the triangle's area, so larger faces count for more.
+        let normal = (pb - pa).cross(pc - pa);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or(Vec3::Y).to_array())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::test_support::test_gpu;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    /// Name, vertex count, index count and material name of each mesh.
+    fn summary(model: &Model) -> Vec<(&str, u32, u32, &str)> {
+        model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                (
+                    mesh.name.as_str(),
+                    mesh.mesh.num_vertices(),
+                    mesh.mesh.num_indices(),
+                    model.materials[mesh.material].name.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loads_obj_objects_and_materials() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        let model = Model::from_obj(&device, &queue, fixture("quad.obj")).unwrap();
+        // The triangle has no material; the quad is triangulated.
+        assert_eq!(
+            summary(&model),
+            [("triangle", 3, 3, "default"), ("quad", 4, 6, "checker")]
+        );
+        assert_eq!(model.materials.len(), 2);
+        assert!(model.has_indices());
+    }
+
+    #[test]
+    fn loads_gltf_scene() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        let model = Model::from_gltf(&device, &queue, fixture("triangles.gltf")).unwrap();
+        // One mesh with u16 indices and one with u32, both under a parent
+        // node; primitives without a material get the default one.
+        assert_eq!(
+            summary(&model),
+            [
+                ("u16 triangle", 3, 3, "red"),
+                ("u32 triangle", 3, 3, "default")
+            ]
+        );
+        assert_eq!(model.materials.len(), 2);
+    }
+
+    #[test]
+    fn reads_gltf_animation_clips() {
+        let path = fixture("animated.gltf");
+        let (document, blob) = read_gltf(&path).unwrap();
+        let buffers = gltf::import_buffers(&document, path.parent(), blob).unwrap();
+        let clips = gltf_clips(&document, &buffers, &path);
+        // One clip sliding the root node 2 units along +X over 2 s.
+        assert_eq!(clips.len(), 1);
+        let channel = &clips[0].channels[0];
+        assert_eq!((channel.node, channel.property), (0, Property::Translation));
+        assert_eq!(channel.sample(1.0), Vec4::new(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn loads_gltf_animation() {
+        let gpu = test_gpu!();
+        let model = Model::load(&gpu.device, &gpu.queue, fixture("animated.gltf")).unwrap();
+        let animation = model.animation.as_ref().unwrap();
+        let clip = &animation.clips[0];
+        assert_eq!((clip.name.as_str(), clip.duration()), ("slide", 2.0));
+        let mut transforms = animation.nodes.rest.clone();
+        clip.apply(1.0, &mut transforms);
+        assert_eq!(transforms[0].translation, Vec3::X);
+        // The child keeps its own offset under the moved root.
+        let world = animation.nodes.world_matrices(&transforms);
+        assert_eq!(
+            world[1].transform_point3(Vec3::ZERO),
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+        model.pose(&gpu.queue, &transforms);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        assert!(Model::from_obj(&device, &queue, fixture("missing.obj")).is_err());
+        assert!(Model::from_gltf(&device, &queue, fixture("missing.gltf")).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::frame::FrameEncoder;
+use crate::label;
+
+/// How a multisampled target is turned into one sample per pixel.
+///
+/// [`Implicit`](Self::Implicit) is the cheapest and right when one pass
+/// draws the whole multisampled image: on tiled GPUs the samples never
+/// leave on-chip memory. Use [`Explicit`](Self::Explicit) when several
+/// passes draw into the target before it is resolved, or to resolve at a
+/// point of your choosing into a texture that post-processing then reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsaaResolve {
+    /// Each pass drawing into the target resolves into its
+    /// `resolve_target` as it ends, and the samples are discarded.
+    Implicit,
+    /// The samples are stored, and [`MsaaTarget::resolve`] resolves them
+    /// with a pass of its own. Costs writing the samples out and reading
+    /// them back.
+    Explicit,
+}
+
+impl fmt::Display for MsaaResolve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Implicit => "implicit",
+            Self::Explicit => "explicit",
+        })
+    }
+}
+
+impl FromStr for MsaaResolve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "implicit" => Ok(Self::Implicit),
+            "explicit" => Ok(Self::Explicit),
+            _ => Err(format!(
+                "unknown MSAA resolve `{s}`, expected `implicit` or `explicit`"
+            )),
+        }
+    }
+}
+
+/// Returns `requested` if every format in `formats` can be rendered with that
+/// many samples (and resolved, for color formats), otherwise 1.
+pub fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+    requested: u32,
+) -> u32 {
+    if requested <= 1 {
+        return 1;
+    }
+    let unsupported = formats.iter().find(|format| {
+        let flags = adapter.get_texture_format_features(**format).flags;
+        let resolvable = format.has_depth_aspect()
+            || flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+        !flags.sample_count_supported(requested) || !resolvable
+    });
+    match unsupported {
+        Some(format) => {
+            log::warn!("{requested}x MSAA is not supported for {format:?}, falling back to 1x");
+            1
+        }
+        None => requested,
+    }
+}
+
+/// A multisampled color target, resolved into the final view either by the
+/// pass drawing into it or by a separate pass; see [`MsaaResolve`].
+pub struct MsaaTarget {
+    view: wgpu::TextureView,
+    resolve: MsaaResolve,
+}
+
+impl MsaaTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        resolve: MsaaResolve,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("msaa::target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { view, resolve }
+    }
+
+    /// The view to render into.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// The `resolve_target` for a pass rendering into [`view`](Self::view)
+    /// on its way to `output`: `output` itself, unless resolving explicitly.
+    pub fn resolve_target<'a>(
+        &self,
+        output: &'a wgpu::TextureView,
+    ) -> Option<&'a wgpu::TextureView> {
+        (self.resolve == MsaaResolve::Implicit).then_some(output)
+    }
+
+    /// What a pass rendering into [`view`](Self::view) does with the
+    /// samples: they are kept only for an explicit resolve.
+    pub fn store_op(&self) -> wgpu::StoreOp {
+        match self.resolve {
+            MsaaResolve::Implicit => wgpu::StoreOp::Discard,
+            MsaaResolve::Explicit => wgpu::StoreOp::Store,
+        }
+    }
+
+    /// Resolves the samples into `output`, a single-sampled view of the
+    /// target's format, with an explicit resolve. Does nothing otherwise,
+    /// as the passes drawing into the target already resolved it.
+    pub fn resolve(&self, frame: &mut FrameEncoder, output: &wgpu::TextureView) {
+        if self.resolve == MsaaResolve::Implicit {
+            return;
+        }
+        // A pass that only loads the samples and resolves them.
+        frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("msaa::resolve_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: Some(output),
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Discard,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{compare, test_gpu, TestRenderer};
+
+    const SIZE: u32 = 32;
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    const SAMPLES: u32 = 4;
+    const TRIANGLE: &str = "
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            var corners = array(vec2(-1.0, 0.9), vec2(-1.0, -1.0), vec2(0.7, -1.0));
+            return vec4<f32>(corners[index], 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    /// Draws a white triangle with a slanted edge over black and resolves it.
+    fn draw_edge(renderer: &TestRenderer, resolve: MsaaResolve) -> image::RgbaImage {
+        let device = &renderer.gpu.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(TRIANGLE.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: SAMPLES,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+        let msaa = MsaaTarget::new(device, FORMAT, SIZE, SIZE, SAMPLES, resolve);
+        renderer.render(|frame, view| {
+            {
+                let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: msaa.view(),
+                        resolve_target: msaa.resolve_target(view),
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: msaa.store_op(),
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                render_pass.set_pipeline(&pipeline);
+                render_pass.draw(0..3, 0..1);
+            }
+            msaa.resolve(frame, view);
+        })
+    }
+
+    #[test]
+    fn explicit_resolve_matches_the_implicit_one() {
+        let renderer = TestRenderer::new(test_gpu!(), SIZE, SIZE, FORMAT);
+        let implicit = draw_edge(&renderer, MsaaResolve::Implicit);
+        let explicit = draw_edge(&renderer, MsaaResolve::Explicit);
+        // The edge is antialiased, not just drawn.
+        assert!(implicit.pixels().any(|pixel| (1..255).contains(&pixel[0])));
+        eprintln!(
+            "{:?} {:?} {:?} {:?}",
+            implicit.get_pixel(0, 31),
+            explicit.get_pixel(0, 31),
+            implicit.get_pixel(31, 0),
+            explicit.get_pixel(31, 0)
+        );
+        if let Err(mismatch) = compare(&explicit, &implicit, 0) {
+            panic!("{mismatch}");
+        }
+    }
+
+    #[test]
+    fn resolve_modes_parse_their_names() {
+        for resolve in [MsaaResolve::Implicit, MsaaResolve::Explicit] {
+            assert_eq!(resolve.to_string().parse(), Ok(resolve));
+        }
+        assert!("manual".parse::<MsaaResolve>().is_err());
+    }
+}
@@ -0,0 +1,451 @@
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("blit.wgsl");
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutputUniform {
+    max_value: f32,
+}
+
+/// Default format of the intermediate target.
+pub const DEFAULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// An intermediate render target the scene is drawn into, then blitted to
+/// the output with a fullscreen pass.
+///
+/// The output may be larger than the target, e.g. for a scene drawn at a
+/// render scale below 1; formats that can be filtered are then upscaled
+/// bilinearly.
+///
+/// Having the frame in a sampleable texture is what post-processing passes
+/// build on; with an `Rgba16Float` target it also keeps blending in linear
+/// space at full precision regardless of the surface.
+pub struct OffscreenTarget {
+    format: wgpu::TextureFormat,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    output_uniform: UniformBuffer<OutputUniform>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl OffscreenTarget {
+    /// The blit clips colors to the SDR range until
+    /// [`set_max_output_value`](Self::set_max_output_value) raises the limit.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Output",
+            &crate::struct_layout!(OutputUniform { max_value }),
+        );
+        let filterable = format
+            .guaranteed_format_features(device.features())
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: label!("offscreen::bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // Filterable only where the format is, so float formats
+                    // that can't be filtered work too, upscaled by nearest.
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(if filterable {
+                        wgpu::SamplerBindingType::Filtering
+                    } else {
+                        wgpu::SamplerBindingType::NonFiltering
+                    }),
+                    count: None,
+                },
+            ],
+        });
+        let filter = if filterable {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: label!("offscreen::sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        let (texture, view, bind_group) =
+            create_texture(device, &bind_group_layout, &sampler, format, width, height);
+        let output_uniform = UniformBuffer::new(
+            device,
+            label!("offscreen::output_uniform"),
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        output_uniform.update(queue, &OutputUniform { max_value: 1.0 });
+        let shader = create_shader(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("offscreen::pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout, output_uniform.layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format, output_format);
+
+        Self {
+            format,
+            texture,
+            view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            output_uniform,
+            shader,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Format of the intermediate target, which the scene pass renders in.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Width and height in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        [self.texture.width(), self.texture.height()]
+    }
+
+    /// The view to render the scene into.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Recreates the intermediate target at the new size; call whenever the
+    /// surface is resized.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.texture.width() == width && self.texture.height() == height {
+            return;
+        }
+        (self.texture, self.view, self.bind_group) = create_texture(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            self.format,
+            width,
+            height,
+        );
+    }
+
+    pub fn set_output_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            create_pipeline(device, shader, &self.pipeline_layout, self.format, format)
+        });
+    }
+
+    /// Sets the largest color value written to the output, relative to SDR
+    /// white; see [`max_output_value`](crate::surface::max_output_value).
+    /// Brighter values are clipped to it.
+    pub fn set_max_output_value(&self, queue: &wgpu::Queue, max_value: f32) {
+        self.output_uniform
+            .update(queue, &OutputUniform { max_value });
+    }
+
+    /// Draws the intermediate target over all of `output`, which must have
+    /// the output format given at creation.
+    pub fn blit(&self, frame: &mut FrameEncoder, output: &wgpu::TextureView) {
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("offscreen::blit_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, self.output_uniform.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::TextureView, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: label!("offscreen::target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("offscreen::bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    (texture, view, bind_group)
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("offscreen::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    output_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let linear_input = is_linear_output(format);
+    let linear_output = is_linear_output(output_format);
+    let flag = |set: bool| if set { 1.0 } else { 0.0 };
+    let mut constants = ShaderConstants::default();
+    constants.set("ENCODE_SRGB", flag(linear_input && !linear_output));
+    constants.set("DECODE_SRGB", flag(!linear_input && linear_output));
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("offscreen::pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format: output_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::read_texture_rgba;
+    use crate::context::GpuContext;
+    use crate::linear::WORKING_FORMAT;
+    use crate::test_support::{test_gpu, TestRenderer};
+
+    /// Covers the target in 50% transparent green.
+    const OVERLAY_SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 0.5);
+}
+"#;
+
+    fn overlay_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        blend: Option<wgpu::BlendState>,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: label!("offscreen::test_shader"),
+            source: wgpu::ShaderSource::Wgsl(OVERLAY_SHADER.into()),
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: label!("offscreen::test_pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    #[test]
+    fn smaller_target_is_upscaled_over_the_output() {
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = TestRenderer::new(test_gpu!(), 8, 8, format);
+        let (device, queue) = (&renderer.gpu.device, &renderer.gpu.queue);
+        let offscreen = OffscreenTarget::new(device, queue, 2, 1, format, format);
+        let pipeline = overlay_pipeline(device, format, None);
+        let image = renderer.render(|frame, view| {
+            {
+                // Red, with the right texel overwritten in green.
+                let mut pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: label!("offscreen::test_pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: offscreen.view(),
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&pipeline);
+                pass.set_viewport(1.0, 0.0, 1.0, 1.0, 0.0, 1.0);
+                pass.draw(0..3, 0..1);
+            }
+            offscreen.blit(frame, view);
+        });
+        for y in 0..8 {
+            assert_eq!(image.get_pixel(0, y).0, [255, 0, 0, 255]);
+            assert_eq!(image.get_pixel(7, y).0, [0, 255, 0, 128]);
+            // Filtered between the two texels, closer to the red one.
+            let [r, g, ..] = image.get_pixel(3, y).0;
+            assert!(r > g && g > 0, "{:?}", image.get_pixel(3, y));
+        }
+    }
+
+    #[test]
+    fn blending_happens_in_linear_space() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        let (device, queue) = (&device, &queue);
+        let output_format = wgpu::TextureFormat::Rgba8Unorm;
+        let offscreen = OffscreenTarget::new(device, queue, 4, 4, WORKING_FORMAT, output_format);
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("offscreen::test_output"),
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: output_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let pipeline = overlay_pipeline(
+            device,
+            WORKING_FORMAT,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+        );
+
+        let mut frame = FrameEncoder::new(device, queue, label!("offscreen::test_encoder"));
+        {
+            let mut pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: label!("offscreen::test_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: offscreen.view(),
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.draw(0..3, 0..1);
+        }
+        offscreen.blit(
+            &mut frame,
+            &output.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        frame.submit();
+
+        // Linear 0.5 red + 0.5 green, sRGB encoded. Blending the encoded
+        // values instead would give 128.
+        let pixels = read_texture_rgba(device, queue, &output).unwrap();
+        for pixel in pixels.chunks_exact(4) {
+            assert!(pixel[0].abs_diff(188) <= 1, "{pixel:?}");
+            assert!(pixel[1].abs_diff(188) <= 1, "{pixel:?}");
+            assert_eq!(pixel[2], 0);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+const CORRECTION_GAIN: f64 = 0.1;
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+
+/// Software frame pacer.
+///
+/// wgpu doesn't expose present timing, so the pacer measures the interval
+/// between consecutive presents and schedules the next redraw a little early
+/// when wake-ups consistently overshoot the target, smoothing `WaitUntil`
+/// deadlines instead of redrawing as fast as possible.
+pub struct FramePacer {
+    target_interval: Duration,
+    last_present: Option<Instant>,
+    correction: f64,
+    stats: PacingStats,
+    stats_start: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PacingStats {
+    frames: u32,
+    sum: f64,
+    sum_sq: f64,
+    min: f64,
+    max: f64,
+}
+
+impl PacingStats {
+    fn record(&mut self, interval: f64) {
+        if self.frames == 0 {
+            self.min = interval;
+            self.max = interval;
+        } else {
+            self.min = self.min.min(interval);
+            self.max = self.max.max(interval);
+        }
+        self.frames += 1;
+        self.sum += interval;
+        self.sum_sq += interval * interval;
+    }
+}
+
+impl FramePacer {
+    pub fn new(target_interval: Duration) -> Self {
+        Self {
+            target_interval,
+            last_present: None,
+            correction: 0.0,
+            stats: PacingStats::default(),
+            stats_start: None,
+        }
+    }
+
+    /// Builds a pacer targeting the refresh rate of the given monitor, or 60 Hz when unknown.
+    pub fn for_monitor(monitor: Option<winit::monitor::MonitorHandle>) -> Self {
+        let millihertz = monitor
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .unwrap_or(60_000);
+        Self::new(Duration::from_secs_f64(1000.0 / millihertz as f64))
+    }
+
+    pub fn target_interval(&self) -> Duration {
+        self.target_interval
+    }
+
+    pub fn set_target_interval(&mut self, target_interval: Duration) {
+        self.target_interval = target_interval;
+        self.correction = 0.0;
+    }
+
+    pub fn last_present(&self) -> Option<Instant> {
+        self.last_present
+    }
+
+    /// Records that a frame was presented at `now`.
+    pub fn frame_presented(&mut self, now: Instant) {
+        if let Some(last) = self.last_present {
+            let interval = now.duration_since(last).as_secs_f64();
+            let target = self.target_interval.as_secs_f64();
+            self.correction =
+                (self.correction + CORRECTION_GAIN * (interval - target)).clamp(0.0, target);
+            self.stats.record(interval);
+        }
+        self.last_present = Some(now);
+        self.log_stats_if_due(now);
+    }
+
+    /// When the next frame should start, or `None` before the first present.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let wait = self.target_interval.as_secs_f64() - self.correction;
+        self.last_present
+            .map(|last| last + Duration::from_secs_f64(wait.max(0.0)))
+    }
+
+    fn log_stats_if_due(&mut self, now: Instant) {
+        let start = *self.stats_start.get_or_insert(now);
+        if now.duration_since(start) < STATS_PERIOD || self.stats.frames == 0 {
+            return;
+        }
+        let stats = std::mem::take(&mut self.stats);
+        let mean = stats.sum / stats.frames as f64;
+        let jitter = (stats.sum_sq / stats.frames as f64 - mean * mean)
+            .max(0.0)
+            .sqrt();
+        log::info!(
+            "frame pacing: {} frames, mean {:.2}ms (target {:.2}ms), min {:.2}ms, max {:.2}ms, jitter {:.2}ms",
+            stats.frames,
+            mean * 1000.0,
+            self.target_interval.as_secs_f64() * 1000.0,
+            stats.min * 1000.0,
+            stats.max * 1000.0,
+            jitter * 1000.0,
+        );
+        self.stats_start = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn no_deadline_before_the_first_present() {
+        assert_eq!(FramePacer::new(ms(16)).next_deadline(), None);
+    }
+
+    #[test]
+    fn on_time_presents_wait_the_full_interval() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(ms(16));
+        pacer.frame_presented(start);
+        pacer.frame_presented(start + ms(16));
+        assert_eq!(pacer.next_deadline(), Some(start + ms(32)));
+    }
+
+    #[test]
+    fn overshooting_presents_move_the_deadline_earlier() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(ms(16));
+        pacer.frame_presented(start);
+        // A 26ms interval overshoots by 10ms; a tenth of that is corrected.
+        pacer.frame_presented(start + ms(26));
+        assert_eq!(pacer.next_deadline(), Some(start + ms(41)));
+    }
+
+    #[test]
+    fn correction_never_goes_negative_or_past_the_interval() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(ms(10));
+        pacer.frame_presented(start);
+        pacer.frame_presented(start + ms(1));
+        assert_eq!(pacer.next_deadline(), Some(start + ms(11)));
+
+        let mut now = start + ms(1);
+        for _ in 0..100 {
+            now += ms(1000);
+            pacer.frame_presented(now);
+        }
+        assert_eq!(pacer.next_deadline(), Some(now));
+    }
+
+    #[test]
+    fn retargeting_clears_the_correction() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(ms(16));
+        pacer.frame_presented(start);
+        pacer.frame_presented(start + ms(26));
+        pacer.set_target_interval(ms(8));
+        assert_eq!(pacer.target_interval(), ms(8));
+        assert_eq!(pacer.next_deadline(), Some(start + ms(34)));
+    }
+}
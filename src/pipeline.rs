@@ -0,0 +1,337 @@
+use std::borrow::Cow;
+
+use crate::label;
+use crate::shader::ShaderConstants;
+use crate::stencil::StencilError;
+use crate::surface::is_linear_output;
+use crate::texture::{DEPTH_FORMAT, MAX_LOD_BIAS};
+
+const SHADER: &str = include_str!("shader.wgsl");
+
+/// The render pipeline for the scene geometry, built from `shader.wgsl`.
+///
+/// Colors are encoded to sRGB in the shader when the target format doesn't
+/// do it in hardware, e.g. when drawing straight to a `Bgra8Unorm` surface
+/// without the offscreen target.
+pub struct ScenePipeline {
+    source: Cow<'static, str>,
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    target: Target,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// What a [`ScenePipeline`] renders into and how.
+#[derive(Clone)]
+struct Target {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_bias: wgpu::DepthBiasState,
+    depth_format: wgpu::TextureFormat,
+    stencil: wgpu::StencilState,
+    lod_bias: f32,
+    write_mask: wgpu::ColorWrites,
+}
+
+impl ScenePipeline {
+    /// `bind_group_layouts` are the camera at group 0, the diffuse texture
+    /// at group 1 and the [`InstanceBuffer`](crate::instance::InstanceBuffer)
+    /// material table at group 2;
+    /// `vertex_layouts` are the buffers bound at slots 0, 1, ..., usually
+    /// from [`Vertex::layout`](crate::vertex::Vertex::layout): the mesh
+    /// vertices, then [`InstanceRaw`](crate::instance::InstanceRaw).
+    ///
+    /// With no `vertex_layouts` the pipeline pulls its vertices instead,
+    /// using `vs_pulled`: group 2 is the instance buffer's pulling layout,
+    /// with the instances next to the materials, and group 3 a
+    /// [`pulling`](crate::pulling) layout for the mesh vertices.
+    ///
+    /// `depth_bias` offsets the depth the geometry writes, e.g. to push it
+    /// behind lines drawn on its surface.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_bias: wgpu::DepthBiasState,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Camera",
+            &crate::struct_layout!(crate::camera::CameraUniform { view_proj }),
+        );
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Material",
+            &crate::struct_layout!(crate::instance::MaterialProperties { tint }),
+        );
+        let source = Cow::Borrowed(SHADER);
+        let shader = create_shader(device, &source);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("scene::pipeline_layout"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let target = Target {
+            format,
+            sample_count,
+            depth_bias,
+            depth_format: DEPTH_FORMAT,
+            stencil: wgpu::StencilState::default(),
+            lod_bias: 0.0,
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+        let pipeline = create_pipeline(device, &source, &shader, &layout, &vertex_layouts, &target);
+        Self {
+            source,
+            shader,
+            layout,
+            vertex_layouts,
+            target,
+            pipeline,
+        }
+    }
+
+    /// Rebuilds the pipeline for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.target.format = format;
+        self.rebuild_shader(device);
+    }
+
+    /// Shifts the mip level every texture is sampled at by `lod_bias`,
+    /// clamped to [`MAX_LOD_BIAS`] either way: negative sharpens textures
+    /// in the distance, positive blurs them.
+    pub fn set_lod_bias(&mut self, device: &wgpu::Device, lod_bias: f32) {
+        self.target.lod_bias = lod_bias.clamp(-MAX_LOD_BIAS, MAX_LOD_BIAS);
+        self.rebuild_shader(device);
+    }
+
+    /// The mip level shift set by [`set_lod_bias`](Self::set_lod_bias).
+    pub fn lod_bias(&self) -> f32 {
+        self.target.lod_bias
+    }
+
+    /// Rebuilds the pipeline for changed override constants.
+    fn rebuild_shader(&mut self, device: &wgpu::Device) {
+        self.pipeline = rebuild_pipeline(
+            device,
+            &mut self.shader,
+            |device| create_shader(device, &self.source),
+            |shader| {
+                create_pipeline(
+                    device,
+                    &self.source,
+                    shader,
+                    &self.layout,
+                    &self.vertex_layouts,
+                    &self.target,
+                )
+            },
+        );
+    }
+
+    /// Rebuilds the pipeline for a new MSAA sample count.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.target.sample_count = sample_count;
+        self.rebuild(device);
+    }
+
+    /// Rebuilds the pipeline with a new depth bias.
+    pub fn set_depth_bias(&mut self, device: &wgpu::Device, depth_bias: wgpu::DepthBiasState) {
+        if depth_bias == self.target.depth_bias {
+            return;
+        }
+        self.target.depth_bias = depth_bias;
+        self.rebuild(device);
+    }
+
+    /// Rebuilds the pipeline to write only the channels in `write_mask`,
+    /// e.g. one channel of a G-buffer; the others keep what the target held.
+    /// New pipelines write [`ColorWrites::ALL`](wgpu::ColorWrites::ALL).
+    pub fn set_write_mask(&mut self, device: &wgpu::Device, write_mask: wgpu::ColorWrites) {
+        if write_mask == self.target.write_mask {
+            return;
+        }
+        self.target.write_mask = write_mask;
+        self.rebuild(device);
+    }
+
+    /// Rebuilds the pipeline for a depth buffer of `depth_format` with the
+    /// stencil test and writes in `stencil`, e.g. to draw only inside a mask
+    /// an earlier pass left in a
+    /// [`DEPTH_STENCIL_FORMAT`](crate::stencil::DEPTH_STENCIL_FORMAT) buffer.
+    /// New pipelines use [`DEPTH_FORMAT`] without stencil.
+    ///
+    /// Fails, keeping the pipeline, if `stencil` is enabled but
+    /// `depth_format` has no stencil aspect.
+    pub fn set_depth_stencil(
+        &mut self,
+        device: &wgpu::Device,
+        depth_format: wgpu::TextureFormat,
+        stencil: wgpu::StencilState,
+    ) -> Result<(), StencilError> {
+        if stencil.is_enabled() {
+            crate::stencil::check_format(depth_format)?;
+        }
+        if (depth_format, &stencil) == (self.target.depth_format, &self.target.stencil) {
+            return Ok(());
+        }
+        self.target.depth_format = depth_format;
+        self.target.stencil = stencil;
+        self.rebuild(device);
+        Ok(())
+    }
+
+    fn rebuild(&mut self, device: &wgpu::Device) {
+        self.pipeline = create_pipeline(
+            device,
+            &self.source,
+            &self.shader,
+            &self.layout,
+            &self.vertex_layouts,
+            &self.target,
+        );
+    }
+
+    /// Swaps in a shader compiled from `source`, read from `path`.
+    ///
+    /// On a compile or validation error the current pipeline is kept and the
+    /// error, with the file and line where naga can tell, is returned.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        path: &std::path::Path,
+        source: &str,
+    ) -> Result<(), String> {
+        crate::shader::validate(source, path)?;
+        // Catches what naga accepts but the device doesn't, like a changed
+        // binding that no longer matches the pipeline layout.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = create_shader(device, source);
+        let pipeline = create_pipeline(
+            device,
+            source,
+            &shader,
+            &self.layout,
+            &self.vertex_layouts,
+            &self.target,
+        );
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(format!("{}: {error}", path.display()));
+        }
+        self.source = Cow::Owned(source.to_owned());
+        self.shader = shader;
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+
+    /// Color format the pipeline renders to.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.target.format
+    }
+}
+
+/// Replaces `shader` with a fresh module from `create_shader` and builds a
+/// pipeline from it, for when a pipeline's override constants change, e.g.
+/// with its target format.
+///
+/// The GL backend caches linked programs per shader module without regard
+/// to override values, so a pipeline built from the old module could keep
+/// the old constants there.
+pub fn rebuild_pipeline(
+    device: &wgpu::Device,
+    shader: &mut wgpu::ShaderModule,
+    create_shader: impl FnOnce(&wgpu::Device) -> wgpu::ShaderModule,
+    create_pipeline: impl FnOnce(&wgpu::ShaderModule) -> wgpu::RenderPipeline,
+) -> wgpu::RenderPipeline {
+    *shader = create_shader(device);
+    create_pipeline(shader)
+}
+
+fn create_shader(device: &wgpu::Device, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("scene::shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    source: &str,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    vertex_layouts: &[wgpu::VertexBufferLayout<'static>],
+    target: &Target,
+) -> wgpu::RenderPipeline {
+    let Target {
+        format,
+        sample_count,
+        depth_bias,
+        depth_format,
+        stencil,
+        lod_bias,
+        write_mask,
+    } = target.clone();
+    let mut constants = ShaderConstants::default();
+    constants.set(
+        "ENCODE_SRGB",
+        if is_linear_output(format) { 0.0 } else { 1.0 },
+    );
+    constants.set("LOD_BIAS", lod_bias.into());
+    let constants = constants.validated(source);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("scene::pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some(if vertex_layouts.is_empty() {
+                "vs_pulled"
+            } else {
+                "vs_main"
+            }),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil,
+            bias: depth_bias,
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
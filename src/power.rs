@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// Reads the current power source.
+///
+/// Only Linux is supported, by reading `/sys/class/power_supply`; other
+/// platforms, and Linux systems without a readable power supply, always
+/// report AC power.
+pub fn current_power_source() -> PowerSource {
+    #[cfg(target_os = "linux")]
+    if let Some(source) = linux_power_source() {
+        return source;
+    }
+    PowerSource::Ac
+}
+
+/// AC when a mains supply is online, battery when a battery is discharging.
+#[cfg(target_os = "linux")]
+fn linux_power_source() -> Option<PowerSource> {
+    let read = |path: std::path::PathBuf| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_owned())
+    };
+    let mut discharging = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        match read(path.join("type")).as_deref() {
+            Some("Mains") if read(path.join("online")).as_deref() == Some("1") => {
+                return Some(PowerSource::Ac);
+            }
+            Some("Battery") if read(path.join("status")).as_deref() == Some("Discharging") => {
+                discharging = true;
+            }
+            _ => {}
+        }
+    }
+    discharging.then_some(PowerSource::Battery)
+}
+
+/// Polls the power source periodically and reports changes.
+pub struct PowerMonitor {
+    source: PowerSource,
+    last_poll: Instant,
+}
+
+impl Default for PowerMonitor {
+    fn default() -> Self {
+        Self {
+            source: current_power_source(),
+            last_poll: Instant::now(),
+        }
+    }
+}
+
+impl PowerMonitor {
+    pub fn source(&self) -> PowerSource {
+        self.source
+    }
+
+    /// Re-reads the power source if a poll is due, returning it when it changed.
+    pub fn poll(&mut self, now: Instant) -> Option<PowerSource> {
+        if now.duration_since(self.last_poll) < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = now;
+        let source = current_power_source();
+        if source == self.source {
+            return None;
+        }
+        self.source = source;
+        Some(source)
+    }
+}
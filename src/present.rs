@@ -0,0 +1,29 @@
+use wgpu::PresentMode;
+
+/// Picks the default present mode from the surface capabilities.
+///
+/// `FifoRelaxed` is preferred over plain `Fifo`: it still caps the framerate
+/// at the refresh rate but presents immediately when a frame misses vsync
+/// instead of waiting a whole extra interval. `Fifo` is always supported,
+/// and is what safe mode uses.
+pub fn default_present_mode(available: &[PresentMode], safe_mode: bool) -> PresentMode {
+    let mode = vsync_present_mode(available, safe_mode);
+    if safe_mode {
+        log::info!("present mode: Fifo (safe mode)");
+    } else if mode == PresentMode::FifoRelaxed {
+        log::info!("present mode: FifoRelaxed");
+    } else {
+        log::info!("present mode: Fifo (FifoRelaxed unsupported)");
+    }
+    mode
+}
+
+/// The vsync mode [`default_present_mode`] picks, without logging it; for
+/// turning vsync back on.
+pub fn vsync_present_mode(available: &[PresentMode], safe_mode: bool) -> PresentMode {
+    if !safe_mode && available.contains(&PresentMode::FifoRelaxed) {
+        PresentMode::FifoRelaxed
+    } else {
+        PresentMode::Fifo
+    }
+}
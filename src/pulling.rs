@@ -0,0 +1,61 @@
+use crate::label;
+
+/// Storage buffers a pulling vertex shader binds: the mesh vertices and the
+/// instances.
+const REQUIRED_STORAGE_BUFFERS: u32 = 2;
+
+/// Whether `adapter` can do vertex pulling, where the vertex shader reads
+/// mesh vertices and instances from storage buffers by `vertex_index` and
+/// `instance_index` instead of from vertex buffers. WebGL2, for one, has no
+/// storage buffers in the vertex stage.
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+        && adapter.limits().max_storage_buffers_per_shader_stage >= REQUIRED_STORAGE_BUFFERS
+}
+
+/// `STORAGE` if buffers on `device` can be pulled from, so meshes and
+/// instances are created bindable either way; otherwise empty.
+pub fn storage_usage(device: &wgpu::Device) -> wgpu::BufferUsages {
+    if device.limits().max_storage_buffers_per_shader_stage >= REQUIRED_STORAGE_BUFFERS {
+        wgpu::BufferUsages::STORAGE
+    } else {
+        wgpu::BufferUsages::empty()
+    }
+}
+
+/// Layout with one read-only storage buffer at binding 0, visible to the
+/// vertex stage.
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: label!("pulling::bind_group_layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
+/// Binds `buffer` for pulling, or `None` if it wasn't created with
+/// [`storage_usage`] on a device that supports it.
+pub fn bind_group(device: &wgpu::Device, buffer: &wgpu::Buffer) -> Option<wgpu::BindGroup> {
+    if !buffer.usage().contains(wgpu::BufferUsages::STORAGE) {
+        return None;
+    }
+    Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: label!("pulling::bind_group"),
+        layout: &bind_group_layout(device),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    }))
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use web_time::Instant;
+
+struct Entry {
+    window_start: Instant,
+    suppressed: u32,
+}
+
+/// Logs each distinct message at most once per period.
+///
+/// Repeats within the period are counted and reported as a single
+/// "N occurrences of X" line once the period has passed.
+pub struct RateLimitedLogger {
+    period: Duration,
+    entries: HashMap<String, Entry>,
+}
+
+impl Default for RateLimitedLogger {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl RateLimitedLogger {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn log(&mut self, level: log::Level, message: &str) {
+        if let Some(line) = self.line(message, Instant::now()) {
+            log::log!(level, "{line}");
+        }
+    }
+
+    /// The line to log for `message` at `now`, or `None` if it's suppressed.
+    fn line(&mut self, message: &str, now: Instant) -> Option<String> {
+        let Some(entry) = self.entries.get_mut(message) else {
+            self.entries.insert(
+                message.to_owned(),
+                Entry {
+                    window_start: now,
+                    suppressed: 0,
+                },
+            );
+            return Some(message.to_owned());
+        };
+
+        let elapsed = now.duration_since(entry.window_start);
+        if elapsed < self.period {
+            entry.suppressed += 1;
+            return None;
+        }
+        let line = if entry.suppressed > 0 {
+            format!(
+                "{} occurrences of \"{message}\" in the last {:.1}s",
+                entry.suppressed + 1,
+                elapsed.as_secs_f32()
+            )
+        } else {
+            message.to_owned()
+        };
+        entry.window_start = now;
+        entry.suppressed = 0;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn repeats_within_the_period_are_suppressed() {
+        let start = Instant::now();
+        let mut logger = RateLimitedLogger::new(ms(1000));
+        assert_eq!(logger.line("lost", start).as_deref(), Some("lost"));
+        assert_eq!(logger.line("lost", start + ms(10)), None);
+        assert_eq!(logger.line("lost", start + ms(999)), None);
+        // Other messages have periods of their own.
+        assert_eq!(
+            logger.line("timeout", start + ms(500)).as_deref(),
+            Some("timeout")
+        );
+    }
+
+    #[test]
+    fn suppressed_repeats_are_summarized_after_the_period() {
+        let start = Instant::now();
+        let mut logger = RateLimitedLogger::new(ms(1000));
+        logger.line("lost", start);
+        logger.line("lost", start + ms(100));
+        logger.line("lost", start + ms(200));
+        assert_eq!(
+            logger.line("lost", start + ms(1500)).as_deref(),
+            Some("3 occurrences of \"lost\" in the last 1.5s")
+        );
+        // The summary starts a new period, and after a quiet one the
+        // message is logged as is.
+        assert_eq!(logger.line("lost", start + ms(2000)), None);
+        assert_eq!(
+            logger.line("lost", start + ms(2500)).as_deref(),
+            Some("2 occurrences of \"lost\" in the last 1.0s")
+        );
+        assert_eq!(
+            logger.line("lost", start + ms(4000)).as_deref(),
+            Some("lost")
+        );
+    }
+}
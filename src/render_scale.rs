@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+/// Range render scales are clamped to: from a quarter of the output's
+/// resolution in each direction up to the full resolution.
+pub const RENDER_SCALE_RANGE: (f32, f32) = (0.25, 1.0);
+
+/// Clamps `scale` to [`RENDER_SCALE_RANGE`], taking NaN as full resolution.
+pub fn clamp(scale: f32) -> f32 {
+    if scale.is_nan() {
+        return RENDER_SCALE_RANGE.1;
+    }
+    scale.clamp(RENDER_SCALE_RANGE.0, RENDER_SCALE_RANGE.1)
+}
+
+/// Size of the scene's targets at `scale` of a `width` by `height` output,
+/// rounded and at least 1x1.
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> [u32; 2] {
+    let scale = |size: u32| ((size as f32 * scale).round() as u32).clamp(1, size.max(1));
+    [scale(width), scale(height)]
+}
+
+/// How often [`AdaptiveRenderScale`] nudges the scale.
+pub const ADJUST_INTERVAL: Duration = Duration::from_secs(1);
+/// How far one nudge moves the scale.
+pub const ADJUST_STEP: f32 = 0.05;
+/// Fraction of the budget the GPU time is predicted to stay under at the
+/// next scale up. Between that and the budget the scale is left alone, so
+/// it doesn't bounce between two steps.
+const HEADROOM: f32 = 0.85;
+
+/// Adapts the render scale to a GPU frame time budget: once per
+/// [`ADJUST_INTERVAL`] it steps the scale down while the GPU time is over the
+/// budget, and back up while it would stay comfortably under it at the next
+/// step.
+#[derive(Debug, Clone)]
+pub struct AdaptiveRenderScale {
+    budget: Duration,
+    /// Lowest and highest scale it adjusts to, within [`RENDER_SCALE_RANGE`].
+    bounds: (f32, f32),
+    last_adjust: Option<Instant>,
+}
+
+impl AdaptiveRenderScale {
+    pub fn new(budget: Duration, (min, max): (f32, f32)) -> Self {
+        let (min, max) = (clamp(min), clamp(max));
+        Self {
+            budget,
+            bounds: (min.min(max), max),
+            last_adjust: None,
+        }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// The scale to switch to from `scale`, given the average GPU frame time
+    /// at it, or `None` to keep it. The first call only starts the interval.
+    pub fn update(&mut self, now: Instant, gpu_time: Duration, scale: f32) -> Option<f32> {
+        let last = self.last_adjust.get_or_insert(now);
+        if now.duration_since(*last) < ADJUST_INTERVAL {
+            return None;
+        }
+        *last = now;
+        let (min, max) = self.bounds;
+        let next = if gpu_time > self.budget {
+            (scale - ADJUST_STEP).max(min)
+        } else {
+            // GPU time grows about with the pixel count, so with the square
+            // of the scale.
+            let up = (scale + ADJUST_STEP).min(max);
+            let predicted = gpu_time.mul_f32((up / scale).powi(2));
+            if predicted > self.budget.mul_f32(HEADROOM) {
+                return None;
+            }
+            up
+        };
+        (next != scale).then_some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn scaled_sizes_round_and_stay_in_bounds() {
+        assert_eq!(scaled_size(1280, 720, 1.0), [1280, 720]);
+        assert_eq!(scaled_size(1280, 720, 0.5), [640, 360]);
+        assert_eq!(scaled_size(1001, 3, 0.5), [501, 2]);
+        assert_eq!(scaled_size(1, 1, 0.25), [1, 1]);
+        assert_eq!(clamp(0.1), RENDER_SCALE_RANGE.0);
+        assert_eq!(clamp(2.0), 1.0);
+        assert_eq!(clamp(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn adaptive_scale_steps_once_per_interval_toward_the_budget() {
+        let mut adaptive = AdaptiveRenderScale::new(ms(16), (0.5, 1.0));
+        let start = Instant::now();
+        assert_eq!(adaptive.update(start, ms(30), 1.0), None);
+        assert_eq!(adaptive.update(start + ms(500), ms(30), 1.0), None);
+        assert_eq!(adaptive.update(start + ms(1000), ms(30), 1.0), Some(0.95));
+        assert_eq!(adaptive.update(start + ms(1500), ms(30), 0.95), None);
+
+        // Steps down to the lower bound, and no further.
+        let mut scale = 0.95;
+        let mut now = start + ms(1000);
+        let mut settle = |gpu_time, scale: &mut f32| {
+            for _ in 0..20 {
+                now += ADJUST_INTERVAL;
+                if let Some(next) = adaptive.update(now, gpu_time, *scale) {
+                    *scale = next;
+                }
+            }
+        };
+        settle(ms(30), &mut scale);
+        assert_eq!(scale, 0.5);
+
+        // Well under budget, back up to full resolution.
+        settle(ms(4), &mut scale);
+        assert!((scale - 1.0).abs() < 1e-6, "{scale}");
+    }
+
+    #[test]
+    fn adaptive_scale_holds_just_under_the_budget() {
+        let mut adaptive = AdaptiveRenderScale::new(ms(16), (0.25, 1.0));
+        let start = Instant::now();
+        adaptive.update(start, ms(15), 0.5);
+        // Under budget, but a step up to 0.55 would predict about 18ms.
+        assert_eq!(adaptive.update(start + ADJUST_INTERVAL, ms(15), 0.5), None);
+        // Comfortably under: 0.55 predicts about 12ms.
+        assert_eq!(
+            adaptive.update(start + ADJUST_INTERVAL * 2, ms(10), 0.5),
+            Some(0.55)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, KeyEvent, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+
+/// The subset of window input the app reacts to, in a serializable form.
+///
+/// Window state such as resizes is not included, since replaying it against
+/// a differently sized window would desync the surface.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Modifiers(ModifiersState),
+    Key {
+        code: KeyCode,
+        pressed: bool,
+        repeat: bool,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    CursorMoved(PhysicalPosition<f64>),
+    Focused(bool),
+}
+
+impl InputEvent {
+    /// Returns `None` for events that aren't input or can't be serialized,
+    /// such as keys without a platform-independent code.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => Some(Self::Modifiers(modifiers.state())),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => Some(Self::Key {
+                code: *code,
+                pressed: *state == ElementState::Pressed,
+                repeat: *repeat,
+            }),
+            WindowEvent::MouseInput { state, button, .. } => Some(Self::MouseButton {
+                button: *button,
+                pressed: state.is_pressed(),
+            }),
+            WindowEvent::CursorMoved { position, .. } => Some(Self::CursorMoved(*position)),
+            WindowEvent::Focused(focused) => Some(Self::Focused(*focused)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedEvent {
+    /// Seconds since recording started.
+    time: f64,
+    event: InputEvent,
+}
+
+/// Records input events with timestamps, saved as one JSON object per line.
+#[derive(Debug)]
+pub struct InputRecorder {
+    path: PathBuf,
+    start: Option<Instant>,
+    events: Vec<RecordedEvent>,
+}
+
+impl InputRecorder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            start: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sets the time zero of the recording, normally when the app starts.
+    pub fn start(&mut self, now: Instant) {
+        self.start.get_or_insert(now);
+    }
+
+    pub fn record(&mut self, now: Instant, event: InputEvent) {
+        let start = *self.start.get_or_insert(now);
+        self.events.push(RecordedEvent {
+            time: now.duration_since(start).as_secs_f64(),
+            event,
+        });
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+}
+
+/// Feeds recorded input back at the cadence it was recorded.
+#[derive(Debug)]
+pub struct InputPlayer {
+    start: Option<Instant>,
+    events: VecDeque<RecordedEvent>,
+}
+
+impl InputPlayer {
+    /// Loads a recording, skipping lines that don't parse (e.g. events from a
+    /// newer version) with a warning.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut events = VecDeque::new();
+        for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(event) => events.push_back(event),
+                Err(e) => log::warn!("skipping {}:{}: {e}", path.display(), index + 1),
+            }
+        }
+        Ok(Self {
+            start: None,
+            events,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Sets the time zero of the playback, normally when the app starts.
+    pub fn start(&mut self, now: Instant) {
+        self.start.get_or_insert(now);
+    }
+
+    /// When the next event is due, if playback has started.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let start = self.start?;
+        let event = self.events.front()?;
+        Some(start + Duration::from_secs_f64(event.time))
+    }
+
+    /// Removes and returns every event due at `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<InputEvent> {
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.duration_since(start).as_secs_f64();
+        let mut due = Vec::new();
+        while let Some(event) = self.events.front() {
+            if event.time > elapsed {
+                break;
+            }
+            due.extend(self.events.pop_front().map(|event| event.event));
+        }
+        due
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+const EXTENSION: &str = "running";
+
+/// A file that exists, locked, only while the app is running.
+///
+/// Every run keeps its own file in a shared directory and holds an exclusive
+/// lock on it. The lock goes away with the process however it ends, so an
+/// unlocked file found at startup means its run didn't reach a clean exit,
+/// which is the cue to start in safe mode. Files of other instances that
+/// are still running stay locked and are left alone.
+pub struct CrashSentinel {
+    path: PathBuf,
+    /// Holds the lock until the sentinel is disarmed or the process dies.
+    _file: Option<File>,
+}
+
+impl CrashSentinel {
+    pub fn default_dir() -> PathBuf {
+        std::env::temp_dir().join("learn1")
+    }
+
+    /// Creates this process's sentinel in `dir`, returning it and whether a
+    /// stale one was found there.
+    pub fn arm(dir: &Path) -> (Self, bool) {
+        Self::arm_named(dir, &std::process::id().to_string())
+    }
+
+    /// [`arm`](Self::arm) with a sentinel called `name`, which must not be
+    /// used by another running instance.
+    fn arm_named(dir: &Path, name: &str) -> (Self, bool) {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            log::warn!(
+                "failed to create crash sentinel directory {}: {e}",
+                dir.display()
+            );
+        }
+        let crashed = remove_stale(dir);
+        let path = dir.join(name).with_extension(EXTENSION);
+        // Locked under another name first, so no other instance starting now
+        // can take it for a stale sentinel.
+        let pending = path.with_extension("pending");
+        let file = File::create(&pending)
+            .and_then(|file| {
+                file.lock()?;
+                std::fs::rename(&pending, &path)?;
+                Ok(file)
+            })
+            .inspect_err(|e| {
+                log::warn!("failed to write crash sentinel {}: {e}", path.display());
+            })
+            .ok();
+        (Self { path, _file: file }, crashed)
+    }
+
+    /// Removes the sentinel after a clean exit.
+    pub fn disarm(self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!(
+                "failed to remove crash sentinel {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Removes the sentinels in `dir` that no running instance holds locked,
+/// returning whether there were any.
+fn remove_stale(dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    let mut found = false;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path
+            .extension()
+            .is_none_or(|extension| extension != EXTENSION)
+        {
+            continue;
+        }
+        let unlocked = File::open(&path).is_ok_and(|file| file.try_lock().is_ok());
+        if unlocked {
+            log::debug!("found stale crash sentinel {}", path.display());
+            found = true;
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn sentinel_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "learn1-sentinel-test-{}-{test}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sentinels(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn other_running_instances_are_not_crashes() {
+        let dir = sentinel_dir("running");
+        let (first, crashed) = CrashSentinel::arm_named(&dir, "1");
+        assert!(!crashed);
+        let (second, crashed) = CrashSentinel::arm_named(&dir, "2");
+        assert!(!crashed);
+        assert_eq!(sentinels(&dir), 2);
+        // Exiting removes only the instance's own sentinel.
+        second.disarm();
+        assert_eq!(sentinels(&dir), 1);
+        let (third, crashed) = CrashSentinel::arm_named(&dir, "3");
+        assert!(!crashed);
+        first.disarm();
+        third.disarm();
+        assert_eq!(sentinels(&dir), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_unlocked_sentinel_is_a_crash() {
+        let dir = sentinel_dir("crashed");
+        std::fs::create_dir_all(&dir).unwrap();
+        // Left behind by a run that died, so nothing holds it locked.
+        std::fs::write(dir.join("1.running"), "").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        let (sentinel, crashed) = CrashSentinel::arm_named(&dir, "2");
+        assert!(crashed);
+        sentinel.disarm();
+        // Only the stale sentinel was cleaned up, so the next run is fine.
+        assert_eq!(sentinels(&dir), 1);
+        let (sentinel, crashed) = CrashSentinel::arm_named(&dir, "3");
+        assert!(!crashed);
+        sentinel.disarm();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use glam::{Quat, Vec3};
+use web_time::Instant;
+use winit::keyboard::KeyCode;
+
+use crate::culling::BoundingSphere;
+use crate::instance::{Instance, MaterialProperties};
+use crate::label;
+use crate::mesh::Mesh;
+use crate::model::{Material, Model, ModelMesh};
+use crate::texture::Texture;
+use crate::vertex::ModelVertex;
+
+/// A self-contained scene the app can switch to in place of the model grid.
+///
+/// It is drawn in the scene pass, so its pipelines must match the scene
+/// format and sample count, and have a `DEPTH_FORMAT` depth stencil state
+/// exactly when [`needs_depth`](Self::needs_depth) is true.
+pub trait Scene {
+    fn name(&self) -> &str;
+
+    /// Whether the scene pass gets a depth buffer. Without one, e.g. for a
+    /// purely 2D scene, the app frees the buffer and skips its clear.
+    fn needs_depth(&self) -> bool {
+        true
+    }
+
+    /// Background the app fades to when switching to the scene.
+    fn preferred_clear_color(&self) -> wgpu::Color {
+        CLEAR_COLOR
+    }
+
+    /// Short state reported with the frame stats, e.g. a tunable count.
+    fn status(&self) -> String {
+        String::new()
+    }
+
+    /// Handles a key press while the scene is shown; returns whether the
+    /// scene used it.
+    fn key_pressed(&mut self, key: KeyCode) -> bool {
+        let _ = key;
+        false
+    }
+
+    /// Rebuilds the pipelines for a new scene format or sample count.
+    fn set_target(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32);
+
+    fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>);
+}
+
+pub const TEXTURE_PNG: &[u8] = include_bytes!("texture.png");
+
+pub const PENTAGON_VERTICES: &[ModelVertex] = &[
+    ModelVertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        tex_coords: [0.4131759, 0.00759614],
+        normal: [0.0, 0.0, 1.0],
+    },
+    ModelVertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        tex_coords: [0.0048659444, 0.43041354],
+        normal: [0.0, 0.0, 1.0],
+    },
+    ModelVertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        tex_coords: [0.28081453, 0.949397],
+        normal: [0.0, 0.0, 1.0],
+    },
+    ModelVertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        tex_coords: [0.85967, 0.84732914],
+        normal: [0.0, 0.0, 1.0],
+    },
+    ModelVertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        tex_coords: [0.9414737, 0.2652641],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+pub const PENTAGON_INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+/// The textured pentagon drawn when no model is loaded.
+pub fn pentagon_model(device: &wgpu::Device, queue: &wgpu::Queue) -> Model {
+    let image = image::load_from_memory(TEXTURE_PNG).expect("the embedded texture is a valid PNG");
+    let texture = Texture::from_layers(device, queue, &[image.to_rgba8()], Some("texture.png"));
+    let layout = Texture::array_bind_group_layout(device);
+    Model {
+        meshes: vec![ModelMesh {
+            name: "pentagon".to_string(),
+            mesh: Mesh::new(
+                device,
+                label!("scene::pentagon"),
+                PENTAGON_VERTICES,
+                PENTAGON_INDICES,
+            ),
+            material: 0,
+            bounds: BoundingSphere::from_vertices(PENTAGON_VERTICES),
+        }],
+        materials: vec![Material::new(device, &layout, "texture.png", &texture)],
+        animation: None,
+    }
+}
+
+pub const CAMERA_EYE: Vec3 = Vec3::new(0.0, 2.5, 5.0);
+pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Tints the grid instances cycle through, so the instanced draw shows its
+/// per-instance materials.
+pub const MATERIALS: &[MaterialProperties] = &[
+    MaterialProperties {
+        tint: [1.0, 1.0, 1.0, 1.0],
+    },
+    MaterialProperties {
+        tint: [1.0, 0.6, 0.6, 1.0],
+    },
+    MaterialProperties {
+        tint: [0.6, 0.8, 1.0, 1.0],
+    },
+];
+
+pub const INSTANCES_PER_ROW: u32 = 3;
+const INSTANCE_SPACING: f32 = 1.2;
+
+/// A `per_row` x `per_row` grid of instances in the XZ plane, centered on
+/// the origin, each spun a little further around its own axis and using the
+/// next of the [`MATERIALS`].
+pub fn grid_instances(per_row: u32) -> Vec<Instance> {
+    let offset = (per_row as f32 - 1.0) * INSTANCE_SPACING / 2.0;
+    (0..per_row * per_row)
+        .map(|i| {
+            let (row, column) = (i / per_row, i % per_row);
+            Instance {
+                position: Vec3::new(
+                    column as f32 * INSTANCE_SPACING - offset,
+                    0.0,
+                    row as f32 * INSTANCE_SPACING - offset,
+                ),
+                rotation: Quat::from_rotation_z(i as f32 * 0.3),
+                material: i % MATERIALS.len() as u32,
+                ..Instance::default()
+            }
+        })
+        .collect()
+}
+
+/// Fades the clear color to a new scene's
+/// [`preferred_clear_color`](Scene::preferred_clear_color) instead of
+/// snapping to it, easing in and out over a fixed duration.
+#[derive(Debug, Clone)]
+pub struct ClearColorFade {
+    duration: Duration,
+    from: wgpu::Color,
+    to: wgpu::Color,
+    /// When the current fade began; `None` when not fading.
+    start: Option<Instant>,
+}
+
+impl ClearColorFade {
+    /// A fade taking `duration`; zero switches colors at once.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            from: CLEAR_COLOR,
+            to: CLEAR_COLOR,
+            start: None,
+        }
+    }
+
+    /// Starts fading from `current` to `to`. During a fade it starts from
+    /// the color the fade has reached instead, so switching again quickly
+    /// doesn't jump.
+    pub fn start(&mut self, now: Instant, current: wgpu::Color, to: wgpu::Color) {
+        self.from = self.color(now).unwrap_or(current);
+        self.to = to;
+        self.start = Some(now);
+    }
+
+    /// Stops the fade where it is, e.g. when the color is set by hand.
+    pub fn cancel(&mut self) {
+        self.start = None;
+    }
+
+    /// The color at `now` while fading, ending with the target color once;
+    /// `None` when not fading.
+    pub fn update(&mut self, now: Instant) -> Option<wgpu::Color> {
+        let color = self.color(now)?;
+        if color == self.to {
+            self.start = None;
+        }
+        Some(color)
+    }
+
+    fn color(&self, now: Instant) -> Option<wgpu::Color> {
+        let elapsed = now.saturating_duration_since(self.start?);
+        if elapsed >= self.duration {
+            return Some(self.to);
+        }
+        let t = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        // Smoothstep: starts and ends at rest.
+        let t = t * t * (3.0 - 2.0 * t);
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Some(wgpu::Color {
+            r: lerp(self.from.r, self.to.r),
+            g: lerp(self.from.g, self.to.g),
+            b: lerp(self.from.b, self.to.b),
+            a: lerp(self.from.a, self.to.a),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: wgpu::Color = wgpu::Color::BLACK;
+    const WHITE: wgpu::Color = wgpu::Color::WHITE;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn fades_with_easing_and_finishes_on_the_target() {
+        let mut fade = ClearColorFade::new(ms(400));
+        let start = Instant::now();
+        assert_eq!(fade.update(start), None);
+        fade.start(start, BLACK, WHITE);
+        assert_eq!(fade.update(start), Some(BLACK));
+        // Eased: slower than linear early on, halfway at the midpoint.
+        assert!(fade.update(start + ms(100)).unwrap().r < 0.25);
+        assert!((fade.update(start + ms(200)).unwrap().r - 0.5).abs() < 1e-9);
+        assert_eq!(fade.update(start + ms(500)), Some(WHITE));
+        assert_eq!(fade.update(start + ms(600)), None);
+    }
+
+    #[test]
+    fn restarting_continues_from_the_current_color() {
+        let mut fade = ClearColorFade::new(ms(400));
+        let start = Instant::now();
+        fade.start(start, BLACK, WHITE);
+        let midway = fade.update(start + ms(200)).unwrap();
+        // The stale color passed in is ignored mid-fade.
+        fade.start(start + ms(200), WHITE, BLACK);
+        assert_eq!(fade.update(start + ms(200)), Some(midway));
+        assert_eq!(fade.update(start + ms(600)), Some(BLACK));
+    }
+
+    #[test]
+    fn a_zero_duration_switches_at_once() {
+        let mut fade = ClearColorFade::new(Duration::ZERO);
+        let now = Instant::now();
+        fade.start(now, BLACK, WHITE);
+        assert_eq!(fade.update(now), Some(WHITE));
+        fade.start(now, WHITE, BLACK);
+        fade.cancel();
+        assert_eq!(fade.update(now), None);
+    }
+}
@@ -0,0 +1,190 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A shareable snapshot of the render settings.
+///
+/// Every field is optional so a partial string only changes what it names.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_eye: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_target: Option<[f32; 3]>,
+    /// Vertical field of view in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_fovy: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clear_color: Option<[f64; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub present_mode: Option<String>,
+    /// An [`AaMode`](crate::aa::AaMode) by its
+    /// [`name`](crate::aa::AaMode::name), e.g. `msaa4`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aa_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crosshair: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireframe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub axis_gizmo: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Base64(base64::DecodeError),
+    Json(serde_json::Error),
+    NotAnObject,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64(e) => write!(f, "settings string is not valid base64: {e}"),
+            Self::Json(e) => write!(f, "settings string is not valid JSON: {e}"),
+            Self::NotAnObject => write!(f, "settings string does not contain an object"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl RenderSettings {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("settings are always serializable");
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// Decodes a settings string, dropping (and logging) fields that don't parse.
+    pub fn decode(encoded: &str) -> Result<Self, SettingsError> {
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded.trim().trim_end_matches('='))
+            .map_err(SettingsError::Base64)?;
+        let value: serde_json::Value =
+            serde_json::from_slice(&bytes).map_err(SettingsError::Json)?;
+        let serde_json::Value::Object(fields) = value else {
+            return Err(SettingsError::NotAnObject);
+        };
+
+        let mut settings = Self::default();
+        for (name, value) in fields {
+            let result = match name.as_str() {
+                "camera_eye" => parse(value, &mut settings.camera_eye),
+                "camera_target" => parse(value, &mut settings.camera_target),
+                "camera_fovy" => parse(value, &mut settings.camera_fovy),
+                "clear_color" => parse(value, &mut settings.clear_color),
+                "present_mode" => parse(value, &mut settings.present_mode),
+                "aa_mode" => parse(value, &mut settings.aa_mode),
+                "crosshair" => parse(value, &mut settings.crosshair),
+                "wireframe" => parse(value, &mut settings.wireframe),
+                "axis_gizmo" => parse(value, &mut settings.axis_gizmo),
+                _ => {
+                    log::warn!("settings: ignoring unknown field `{name}`");
+                    continue;
+                }
+            };
+            if let Err(e) = result {
+                log::warn!("settings: ignoring invalid `{name}`: {e}");
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Sets `field` from `value` if it has the field's type.
+fn parse<T: serde::de::DeserializeOwned>(
+    value: serde_json::Value,
+    field: &mut Option<T>,
+) -> Result<(), serde_json::Error> {
+    *field = Some(serde_json::from_value(value)?);
+    Ok(())
+}
+
+pub fn present_mode_name(mode: wgpu::PresentMode) -> String {
+    format!("{mode:?}")
+}
+
+pub fn parse_present_mode(name: &str) -> Option<wgpu::PresentMode> {
+    use wgpu::PresentMode;
+    [
+        PresentMode::AutoVsync,
+        PresentMode::AutoNoVsync,
+        PresentMode::Fifo,
+        PresentMode::FifoRelaxed,
+        PresentMode::Immediate,
+        PresentMode::Mailbox,
+    ]
+    .into_iter()
+    .find(|mode| present_mode_name(*mode).eq_ignore_ascii_case(name))
+}
+
+/// Copies text to the system clipboard.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn copy_to_clipboard(text: &str) -> Result<(), arboard::Error> {
+    arboard::Clipboard::new()?.set_text(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_json(json: &str) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = RenderSettings {
+            camera_eye: Some([1.0, 2.5, -3.0]),
+            camera_target: Some([0.0, 0.5, 0.0]),
+            camera_fovy: Some(30.0),
+            clear_color: Some([0.1, 0.2, 0.3, 1.0]),
+            present_mode: Some(present_mode_name(wgpu::PresentMode::Mailbox)),
+            aa_mode: Some("fxaa".to_string()),
+            crosshair: Some(true),
+            wireframe: Some(false),
+            axis_gizmo: Some(true),
+        };
+        assert_eq!(
+            RenderSettings::decode(&settings.encode()).unwrap(),
+            settings
+        );
+        assert_eq!(
+            RenderSettings::decode(&RenderSettings::default().encode()).unwrap(),
+            RenderSettings::default()
+        );
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        assert!(matches!(
+            RenderSettings::decode("not base64!"),
+            Err(SettingsError::Base64(_))
+        ));
+        assert!(matches!(
+            RenderSettings::decode(&encode_json("{\"crosshair\": tru")),
+            Err(SettingsError::Json(_))
+        ));
+        assert!(matches!(
+            RenderSettings::decode(&encode_json("[true]")),
+            Err(SettingsError::NotAnObject)
+        ));
+    }
+
+    #[test]
+    fn partial_strings_keep_only_the_valid_fields() {
+        let encoded = encode_json(
+            r#"{"camera_fovy": 60, "wireframe": "yes", "camera_eye": [1, 2], "axis_gizmo": true, "bloom": 1}"#,
+        );
+        // Padding and surrounding whitespace, as pasted, are fine too.
+        let settings = RenderSettings::decode(&format!(" {encoded}== ")).unwrap();
+        assert_eq!(
+            settings,
+            RenderSettings {
+                camera_fovy: Some(60.0),
+                axis_gizmo: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Values for a shader's pipeline-overridable (`override`) constants, keyed
+/// by name or by `@id`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShaderConstants {
+    values: BTreeMap<String, f64>,
+}
+
+impl ShaderConstants {
+    pub fn set(&mut self, name: impl Into<String>, value: f64) -> &mut Self {
+        self.values.insert(name.into(), value);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values.get(name).copied()
+    }
+
+    /// Returns the entries declared as `override` in `source`, logging and
+    /// dropping the rest (pipeline creation fails on unknown constants).
+    pub fn validated(&self, source: &str) -> Vec<(&str, f64)> {
+        let declared = match override_names(source) {
+            Ok(declared) => declared,
+            Err(e) => {
+                log::error!("shader constants: failed to reflect shader: {e}");
+                return Vec::new();
+            }
+        };
+        self.values
+            .iter()
+            .filter(|(name, _)| {
+                let known = declared.contains(name);
+                if !known {
+                    log::error!("shader constants: `{name}` is not an override in this shader");
+                }
+                known
+            })
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect()
+    }
+}
+
+/// Parses and validates a WGSL module, returning the first error formatted
+/// with `path` and the offending line.
+pub fn validate(source: &str, path: &Path) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string_with_path(source, path))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| e.emit_to_string_with_path(source, &path.to_string_lossy()))?;
+    Ok(())
+}
+
+/// Names (and numeric `@id`s) of the `override` declarations in a WGSL module.
+pub fn override_names(source: &str) -> Result<Vec<String>, naga::front::wgsl::ParseError> {
+    let module = naga::front::wgsl::parse_str(source)?;
+    let mut names = Vec::new();
+    for (_, constant) in module.overrides.iter() {
+        names.extend(constant.name.clone());
+        names.extend(constant.id.map(|id| id.to_string()));
+    }
+    Ok(names)
+}
+
+/// Size and field offsets of a `#[repr(C)]` Rust struct mirrored in a shader.
+///
+/// Build it with [`struct_layout!`](crate::struct_layout).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    pub size: usize,
+    pub fields: Vec<(&'static str, usize)>,
+}
+
+/// Describes a Rust struct's layout for [`check_struct_layout`]. Fields
+/// starting with `_` are treated as padding and may be left out.
+#[macro_export]
+macro_rules! struct_layout {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        $crate::shader::StructLayout {
+            size: ::std::mem::size_of::<$ty>(),
+            fields: ::std::vec![$((stringify!($field), ::std::mem::offset_of!($ty, $field))),*],
+        }
+    };
+}
+
+/// Compares the WGSL struct `name` in `source` against a Rust layout, logging
+/// an error per mismatch. Returns whether the layouts agree.
+pub fn check_struct_layout(source: &str, name: &str, layout: &StructLayout) -> bool {
+    let errors = match struct_layout_mismatches(source, name, layout) {
+        Ok(errors) => errors,
+        Err(e) => {
+            log::error!("struct layout: failed to reflect shader: {e}");
+            return false;
+        }
+    };
+    for error in &errors {
+        log::error!("struct layout of `{name}`: {error}");
+    }
+    errors.is_empty()
+}
+
+fn struct_layout_mismatches(
+    source: &str,
+    name: &str,
+    layout: &StructLayout,
+) -> Result<Vec<String>, naga::front::wgsl::ParseError> {
+    let module = naga::front::wgsl::parse_str(source)?;
+    let Some((members, span)) = module.types.iter().find_map(|(_, ty)| match &ty.inner {
+        naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+            Some((members, *span))
+        }
+        _ => None,
+    }) else {
+        return Ok(vec!["not declared in the shader".to_string()]);
+    };
+
+    let mut errors = Vec::new();
+    if span as usize != layout.size {
+        errors.push(format!(
+            "shader size is {span} bytes but the Rust size is {}",
+            layout.size
+        ));
+    }
+    for member in members {
+        let member_name = member.name.as_deref().unwrap_or_default();
+        match layout
+            .fields
+            .iter()
+            .find(|(field, _)| *field == member_name)
+        {
+            Some(&(_, offset)) if offset != member.offset as usize => errors.push(format!(
+                "`{member_name}` is at offset {} in the shader but {offset} in Rust",
+                member.offset
+            )),
+            Some(_) => {}
+            None => errors.push(format!("`{member_name}` is missing on the Rust side")),
+        }
+    }
+    for (field, _) in &layout.fields {
+        let in_shader = members
+            .iter()
+            .any(|member| member.name.as_deref() == Some(field));
+        if !in_shader && !field.starts_with('_') {
+            errors.push(format!("`{field}` is missing in the shader"));
+        }
+    }
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "
+        struct Params {
+            color: vec4<f32>,
+            scale: f32,
+            count: u32,
+        }
+    ";
+
+    fn mismatches(size: usize, fields: &[(&'static str, usize)]) -> Vec<String> {
+        let layout = StructLayout {
+            size,
+            fields: fields.to_vec(),
+        };
+        struct_layout_mismatches(SOURCE, "Params", &layout).unwrap()
+    }
+
+    #[test]
+    fn matching_layouts_have_no_mismatches() {
+        let fields = [("color", 0), ("scale", 16), ("count", 20)];
+        assert_eq!(mismatches(32, &fields), Vec::<String>::new());
+    }
+
+    #[test]
+    fn size_and_offset_mismatches_are_reported() {
+        let fields = [("color", 0), ("scale", 16), ("count", 20)];
+        assert_eq!(
+            mismatches(24, &fields),
+            ["shader size is 32 bytes but the Rust size is 24"]
+        );
+        let fields = [("color", 0), ("scale", 20), ("count", 20)];
+        assert_eq!(
+            mismatches(32, &fields),
+            ["`scale` is at offset 16 in the shader but 20 in Rust"]
+        );
+    }
+
+    #[test]
+    fn fields_missing_on_either_side_are_reported() {
+        assert_eq!(
+            mismatches(32, &[("color", 0), ("scale", 16)]),
+            ["`count` is missing on the Rust side"]
+        );
+        let fields = [("color", 0), ("scale", 16), ("count", 20), ("extra", 24)];
+        assert_eq!(
+            mismatches(32, &fields),
+            ["`extra` is missing in the shader"]
+        );
+        assert_eq!(
+            struct_layout_mismatches(
+                SOURCE,
+                "Other",
+                &StructLayout {
+                    size: 0,
+                    fields: Vec::new()
+                }
+            )
+            .unwrap(),
+            ["not declared in the shader"]
+        );
+    }
+
+    #[test]
+    fn padding_fields_may_be_left_out_of_the_shader() {
+        let fields = [("color", 0), ("scale", 16), ("count", 20), ("_padding", 24)];
+        assert_eq!(mismatches(32, &fields), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,114 @@
+// Set when the target format is not sRGB, so the encode happens here
+// instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+// Added to the mip level of every texture sample; see
+// `ScenePipeline::set_lod_bias`.
+override LOD_BIAS: f32 = 0.0;
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+// An array, so instances of one material can each pick a layer.
+var diffuse_texture: texture_2d_array<f32>;
+@group(1) @binding(1)
+var diffuse_sampler: sampler;
+
+struct Material {
+    tint: vec4<f32>,
+}
+
+// Indexed by each instance's material, so instances with different
+// materials still share one draw.
+@group(2) @binding(0)
+var<storage, read> materials: array<Material>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) material: u32,
+    @location(10) layer: u32,
+    @location(11) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) material: u32,
+    @location(2) @interpolate(flat) layer: u32,
+    @location(3) @interpolate(flat) tint: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.material = instance.material;
+    out.layer = instance.layer;
+    out.tint = instance.tint;
+    return out;
+}
+
+// Vertex pulling: the same inputs read by index from storage buffers, for
+// pipelines without vertex buffers. A vertex is 8 floats, laid out like
+// `ModelVertex`: position, tex_coords, normal.
+struct InstanceData {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+    material: u32,
+    layer: u32,
+}
+
+@group(2) @binding(1)
+var<storage, read> instances: array<InstanceData>;
+@group(3) @binding(0)
+var<storage, read> vertices: array<f32>;
+
+@vertex
+fn vs_pulled(
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+    let base = index * 8u;
+    let position = vec3<f32>(vertices[base], vertices[base + 1u], vertices[base + 2u]);
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * instances[instance].model * vec4<f32>(position, 1.0);
+    out.tex_coords = vec2<f32>(vertices[base + 3u], vertices[base + 4u]);
+    out.material = instances[instance].material;
+    out.layer = instances[instance].layer;
+    out.tint = instances[instance].tint;
+    return out;
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Indices are validated on upload; the clamp only keeps a stale one in
+    // bounds.
+    let material = materials[min(in.material, arrayLength(&materials) - 1u)];
+    let layer = min(in.layer, textureNumLayers(diffuse_texture) - 1u);
+    let color = textureSampleBias(diffuse_texture, diffuse_sampler, in.tex_coords, layer, LOD_BIAS)
+        * material.tint * in.tint;
+    if ENCODE_SRGB {
+        return vec4<f32>(linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0))), color.a);
+    }
+    return color;
+}
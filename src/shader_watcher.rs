@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::UserEvent;
+
+/// Watches a directory for `.wgsl` files being written.
+///
+/// The notify callback sends each written path to the event loop as
+/// [`UserEvent::ShaderChanged`], which also wakes it; editors often save a
+/// file in several writes, so the same path may arrive more than once.
+pub struct ShaderWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    pub fn new(dir: &Path, proxy: EventLoopProxy<UserEvent>) -> notify::Result<Self> {
+        let mut watcher = notify::recommended_watcher(
+            move |result: notify::Result<notify::Event>| match result {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths.into_iter().filter(|path| is_wgsl(path)) {
+                        // Fails only once the event loop has exited.
+                        let _ = proxy.send_event(UserEvent::ShaderChanged(path));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("shader watcher: {e}"),
+            },
+        )?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+fn is_wgsl(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "wgsl")
+}
@@ -0,0 +1,398 @@
+use wgpu::util::DeviceExt;
+
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::texture::{Texture, DEPTH_FORMAT};
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("sprite.wgsl");
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteUniform {
+    screen_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    rect: [f32; 4],
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+    depth: f32,
+}
+
+/// A sprite waiting for the next [`SpriteBatch::draw`].
+#[derive(Debug, Clone, Copy)]
+struct Sprite {
+    layer: u16,
+    instance: SpriteInstance,
+}
+
+impl Sprite {
+    /// Only a fully opaque tint goes through the depth-tested pass.
+    fn is_opaque(&self) -> bool {
+        self.instance.color[3] >= 1.0
+    }
+}
+
+/// Depth for `layer`, from just under 1.0 for layer 0 down towards 0.0, so
+/// a higher layer passes the depth test over a lower one.
+fn layer_depth(layer: u16) -> f32 {
+    1.0 - (f32::from(layer) + 1.0) / (f32::from(u16::MAX) + 2.0)
+}
+
+/// Draws textured quads in layer order, whatever order they were submitted
+/// in.
+///
+/// Sprites are [`draw_sprite`](Self::draw_sprite)n in physical pixels and
+/// flushed by the next [`draw`](Self::draw), sorted by layer with a stable
+/// sort so sprites within a layer keep their submission order. Sprites with
+/// an opaque tint go first, depth-tested against a depth buffer the batch
+/// owns, with texels under half alpha cut out. The rest are blended on top
+/// in painter's order by layer, still tested against the opaque ones so a
+/// higher-layer opaque sprite hides them.
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+    depth: wgpu::TextureView,
+    uniform_buffer: UniformBuffer<SpriteUniform>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    opaque_pipeline: wgpu::RenderPipeline,
+    transparent_pipeline: wgpu::RenderPipeline,
+    width: u32,
+    height: u32,
+}
+
+impl SpriteBatch {
+    /// Creates a batch for a `width` by `height` target of `format`.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Sprites",
+            &crate::struct_layout!(SpriteUniform {
+                screen_size,
+                _padding,
+            }),
+        );
+        let (width, height) = (width.max(1), height.max(1));
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("sprite::uniform"),
+            wgpu::ShaderStages::VERTEX,
+        );
+        let shader = create_shader(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("sprite::pipeline_layout"),
+            bind_group_layouts: &[uniform_buffer.layout(), &Texture::bind_group_layout(device)],
+            push_constant_ranges: &[],
+        });
+        let (opaque_pipeline, transparent_pipeline) =
+            create_pipelines(device, &shader, &pipeline_layout, format);
+
+        Self {
+            sprites: Vec::new(),
+            depth: create_depth(device, width, height),
+            uniform_buffer,
+            shader,
+            pipeline_layout,
+            opaque_pipeline,
+            transparent_pipeline,
+            width,
+            height,
+        }
+    }
+
+    /// Rebuilds the pipelines for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        // A fresh module for both, as in `rebuild_pipeline`.
+        self.shader = create_shader(device);
+        (self.opaque_pipeline, self.transparent_pipeline) =
+            create_pipelines(device, &self.shader, &self.pipeline_layout, format);
+    }
+
+    /// Follows a resize of the target to `width` by `height` physical pixels.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.depth = create_depth(device, width, height);
+        }
+    }
+
+    /// Queues a sprite covering `rect` (min x, min y, max x, max y in
+    /// physical pixels) for the next [`draw`](Self::draw), textured with
+    /// `uv_rect` of the bound texture, e.g. from
+    /// [`TextureAtlas::uv_rect`](crate::texture::TextureAtlas::uv_rect).
+    /// `color` is an sRGB tint with straight alpha; sprites on a higher
+    /// `layer` are drawn over lower ones.
+    pub fn draw_sprite(&mut self, rect: [f32; 4], uv_rect: [f32; 4], color: [f32; 4], layer: u16) {
+        self.sprites.push(Sprite {
+            layer,
+            instance: SpriteInstance {
+                rect,
+                uv_rect,
+                color,
+                depth: layer_depth(layer),
+            },
+        });
+    }
+
+    /// Paints the queued sprites over `view` with `LoadOp::Load`, sampling
+    /// `texture`, a bind group for [`Texture::bind_group_layout`], then
+    /// clears the queue.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+        texture: &wgpu::BindGroup,
+    ) {
+        let (opaque, transparent) = self.flush();
+        if opaque.is_empty() && transparent.is_empty() {
+            return;
+        }
+
+        self.uniform_buffer.update(
+            queue,
+            &SpriteUniform {
+                screen_size: [self.width as f32, self.height as f32],
+                _padding: [0.0; 2],
+            },
+        );
+        let instances = [opaque.as_slice(), transparent.as_slice()].concat();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: label!("sprite::instance_buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("sprite::render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_bind_group(0, self.uniform_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, texture, &[]);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        let opaque_count = opaque.len() as u32;
+        if !opaque.is_empty() {
+            render_pass.set_pipeline(&self.opaque_pipeline);
+            render_pass.draw(0..4, 0..opaque_count);
+        }
+        if !transparent.is_empty() {
+            render_pass.set_pipeline(&self.transparent_pipeline);
+            render_pass.draw(0..4, opaque_count..instances.len() as u32);
+        }
+    }
+
+    /// Empties the queue into the opaque and the transparent sprites, each
+    /// in layer order and in submission order within a layer.
+    fn flush(&mut self) -> (Vec<SpriteInstance>, Vec<SpriteInstance>) {
+        // `sort_by_key` is stable.
+        self.sprites.sort_by_key(|sprite| sprite.layer);
+        let (opaque, transparent): (Vec<_>, Vec<_>) =
+            self.sprites.drain(..).partition(Sprite::is_opaque);
+        let instances = |sprites: Vec<Sprite>| sprites.into_iter().map(|s| s.instance).collect();
+        (instances(opaque), instances(transparent))
+    }
+}
+
+fn create_depth(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: label!("sprite::depth"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("sprite::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+/// The opaque and the transparent pipeline.
+fn create_pipelines(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32,
+    ];
+    let mut constants = ShaderConstants::default();
+    let encode = if is_linear_output(format) { 0.0 } else { 1.0 };
+    constants.set("ENCODE_SRGB", encode);
+    let constants = constants.validated(SHADER);
+    let create = |entry_point, blend, depth_write_enabled| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: label!("sprite::pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &ATTRIBUTES,
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            // Equal depth passes, so within a layer the later sprite wins.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    };
+    (
+        create("fs_opaque", None, true),
+        create(
+            "fs_transparent",
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            false,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_gpu, TestRenderer};
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+    const FULL: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    #[test]
+    fn sorts_by_layer_keeping_submission_order() {
+        let gpu = test_gpu!();
+        let mut batch = SpriteBatch::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, 8, 8);
+        let translucent = [1.0, 1.0, 1.0, 0.5];
+        batch.draw_sprite([0.0; 4], FULL, translucent, 2);
+        batch.draw_sprite([1.0; 4], FULL, RED, 1);
+        batch.draw_sprite([2.0; 4], FULL, translucent, 0);
+        batch.draw_sprite([3.0; 4], FULL, BLUE, 1);
+        batch.draw_sprite([4.0; 4], FULL, translucent, 2);
+        let (opaque, transparent) = batch.flush();
+        let order = |instances: Vec<SpriteInstance>| -> Vec<f32> {
+            instances.iter().map(|i| i.rect[0]).collect()
+        };
+        assert_eq!(order(opaque), [1.0, 3.0]);
+        assert_eq!(order(transparent), [2.0, 0.0, 4.0]);
+        assert!(batch.sprites.is_empty());
+        assert!(layer_depth(1) < layer_depth(0) && layer_depth(u16::MAX) > 0.0);
+    }
+
+    /// Draws a red and a blue sprite overlapping in the middle, submitting
+    /// the higher layer first, and returns the pixel in the overlap.
+    fn overlap(alpha: f32) -> Option<[u8; 4]> {
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = TestRenderer::new(test_gpu!(), 32, 16, format);
+        let (device, queue) = (&renderer.gpu.device, &renderer.gpu.queue);
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255; 4]),
+        ));
+        let texture = Texture::from_image(device, queue, &white, None);
+        let texture = texture.bind_group(device, &Texture::bind_group_layout(device));
+
+        let mut batch = SpriteBatch::new(device, format, 32, 16);
+        let [r, g, b, _] = BLUE;
+        batch.draw_sprite([8.0, 0.0, 32.0, 16.0], FULL, [r, g, b, alpha], 1);
+        let [r, g, b, _] = RED;
+        batch.draw_sprite([0.0, 0.0, 24.0, 16.0], FULL, [r, g, b, alpha], 0);
+        let image = renderer.render(|frame, view| batch.draw(device, queue, frame, view, &texture));
+
+        let pixel = |x: u32| -> [u8; 4] { image.get_pixel(x, 8).0 };
+        // Either sprite alone shows its own color.
+        assert!(pixel(2)[0] > 100 && pixel(2)[2] == 0);
+        assert!(pixel(30)[2] > 100 && pixel(30)[0] == 0);
+        Some(pixel(16))
+    }
+
+    #[test]
+    fn higher_opaque_layer_occludes_a_lower_one() {
+        let Some(pixel) = overlap(1.0) else {
+            return;
+        };
+        assert_eq!(pixel, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn higher_transparent_layer_blends_over_a_lower_one() {
+        let Some(pixel) = overlap(0.6) else {
+            return;
+        };
+        // Blue blended last over red, so blue dominates.
+        assert!(pixel[2] > pixel[0], "{pixel:?}");
+    }
+}
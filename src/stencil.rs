@@ -0,0 +1,251 @@
+use std::fmt;
+
+/// A depth format with a stencil aspect, for passes that mask with stencil.
+pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StencilError {
+    /// Stencil ops or tests were given for a depth format without a stencil
+    /// aspect, like the default `Depth32Float`.
+    NoStencilAspect(wgpu::TextureFormat),
+}
+
+impl fmt::Display for StencilError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoStencilAspect(format) => write!(
+                f,
+                "{format:?} has no stencil aspect; use a format like {DEPTH_STENCIL_FORMAT:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StencilError {}
+
+/// Checks that `format` has a stencil aspect, as stencil ops and tests
+/// need. wgpu only reports a mismatch once the pass or pipeline is created,
+/// as a validation error.
+pub fn check_format(format: wgpu::TextureFormat) -> Result<(), StencilError> {
+    if format.has_stencil_aspect() {
+        Ok(())
+    } else {
+        Err(StencilError::NoStencilAspect(format))
+    }
+}
+
+/// The depth-stencil attachment of a pass over `view`, of `format`, with
+/// the depth and stencil ops set independently.
+///
+/// Stencil ops decide whether a mask survives from pass to pass: a pass
+/// that writes it must store it, and a later pass reading it must load it
+/// rather than clear it. `None` leaves that aspect read-only.
+pub fn depth_stencil_attachment<'a>(
+    view: &'a wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    depth_ops: Option<wgpu::Operations<f32>>,
+    stencil_ops: Option<wgpu::Operations<u32>>,
+) -> Result<wgpu::RenderPassDepthStencilAttachment<'a>, StencilError> {
+    if stencil_ops.is_some() {
+        check_format(format)?;
+    }
+    Ok(wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops,
+        stencil_ops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_gpu, TestRenderer};
+
+    const SIZE: u32 = 32;
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    const SHADER: &str = "
+        // The left half of the target, as two triangles.
+        @vertex
+        fn vs_left(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            var corners = array(
+                vec2(-1.0, -1.0), vec2(0.0, -1.0), vec2(0.0, 1.0),
+                vec2(-1.0, -1.0), vec2(0.0, 1.0), vec2(-1.0, 1.0),
+            );
+            return vec4<f32>(corners[index], 0.0, 1.0);
+        }
+
+        // One triangle covering the whole target.
+        @vertex
+        fn vs_full(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            var corners = array(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+            return vec4<f32>(corners[index], 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    fn pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        vertex: &str,
+        write_mask: wgpu::ColorWrites,
+        stencil: wgpu::StencilFaceState,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some(vertex),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: FORMAT,
+                    blend: None,
+                    write_mask,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_STENCIL_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: stencil,
+                    back: stencil,
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Writes a mask over the left half in one pass, then fills the target
+    /// where the mask is set in a second pass that starts its stencil with
+    /// `second_load`.
+    fn draw_masked(renderer: &TestRenderer, second_load: wgpu::LoadOp<u32>) -> image::RgbaImage {
+        let device = &renderer.gpu.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let write_mask = pipeline(
+            device,
+            &shader,
+            "vs_left",
+            wgpu::ColorWrites::empty(),
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Always,
+                pass_op: wgpu::StencilOperation::Replace,
+                ..Default::default()
+            },
+        );
+        let read_mask = pipeline(
+            device,
+            &shader,
+            "vs_full",
+            wgpu::ColorWrites::ALL,
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Equal,
+                ..Default::default()
+            },
+        );
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_STENCIL_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+        renderer.render(|frame, view| {
+            let passes = [
+                (
+                    &write_mask,
+                    6,
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    wgpu::LoadOp::Clear(0),
+                ),
+                (&read_mask, 3, wgpu::LoadOp::Load, second_load),
+            ];
+            for (pipeline, vertices, color_load, stencil_load) in passes {
+                let attachment = depth_stencil_attachment(
+                    &depth_view,
+                    DEPTH_STENCIL_FORMAT,
+                    Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    Some(wgpu::Operations {
+                        load: stencil_load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                )
+                .unwrap();
+                let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: color_load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(attachment),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_stencil_reference(1);
+                render_pass.draw(0..vertices, 0..1);
+            }
+        })
+    }
+
+    #[test]
+    fn stencil_ops_need_a_stencil_aspect() {
+        assert_eq!(check_format(DEPTH_STENCIL_FORMAT), Ok(()));
+        assert_eq!(
+            check_format(crate::texture::DEPTH_FORMAT),
+            Err(StencilError::NoStencilAspect(crate::texture::DEPTH_FORMAT))
+        );
+    }
+
+    #[test]
+    fn mask_stored_by_one_pass_is_loaded_by_the_next() {
+        let gpu = test_gpu!();
+        let renderer = TestRenderer::new(gpu, SIZE, SIZE, FORMAT);
+        let white = image::Rgba([255, 255, 255, 255]);
+        let black = image::Rgba([0, 0, 0, 255]);
+
+        let image = draw_masked(&renderer, wgpu::LoadOp::Load);
+        assert_eq!(image.get_pixel(SIZE / 4, SIZE / 2), &white);
+        assert_eq!(image.get_pixel(SIZE * 3 / 4, SIZE / 2), &black);
+
+        // Clearing the stencil at the start of the second pass drops the mask.
+        let image = draw_masked(&renderer, wgpu::LoadOp::Clear(0));
+        assert_eq!(image.get_pixel(SIZE / 4, SIZE / 2), &black);
+        assert_eq!(image.get_pixel(SIZE * 3 / 4, SIZE / 2), &black);
+    }
+}
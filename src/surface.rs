@@ -0,0 +1,48 @@
+use wgpu::TextureFormat;
+
+/// Nits corresponding to 1.0 in extended-range (scRGB) output.
+pub const SDR_WHITE_NITS: f32 = 80.0;
+
+/// Picks the surface format, preferring `Rgba16Float` when HDR output is
+/// requested and otherwise an sRGB format, so colors look the same whichever
+/// format the platform happens to list first.
+///
+/// wgpu doesn't expose surface color spaces; on the backends that offer a
+/// float swapchain it is presented as extended-range linear sRGB.
+pub fn select_format(formats: &[TextureFormat], hdr_output: bool) -> TextureFormat {
+    if hdr_output {
+        if formats.contains(&TextureFormat::Rgba16Float) {
+            log::info!("HDR output: using Rgba16Float surface");
+            return TextureFormat::Rgba16Float;
+        }
+        log::warn!("HDR output requested but unsupported by the surface, falling back to SDR");
+    }
+    match formats.iter().copied().find(TextureFormat::is_srgb) {
+        Some(format) => {
+            log::info!("surface format: {format:?}");
+            format
+        }
+        None => {
+            log::info!(
+                "surface format: {:?} (no sRGB format available)",
+                formats[0]
+            );
+            formats[0]
+        }
+    }
+}
+
+/// Largest color value the display can show, relative to SDR white.
+pub fn max_output_value(format: TextureFormat, max_luminance: f32) -> f32 {
+    if format == TextureFormat::Rgba16Float {
+        max_luminance / SDR_WHITE_NITS
+    } else {
+        1.0
+    }
+}
+
+/// Whether shader output written to `format` is interpreted as linear: sRGB
+/// formats encode in hardware and float formats store linear values.
+pub fn is_linear_output(format: TextureFormat) -> bool {
+    format.is_srgb() || format == TextureFormat::Rgba16Float
+}
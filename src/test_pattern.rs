@@ -0,0 +1,162 @@
+use crate::label;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("test_pattern.wgsl");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// SMPTE color bars; with correct sRGB handling the 75% bars read back
+    /// as 191/255.
+    SmpteBars,
+    /// A one-pixel checkerboard, which blurs visibly if the output is
+    /// scaled or misaligned.
+    Checkerboard,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TestPatternUniform {
+    screen_size: [f32; 2],
+    pattern: u32,
+    cell_size: u32,
+}
+
+/// A fullscreen calibration pattern for checking color handling and pixel
+/// alignment.
+pub struct TestPattern {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    pattern: Pattern,
+    uniform: TestPatternUniform,
+    uniform_buffer: UniformBuffer<TestPatternUniform>,
+}
+
+impl TestPattern {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "TestPattern",
+            &crate::struct_layout!(TestPatternUniform {
+                screen_size,
+                pattern,
+                cell_size,
+            }),
+        );
+        let shader = create_shader(device);
+
+        let pattern = Pattern::SmpteBars;
+        let uniform = TestPatternUniform {
+            screen_size: [width as f32, height as f32],
+            pattern: pattern as u32,
+            cell_size: 1,
+        };
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("test_pattern::uniform"),
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        uniform_buffer.update(queue, &uniform);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("test_pattern::pipeline_layout"),
+            bind_group_layouts: &[uniform_buffer.layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format);
+
+        Self {
+            shader,
+            pipeline_layout,
+            pipeline,
+            pattern,
+            uniform,
+            uniform_buffer,
+        }
+    }
+
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    pub fn set_pattern(&mut self, queue: &wgpu::Queue, pattern: Pattern) {
+        self.pattern = pattern;
+        self.uniform.pattern = pattern as u32;
+        self.uniform_buffer.update(queue, &self.uniform);
+    }
+
+    /// Rebuilds the pipeline for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            create_pipeline(device, shader, &self.pipeline_layout, format)
+        });
+    }
+
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.uniform.screen_size = [width as f32, height as f32];
+        self.uniform_buffer.update(queue, &self.uniform);
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.uniform_buffer.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("test_pattern::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let mut constants = ShaderConstants::default();
+    let decode = if is_linear_output(format) { 1.0 } else { 0.0 };
+    constants.set("DECODE_SRGB", decode);
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("test_pattern::pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,85 @@
+struct TestPattern {
+    screen_size: vec2<f32>,
+    // 0: SMPTE color bars, 1: checkerboard.
+    pattern: u32,
+    // Checkerboard cell size in pixels.
+    cell_size: u32,
+}
+
+// Set when the target stores linear values (sRGB or float formats), so the
+// sRGB-encoded reference colors are decoded before being written.
+override DECODE_SRGB: bool = true;
+
+@group(0) @binding(0)
+var<uniform> test_pattern: TestPattern;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the viewport.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn srgb_to_linear(value: vec3<f32>) -> vec3<f32> {
+    let low = value / 12.92;
+    let high = pow((value + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, value <= vec3<f32>(0.04045));
+}
+
+// Simplified SMPTE ECR 1-1978 bars, as sRGB-encoded values.
+fn smpte_bars(uv: vec2<f32>) -> vec3<f32> {
+    let top = array<vec3<f32>, 7>(
+        vec3<f32>(0.75, 0.75, 0.75),
+        vec3<f32>(0.75, 0.75, 0.0),
+        vec3<f32>(0.0, 0.75, 0.75),
+        vec3<f32>(0.0, 0.75, 0.0),
+        vec3<f32>(0.75, 0.0, 0.75),
+        vec3<f32>(0.75, 0.0, 0.0),
+        vec3<f32>(0.0, 0.0, 0.75),
+    );
+    let bar = min(u32(uv.x * 7.0), 6u);
+    if uv.y < 0.67 {
+        return top[bar];
+    }
+    if uv.y < 0.75 {
+        // Reversed blue bars, alternating with black.
+        if bar % 2u == 1u {
+            return vec3<f32>(0.0);
+        }
+        return top[6u - bar];
+    }
+    // -I, white, +Q, black, then a PLUGE strip: black, 2% and 4% above black.
+    let x = uv.x * 7.0;
+    if x < 1.25 {
+        return vec3<f32>(0.0, 0.129, 0.298);
+    }
+    if x < 2.5 {
+        return vec3<f32>(1.0);
+    }
+    if x < 3.75 {
+        return vec3<f32>(0.196, 0.0, 0.416);
+    }
+    if x < 5.0 || x >= 6.0 {
+        return vec3<f32>(0.0);
+    }
+    return vec3<f32>(floor((x - 5.0) * 3.0) * 0.02);
+}
+
+fn checkerboard(position: vec2<f32>) -> vec3<f32> {
+    let cell = vec2<u32>(position) / max(test_pattern.cell_size, 1u);
+    return vec3<f32>(f32((cell.x + cell.y) % 2u));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    var color: vec3<f32>;
+    if test_pattern.pattern == 0u {
+        color = smpte_bars(position.xy / test_pattern.screen_size);
+    } else {
+        color = checkerboard(position.xy);
+    }
+    if DECODE_SRGB {
+        color = srgb_to_linear(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
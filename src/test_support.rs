@@ -0,0 +1,269 @@
+//! Fixtures shared by the GPU tests: a device, a fixed-size render target
+//! read back as an image, and comparison against reference images. The
+//! `pub` ones are also used by the benchmarks, through the `test-support`
+//! feature.
+#![cfg_attr(not(test), allow(dead_code, unused_imports, unused_macros))]
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::bundle::BundleTarget;
+use crate::config::AppConfig;
+use crate::context::GpuContext;
+use crate::error::InitError;
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::mesh::Mesh;
+use crate::vertex::{ModelVertex, Vertex};
+
+/// Set to let GPU tests pass without checking anything on machines with no
+/// adapter at all, not even a software one. Without it they fail there.
+const SKIP_VAR: &str = "LEARN1_SKIP_GPU_TESTS";
+/// Set to write rendered images as the new references instead of comparing.
+const UPDATE_VAR: &str = "LEARN1_UPDATE_REFERENCES";
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference");
+/// Where mismatching images and their diffs are written.
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/reference-output");
+
+/// The [`GpuContext`] for a test, falling back to a software adapter. Returns
+/// from the test if there is no adapter and `LEARN1_SKIP_GPU_TESTS` is set.
+macro_rules! test_gpu {
+    () => {
+        match $crate::test_support::gpu() {
+            Some(gpu) => gpu,
+            None => return Default::default(),
+        }
+    };
+}
+pub(crate) use test_gpu;
+
+/// Use [`test_gpu!`] rather than calling this directly.
+pub(crate) fn gpu() -> Option<GpuContext> {
+    let config = AppConfig::default();
+    let instance = GpuContext::create_instance(&config);
+    match pollster::block_on(GpuContext::new(instance, None, &config)) {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            skip_without_adapter(e);
+            None
+        }
+    }
+}
+
+/// Returns if `e` is a missing adapter and GPU tests may be skipped, and
+/// fails the test otherwise.
+pub(crate) fn skip_without_adapter(e: InitError) {
+    match e {
+        InitError::NoAdapter(_) if std::env::var_os(SKIP_VAR).is_some() => {
+            eprintln!("skipping GPU test: {e}");
+        }
+        InitError::NoAdapter(_) => {
+            panic!("{e}; set {SKIP_VAR} to skip GPU tests on machines without an adapter")
+        }
+        e => panic!("{e}"),
+    }
+}
+
+/// Renders into a fixed-size texture and reads it back as an image.
+pub(crate) struct TestRenderer {
+    pub gpu: GpuContext,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl TestRenderer {
+    /// A `width` by `height` target of `format`, 8-bit RGBA or BGRA; BGRA
+    /// comes back swizzled to RGBA.
+    pub fn new(gpu: GpuContext, width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: label!("test_support::target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { gpu, texture, view }
+    }
+
+    /// Has `draw` record passes into the target, submits them and reads the
+    /// target back. The target keeps what earlier renders left in it.
+    pub fn render(&self, draw: impl FnOnce(&mut FrameEncoder, &wgpu::TextureView)) -> RgbaImage {
+        let mut frame = FrameEncoder::new(&self.gpu.device, &self.gpu.queue, None);
+        draw(&mut frame, &self.view);
+        frame.submit();
+        let pixels =
+            crate::capture::read_texture_rgba(&self.gpu.device, &self.gpu.queue, &self.texture)
+                .unwrap();
+        RgbaImage::from_raw(self.texture.width(), self.texture.height(), pixels).unwrap()
+    }
+}
+
+/// A pipeline for `target` and a one-triangle mesh to draw with it.
+pub fn triangle_pipeline(
+    device: &wgpu::Device,
+    target: &BundleTarget,
+) -> (wgpu::RenderPipeline, Mesh) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(
+            "@vertex fn vs(@location(0) p: vec3<f32>) -> @builtin(position) vec4<f32> {
+                 return vec4<f32>(p, 1.0);
+             }
+             @fragment fn fs() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }"
+                .into(),
+        ),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs"),
+            compilation_options: Default::default(),
+            buffers: &[ModelVertex::layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs"),
+            compilation_options: Default::default(),
+            targets: &[Some(target.color_format.into())],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+        cache: None,
+    });
+    let vertex = ModelVertex {
+        position: [0.0; 3],
+        tex_coords: [0.0; 2],
+        normal: [0.0, 0.0, 1.0],
+    };
+    let mesh = Mesh::new(device, None, &[vertex; 3], &[0u16, 1, 2]);
+    (pipeline, mesh)
+}
+
+/// How an image differs from its reference.
+#[derive(Debug)]
+pub(crate) struct Mismatch {
+    /// Pixels with a channel off by more than the tolerance, or `None` if
+    /// the sizes differ.
+    pub pixels: Option<usize>,
+    /// The differing pixels in red over a dimmed copy of the reference.
+    pub diff: Option<RgbaImage>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pixels {
+            Some(pixels) => write!(f, "{pixels} pixels differ"),
+            None => f.write_str("the sizes differ"),
+        }
+    }
+}
+
+/// Compares two images, allowing each channel to be off by `tolerance` to
+/// absorb rasterization and filtering differences between GPUs.
+pub(crate) fn compare(
+    image: &RgbaImage,
+    reference: &RgbaImage,
+    tolerance: u8,
+) -> Result<(), Mismatch> {
+    if image.dimensions() != reference.dimensions() {
+        return Err(Mismatch {
+            pixels: None,
+            diff: None,
+        });
+    }
+    let mut diff = RgbaImage::new(image.width(), image.height());
+    let mut pixels = 0;
+    for ((actual, expected), out) in image
+        .pixels()
+        .zip(reference.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let differs = (0..4).any(|c| actual[c].abs_diff(expected[c]) > tolerance);
+        *out = if differs {
+            pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected.0.map(|c| c / 4);
+            image::Rgba([r, g, b, 255])
+        };
+    }
+    if pixels == 0 {
+        return Ok(());
+    }
+    Err(Mismatch {
+        pixels: Some(pixels),
+        diff: Some(diff),
+    })
+}
+
+/// Compares `image` against `tests/reference/<name>.png` with [`compare`].
+/// On a mismatch the image and its diff are written to
+/// `target/reference-output` and the test fails. With
+/// `LEARN1_UPDATE_REFERENCES` set the reference is rewritten instead.
+pub(crate) fn assert_matches_reference(image: &RgbaImage, name: &str, tolerance: u8) {
+    let reference_path = Path::new(REFERENCE_DIR).join(format!("{name}.png"));
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        image.save(&reference_path).unwrap();
+        eprintln!("updated {}", reference_path.display());
+        return;
+    }
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.into_rgba8(),
+        Err(e) => panic!(
+            "{}: {e}; set {UPDATE_VAR} to create it",
+            reference_path.display()
+        ),
+    };
+    let Err(mismatch) = compare(image, &reference, tolerance) else {
+        return;
+    };
+    let output =
+        |suffix: &str| -> PathBuf { Path::new(OUTPUT_DIR).join(format!("{name}.{suffix}.png")) };
+    std::fs::create_dir_all(OUTPUT_DIR).unwrap();
+    image.save(output("actual")).unwrap();
+    if let Some(diff) = &mismatch.diff {
+        diff.save(output("diff")).unwrap();
+    }
+    panic!(
+        "{name} doesn't match {}: {mismatch}; see {}",
+        reference_path.display(),
+        OUTPUT_DIR
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_allows_the_tolerance() {
+        let reference = RgbaImage::from_pixel(4, 2, image::Rgba([100, 100, 100, 255]));
+        let mut image = reference.clone();
+        image.put_pixel(0, 0, image::Rgba([103, 97, 100, 255]));
+        assert!(compare(&image, &reference, 3).is_ok());
+
+        image.put_pixel(3, 1, image::Rgba([100, 100, 104, 255]));
+        let mismatch = compare(&image, &reference, 3).unwrap_err();
+        assert_eq!(mismatch.pixels, Some(1));
+        let diff = mismatch.diff.unwrap();
+        assert_eq!(diff.get_pixel(3, 1), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(diff.get_pixel(0, 0), &image::Rgba([25, 25, 25, 255]));
+
+        let smaller = RgbaImage::new(4, 1);
+        assert_eq!(compare(&smaller, &reference, 255).unwrap_err().pixels, None);
+    }
+}
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontArc, GlyphId, ScaleFont};
+use wgpu::util::DeviceExt;
+
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::texture::{ShelfAllocator, Texture};
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("text.wgsl");
+
+/// Side of the square glyph atlas, capped at the device limit.
+const ATLAS_SIZE: u32 = 1024;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextUniform {
+    screen_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    rect: [f32; 4],
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+}
+
+/// A string waiting for the next [`TextRenderer::draw`], in logical pixels.
+struct Section {
+    x: f32,
+    y: f32,
+    text: String,
+    px: f32,
+    color: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    id: GlyphId,
+    /// Bits of the size in physical pixels.
+    px: u32,
+}
+
+/// A rasterized glyph's place in the atlas.
+#[derive(Debug, Clone, Copy)]
+struct CachedGlyph {
+    /// Top-left corner of the bitmap relative to the pen on the baseline.
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_rect: [f32; 4],
+}
+
+/// Coverage bitmaps rasterized since the last draw, waiting to be copied
+/// into the atlas.
+struct Upload {
+    origin: [u32; 2],
+    size: [u32; 2],
+    coverage: Vec<u8>,
+}
+
+/// The glyph atlas is out of room for this frame's text.
+struct AtlasFull;
+
+/// Draws UTF-8 strings over a finished frame.
+///
+/// Strings are [`queue`](Self::queue)d at positions in logical pixels and
+/// painted by the next [`draw`](Self::draw). Glyphs are rasterized at the
+/// physical size for the current scale factor, so text stays sharp on HiDPI
+/// displays. Characters the bundled monospace font lacks fall back to a
+/// proportional font, then to an emoji font, and finally to the missing
+/// glyph box; text running off the target is clipped.
+pub struct TextRenderer {
+    fonts: Vec<FontArc>,
+    sections: Vec<Section>,
+    glyphs: HashMap<GlyphKey, Option<CachedGlyph>>,
+    /// Space in the atlas; when it fills up the atlas is cleared as a whole.
+    shelves: ShelfAllocator,
+    uploads: Vec<Upload>,
+    atlas: Texture,
+    atlas_bind_group: wgpu::BindGroup,
+    uniform_buffer: UniformBuffer<TextUniform>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+}
+
+impl TextRenderer {
+    /// Creates a renderer for a `width` by `height` target of `format` on a
+    /// display with `scale_factor` physical pixels per logical pixel.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Text",
+            &crate::struct_layout!(TextUniform {
+                screen_size,
+                _padding,
+            }),
+        );
+        let fonts = [
+            epaint_default_fonts::HACK_REGULAR,
+            epaint_default_fonts::UBUNTU_LIGHT,
+            epaint_default_fonts::NOTO_EMOJI_REGULAR,
+        ]
+        .into_iter()
+        .map(|data| FontArc::try_from_slice(data).expect("bundled fonts are valid"))
+        .collect();
+
+        let size = ATLAS_SIZE.min(device.limits().max_texture_dimension_2d);
+        let atlas = create_atlas(device, size);
+        let texture_layout = Texture::bind_group_layout(device);
+        let atlas_bind_group = atlas.bind_group(device, &texture_layout);
+        let uniform_buffer =
+            UniformBuffer::new(device, label!("text::uniform"), wgpu::ShaderStages::VERTEX);
+        let shader = create_shader(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("text::pipeline_layout"),
+            bind_group_layouts: &[uniform_buffer.layout(), &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format);
+
+        Self {
+            fonts,
+            sections: Vec::new(),
+            glyphs: HashMap::new(),
+            shelves: ShelfAllocator::new(size),
+            uploads: Vec::new(),
+            atlas,
+            atlas_bind_group,
+            uniform_buffer,
+            shader,
+            pipeline_layout,
+            pipeline,
+            width: width.max(1),
+            height: height.max(1),
+            scale_factor: scale_factor as f32,
+        }
+    }
+
+    /// Rebuilds the pipeline for a new target format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            create_pipeline(device, shader, &self.pipeline_layout, format)
+        });
+    }
+
+    /// Follows a resize of the target to `width` by `height` physical pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+    }
+
+    /// Follows the window to a display with a different scale factor. Glyphs
+    /// are rasterized again at the new physical size as they are drawn.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+    }
+
+    /// Queues `text` for the next [`draw`](Self::draw), with the top-left
+    /// corner of its first line at `x`, `y` and a font size of `px`, both in
+    /// logical pixels. `color` is sRGB with straight alpha. Lines break at
+    /// `\n` only.
+    pub fn queue(&mut self, x: f32, y: f32, text: &str, px: f32, color: [f32; 4]) {
+        if text.is_empty() || !(px > 0.0 && px.is_finite()) {
+            return;
+        }
+        self.sections.push(Section {
+            x,
+            y,
+            text: text.to_owned(),
+            px,
+            color,
+        });
+    }
+
+    /// Paints the queued text over `view` with `LoadOp::Load`, then clears
+    /// the queue.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let instances = match self.layout(false) {
+            Ok(instances) => instances,
+            Err(AtlasFull) => {
+                // Start the atlas over with only this frame's glyphs.
+                self.clear_atlas();
+                self.layout(true).unwrap_or_default()
+            }
+        };
+        self.sections.clear();
+        self.upload_glyphs(queue);
+        if instances.is_empty() {
+            return;
+        }
+
+        self.uniform_buffer.update(
+            queue,
+            &TextUniform {
+                screen_size: [self.width as f32, self.height as f32],
+                _padding: [0.0; 2],
+            },
+        );
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: label!("text::instance_buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("text::render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, self.uniform_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.atlas_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        render_pass.draw(0..4, 0..instances.len() as u32);
+    }
+
+    /// Lays out the queued sections into one quad per visible glyph,
+    /// rasterizing glyphs that aren't in the atlas yet. With `skip_overflow`
+    /// glyphs that don't fit are left out instead of failing.
+    fn layout(&mut self, skip_overflow: bool) -> Result<Vec<GlyphInstance>, AtlasFull> {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let mut instances = Vec::new();
+        let mut skipped = 0;
+        let sections = std::mem::take(&mut self.sections);
+        let result = sections.iter().try_for_each(|section| {
+            let px = section.px * self.scale_factor;
+            let primary = self.fonts[0].as_scaled(px);
+            let line_height = primary.height() + primary.line_gap();
+            let left = (section.x * self.scale_factor).round();
+            let mut caret = [
+                left,
+                (section.y * self.scale_factor + primary.ascent()).round(),
+            ];
+            let mut previous = None;
+            for c in section.text.chars() {
+                if c == '\n' {
+                    caret = [left, (caret[1] + line_height).round()];
+                    previous = None;
+                    continue;
+                }
+                if c.is_control() {
+                    continue;
+                }
+                let (font, id) = self.glyph_id(c);
+                let scaled = self.fonts[font].as_scaled(px);
+                if let Some((previous_font, previous_id)) = previous {
+                    if previous_font == font {
+                        caret[0] += scaled.kern(previous_id, id);
+                    }
+                }
+                let pen = [caret[0].round(), caret[1]];
+                caret[0] += scaled.h_advance(id);
+                previous = Some((font, id));
+                // Past the right or bottom edge nothing more of the line or
+                // section can show.
+                if pen[0] >= width || pen[1] - line_height >= height {
+                    continue;
+                }
+
+                let key = GlyphKey {
+                    font,
+                    id,
+                    px: px.to_bits(),
+                };
+                let glyph = match self.cached_glyph(key) {
+                    Ok(glyph) => glyph,
+                    Err(AtlasFull) if skip_overflow => {
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(AtlasFull) => return Err(AtlasFull),
+                };
+                let Some(glyph) = glyph else {
+                    continue;
+                };
+                let min = [pen[0] + glyph.offset[0], pen[1] + glyph.offset[1]];
+                let max = [min[0] + glyph.size[0], min[1] + glyph.size[1]];
+                if max[0] <= 0.0 || max[1] <= 0.0 || min[0] >= width || min[1] >= height {
+                    continue;
+                }
+                instances.push(GlyphInstance {
+                    rect: [min[0], min[1], max[0], max[1]],
+                    uv_rect: glyph.uv_rect,
+                    color: section.color,
+                });
+            }
+            Ok(())
+        });
+        self.sections = sections;
+        if skipped > 0 {
+            log::warn!("text: glyph atlas is full, skipped {skipped} glyphs");
+        }
+        result.map(|()| instances)
+    }
+
+    /// The first font with a glyph for `c`, falling back to the primary
+    /// font's missing glyph.
+    fn glyph_id(&self, c: char) -> (usize, GlyphId) {
+        self.fonts
+            .iter()
+            .enumerate()
+            .map(|(font, data)| (font, data.glyph_id(c)))
+            .find(|&(_, id)| id.0 != 0)
+            .unwrap_or((0, GlyphId(0)))
+    }
+
+    /// The glyph for `key`, rasterizing it into the atlas on first use.
+    /// `None` for glyphs with nothing to draw, like spaces.
+    fn cached_glyph(&mut self, key: GlyphKey) -> Result<Option<CachedGlyph>, AtlasFull> {
+        if let Some(&glyph) = self.glyphs.get(&key) {
+            return Ok(glyph);
+        }
+        let px = f32::from_bits(key.px);
+        let glyph = key
+            .id
+            .with_scale_and_position(px, ab_glyph::point(0.0, 0.0));
+        let Some(outlined) = self.fonts[key.font].outline_glyph(glyph) else {
+            self.glyphs.insert(key, None);
+            return Ok(None);
+        };
+        let bounds = outlined.px_bounds();
+        let size = [bounds.width() as u32, bounds.height() as u32];
+        // Glyphs too large for even an empty atlas are never drawn.
+        if size[0] == 0 || size[1] == 0 || !self.shelves.fits_empty(size[0], size[1]) {
+            self.glyphs.insert(key, None);
+            return Ok(None);
+        }
+        let origin = self.shelves.allocate(size[0], size[1]).ok_or(AtlasFull)?;
+
+        let mut coverage = vec![0; size[0] as usize * size[1] as usize];
+        outlined.draw(|x, y, value| {
+            if let Some(texel) = coverage.get_mut((y * size[0] + x) as usize) {
+                *texel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+        self.uploads.push(Upload {
+            origin,
+            size,
+            coverage,
+        });
+
+        let scale = 1.0 / self.shelves.size() as f32;
+        let glyph = CachedGlyph {
+            offset: [bounds.min.x, bounds.min.y],
+            size: [size[0] as f32, size[1] as f32],
+            uv_rect: [
+                origin[0] as f32 * scale,
+                origin[1] as f32 * scale,
+                (origin[0] + size[0]) as f32 * scale,
+                (origin[1] + size[1]) as f32 * scale,
+            ],
+        };
+        self.glyphs.insert(key, Some(glyph));
+        Ok(Some(glyph))
+    }
+
+    /// Forgets every rasterized glyph. The texels are left as they are and
+    /// overwritten as the space is handed out again.
+    fn clear_atlas(&mut self) {
+        self.glyphs.clear();
+        self.uploads.clear();
+        self.shelves.clear();
+    }
+
+    fn upload_glyphs(&mut self, queue: &wgpu::Queue) {
+        for upload in self.uploads.drain(..) {
+            let [x, y] = upload.origin;
+            let [width, height] = upload.size;
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.atlas.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &upload.coverage,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+fn create_atlas(device: &wgpu::Device, size: u32) -> Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: label!("text::atlas"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Quads are aligned to whole pixels, so each fragment samples exactly
+    // one texel.
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: label!("text::sampler"),
+        ..Default::default()
+    });
+    Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("text::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x4];
+    let mut constants = ShaderConstants::default();
+    let encode = if is_linear_output(format) { 0.0 } else { 1.0 };
+    constants.set("ENCODE_SRGB", encode);
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("text::pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &ATTRIBUTES,
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::test_support::test_gpu;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn renderer(gpu: &GpuContext) -> TextRenderer {
+        TextRenderer::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, 64, 32, 1.0)
+    }
+
+    #[test]
+    fn falls_back_through_the_fonts() {
+        let gpu = test_gpu!();
+        let text = renderer(&gpu);
+        assert_eq!(text.glyph_id('A').0, 0);
+        // Not in the monospace font, but in the proportional one.
+        assert_eq!(text.glyph_id('\u{0180}').0, 1);
+        assert_eq!(text.glyph_id('\u{1F600}').0, 2);
+        // In no font: the primary font's missing glyph box.
+        assert_eq!(text.glyph_id('\u{E000}'), (0, GlyphId(0)));
+    }
+
+    #[test]
+    fn lays_out_visible_glyphs_once() {
+        let gpu = test_gpu!();
+        let mut text = renderer(&gpu);
+        text.queue(0.0, 0.0, "a b\nc", 12.0, WHITE);
+        // Past the bottom edge.
+        text.queue(0.0, 40.0, "d", 12.0, WHITE);
+        let instances = text.layout(false).ok().unwrap();
+        assert_eq!(
+            instances.len(),
+            3,
+            "the space and clipped text draw nothing"
+        );
+        assert_eq!(text.uploads.len(), 3);
+        // `c` is on the next line.
+        assert!(instances[2].rect[1] > instances[0].rect[1]);
+        assert!(instances[2].rect[0] < instances[1].rect[0]);
+
+        // Cached glyphs aren't rasterized again.
+        text.uploads.clear();
+        text.sections.clear();
+        text.queue(10.0, 10.0, "cab", 12.0, WHITE);
+        assert_eq!(text.layout(false).ok().unwrap().len(), 3);
+        assert!(text.uploads.is_empty());
+    }
+
+    #[test]
+    fn skips_glyphs_once_the_atlas_is_full() {
+        let gpu = test_gpu!();
+        let mut text = renderer(&gpu);
+        text.shelves = ShelfAllocator::new(32);
+        text.queue(0.0, 0.0, "ABCDEFGH", 16.0, WHITE);
+        assert!(text.layout(false).is_err());
+        let instances = text.layout(true).ok().unwrap();
+        assert!(!instances.is_empty() && instances.len() < 8);
+    }
+
+    #[test]
+    fn draws_coverage_into_the_target() {
+        let gpu = test_gpu!();
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut text = renderer(&gpu);
+        text.queue(2.0, 2.0, "\u{2588}", 16.0, [1.0, 0.0, 0.0, 1.0]);
+        let mut frame = FrameEncoder::new(device, queue, None);
+        text.draw(device, queue, &mut frame, &view);
+        frame.submit();
+
+        let pixels = crate::capture::read_texture_rgba(device, queue, &target).unwrap();
+        let red = pixels
+            .chunks_exact(4)
+            .filter(|pixel| pixel[0] > 200 && pixel[1] == 0)
+            .count();
+        assert!(red > 50, "only {red} pixels covered");
+        // The top-left corner is left alone.
+        assert_eq!(&pixels[..4], &[0, 0, 0, 0]);
+    }
+}
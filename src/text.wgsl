@@ -0,0 +1,72 @@
+// Set when the output format is not sRGB, so the encode happens here
+// instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+
+struct Text {
+    // Size of the target in physical pixels.
+    screen_size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> text: Text;
+
+// Glyph coverage in the red channel.
+@group(1) @binding(0)
+var glyph_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var glyph_sampler: sampler;
+
+struct GlyphInput {
+    // Corners of the quad in physical pixels: min x, min y, max x, max y.
+    @location(0) rect: vec4<f32>,
+    @location(1) uv_rect: vec4<f32>,
+    // sRGB with straight alpha.
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // Linear with straight alpha.
+    @location(1) color: vec4<f32>,
+}
+
+fn srgb_to_linear(value: vec3<f32>) -> vec3<f32> {
+    let low = value / 12.92;
+    let high = pow((value + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, value <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+// One quad per instance, drawn as a four-vertex triangle strip.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, glyph: GlyphInput) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let position = mix(glyph.rect.xy, glyph.rect.zw, corner);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        2.0 * position.x / text.screen_size.x - 1.0,
+        1.0 - 2.0 * position.y / text.screen_size.y,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = mix(glyph.uv_rect.xy, glyph.uv_rect.zw, corner);
+    out.color = vec4<f32>(srgb_to_linear(glyph.color.rgb), glyph.color.a);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = in.color.a * textureSample(glyph_atlas, glyph_sampler, in.tex_coords).r;
+    var rgb = clamp(in.color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if ENCODE_SRGB {
+        rgb = linear_to_srgb(rgb);
+    }
+    return vec4<f32>(rgb * alpha, alpha);
+}
@@ -0,0 +1,836 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::path::Path;
+
+use image::{DynamicImage, ImageError, RgbaImage};
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// A 2D texture or texture array with its view and sampler.
+#[derive(Clone)]
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+/// Furthest the scene shader shifts the mip level it samples, either way;
+/// WGSL's `textureSampleBias` takes biases in `[-16, 15.99]`.
+pub const MAX_LOD_BIAS: f32 = 15.99;
+
+/// How a [`Texture`]'s sampler filters and wraps, e.g. nearest filtering
+/// for pixel art and linear for photographs.
+///
+/// wgpu samplers have no LOD bias; the scene shader applies one to every
+/// texture instead, see
+/// [`ScenePipeline::set_lod_bias`](crate::pipeline::ScenePipeline::set_lod_bias).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerOptions {
+    /// Wrapping in every direction.
+    pub address_mode: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Finest mip level sampled, e.g. above 0 to blur a texture.
+    pub lod_min_clamp: f32,
+    /// Coarsest mip level sampled, e.g. to keep a texture from blurring out
+    /// in the distance.
+    pub lod_max_clamp: f32,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// The default wrapping with `filter` for magnification, minification
+    /// and between mip levels.
+    pub fn filtered(filter: wgpu::FilterMode) -> Self {
+        Self {
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Self::default()
+        }
+    }
+
+    /// These options, with linear filtering turned to nearest if `format`
+    /// can't be filtered with `features`, e.g. 32-bit float formats without
+    /// `Features::FLOAT32_FILTERABLE`, and the LOD clamps made valid: not
+    /// negative, with the minimum at most the maximum.
+    fn supported(mut self, format: wgpu::TextureFormat, features: wgpu::Features) -> Self {
+        if !(self.lod_min_clamp >= 0.0 && self.lod_min_clamp <= self.lod_max_clamp) {
+            log::warn!(
+                "invalid LOD clamps {}..{}, using 0..{}",
+                self.lod_min_clamp,
+                self.lod_max_clamp,
+                self.lod_max_clamp.max(0.0)
+            );
+            self.lod_min_clamp = 0.0;
+            self.lod_max_clamp = self.lod_max_clamp.max(0.0);
+        }
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .contains(&wgpu::FilterMode::Linear);
+        let filterable = format
+            .guaranteed_format_features(features)
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+        if !linear || filterable {
+            return self;
+        }
+        log::warn!("{format:?} textures can't be filtered linearly, using nearest filtering");
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..self
+        }
+    }
+}
+
+impl Texture {
+    /// Decodes an encoded image (PNG or JPEG).
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self, ImageError> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &image, Some(label)))
+    }
+
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        let image = image::open(path)?;
+        Ok(Self::from_image(
+            device,
+            queue,
+            &image,
+            Some(&path.display().to_string()),
+        ))
+    }
+
+    /// Uploads an image of any size and channel layout as sRGB RGBA8, with
+    /// a full mip chain so [`SamplerOptions`]' mip filter and LOD clamps
+    /// apply.
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &DynamicImage,
+        label: Option<&str>,
+    ) -> Self {
+        let rgba = image.to_rgba8();
+        let mip_level_count = full_mip_level_count(rgba.width(), rgba.height());
+        Self::upload(device, queue, vec![rgba], mip_level_count, label)
+    }
+
+    /// Uploads `layers` as the layers of a texture array, like
+    /// [`from_image`](Self::from_image), for materials bound with
+    /// [`array_bind_group`](Self::array_bind_group). Layers are resized to
+    /// the size of the first; an instance picks one with
+    /// [`Instance::layer`](crate::instance::Instance::layer).
+    ///
+    /// A single layer is stored twice: GL can only view textures made with
+    /// more than one layer as arrays.
+    pub fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[RgbaImage],
+        label: Option<&str>,
+    ) -> Self {
+        let (width, height) = layers
+            .first()
+            .expect("a texture array needs at least one layer")
+            .dimensions();
+        let mut layers: Vec<_> = layers
+            .iter()
+            .map(|layer| {
+                if layer.dimensions() == (width, height) {
+                    layer.clone()
+                } else {
+                    image::imageops::resize(
+                        layer,
+                        width,
+                        height,
+                        image::imageops::FilterType::Triangle,
+                    )
+                }
+            })
+            .collect();
+        if layers.len() == 1 {
+            layers.push(layers[0].clone());
+        }
+        let mip_level_count = full_mip_level_count(width, height);
+        Self::upload(device, queue, layers, mip_level_count, label)
+    }
+
+    /// Uploads each of the same-sized `layers` to the first
+    /// `mip_level_count` levels of its layer of a texture, each half the
+    /// size of the one before.
+    ///
+    /// The levels are downsampled in sRGB space, which darkens fine detail a
+    /// little compared to averaging linear colors but needs no GPU pass.
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut layers: Vec<RgbaImage>,
+        mip_level_count: u32,
+        label: Option<&str>,
+    ) -> Self {
+        let (width, height) = layers[0].dimensions();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers.len() as u32,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for mip_level in 0..mip_level_count {
+            for (z, level) in (0..).zip(&mut layers) {
+                if mip_level > 0 {
+                    *level = image::imageops::resize(
+                        level,
+                        (level.width() / 2).max(1),
+                        (level.height() / 2).max(1),
+                        image::imageops::FilterType::Triangle,
+                    );
+                }
+                let (width, height) = level.dimensions();
+                // Unlike buffer-to-texture copies, `write_texture` has no
+                // 256-byte row alignment requirement, so tightly packed rows
+                // are fine.
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    level,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * width),
+                        rows_per_image: Some(height),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = create_sampler(device, label, &SamplerOptions::default());
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Replaces the sampler with one made from `options`. Bind groups made
+    /// before keep the old sampler, so recreate them with
+    /// [`bind_group`](Self::bind_group).
+    ///
+    /// Linear filtering falls back to nearest, with a warning, if the
+    /// texture's format can't be filtered on `device`.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, options: SamplerOptions) {
+        let options = options.supported(self.texture.format(), device.features());
+        self.sampler = create_sampler(device, crate::label!("texture::sampler"), &options);
+    }
+
+    /// Creates a depth buffer matching the surface size, at least 1x1.
+    ///
+    /// `sample_count` must match the color target it is used with. Only a
+    /// single-sampled depth buffer can be bound for sampling.
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        // On GL, sampleable multisampled depth textures break the color
+        // resolve at the end of the pass, so leave them attachment-only.
+        let usage = if sample_count > 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Layout with the texture at binding 0 and its sampler at binding 1.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        create_bind_group_layout(
+            device,
+            crate::label!("texture::bind_group_layout"),
+            wgpu::TextureViewDimension::D2,
+        )
+    }
+
+    /// Like [`bind_group_layout`](Self::bind_group_layout), with the texture
+    /// viewed as an array, as the scene shader samples materials.
+    pub fn array_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        create_bind_group_layout(
+            device,
+            crate::label!("texture::array_bind_group_layout"),
+            wgpu::TextureViewDimension::D2Array,
+        )
+    }
+
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        create_bind_group(device, layout, &self.view, &self.sampler)
+    }
+
+    /// A bind group for [`array_bind_group_layout`](Self::array_bind_group_layout),
+    /// viewing the texture as an array. On GL, only textures made with
+    /// [`from_layers`](Self::from_layers) can be viewed that way.
+    pub fn array_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        create_bind_group(device, layout, &view, &self.sampler)
+    }
+}
+
+/// Mip levels down to 1x1 from a `width` by `height` texture.
+fn full_mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+fn create_bind_group_layout(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: crate::label!("texture::bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn create_sampler(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    options: &SamplerOptions,
+) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label,
+        address_mode_u: options.address_mode,
+        address_mode_v: options.address_mode,
+        address_mode_w: options.address_mode,
+        mag_filter: options.mag_filter,
+        min_filter: options.min_filter,
+        mipmap_filter: options.mipmap_filter,
+        lod_min_clamp: options.lod_min_clamp,
+        lod_max_clamp: options.lod_max_clamp,
+        ..Default::default()
+    })
+}
+
+/// Transparent pixels left between packed images, so filtering at an edge
+/// doesn't pick up the neighbour.
+const ATLAS_PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// An image's pixel data doesn't match its dimensions.
+    InvalidImage {
+        index: usize,
+        width: u32,
+        height: u32,
+        len: usize,
+    },
+    /// The images don't fit even at the maximum texture size.
+    DoesNotFit { max: u32 },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidImage {
+                index,
+                width,
+                height,
+                len,
+            } => write!(
+                f,
+                "image {index} is {width}x{height} but has {len} bytes instead of {}",
+                *width as usize * *height as usize * 4
+            ),
+            Self::DoesNotFit { max } => {
+                write!(f, "the images don't fit in a {max}x{max} atlas")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+/// An image to pack into a [`TextureAtlas`], as tightly packed sRGB RGBA8.
+pub struct AtlasImage<'a, K> {
+    pub id: K,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+/// Many small images packed into one square texture, e.g. for sprites, so
+/// they can be drawn with a single bind group.
+pub struct TextureAtlas<K> {
+    texture: Texture,
+    bind_group: wgpu::BindGroup,
+    uv_rects: HashMap<K, [f32; 4]>,
+}
+
+impl<K: Hash + Eq> TextureAtlas<K> {
+    /// Packs `images` into the smallest power-of-two square texture they fit
+    /// in, up to the device's `max_texture_dimension_2d`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: Vec<AtlasImage<'_, K>>,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self, AtlasError> {
+        for (index, image) in images.iter().enumerate() {
+            let len = image.rgba.len();
+            if len != image.width as usize * image.height as usize * 4 {
+                return Err(AtlasError::InvalidImage {
+                    index,
+                    width: image.width,
+                    height: image.height,
+                    len,
+                });
+            }
+        }
+        let max = device.limits().max_texture_dimension_2d;
+        let sizes: Vec<[u32; 2]> = images
+            .iter()
+            .map(|image| [image.width, image.height])
+            .collect();
+        let (size, positions) = pack_shelves(&sizes, max).ok_or(AtlasError::DoesNotFit { max })?;
+
+        let mut canvas = RgbaImage::new(size, size);
+        let mut uv_rects = HashMap::with_capacity(images.len());
+        for (image, [x, y]) in images.into_iter().zip(positions) {
+            let row_bytes = (image.width as usize * 4).max(1);
+            for (row, pixels) in image.rgba.chunks_exact(row_bytes).enumerate() {
+                let start = ((y as usize + row) * size as usize + x as usize) * 4;
+                canvas.as_mut()[start..start + pixels.len()].copy_from_slice(pixels);
+            }
+            let scale = 1.0 / size as f32;
+            let uv_rect = [
+                x as f32 * scale,
+                y as f32 * scale,
+                (x + image.width) as f32 * scale,
+                (y + image.height) as f32 * scale,
+            ];
+            uv_rects.insert(image.id, uv_rect);
+        }
+        // A single level: smaller mips would blend neighbouring images
+        // across the one-texel padding.
+        let texture = Texture::upload(
+            device,
+            queue,
+            vec![canvas],
+            1,
+            crate::label!("texture::atlas"),
+        );
+        let bind_group = texture.bind_group(device, layout);
+        Ok(Self {
+            texture,
+            bind_group,
+            uv_rects,
+        })
+    }
+
+    /// The image's `[u_min, v_min, u_max, v_max]` within the atlas.
+    pub fn uv_rect(&self, id: &K) -> Option<[f32; 4]> {
+        self.uv_rects.get(id).copied()
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Bind group for the layout passed to [`new`](Self::new), usually
+    /// [`Texture::bind_group_layout`].
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// Hands out space in a square texture left to right on shelves, opening a
+/// new shelf below once the current one is full. A transparent texel is
+/// left after each rectangle, so filtering at its edge doesn't pick up the
+/// neighbour.
+///
+/// Nothing is freed individually; [`clear`](Self::clear) starts over, e.g.
+/// when a glyph cache fills up.
+#[derive(Debug, Clone)]
+pub struct ShelfAllocator {
+    size: u32,
+    x: u32,
+    y: u32,
+    shelf_height: u32,
+}
+
+impl ShelfAllocator {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            x: 0,
+            y: 0,
+            shelf_height: 0,
+        }
+    }
+
+    /// Width and height of the square being allocated from.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether a `width` by `height` rectangle fits in an empty square.
+    pub fn fits_empty(&self, width: u32, height: u32) -> bool {
+        width + ATLAS_PADDING <= self.size && height + ATLAS_PADDING <= self.size
+    }
+
+    /// The top-left corner for a `width` by `height` rectangle, or `None`
+    /// when the square is full.
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        let (width, height) = (width + ATLAS_PADDING, height + ATLAS_PADDING);
+        if self.x + width > self.size {
+            self.x = 0;
+            self.y += self.shelf_height;
+            self.shelf_height = 0;
+        }
+        if width > self.size || self.y + height > self.size {
+            return None;
+        }
+        let origin = [self.x, self.y];
+        self.x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(origin)
+    }
+
+    /// Makes the whole square available again.
+    pub fn clear(&mut self) {
+        *self = Self::new(self.size);
+    }
+}
+
+/// Places rectangles of `sizes` on shelves in the smallest power-of-two
+/// square up to `max`, returning that size and each rectangle's top-left
+/// corner in input order.
+///
+/// Tallest rectangles go first, so each shelf wastes little height.
+fn pack_shelves(sizes: &[[u32; 2]], max: u32) -> Option<(u32, Vec<[u32; 2]>)> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((sizes[i][1], sizes[i][0])));
+
+    let padded = |[width, height]: [u32; 2]| [width + ATLAS_PADDING, height + ATLAS_PADDING];
+    let area: u64 = sizes
+        .iter()
+        .map(|&size| {
+            let [width, height] = padded(size);
+            width as u64 * height as u64
+        })
+        .sum();
+    let widest = sizes.iter().map(|&size| padded(size)[0]).max().unwrap_or(1);
+    let tallest = sizes.iter().map(|&size| padded(size)[1]).max().unwrap_or(1);
+    let mut size = widest
+        .max(tallest)
+        .max((area as f64).sqrt().ceil() as u32)
+        .max(1)
+        .checked_next_power_of_two()?;
+
+    'grow: while size <= max {
+        let mut shelves = ShelfAllocator::new(size);
+        let mut positions = vec![[0, 0]; sizes.len()];
+        for &i in &order {
+            let [width, height] = sizes[i];
+            let Some(position) = shelves.allocate(width, height) else {
+                size = size.checked_mul(2)?;
+                continue 'grow;
+            };
+            positions[i] = position;
+        }
+        return Some((size, positions));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_gpu;
+
+    #[test]
+    fn shelf_allocator_fills_shelves_left_to_right() {
+        let mut shelves = ShelfAllocator::new(16);
+        assert_eq!(shelves.allocate(4, 3), Some([0, 0]));
+        // One texel of padding after each rectangle.
+        assert_eq!(shelves.allocate(4, 5), Some([5, 0]));
+        assert_eq!(shelves.allocate(5, 2), Some([10, 0]));
+        // Too wide for the rest of the shelf: a new one opens below the
+        // tallest rectangle on this one.
+        assert_eq!(shelves.allocate(2, 2), Some([0, 6]));
+    }
+
+    #[test]
+    fn shelf_allocator_reports_full_and_clears() {
+        let mut shelves = ShelfAllocator::new(8);
+        assert!(shelves.fits_empty(7, 7));
+        assert!(!shelves.fits_empty(8, 1));
+        assert_eq!(shelves.allocate(7, 7), Some([0, 0]));
+        assert_eq!(shelves.allocate(1, 1), None);
+        shelves.clear();
+        assert_eq!(shelves.allocate(1, 1), Some([0, 0]));
+    }
+
+    #[test]
+    fn pack_shelves_keeps_input_order_and_padding() {
+        let sizes = [[2, 2], [6, 6], [3, 4]];
+        let (size, positions) = pack_shelves(&sizes, 1024).unwrap();
+        assert_eq!(size, 16);
+        // Tallest first: the 6x6 image opens the first shelf.
+        assert_eq!(positions, vec![[11, 0], [0, 0], [7, 0]]);
+        for (i, (&[w, h], &[x, y])) in sizes.iter().zip(&positions).enumerate() {
+            assert!(x + w + ATLAS_PADDING <= size && y + h + ATLAS_PADDING <= size);
+            for (&[w2, h2], &[x2, y2]) in sizes.iter().zip(&positions).skip(i + 1) {
+                let apart = x + w + ATLAS_PADDING <= x2
+                    || x2 + w2 + ATLAS_PADDING <= x
+                    || y + h + ATLAS_PADDING <= y2
+                    || y2 + h2 + ATLAS_PADDING <= y;
+                assert!(apart, "padded images overlap");
+            }
+        }
+    }
+
+    #[test]
+    fn pack_shelves_grows_to_the_next_power_of_two() {
+        // Five 7x7 images padded to 8x8 need more than one 16x16 square.
+        let (size, _) = pack_shelves(&[[7, 7]; 5], 1024).unwrap();
+        assert_eq!(size, 32);
+    }
+
+    #[test]
+    fn pack_shelves_fails_past_max() {
+        assert_eq!(pack_shelves(&[[64, 64]], 64), None);
+        assert_eq!(pack_shelves(&[[31, 31]; 5], 64), None);
+        assert!(pack_shelves(&[[31, 31]; 4], 64).is_some());
+    }
+
+    #[test]
+    fn linear_filtering_needs_a_filterable_format() {
+        let linear = SamplerOptions {
+            address_mode: wgpu::AddressMode::Repeat,
+            ..SamplerOptions::filtered(wgpu::FilterMode::Linear)
+        };
+        let srgb = wgpu::TextureFormat::Rgba8UnormSrgb;
+        assert_eq!(linear.supported(srgb, wgpu::Features::empty()), linear);
+        let float = wgpu::TextureFormat::Rgba32Float;
+        assert_eq!(
+            linear.supported(float, wgpu::Features::FLOAT32_FILTERABLE),
+            linear
+        );
+        let nearest = linear.supported(float, wgpu::Features::empty());
+        assert_eq!(nearest.min_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(nearest.mag_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(nearest.address_mode, wgpu::AddressMode::Repeat);
+    }
+
+    #[test]
+    fn lod_clamps_are_made_valid() {
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let clamped = |lod_min_clamp, lod_max_clamp| {
+            let options = SamplerOptions {
+                lod_min_clamp,
+                lod_max_clamp,
+                ..SamplerOptions::default()
+            }
+            .supported(format, wgpu::Features::empty());
+            (options.lod_min_clamp, options.lod_max_clamp)
+        };
+        assert_eq!(clamped(1.0, 4.0), (1.0, 4.0));
+        assert_eq!(clamped(2.0, 2.0), (2.0, 2.0));
+        assert_eq!(clamped(5.0, 4.0), (0.0, 4.0));
+        assert_eq!(clamped(-1.0, 4.0), (0.0, 4.0));
+        assert_eq!(clamped(0.0, -1.0), (0.0, 0.0));
+        assert_eq!(clamped(f32::NAN, 4.0), (0.0, 4.0));
+    }
+
+    #[test]
+    fn images_get_a_full_mip_chain() {
+        let gpu = test_gpu!();
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(20, 6));
+        let texture = Texture::from_image(&gpu.device, &gpu.queue, &image, None);
+        // 20x6, 10x3, 5x1, 2x1 and 1x1.
+        assert_eq!(texture.texture.mip_level_count(), 5);
+    }
+
+    #[test]
+    fn layers_are_resized_to_the_first() {
+        let gpu = test_gpu!();
+        let layers = [RgbaImage::new(8, 4), RgbaImage::new(3, 3)];
+        let texture = Texture::from_layers(&gpu.device, &gpu.queue, &layers, None);
+        assert_eq!(texture.texture.size().depth_or_array_layers, 2);
+        assert_eq!((texture.texture.width(), texture.texture.height()), (8, 4));
+        assert_eq!(texture.texture.mip_level_count(), 4);
+        // A lone layer is doubled, for GL to view it as an array.
+        let texture = Texture::from_layers(&gpu.device, &gpu.queue, &layers[..1], None);
+        assert_eq!(texture.texture.size().depth_or_array_layers, 2);
+    }
+
+    #[test]
+    fn texture_atlas_rejects_mismatched_pixels() {
+        let gpu = test_gpu!();
+        let layout = Texture::bind_group_layout(&gpu.device);
+        let images = vec![AtlasImage {
+            id: "bad",
+            width: 2,
+            height: 2,
+            rgba: &[0; 15],
+        }];
+        let result = TextureAtlas::new(&gpu.device, &gpu.queue, images, &layout);
+        assert!(matches!(
+            result,
+            Err(AtlasError::InvalidImage {
+                index: 0,
+                len: 15,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn texture_atlas_maps_uv_rects() {
+        let gpu = test_gpu!();
+        let layout = Texture::bind_group_layout(&gpu.device);
+        let (red, blue) = (
+            [255, 0, 0, 255].repeat(6 * 6),
+            [0, 0, 255, 255].repeat(2 * 2),
+        );
+        let images = vec![
+            AtlasImage {
+                id: "red",
+                width: 6,
+                height: 6,
+                rgba: &red,
+            },
+            AtlasImage {
+                id: "blue",
+                width: 2,
+                height: 2,
+                rgba: &blue,
+            },
+        ];
+        let atlas = TextureAtlas::new(&gpu.device, &gpu.queue, images, &layout).unwrap();
+        assert_eq!(atlas.texture().texture.width(), 16);
+        assert_eq!(atlas.uv_rect(&"red"), Some([0.0, 0.0, 0.375, 0.375]));
+        assert_eq!(atlas.uv_rect(&"blue"), Some([0.4375, 0.0, 0.5625, 0.125]));
+        assert_eq!(atlas.uv_rect(&"green"), None);
+    }
+}
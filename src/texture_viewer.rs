@@ -0,0 +1,422 @@
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::uniform::UniformBuffer;
+
+const SHADER: &str = include_str!("texture_viewer.wgsl");
+
+/// Gap in physical pixels between the viewer and the edges of the output.
+const MARGIN: f32 = 8.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewerUniform {
+    znear: f32,
+    zfar: f32,
+    _padding: [f32; 2],
+}
+
+/// How a debug texture's contents are turned into colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugTextureKind {
+    /// Float color, shown as is.
+    Color,
+    /// A depth buffer, shown as gray from black at the near plane to white at
+    /// the far plane.
+    Depth,
+}
+
+/// An intermediate texture the [`TextureViewer`] can show. The view must
+/// have `TEXTURE_BINDING` usage and a single sample.
+pub struct DebugTexture<'a> {
+    pub name: &'a str,
+    pub view: &'a wgpu::TextureView,
+    pub size: [u32; 2],
+    pub kind: DebugTextureKind,
+}
+
+/// Shows one of the frame's intermediate textures in the bottom-right corner
+/// of the output, e.g. to check what an earlier pass wrote.
+///
+/// [`cycle`](Self::cycle) steps through the available textures and then
+/// hides the viewer again.
+pub struct TextureViewer {
+    color_layout: wgpu::BindGroupLayout,
+    depth_layout: wgpu::BindGroupLayout,
+    uniform_buffer: UniformBuffer<ViewerUniform>,
+    shader: wgpu::ShaderModule,
+    color_pipeline_layout: wgpu::PipelineLayout,
+    depth_pipeline_layout: wgpu::PipelineLayout,
+    color_pipeline: wgpu::RenderPipeline,
+    depth_pipeline: wgpu::RenderPipeline,
+    size: u32,
+    selected: Option<usize>,
+}
+
+impl TextureViewer {
+    /// Creates a hidden viewer drawing into `format`, showing textures at
+    /// most `size` physical pixels on their longer side.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: u32) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Viewer",
+            &crate::struct_layout!(ViewerUniform {
+                znear,
+                zfar,
+                _padding,
+            }),
+        );
+        let texture_layout = |binding, label| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label,
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            })
+        };
+        let color_layout = texture_layout(0, label!("texture_viewer::color_layout"));
+        let depth_layout = texture_layout(1, label!("texture_viewer::depth_layout"));
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("texture_viewer::uniform"),
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        let color_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: label!("texture_viewer::color_pipeline_layout"),
+                bind_group_layouts: &[&color_layout],
+                push_constant_ranges: &[],
+            });
+        let depth_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: label!("texture_viewer::depth_pipeline_layout"),
+                bind_group_layouts: &[&depth_layout, uniform_buffer.layout()],
+                push_constant_ranges: &[],
+            });
+        let shader = create_shader(device);
+        let color_pipeline =
+            create_pipeline(device, &shader, &color_pipeline_layout, "fs_color", format);
+        let depth_pipeline =
+            create_pipeline(device, &shader, &depth_pipeline_layout, "fs_depth", format);
+
+        Self {
+            color_layout,
+            depth_layout,
+            uniform_buffer,
+            shader,
+            color_pipeline_layout,
+            depth_pipeline_layout,
+            color_pipeline,
+            depth_pipeline,
+            size,
+            selected: None,
+        }
+    }
+
+    /// Rebuilds the pipelines for a new output format.
+    pub fn set_format(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        let mut depth_pipeline = None;
+        self.color_pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            depth_pipeline = Some(create_pipeline(
+                device,
+                shader,
+                &self.depth_pipeline_layout,
+                "fs_depth",
+                format,
+            ));
+            create_pipeline(
+                device,
+                shader,
+                &self.color_pipeline_layout,
+                "fs_color",
+                format,
+            )
+        });
+        self.depth_pipeline = depth_pipeline.expect("built with the color pipeline");
+    }
+
+    /// Sets the longer side of the shown texture, in physical pixels.
+    pub fn set_size(&mut self, size: u32) {
+        self.size = size;
+    }
+
+    /// Sets the clip planes depth textures were rendered with.
+    pub fn set_depth_range(&self, queue: &wgpu::Queue, znear: f32, zfar: f32) {
+        self.uniform_buffer.update(
+            queue,
+            &ViewerUniform {
+                znear,
+                zfar,
+                _padding: [0.0; 2],
+            },
+        );
+    }
+
+    /// Index of the shown texture, `None` while hidden.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Shows the next of `count` textures, hiding the viewer after the last.
+    pub fn cycle(&mut self, count: usize) {
+        self.selected = match self.selected {
+            None if count > 0 => Some(0),
+            Some(index) if index + 1 < count => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    /// Where a `texture_size` texture is shown on a `output_size` output:
+    /// `[x, y, width, height]` in physical pixels, keeping the aspect ratio.
+    pub fn rect(&self, texture_size: [u32; 2], output_size: [u32; 2]) -> [f32; 4] {
+        let [texture_width, texture_height] = texture_size.map(|side| side.max(1) as f32);
+        let [output_width, output_height] = output_size.map(|side| side as f32);
+        let fit = (self.size as f32 / texture_width.max(texture_height))
+            .min((output_width - 2.0 * MARGIN) / texture_width)
+            .min((output_height - 2.0 * MARGIN) / texture_height)
+            .max(0.0);
+        let (width, height) = (texture_width * fit, texture_height * fit);
+        [
+            output_width - MARGIN - width,
+            output_height - MARGIN - height,
+            width,
+            height,
+        ]
+    }
+
+    /// Draws `texture` over `output`, which is `output_size` physical pixels,
+    /// and returns the rect it was drawn in.
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        frame: &mut FrameEncoder,
+        output: &wgpu::TextureView,
+        output_size: [u32; 2],
+        texture: &DebugTexture<'_>,
+    ) -> [f32; 4] {
+        let rect = self.rect(texture.size, output_size);
+        let [x, y, width, height] = rect;
+        if width < 1.0 || height < 1.0 {
+            return rect;
+        }
+        let (layout, binding, pipeline) = match texture.kind {
+            DebugTextureKind::Color => (&self.color_layout, 0, &self.color_pipeline),
+            DebugTextureKind::Depth => (&self.depth_layout, 1, &self.depth_pipeline),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: label!("texture_viewer::bind_group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(texture.view),
+            }],
+        });
+        let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: label!("texture_viewer::pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        if texture.kind == DebugTextureKind::Depth {
+            render_pass.set_bind_group(1, self.uniform_buffer.bind_group(), &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+        rect
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("texture_viewer::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    fragment_entry: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let mut constants = ShaderConstants::default();
+    constants.set(
+        "ENCODE_SRGB",
+        if is_linear_output(format) { 0.0 } else { 1.0 },
+    );
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("texture_viewer::pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fragment_entry),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::read_texture_rgba;
+    use crate::test_support::test_gpu;
+    use crate::texture::DEPTH_FORMAT;
+
+    #[test]
+    fn cycle_wraps_through_hidden() {
+        let gpu = test_gpu!();
+        let mut viewer = TextureViewer::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, 256);
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            viewer.cycle(2);
+            seen.push(viewer.selected());
+        }
+        assert_eq!(seen, [Some(0), Some(1), None, Some(0)]);
+        viewer.cycle(0);
+        viewer.cycle(0);
+        assert_eq!(viewer.selected(), None);
+    }
+
+    #[test]
+    fn rect_keeps_the_aspect_ratio_in_the_corner() {
+        let gpu = test_gpu!();
+        let viewer = TextureViewer::new(&gpu.device, wgpu::TextureFormat::Rgba8Unorm, 200);
+        assert_eq!(
+            viewer.rect([800, 400], [1000, 500]),
+            [792.0, 392.0, 200.0, 100.0]
+        );
+        // Shrunk to fit a small output.
+        assert_eq!(viewer.rect([100, 100], [116, 66]), [58.0, 8.0, 50.0, 50.0]);
+    }
+
+    #[test]
+    fn depth_is_shown_linearized() {
+        let gpu = test_gpu!();
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&Default::default());
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 32,
+                height: 32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output.create_view(&Default::default());
+        let viewer = TextureViewer::new(device, wgpu::TextureFormat::Rgba8Unorm, 16);
+        // Halfway between the planes in view distance.
+        let (znear, zfar) = (1.0, 3.0);
+        let depth_value = zfar / (zfar - znear) * (1.0 - znear / 2.0);
+        viewer.set_depth_range(queue, znear, zfar);
+
+        let mut frame = FrameEncoder::new(device, queue, None);
+        frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(depth_value),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        let rect = viewer.draw(
+            device,
+            &mut frame,
+            &output_view,
+            [32, 32],
+            &DebugTexture {
+                name: "depth",
+                view: &depth_view,
+                size: [4, 4],
+                kind: DebugTextureKind::Depth,
+            },
+        );
+        frame.submit();
+        assert_eq!(rect, [8.0, 8.0, 16.0, 16.0]);
+
+        let pixels = read_texture_rgba(device, queue, &output).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 32 + x) * 4..][..4];
+        // The gray encodes 0.5 for the non-sRGB output.
+        let gray = pixel(16, 16);
+        assert!((gray[0] as i32 - 188).abs() <= 2, "{gray:?}");
+        assert_eq!(pixel(2, 2), [0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,71 @@
+// Set when the output format is not sRGB, so the encode happens here
+// instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+
+// Only one of these is bound, depending on the texture's kind. Depth is
+// bound as unfilterable float rather than `texture_depth_2d`, which GL can't
+// `textureLoad` from.
+@group(0) @binding(0)
+var color_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var depth_texture: texture_2d<f32>;
+
+struct Viewer {
+    // Camera clip planes the depth texture was rendered with.
+    znear: f32,
+    zfar: f32,
+    _padding: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> viewer: Viewer;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A single triangle covering the viewport, which is set to the rect.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+fn output(rgb: vec3<f32>) -> vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4<f32>(linear_to_srgb(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0))), 1.0);
+    }
+    return vec4<f32>(rgb, 1.0);
+}
+
+// Nearest texel, read without a sampler so unfilterable formats work too.
+fn texel(size: vec2<u32>, tex_coords: vec2<f32>) -> vec2<u32> {
+    return min(vec2<u32>(tex_coords * vec2<f32>(size)), size - 1u);
+}
+
+@fragment
+fn fs_color(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(color_texture);
+    return output(textureLoad(color_texture, texel(size, in.tex_coords), 0).rgb);
+}
+
+// Perspective depth is bunched up near 1.0, so it is converted back to view
+// distance and shown from black at the near plane to white at the far one.
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(depth_texture);
+    let depth = textureLoad(depth_texture, texel(size, in.tex_coords), 0).r;
+    let distance = viewer.znear * viewer.zfar / (viewer.zfar - depth * (viewer.zfar - viewer.znear));
+    let value = (distance - viewer.znear) / (viewer.zfar - viewer.znear);
+    return output(vec3<f32>(value));
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(1);
+/// Frames this much slower than the baseline count as degraded.
+const SLOWDOWN_RATIO: f64 = 1.25;
+/// Consecutive degraded windows before throttling is reported.
+const SUSTAINED_WINDOWS: u32 = 5;
+
+/// Heuristic thermal-throttling detector.
+///
+/// wgpu exposes no thermal or power state, so this watches the mean busy
+/// time of frames per one-second window and flags a sustained rise above the
+/// best mean seen so far. Busy time is the CPU or GPU work of a frame, not
+/// the interval between frames, so waiting for vsync or a frame rate cap
+/// doesn't count as a slowdown.
+#[derive(Default)]
+pub struct ThrottleDetector {
+    window_start: Option<Instant>,
+    window_sum: f64,
+    window_frames: u32,
+    baseline: Option<f64>,
+    degraded_windows: u32,
+    throttling_likely: bool,
+}
+
+impl ThrottleDetector {
+    /// Records a frame finished at `now` that kept the CPU or GPU busy for
+    /// `busy`, whichever is longer.
+    pub fn record_frame(&mut self, now: Instant, busy: Duration) {
+        self.window_sum += busy.as_secs_f64();
+        self.window_frames += 1;
+
+        let start = *self.window_start.get_or_insert(now);
+        if now.duration_since(start) < WINDOW {
+            return;
+        }
+        let mean = self.window_sum / self.window_frames as f64;
+        self.window_start = Some(now);
+        self.window_sum = 0.0;
+        self.window_frames = 0;
+        self.evaluate(mean);
+    }
+
+    /// Whether frame times have been elevated long enough to suggest throttling.
+    pub fn throttling_likely(&self) -> bool {
+        self.throttling_likely
+    }
+
+    /// Forgets the baseline, for when the workload changes, e.g. a different
+    /// scene, so that a heavier one isn't mistaken for throttling.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn evaluate(&mut self, mean: f64) {
+        let baseline = *self.baseline.get_or_insert(mean);
+        if mean < baseline {
+            self.baseline = Some(mean);
+        }
+
+        if mean > baseline * SLOWDOWN_RATIO {
+            self.degraded_windows += 1;
+        } else {
+            self.degraded_windows = 0;
+            if self.throttling_likely {
+                log::info!("frame times back to normal ({:.2}ms)", mean * 1000.0);
+                self.throttling_likely = false;
+            }
+        }
+
+        if self.degraded_windows >= SUSTAINED_WINDOWS && !self.throttling_likely {
+            self.throttling_likely = true;
+            log::warn!(
+                "sustained frame-time increase ({:.2}ms vs {:.2}ms baseline); thermal throttling likely, consider lowering the render scale",
+                mean * 1000.0,
+                baseline * 1000.0
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `seconds` windows of 60 frames that each took `busy_ms`.
+    fn run(detector: &mut ThrottleDetector, start: &mut Instant, seconds: u32, busy_ms: u64) {
+        for _ in 0..seconds * 60 {
+            *start += Duration::from_micros(16_667);
+            detector.record_frame(*start, Duration::from_millis(busy_ms));
+        }
+    }
+
+    fn baseline_ms(detector: &ThrottleDetector) -> f64 {
+        (detector.baseline.unwrap() * 1e6).round() / 1e3
+    }
+
+    #[test]
+    fn sustained_slowdown_is_reported_and_recovers() {
+        let mut detector = ThrottleDetector::default();
+        let mut now = Instant::now();
+        run(&mut detector, &mut now, 3, 4);
+        assert_eq!(baseline_ms(&detector), 4.0);
+
+        // Shorter than the sustained period.
+        run(&mut detector, &mut now, SUSTAINED_WINDOWS - 1, 8);
+        assert!(!detector.throttling_likely());
+        run(&mut detector, &mut now, 2, 8);
+        assert!(detector.throttling_likely());
+        // The baseline isn't dragged up by the slow frames.
+        assert_eq!(baseline_ms(&detector), 4.0);
+
+        run(&mut detector, &mut now, 2, 4);
+        assert!(!detector.throttling_likely());
+    }
+
+    #[test]
+    fn baseline_tracks_the_fastest_window() {
+        let mut detector = ThrottleDetector::default();
+        let mut now = Instant::now();
+        run(&mut detector, &mut now, 2, 6);
+        run(&mut detector, &mut now, 2, 3);
+        assert_eq!(baseline_ms(&detector), 3.0);
+        // A small rise doesn't count as degraded.
+        run(&mut detector, &mut now, SUSTAINED_WINDOWS + 1, 3);
+        assert_eq!(detector.degraded_windows, 0);
+    }
+
+    #[test]
+    fn reset_forgets_the_baseline() {
+        let mut detector = ThrottleDetector::default();
+        let mut now = Instant::now();
+        run(&mut detector, &mut now, 2, 2);
+        run(&mut detector, &mut now, SUSTAINED_WINDOWS + 1, 8);
+        assert!(detector.throttling_likely());
+
+        detector.reset();
+        assert!(!detector.throttling_likely());
+        run(&mut detector, &mut now, SUSTAINED_WINDOWS + 1, 8);
+        assert!(!detector.throttling_likely());
+        assert_eq!(baseline_ms(&detector), 8.0);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use web_time::Instant;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// With spinning on, how long before a capped frame's deadline the event
+/// loop's `ControlFlow::WaitUntil` wake-up is scheduled; the limiter spins
+/// through the rest, since timed waits tend to overshoot by about a
+/// millisecond.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+/// Frame time statistics over the counter's rolling window.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub fps: f64,
+    pub average: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} FPS ({:.2}ms, {:.2}-{:.2}ms)",
+            self.fps,
+            self.average.as_secs_f64() * 1000.0,
+            self.min.as_secs_f64() * 1000.0,
+            self.max.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Measures the time between consecutive frames over the last `window`
+/// frames.
+pub struct FrameCounter {
+    frame_times: VecDeque<Duration>,
+    window: usize,
+    last_frame: Option<Instant>,
+    last_report: Option<Instant>,
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        Self::new(120)
+    }
+}
+
+impl FrameCounter {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            frame_times: VecDeque::with_capacity(window),
+            window,
+            last_frame: None,
+            last_report: None,
+        }
+    }
+
+    /// Records a frame finished at `now`; the first tick only sets the
+    /// starting point.
+    pub fn tick(&mut self, now: Instant) {
+        if let Some(last) = self.last_frame.replace(now) {
+            if self.frame_times.len() == self.window {
+                self.frame_times.pop_front();
+            }
+            self.frame_times
+                .push_back(now.saturating_duration_since(last));
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let (Some(&min), Some(&max)) =
+            (self.frame_times.iter().min(), self.frame_times.iter().max())
+        else {
+            return FrameStats::default();
+        };
+        let average = self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32;
+        let fps = if average.is_zero() {
+            0.0
+        } else {
+            1.0 / average.as_secs_f64()
+        };
+        FrameStats {
+            fps,
+            average,
+            min,
+            max,
+        }
+    }
+
+    /// Returns true at most once per second, for updating a display.
+    pub fn report_due(&mut self, now: Instant) -> bool {
+        if self
+            .last_report
+            .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL)
+        {
+            return false;
+        }
+        self.last_report = Some(now);
+        true
+    }
+}
+
+/// Caps the frame rate by scheduling each frame a fixed interval after the
+/// previous one.
+///
+/// The limiter never sleeps a thread itself: the event loop waits with
+/// `ControlFlow::WaitUntil` until [`wake_time`](Self::wake_time), which by
+/// default is the deadline. With [`set_spin`](Self::set_spin) the wake-up
+/// comes a little early and [`spin`](Self::spin) busy-waits the last
+/// stretch, trading a core's worth of power for less jitter.
+/// Deadlines advance from the previous deadline rather than from when the
+/// frame finished, so the cadence doesn't drift; after falling more than a
+/// frame behind the schedule restarts from the late frame.
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Option<Instant>,
+    spin: bool,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: Option<u32>) -> Self {
+        let mut limiter = Self::default();
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    /// Sets the cap; `None` or 0 leaves the frame rate uncapped.
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_time = target_fps
+            .filter(|&fps| fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.next_frame = None;
+    }
+
+    /// Whether to wake early and busy-wait up to each deadline; off by
+    /// default.
+    pub fn set_spin(&mut self, spin: bool) {
+        self.spin = spin;
+    }
+
+    pub fn target_fps(&self) -> Option<u32> {
+        self.frame_time
+            .map(|frame_time| (1.0 / frame_time.as_secs_f64()).round() as u32)
+    }
+
+    /// Records a frame rendered at `now` and schedules the next one.
+    pub fn frame_rendered(&mut self, now: Instant) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+        let next = self.next_frame.unwrap_or(now) + frame_time;
+        self.next_frame = Some(if next < now { now + frame_time } else { next });
+    }
+
+    /// When the next frame may start, or `None` when uncapped or before the
+    /// first frame.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next_frame
+    }
+
+    /// When the event loop should wake for the next frame: the deadline,
+    /// or with spinning on a little before it, leaving the rest to
+    /// [`spin`](Self::spin).
+    pub fn wake_time(&self) -> Option<Instant> {
+        if !self.spin {
+            return self.next_frame;
+        }
+        self.next_frame
+            .map(|deadline| deadline.checked_sub(SPIN_THRESHOLD).unwrap_or(deadline))
+    }
+
+    /// With spinning on, busy-waits for the deadline if it is less than the
+    /// spin threshold past `now`. Farther deadlines return immediately, so a
+    /// redraw requested early, e.g. for input, is never held up.
+    pub fn spin(&self, now: Instant) {
+        // Blocking the browser's main thread stalls the page, and the
+        // browser paces frames there anyway.
+        if !self.spin || cfg!(target_arch = "wasm32") {
+            return;
+        }
+        let Some(deadline) = self
+            .next_frame
+            .filter(|&deadline| deadline.saturating_duration_since(now) <= SPIN_THRESHOLD)
+        else {
+            return;
+        };
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn first_tick_only_sets_the_start() {
+        let mut counter = FrameCounter::new(4);
+        counter.tick(Instant::now());
+        assert_eq!(counter.stats(), FrameStats::default());
+    }
+
+    #[test]
+    fn stats_cover_the_rolling_window() {
+        let start = Instant::now();
+        let mut counter = FrameCounter::new(3);
+        let mut now = start;
+        counter.tick(now);
+        for frame_time in [40, 10, 20, 30] {
+            now += ms(frame_time);
+            counter.tick(now);
+        }
+        // The 40ms frame has dropped out of the window.
+        let stats = counter.stats();
+        assert_eq!(stats.average, ms(20));
+        assert_eq!(stats.min, ms(10));
+        assert_eq!(stats.max, ms(30));
+        assert!((stats.fps - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reports_at_most_once_per_second() {
+        let start = Instant::now();
+        let mut counter = FrameCounter::default();
+        assert!(counter.report_due(start));
+        assert!(!counter.report_due(start + ms(999)));
+        assert!(counter.report_due(start + ms(1000)));
+        assert!(!counter.report_due(start + ms(1500)));
+    }
+
+    #[test]
+    fn uncapped_limiter_has_no_deadline() {
+        let mut limiter = FrameLimiter::new(None);
+        limiter.frame_rendered(Instant::now());
+        assert_eq!(limiter.deadline(), None);
+        assert_eq!(limiter.wake_time(), None);
+        assert_eq!(FrameLimiter::new(Some(0)).target_fps(), None);
+    }
+
+    #[test]
+    fn deadlines_advance_from_the_previous_deadline() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Some(50));
+        assert_eq!(limiter.target_fps(), Some(50));
+        limiter.frame_rendered(start);
+        assert_eq!(limiter.deadline(), Some(start + ms(20)));
+        // A frame finishing 3ms late doesn't push the schedule back.
+        limiter.frame_rendered(start + ms(23));
+        assert_eq!(limiter.deadline(), Some(start + ms(40)));
+        assert_eq!(limiter.wake_time(), Some(start + ms(40)));
+        limiter.set_spin(true);
+        assert_eq!(limiter.wake_time(), Some(start + ms(39)));
+    }
+
+    #[test]
+    fn waking_late_adds_jitter_but_no_drift() {
+        // Wakes from `WaitUntil` overshoot by up to a millisecond; frames
+        // start then and take 2ms to render.
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Some(100));
+        let mut counter = FrameCounter::new(1000);
+        let mut now = start;
+        for frame in 0..1000u64 {
+            if let Some(wake) = limiter.wake_time() {
+                now = now.max(wake) + Duration::from_micros(frame * 7919 % 1000);
+            }
+            // The first frame is the schedule's start, not a capped one.
+            if frame > 0 {
+                counter.tick(now);
+            }
+            now += ms(2);
+            limiter.frame_rendered(now);
+        }
+        let stats = counter.stats();
+        let jitter = stats.max - stats.min;
+        // Each frame time is off by at most the difference of two
+        // overshoots.
+        assert!(jitter < ms(2), "{jitter:?}");
+        assert!(stats.min > ms(9) && stats.max < ms(11), "{stats}");
+        // Deadlines don't pick up the overshoots: the last frame started
+        // 998 frame times after the second, give or take one overshoot.
+        let elapsed = now - ms(2) - start;
+        assert!(
+            elapsed >= ms(12 + 9980) && elapsed < ms(12 + 9980 + 1),
+            "{elapsed:?}"
+        );
+        assert!((stats.fps - 100.0).abs() < 0.01, "{stats}");
+    }
+
+    #[test]
+    fn schedule_restarts_after_falling_a_frame_behind() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Some(50));
+        limiter.frame_rendered(start);
+        limiter.frame_rendered(start + ms(45));
+        assert_eq!(limiter.deadline(), Some(start + ms(65)));
+    }
+
+    #[test]
+    fn changing_the_cap_resets_the_schedule() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Some(50));
+        limiter.frame_rendered(start);
+        limiter.set_target_fps(Some(100));
+        assert_eq!(limiter.deadline(), None);
+        limiter.frame_rendered(start + ms(5));
+        assert_eq!(limiter.deadline(), Some(start + ms(15)));
+    }
+}
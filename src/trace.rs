@@ -0,0 +1,96 @@
+use serde::Serialize;
+use std::path::Path;
+use web_time::Instant;
+
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+    args: TraceArgs,
+}
+
+#[derive(Serialize)]
+struct TraceArgs {
+    frame: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: &'a [TraceEvent],
+}
+
+/// Records CPU-side frame timelines in the Chrome tracing format.
+///
+/// Load the written JSON in `chrome://tracing` or Perfetto.
+pub struct FrameTracer {
+    origin: Instant,
+    recording: bool,
+    frame: u64,
+    events: Vec<TraceEvent>,
+}
+
+impl Default for FrameTracer {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+            recording: false,
+            frame: 0,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl FrameTracer {
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.events.clear();
+    }
+
+    /// Stops recording and writes the captured timeline to `path`.
+    pub fn stop(&mut self, path: &Path) -> std::io::Result<()> {
+        self.recording = false;
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let trace = Trace {
+            trace_events: &self.events,
+        };
+        serde_json::to_writer(file, &trace)?;
+        self.events.clear();
+        Ok(())
+    }
+
+    /// Records a frame as consecutive spans nested under a `frame` span: each
+    /// mark ends the span named after it, starting where the previous one ended.
+    pub fn record_frame(&mut self, frame_start: Instant, marks: &[(&'static str, Instant)]) {
+        self.frame += 1;
+        if !self.recording {
+            return;
+        }
+        let mut span_start = frame_start;
+        for &(name, end) in marks {
+            self.push(name, span_start, end);
+            span_start = end;
+        }
+        self.push("frame", frame_start, span_start);
+    }
+
+    fn push(&mut self, name: &'static str, start: Instant, end: Instant) {
+        self.events.push(TraceEvent {
+            name,
+            ph: "X",
+            ts: start.duration_since(self.origin).as_secs_f64() * 1e6,
+            dur: end.duration_since(start).as_secs_f64() * 1e6,
+            pid: 1,
+            tid: 1,
+            args: TraceArgs { frame: self.frame },
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::frame::FrameEncoder;
+use crate::label;
+use crate::replay::InputEvent;
+
+/// An egui debug overlay: the egui context, its winit integration and an
+/// `egui_wgpu` renderer drawing its output on top of a finished frame.
+///
+/// Each frame, [`run`](Self::run) builds the UI and [`draw`](Self::draw)
+/// paints it.
+pub struct Ui {
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    primitives: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+    /// CPU copies of the live textures, re-uploaded when the renderer is
+    /// rebuilt for a new format.
+    textures: HashMap<egui::TextureId, (egui::ColorImage, egui::TextureOptions)>,
+}
+
+impl Ui {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, window: &Window) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+
+        Self {
+            context,
+            state,
+            renderer: create_renderer(device, format),
+            primitives: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Rebuilds the renderer for a new target format.
+    pub fn set_format(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+    ) {
+        self.renderer = create_renderer(device, format);
+        for (id, (image, options)) in &self.textures {
+            let delta = egui::epaint::ImageDelta::full(image.clone(), *options);
+            self.renderer.update_texture(device, queue, *id, &delta);
+        }
+    }
+
+    /// Passes a window event to egui; see [`wants_input`](Self::wants_input)
+    /// for whether the app should still handle it.
+    ///
+    /// Scale factor changes are picked up here, so the UI stays sharp when
+    /// the window moves between displays.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) {
+        let _ = self.state.on_window_event(window, event);
+    }
+
+    /// Whether egui wants `input` for itself, e.g. clicks on a panel or keys
+    /// typed into a text field. Releases are never claimed, so app state
+    /// can't be left with a key or button held.
+    pub fn wants_input(&self, input: &InputEvent) -> bool {
+        match input {
+            InputEvent::Key { pressed: true, .. } => self.context.wants_keyboard_input(),
+            InputEvent::MouseButton { pressed: true, .. } | InputEvent::CursorMoved(_) => {
+                self.context.wants_pointer_input()
+            }
+            _ => false,
+        }
+    }
+
+    /// Builds this frame's UI with `build`.
+    pub fn run(&mut self, window: &Window, build: impl FnMut(&egui::Context)) {
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, build);
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        self.primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        self.textures_delta.append(output.textures_delta);
+    }
+
+    /// Paints the UI built by the last [`run`](Self::run) over `view`, which
+    /// is `width` by `height` pixels.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &mut FrameEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        for (id, delta) in std::mem::take(&mut self.textures_delta.set) {
+            self.keep_texture(id, &delta);
+            self.renderer.update_texture(device, queue, id, &delta);
+        }
+
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [width, height],
+            pixels_per_point: self.context.pixels_per_point(),
+        };
+        // Without paint callbacks there are no extra command buffers.
+        let _ =
+            self.renderer
+                .update_buffers(device, queue, frame.encoder(), &self.primitives, &screen);
+        if !self.primitives.is_empty() {
+            let mut render_pass = frame
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: label!("ui::render_pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                })
+                .forget_lifetime();
+            self.renderer
+                .render(&mut render_pass, &self.primitives, &screen);
+        }
+
+        for id in std::mem::take(&mut self.textures_delta.free) {
+            self.textures.remove(&id);
+            self.renderer.free_texture(&id);
+        }
+    }
+
+    /// Applies `delta` to the CPU copy of texture `id`.
+    fn keep_texture(&mut self, id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
+        let egui::ImageData::Color(image) = &delta.image;
+        let Some([x, y]) = delta.pos else {
+            self.textures.insert(id, ((**image).clone(), delta.options));
+            return;
+        };
+        let Some((texture, _)) = self.textures.get_mut(&id) else {
+            log::warn!("ui: update for unknown texture {id:?}");
+            return;
+        };
+        let [width, height] = image.size;
+        for row in 0..height {
+            let start = (y + row) * texture.size[0] + x;
+            texture.pixels[start..start + width]
+                .copy_from_slice(&image.pixels[row * width..(row + 1) * width]);
+        }
+    }
+}
+
+fn create_renderer(device: &wgpu::Device, format: wgpu::TextureFormat) -> egui_wgpu::Renderer {
+    egui_wgpu::Renderer::new(device, format, egui_wgpu::RendererOptions::default())
+}
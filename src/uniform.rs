@@ -0,0 +1,117 @@
+use std::marker::PhantomData;
+
+/// A uniform buffer holding a single `T`, together with the bind group layout
+/// and bind group exposing it at binding 0.
+pub struct UniformBuffer<T: bytemuck::Pod> {
+    buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> UniformBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: Option<&str>, visibility: wgpu::ShaderStages) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: std::mem::size_of::<T>() as wgpu::BufferAddress,
+            // COPY_SRC lets the contents be read back, e.g. in tests.
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label,
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, value: &T) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(value));
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::GpuContext;
+    use crate::test_support::test_gpu;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    struct TestUniform {
+        color: [f32; 4],
+        size: [u32; 2],
+        _padding: [u32; 2],
+    }
+
+    #[test]
+    fn update_writes_the_value() {
+        let GpuContext { device, queue, .. } = test_gpu!();
+        let uniform = UniformBuffer::<TestUniform>::new(
+            &device,
+            crate::label!("uniform::test"),
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        let value = TestUniform {
+            color: [0.25, 0.5, 0.75, 1.0],
+            size: [640, 480],
+            _padding: [0; 2],
+        };
+        uniform.update(&queue, &value);
+
+        let size = uniform.buffer().size();
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: crate::label!("uniform::test_staging"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(uniform.buffer(), 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+        staging.slice(..).map_async(wgpu::MapMode::Read, |result| {
+            result.unwrap();
+        });
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+        let bytes = staging.slice(..).get_mapped_range();
+        assert_eq!(&bytes[..], bytemuck::bytes_of(&value));
+    }
+}
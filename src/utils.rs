@@ -1,6 +1,7 @@
 pub fn init_logger() {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
+            console_error_panic_hook::set_once();
             console_log::init_with_level(log::Level::Debug).expect("Failed to init console_log");
         } else {
             env_logger::builder()
@@ -12,3 +13,23 @@ pub fn init_logger() {
         }
     }
 }
+
+/// Debug label for a GPU resource, namespaced as `learn1::<name>`.
+///
+/// Labels are kept in debug builds and in release builds with the `labels`
+/// feature; otherwise this evaluates to `None`.
+#[cfg(any(debug_assertions, feature = "labels"))]
+#[macro_export]
+macro_rules! label {
+    ($name:literal) => {
+        Some(concat!("learn1::", $name))
+    };
+}
+
+#[cfg(not(any(debug_assertions, feature = "labels")))]
+#[macro_export]
+macro_rules! label {
+    ($name:literal) => {
+        None
+    };
+}
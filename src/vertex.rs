@@ -0,0 +1,117 @@
+/// A vertex type that can be uploaded to a vertex buffer.
+pub trait Vertex: bytemuck::Pod {
+    /// Layout of one element, with attributes at consecutive shader
+    /// locations (starting at 0 for per-vertex data).
+    fn layout() -> wgpu::VertexBufferLayout<'static>;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PosColorVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl Vertex for PosColorVertex {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PosTexVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+impl Vertex for PosTexVertex {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// A vertex of a loaded model. Position and texture coordinates sit at the
+/// same locations as in [`PosTexVertex`], so shaders written for it can draw
+/// models too.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex for ModelVertex {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use super::*;
+
+    #[test]
+    fn pos_color_vertex_layout_matches_the_struct() {
+        let layout = PosColorVertex::layout();
+        assert_eq!(layout.array_stride, size_of::<PosColorVertex>() as u64);
+        let offsets: Vec<_> = layout.attributes.iter().map(|a| a.offset).collect();
+        assert_eq!(
+            offsets,
+            [
+                offset_of!(PosColorVertex, position),
+                offset_of!(PosColorVertex, color),
+            ]
+            .map(|offset| offset as u64)
+        );
+        let locations: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|a| a.shader_location)
+            .collect();
+        assert_eq!(locations, [0, 1]);
+    }
+
+    #[test]
+    fn model_vertex_layout_matches_the_struct() {
+        let layout = ModelVertex::layout();
+        assert_eq!(layout.array_stride, size_of::<ModelVertex>() as u64);
+        let offsets: Vec<_> = layout.attributes.iter().map(|a| a.offset).collect();
+        assert_eq!(
+            offsets,
+            [
+                offset_of!(ModelVertex, position),
+                offset_of!(ModelVertex, tex_coords),
+                offset_of!(ModelVertex, normal),
+            ]
+            .map(|offset| offset as u64)
+        );
+        let locations: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|a| a.shader_location)
+            .collect();
+        assert_eq!(locations, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,88 @@
+use web_time::Instant;
+
+/// A pipeline to warm up: a name for logging, the depth format and sample
+/// count its pass needs, and a closure recording one draw with it.
+pub struct WarmupDraw<'a> {
+    pub name: &'a str,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub sample_count: u32,
+    pub draw: &'a dyn Fn(&mut wgpu::RenderPass<'_>),
+}
+
+/// Draws once with each pipeline into a throwaway 1x1 target and waits for the GPU.
+///
+/// Some drivers defer the final shader compile until a pipeline is first
+/// used, which shows up as a hitch on the first frame; this moves that cost
+/// to startup.
+pub fn warm_up(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    draws: &[WarmupDraw<'_>],
+) {
+    let start = Instant::now();
+    let create_view = |format, sample_count| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: crate::label!("warmup::target"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: crate::label!("warmup::encoder"),
+    });
+    for warmup in draws {
+        let view = create_view(format, warmup.sample_count);
+        let depth_view = warmup
+            .depth_format
+            .map(|format| create_view(format, warmup.sample_count));
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crate::label!("warmup::render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Discard,
+                },
+            })],
+            depth_stencil_attachment: depth_view.as_ref().map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        (warmup.draw)(&mut render_pass);
+    }
+    queue.submit(Some(encoder.finish()));
+    if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+        log::warn!("pipeline warmup: failed to wait for the GPU: {e}");
+    }
+
+    let names: Vec<&str> = draws.iter().map(|warmup| warmup.name).collect();
+    log::info!(
+        "warmed up {} pipeline(s) [{}] in {:.1}ms",
+        draws.len(),
+        names.join(", "),
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+}
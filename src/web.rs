@@ -0,0 +1,45 @@
+use winit::dpi::PhysicalSize;
+use winit::platform::web::WindowExtWebSys;
+use winit::window::Window;
+
+/// Id of the element the canvas is appended to; the document body is used
+/// if the page has none.
+const CONTAINER_ID: &str = "wgpu-container";
+
+/// Appends the window's canvas to the page.
+pub fn attach_canvas(window: &Window) {
+    let Some(canvas) = window.canvas() else {
+        log::error!("the window has no canvas");
+        return;
+    };
+    let document = web_sys::window().and_then(|window| window.document());
+    let parent = document.and_then(|document| {
+        document
+            .get_element_by_id(CONTAINER_ID)
+            .or_else(|| document.body().map(Into::into))
+    });
+    match parent {
+        Some(parent) => {
+            if let Err(e) = parent.append_child(&canvas) {
+                log::error!("failed to append the canvas: {e:?}");
+            }
+        }
+        None => log::error!("no #{CONTAINER_ID} element or document body for the canvas"),
+    }
+}
+
+/// Size of the canvas in physical pixels, from its laid-out size and the
+/// device pixel ratio.
+///
+/// `Window::inner_size` can still be 0x0 right after the canvas is created
+/// on some browsers, so this is used for the initial surface size instead.
+pub fn canvas_size(window: &Window) -> PhysicalSize<u32> {
+    let scale = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+    let (width, height) = window.canvas().map_or((0, 0), |canvas| {
+        (canvas.client_width(), canvas.client_height())
+    });
+    PhysicalSize::new(
+        (width as f64 * scale).round() as u32,
+        (height as f64 * scale).round() as u32,
+    )
+}
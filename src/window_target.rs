@@ -0,0 +1,152 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use web_time::Instant;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use crate::context::GpuContext;
+
+/// Default for [`WindowTarget::resize_debounce`].
+pub const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// A window and the surface presenting to it.
+pub struct WindowTarget {
+    pub window: Arc<Window>,
+    /// `None` while the app is suspended.
+    pub surface: Option<wgpu::Surface<'static>>,
+    pub config: wgpu::SurfaceConfiguration,
+    /// The latest window size, which the surface follows once it settles.
+    pub size: PhysicalSize<u32>,
+    /// How long the window size must stay unchanged before the surface is
+    /// reconfigured for it. Interactive resizes send a flood of `Resized`
+    /// events; until they stop, frames keep the old configuration and the
+    /// compositor scales them to the window.
+    pub resize_debounce: Duration,
+    resized_at: Option<Instant>,
+    reconfigure_pending: bool,
+}
+
+impl WindowTarget {
+    /// Creates a surface for `window` with `config` at the window's size.
+    /// Check the format is supported, then call
+    /// [`reconfigure`](Self::reconfigure) before the first frame.
+    pub fn new(
+        context: &GpuContext,
+        window: Arc<Window>,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<Self, wgpu::CreateSurfaceError> {
+        let surface = context.instance.create_surface(window.clone())?;
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            ..config.clone()
+        };
+        Ok(Self::with_surface(window, surface, config))
+    }
+
+    /// Wraps a surface already created for `window`, sized to `config`.
+    pub fn with_surface(
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    ) -> Self {
+        Self {
+            size: PhysicalSize::new(config.width, config.height),
+            window,
+            surface: Some(surface),
+            config,
+            resize_debounce: RESIZE_DEBOUNCE,
+            resized_at: None,
+            reconfigure_pending: false,
+        }
+    }
+
+    /// Records a new window size at `now`, applied by
+    /// [`resize_if_needed`](Self::resize_if_needed) once no other size has
+    /// arrived for [`resize_debounce`](Self::resize_debounce).
+    pub fn set_resized(&mut self, size: PhysicalSize<u32>, now: Instant) {
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.resized_at = (!self.size_matches_config()).then_some(now);
+    }
+
+    /// Reconfigures the surface before the next frame without waiting, e.g.
+    /// after changing the present mode.
+    pub fn request_reconfigure(&mut self) {
+        self.reconfigure_pending = true;
+    }
+
+    /// When a recorded size will be due, for scheduling a redraw so the
+    /// final size of a resize is applied even if nothing else redraws.
+    pub fn resize_deadline(&self) -> Option<Instant> {
+        self.resized_at
+            .map(|resized_at| resized_at + self.resize_debounce)
+    }
+
+    /// Applies the configuration to the surface, e.g. after it was lost or
+    /// became outdated.
+    pub fn reconfigure(&self, device: &wgpu::Device) {
+        if let Some(surface) = &self.surface {
+            surface.configure(device, &self.config);
+        }
+    }
+
+    /// Drops the surface, which must not outlive the native window while the
+    /// app is suspended, e.g. when Android sends it to the background.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    /// Recreates a surface dropped by [`suspend`](Self::suspend) and
+    /// configures it as before. Route the window's current size through
+    /// [`set_resized`](Self::set_resized) afterwards.
+    pub fn resume(&mut self, context: &GpuContext) -> Result<(), wgpu::CreateSurfaceError> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+        self.surface = Some(context.instance.create_surface(self.window.clone())?);
+        self.reconfigure(&context.device);
+        Ok(())
+    }
+
+    /// Reconfigures the surface for a size that has settled by `now`, or
+    /// for [`request_reconfigure`](Self::request_reconfigure), returning
+    /// whether the size changed.
+    pub fn resize_if_needed(&mut self, device: &wgpu::Device, now: Instant) -> bool {
+        if self
+            .resize_deadline()
+            .is_some_and(|deadline| now < deadline)
+        {
+            if self.reconfigure_pending {
+                self.reconfigure_pending = false;
+                self.reconfigure(device);
+            }
+            return false;
+        }
+        self.apply_resize(device)
+    }
+
+    /// Reconfigures the surface for the latest size right away, e.g. when
+    /// the old size made the surface outdated. Returns whether the size
+    /// changed; the surface is reconfigured either way if it was pending.
+    pub fn apply_resize(&mut self, device: &wgpu::Device) -> bool {
+        let resized = !self.size_matches_config();
+        if !resized && !self.reconfigure_pending {
+            return false;
+        }
+        self.config.width = self.size.width;
+        self.config.height = self.size.height;
+        self.reconfigure(device);
+        self.resized_at = None;
+        self.reconfigure_pending = false;
+        resized
+    }
+
+    fn size_matches_config(&self) -> bool {
+        self.size.width == self.config.width && self.size.height == self.config.height
+    }
+}
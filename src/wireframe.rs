@@ -0,0 +1,388 @@
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::label;
+use crate::model::Model;
+use crate::pipeline::rebuild_pipeline;
+use crate::shader::ShaderConstants;
+use crate::surface::is_linear_output;
+use crate::texture::DEPTH_FORMAT;
+use crate::uniform::UniformBuffer;
+use crate::vertex::{ModelVertex, Vertex};
+
+const SHADER: &str = include_str!("wireframe.wgsl");
+
+/// Linear color of the overlay's lines.
+pub const WIRE_COLOR: [f32; 3] = [1.0, 0.6, 0.05];
+
+/// Depth bias for the surface under the overlay, pushing it just behind its
+/// own edges so the lines don't z-fight with it.
+///
+/// The bias goes on the surface rather than the lines because Vulkan and GL
+/// only apply a pipeline's bias to triangles. The slope term covers the
+/// depth change across a pixel, as a line's depth is the surface's on the
+/// line rather than at the pixel center; the constant term covers rounding,
+/// in units of the depth buffer's precision at the surface.
+pub const SURFACE_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: 4,
+    slope_scale: 1.0,
+    clamp: 0.0,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct WireUniform {
+    color: [f32; 3],
+    _padding: f32,
+}
+
+/// Draws a model's triangle edges in a constant color over its shaded
+/// surface, for inspecting topology.
+///
+/// It goes in the scene pass after the model, as a `LineList` over the same
+/// vertex and instance buffers, depth-tested without writing depth. The
+/// model has to be drawn with [`SURFACE_DEPTH_BIAS`] so the lines don't
+/// z-fight with the surface under them.
+pub struct WireOverlay {
+    uniform_buffer: UniformBuffer<WireUniform>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl WireOverlay {
+    /// `camera_layout` is the scene's camera layout, bound at group 0.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        #[cfg(debug_assertions)]
+        crate::shader::check_struct_layout(
+            SHADER,
+            "Wire",
+            &crate::struct_layout!(WireUniform { color, _padding }),
+        );
+        let uniform_buffer = UniformBuffer::new(
+            device,
+            label!("wireframe::uniform"),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+        );
+        let shader = create_shader(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: label!("wireframe::pipeline_layout"),
+            bind_group_layouts: &[camera_layout, uniform_buffer.layout()],
+            push_constant_ranges: &[],
+        });
+        uniform_buffer.update(
+            queue,
+            &WireUniform {
+                color: WIRE_COLOR,
+                _padding: 0.0,
+            },
+        );
+        let pipeline = create_pipeline(device, &shader, &pipeline_layout, format, sample_count);
+        Self {
+            uniform_buffer,
+            shader,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Rebuilds the pipeline for the scene pass's format and sample count.
+    pub fn set_target(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.pipeline = rebuild_pipeline(device, &mut self.shader, create_shader, |shader| {
+            create_pipeline(device, shader, &self.pipeline_layout, format, sample_count)
+        });
+    }
+
+    /// Draws the edges of `model` for every instance in `instances`. The
+    /// pass must have the scene's depth buffer, already holding the model.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        camera: &wgpu::BindGroup,
+        model: &Model,
+        instances: &InstanceBuffer,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera, &[]);
+        render_pass.set_bind_group(1, self.uniform_buffer.bind_group(), &[]);
+        render_pass.set_vertex_buffer(1, instances.slice());
+        model.draw_edges(render_pass, 0..instances.len());
+    }
+}
+
+fn create_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: label!("wireframe::shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let mut constants = ShaderConstants::default();
+    let encode = if is_linear_output(format) { 0.0 } else { 1.0 };
+    constants.set("ENCODE_SRGB", encode);
+    let constants = constants.validated(SHADER);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: label!("wireframe::pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[ModelVertex::layout(), InstanceRaw::layout()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::*;
+    use crate::camera::{Camera, CameraUniform};
+    use crate::context::GpuContext;
+    use crate::culling::BoundingSphere;
+    use crate::frame::FrameEncoder;
+    use crate::instance::Instance;
+    use crate::mesh::Mesh;
+    use crate::model::{Material, ModelMesh};
+    use crate::test_support::test_gpu;
+    use crate::texture::Texture;
+
+    const SIZE: u32 = 256;
+
+    /// Fills the quad's depth like the scene pipeline does.
+    const SOLID_SHADER: &str = "
+        @group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+        @vertex fn vs(
+            @location(0) p: vec3<f32>,
+            @location(5) m0: vec4<f32>,
+            @location(6) m1: vec4<f32>,
+            @location(7) m2: vec4<f32>,
+            @location(8) m3: vec4<f32>,
+        ) -> @builtin(position) vec4<f32> {
+            return view_proj * mat4x4<f32>(m0, m1, m2, m3) * vec4<f32>(p, 1.0);
+        }
+        @fragment fn fs() -> @location(0) vec4<f32> { return vec4<f32>(0.2, 0.2, 0.2, 1.0); }";
+
+    /// Draws a quad tilted away from the camera, optionally shaded `surface`
+    /// units nearer the camera first with `bias`, then its wire overlay, and
+    /// counts the pixels in the wire color.
+    fn wire_pixels(gpu: &GpuContext, surface: Option<f32>, bias: wgpu::DepthBiasState) -> usize {
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: SIZE,
+            height: SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: Vec::new(),
+        };
+        let depth = Texture::create_depth_texture(device, &config, 1, "wireframe::tests::depth");
+
+        let mut camera = Camera::new(1.0);
+        camera.eye = Vec3::new(0.0, 0.0, 3.0);
+        let camera_uniform = UniformBuffer::new(device, None, wgpu::ShaderStages::VERTEX);
+        camera_uniform.update(queue, &CameraUniform::from(&camera));
+        let corner = |x: f32, y: f32| ModelVertex {
+            position: [x, y, 0.0],
+            tex_coords: [0.0; 2],
+            normal: [0.0, 0.0, 1.0],
+        };
+        let vertices = [
+            corner(-1.0, -1.0),
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+        ];
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let texture = Texture::from_layers(device, queue, &[white], None);
+        let model = Model {
+            meshes: vec![ModelMesh {
+                name: "quad".into(),
+                mesh: Mesh::new(device, None, &vertices, &[0u16, 1, 2, 0, 2, 3]),
+                material: 0,
+                bounds: BoundingSphere::from_vertices(&vertices),
+            }],
+            materials: vec![Material::new(
+                device,
+                &Texture::array_bind_group_layout(device),
+                "white",
+                &texture,
+            )],
+            animation: None,
+        };
+        let instance = |z: f32| Instance {
+            position: Vec3::new(0.0, 0.0, z),
+            rotation: Quat::from_rotation_x(-1.0),
+            ..Instance::default()
+        };
+        let mut wire_instances = InstanceBuffer::new(device);
+        wire_instances
+            .update(device, queue, &[instance(0.0)])
+            .unwrap();
+        let mut surface_instances = InstanceBuffer::new(device);
+        surface_instances
+            .update(device, queue, &[instance(surface.unwrap_or(0.0))])
+            .unwrap();
+
+        let overlay = WireOverlay::new(device, queue, format, 1, camera_uniform.layout());
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SOLID_SHADER.into()),
+        });
+        let solid = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[camera_uniform.layout()],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs"),
+                compilation_options: Default::default(),
+                buffers: &[ModelVertex::layout(), InstanceRaw::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias,
+            }),
+            multisample: Default::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let mut frame = FrameEncoder::new(device, queue, None);
+        {
+            let mut render_pass = frame.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            if surface.is_some() {
+                render_pass.set_pipeline(&solid);
+                render_pass.set_bind_group(0, camera_uniform.bind_group(), &[]);
+                render_pass.set_vertex_buffer(1, surface_instances.slice());
+                model.meshes[0].mesh.draw(&mut render_pass, 0..1);
+            }
+            overlay.draw(
+                &mut render_pass,
+                camera_uniform.bind_group(),
+                &model,
+                &wire_instances,
+            );
+        }
+        frame.submit();
+        let pixels = crate::capture::read_texture_rgba(device, queue, &target).unwrap();
+        pixels
+            .chunks_exact(4)
+            .filter(|pixel| pixel[0] > 250)
+            .count()
+    }
+
+    #[test]
+    fn lines_show_fully_over_their_biased_surface() {
+        let gpu = test_gpu!();
+        let alone = wire_pixels(&gpu, None, SURFACE_DEPTH_BIAS);
+        assert!(alone > 100, "only {alone} wire pixels");
+        assert_eq!(wire_pixels(&gpu, Some(0.0), SURFACE_DEPTH_BIAS), alone);
+        // Without it, lines flicker in and out along the tilted surface.
+        let unbiased = wire_pixels(&gpu, Some(0.0), wgpu::DepthBiasState::default());
+        assert!(unbiased < alone, "{unbiased} of {alone}");
+        // A surface clearly in front still hides them.
+        assert!(wire_pixels(&gpu, Some(0.5), SURFACE_DEPTH_BIAS) < alone / 4);
+    }
+}
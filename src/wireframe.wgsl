@@ -0,0 +1,45 @@
+// Set when the target format is not sRGB, so the encode happens here
+// instead of in the hardware.
+override ENCODE_SRGB: bool = false;
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Wire {
+    // Linear.
+    color: vec3<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> wire: Wire;
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    return camera.view_proj * model * vec4<f32>(position, 1.0);
+}
+
+fn linear_to_srgb(value: vec3<f32>) -> vec3<f32> {
+    let low = value * 12.92;
+    let high = 1.055 * pow(value, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, value <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4<f32>(linear_to_srgb(wire.color.rgb), 1.0);
+    }
+    return vec4<f32>(wire.color.rgb, 1.0);
+}